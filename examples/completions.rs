@@ -3,6 +3,7 @@
 //
 // "t" [Tab] will allow you to select the completions "test" and "this is the reedline crate"
 // [Enter] to select the chosen alternative
+// "cl" [Alt-/] completes "clear" without showing the menu
 
 use reedline::{
     default_emacs_keybindings, ColumnarMenu, DefaultCompleter, DefaultPrompt, EditCommand, Emacs,
@@ -19,6 +20,11 @@ fn add_menu_keybindings(keybindings: &mut Keybindings) {
             ReedlineEvent::MenuNext,
         ]),
     );
+    keybindings.add_binding(
+        KeyModifiers::ALT,
+        KeyCode::Char('/'),
+        ReedlineEvent::AcceptTopCompletion("completion_menu".to_string()),
+    );
     keybindings.add_binding(
         KeyModifiers::ALT,
         KeyCode::Enter,
@@ -29,6 +29,7 @@ fn create_item(cwd: &str, cmd: &str, exit_status: i64) -> reedline::HistoryItem
         duration: Some(Duration::from_millis(1000)),
         exit_status: Some(exit_status),
         more_info: None,
+        tags: Default::default(),
     }
 }
 
@@ -76,6 +76,7 @@ fn main() -> reedline::Result<()> {
     let cursor_config = CursorConfig {
         vi_insert: Some(SetCursorStyle::BlinkingBar),
         vi_normal: Some(SetCursorStyle::SteadyBlock),
+        vi_visual: Some(SetCursorStyle::SteadyUnderScore),
         emacs: None,
     };
 
@@ -28,10 +28,6 @@ impl Prompt for TransientPrompt {
         Cow::Owned(String::new())
     }
 
-    fn render_prompt_right(&self) -> Cow<str> {
-        Cow::Owned(String::new())
-    }
-
     fn render_prompt_indicator(&self, _prompt_mode: PromptEditMode) -> Cow<str> {
         Cow::Borrowed(TRANSIENT_PROMPT)
     }
@@ -0,0 +1,316 @@
+use crate::{Completer, Suggestion};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+};
+
+/// Future resolving to the suggestions of an [`AsyncCompleter`]
+pub type CompletionFuture = Pin<Box<dyn Future<Output = Vec<Suggestion>> + Send>>;
+
+/// A completer whose suggestions take a while to compute, e.g. when they are fetched over the network
+///
+/// To be used by [`Reedline`](crate::Reedline) or a menu, it must be wrapped in an [`AsyncCompleterAdapter`].
+pub trait AsyncCompleter: Send {
+    /// Starts computing the suggestions for the line and position, see [`Completer::complete`]
+    ///
+    /// The returned future is polled regularly by the line editor without blocking the input,
+    /// so it doesn't require any async runtime and doesn't have to wake its waker.
+    /// It is dropped, hence cancelled, if the line changes before it resolves.
+    fn complete(&mut self, line: &str, pos: usize) -> CompletionFuture;
+}
+
+/// Use an [`AsyncCompleter`] as a [`Completer`]
+///
+/// When the suggestions for a line aren't available yet, the previous suggestions are returned
+/// as long as the user is only typing after them, with their span extended to the cursor.
+/// Otherwise no suggestion is returned until the new ones are available.
+///
+/// Meanwhile the [`Reedline`](crate::Reedline) read loop keeps handling keystrokes and polls
+/// the pending request in between. Once it resolves, the active menu is updated with the new
+/// suggestions. A request started for a line that was edited since is cancelled, and results that
+/// arrive late are only ever shown in the menu: accepting a suggestion still requires a keystroke.
+///
+/// # Example
+///
+/// ```rust
+/// use reedline::{AsyncCompleter, AsyncCompleterAdapter, CompletionFuture, Reedline, Span, Suggestion};
+///
+/// struct RemoteCompleter;
+///
+/// impl AsyncCompleter for RemoteCompleter {
+///     fn complete(&mut self, line: &str, pos: usize) -> CompletionFuture {
+///         let line = line.to_string();
+///         Box::pin(async move {
+///             // e.g. query a server for the completions of `line`
+///             vec![Suggestion {
+///                 value: format!("{line}-remote"),
+///                 span: Span::new(0, pos),
+///                 ..Suggestion::default()
+///             }]
+///         })
+///     }
+/// }
+///
+/// let completer = Box::new(AsyncCompleterAdapter::new(Box::new(RemoteCompleter)));
+///
+/// let mut line_editor = Reedline::create().with_completer(completer);
+/// ```
+pub struct AsyncCompleterAdapter {
+    completer: Box<dyn AsyncCompleter>,
+    pending: Option<(CompletionRequest, CompletionFuture)>,
+    latest: Option<(CompletionRequest, Vec<Suggestion>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CompletionRequest {
+    line: String,
+    pos: usize,
+}
+
+/// The line editor polls pending completions regularly, so there is no need to be woken up
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+impl AsyncCompleterAdapter {
+    /// Wrap `completer` to be used as a [`Completer`]
+    pub fn new(completer: Box<dyn AsyncCompleter>) -> Self {
+        Self {
+            completer,
+            pending: None,
+            latest: None,
+        }
+    }
+
+    /// Polls the pending request, returning `true` if it just resolved
+    fn poll(&mut self) -> bool {
+        let Some((_, future)) = &mut self.pending else {
+            return false;
+        };
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(suggestions) => {
+                let (request, _) = self.pending.take().unwrap();
+                self.latest = Some((request, suggestions));
+                true
+            }
+            Poll::Pending => false,
+        }
+    }
+
+    /// Previous suggestions still applying to `request`, while its own are computed
+    fn stale_suggestions(&self, request: &CompletionRequest) -> Vec<Suggestion> {
+        let Some((previous, suggestions)) = &self.latest else {
+            return vec![];
+        };
+
+        // The user must only have typed at the cursor since the previous request
+        let typed = request.line.len().checked_sub(previous.line.len());
+        let extended = request.line.starts_with(&previous.line)
+            && typed.map_or(false, |typed| previous.pos + typed == request.pos);
+
+        if !extended {
+            return vec![];
+        }
+
+        suggestions
+            .iter()
+            .filter(|suggestion| suggestion.span.end == previous.pos)
+            .map(|suggestion| {
+                let mut suggestion = suggestion.clone();
+                suggestion.span.end = request.pos;
+                suggestion
+            })
+            .collect()
+    }
+}
+
+impl Completer for AsyncCompleterAdapter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let request = CompletionRequest {
+            line: line.to_string(),
+            pos,
+        };
+
+        if !matches!(&self.latest, Some((latest, _)) if *latest == request) {
+            if !matches!(&self.pending, Some((pending, _)) if *pending == request) {
+                // Dropping the previous future cancels it
+                let future = self.completer.complete(line, pos);
+                self.pending = Some((request.clone(), future));
+            }
+
+            // The suggestions may be immediately available
+            self.poll();
+        }
+
+        match &self.latest {
+            Some((latest, suggestions)) if *latest == request => suggestions.clone(),
+            _ => self.stale_suggestions(&request),
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn poll_pending(&mut self) -> bool {
+        self.poll()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Span;
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+
+    type SharedResults = Arc<Mutex<Option<Vec<Suggestion>>>>;
+
+    /// Resolves once its suggestions are provided through `results`
+    struct ControlledFuture {
+        results: SharedResults,
+    }
+
+    impl Future for ControlledFuture {
+        type Output = Vec<Suggestion>;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+            match self.results.lock().unwrap().take() {
+                Some(results) => Poll::Ready(results),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    #[derive(Default, Clone)]
+    struct ControlledCompleter {
+        requests: Arc<Mutex<Vec<(String, SharedResults)>>>,
+    }
+
+    impl ControlledCompleter {
+        fn resolve(&self, index: usize, values: &[&str]) {
+            let requests = self.requests.lock().unwrap();
+            let (line, results) = &requests[index];
+
+            *results.lock().unwrap() = Some(
+                values
+                    .iter()
+                    .map(|value| suggestion(value, Span::new(0, line.len())))
+                    .collect(),
+            );
+        }
+
+        fn request_count(&self) -> usize {
+            self.requests.lock().unwrap().len()
+        }
+    }
+
+    impl AsyncCompleter for ControlledCompleter {
+        fn complete(&mut self, line: &str, _pos: usize) -> CompletionFuture {
+            let results = Arc::new(Mutex::new(None));
+            self.requests
+                .lock()
+                .unwrap()
+                .push((line.to_string(), results.clone()));
+            Box::pin(ControlledFuture { results })
+        }
+    }
+
+    fn suggestion(value: &str, span: Span) -> Suggestion {
+        Suggestion {
+            value: value.to_string(),
+            span,
+            ..Suggestion::default()
+        }
+    }
+
+    #[test]
+    fn suggestions_are_returned_once_resolved() {
+        let requests = ControlledCompleter::default();
+        let mut completer = AsyncCompleterAdapter::new(Box::new(requests.clone()));
+
+        assert_eq!(completer.complete("gi", 2), vec![]);
+        assert!(completer.is_pending());
+        assert!(!completer.poll_pending());
+
+        requests.resolve(0, &["git"]);
+        assert!(completer.poll_pending());
+        assert!(!completer.is_pending());
+
+        assert_eq!(
+            completer.complete("gi", 2),
+            vec![suggestion("git", Span::new(0, 2))]
+        );
+        assert_eq!(requests.request_count(), 1);
+    }
+
+    #[test]
+    fn immediate_results_are_returned_directly() {
+        struct Immediate;
+
+        impl AsyncCompleter for Immediate {
+            fn complete(&mut self, _line: &str, pos: usize) -> CompletionFuture {
+                Box::pin(async move { vec![suggestion("ready", Span::new(0, pos))] })
+            }
+        }
+
+        let mut completer = AsyncCompleterAdapter::new(Box::new(Immediate));
+
+        assert_eq!(
+            completer.complete("re", 2),
+            vec![suggestion("ready", Span::new(0, 2))]
+        );
+        assert!(!completer.is_pending());
+    }
+
+    #[test]
+    fn new_input_cancels_pending_request() {
+        let requests = ControlledCompleter::default();
+        let mut completer = AsyncCompleterAdapter::new(Box::new(requests.clone()));
+
+        completer.complete("g", 1);
+        completer.complete("gi", 2);
+        assert_eq!(requests.request_count(), 2);
+
+        // The first request was dropped, its results never show up
+        requests.resolve(0, &["go"]);
+        assert!(!completer.poll_pending());
+        assert!(completer.is_pending());
+
+        requests.resolve(1, &["git"]);
+        assert!(completer.poll_pending());
+        assert_eq!(
+            completer.complete("gi", 2),
+            vec![suggestion("git", Span::new(0, 2))]
+        );
+    }
+
+    #[test]
+    fn stale_suggestions_follow_typed_text() {
+        let requests = ControlledCompleter::default();
+        let mut completer = AsyncCompleterAdapter::new(Box::new(requests.clone()));
+
+        completer.complete("gi", 2);
+        requests.resolve(0, &["git", "gist"]);
+        completer.poll_pending();
+
+        // Still typing the same word, the previous suggestions now cover the typed character
+        assert_eq!(
+            completer.complete("gis", 3),
+            vec![
+                suggestion("git", Span::new(0, 3)),
+                suggestion("gist", Span::new(0, 3))
+            ]
+        );
+
+        // The previous suggestions don't apply to a different line
+        assert_eq!(completer.complete("l", 1), vec![]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::LineBuffer;
 use nu_ansi_term::Style;
 use std::ops::Range;
 
@@ -27,13 +28,47 @@ impl Span {
     }
 }
 
+/// Start of the whitespace delimited word ending at the end of `line`
+///
+/// Returns `line.len()` if `line` ends with a whitespace.
+pub(crate) fn current_word_start(line: &str) -> usize {
+    if line.ends_with(char::is_whitespace) {
+        line.len()
+    } else {
+        LineBuffer::from(line).big_word_left_index()
+    }
+}
+
 /// A trait that defines how to convert some text and a position to a list of potential completions in that position.
-/// The text could be a part of the whole line, and the position is the index of the end of the text in the original line.
+///
+/// The menus give the whole buffer, all its lines included, and the cursor position as a byte
+/// offset in it, so the text after the cursor and on the other lines is known. The spans of
+/// the suggestions are in the same coordinates, replacing text anywhere in the buffer. A
+/// completer written for the current line only can be wrapped in a [`crate::CurrentLineCompleter`].
+///
+/// With [`crate::MenuBuilder::with_only_buffer_difference`], the text is instead the part of the
+/// buffer typed since the menu was activated, and the position the index of its end in the buffer.
 pub trait Completer: Send {
     /// the action that will take the line and position and convert it to a vector of completions, which include the
     /// span to replace and the contents of that replacement
+    ///
+    /// The span can be anywhere in the line and doesn't have to end at `pos`, e.g. to complete
+    /// the variable `$VA` in the middle of `echo $VA rest`.
     fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion>;
 
+    /// Same as [`Completer::complete`] but also returns the span of the line replaced by the
+    /// suggestions
+    ///
+    /// Defaults to the span of the first suggestion of [`Completer::complete`], or to an empty
+    /// span at `pos` if there is none
+    fn complete_at(&mut self, line: &str, pos: usize) -> (Span, Vec<Suggestion>) {
+        let suggestions = self.complete(line, pos);
+        let span = suggestions
+            .first()
+            .map_or(Span::new(pos, pos), |suggestion| suggestion.span);
+        (span, suggestions)
+    }
+
     /// same as [`Completer::complete`] but it will return a vector of ranges of the strings
     /// the suggestions are based on
     fn complete_with_base_ranges(
@@ -71,6 +106,17 @@ pub trait Completer: Send {
     fn total_completions(&mut self, line: &str, pos: usize) -> usize {
         self.complete(line, pos).len()
     }
+
+    /// Whether completions are being computed in the background (see [`AsyncCompleterAdapter`](crate::AsyncCompleterAdapter))
+    fn is_pending(&self) -> bool {
+        false
+    }
+
+    /// Makes progress on the completions computed in the background, if any.
+    /// Returns `true` once they became available, so that the menu can be updated
+    fn poll_pending(&mut self) -> bool {
+        false
+    }
 }
 
 /// Suggestion returned by the Completer
@@ -90,4 +136,74 @@ pub struct Suggestion {
     /// Whether to append a space after selecting this suggestion.
     /// This helps to avoid that a completer repeats the complete suggestion.
     pub append_whitespace: bool,
+    /// Optional indices of the characters of `value` matched by the typed text.
+    /// These can be used by menus to emphasize the matched characters
+    pub match_indices: Option<Vec<usize>>,
+    /// Optional score given by the completer to the suggestion, higher is better.
+    /// This can be used to sort or threshold the suggestions
+    pub score: Option<i64>,
+    /// Optional expanded text shown for the selected suggestion, such as a full
+    /// description or example usage. It may span multiple lines
+    pub preview: Option<String>,
+    /// Optional offset in bytes of the cursor from the end of the inserted value once the
+    /// suggestion is selected, e.g. `-1` to put it between the parentheses of `print()`.
+    /// The cursor is kept within the value, which doesn't include the appended whitespace.
+    /// Without an offset the cursor goes after the inserted text
+    pub cursor_offset: Option<isize>,
+    /// Optional name of the source of the suggestion, such as the completer it comes from
+    /// in a [`MultiCompleter`](crate::MultiCompleter). Menus can use it to group the suggestions
+    pub source: Option<String>,
+    /// Whether the text after this suggestion is completed right away when it is accepted with
+    /// [`ReedlineEvent::AcceptAndContinue`](crate::ReedlineEvent::AcceptAndContinue), e.g. for a
+    /// directory whose entries are completed next. Only used without `append_whitespace`
+    pub continue_completion: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Completes the variable under the cursor, which may be in the middle of the line
+    struct VariableCompleter;
+
+    impl Completer for VariableCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            let start = line[..pos].rfind('$').unwrap_or(pos);
+            let end = line[pos..]
+                .find(char::is_whitespace)
+                .map_or(line.len(), |len| pos + len);
+            ["$VAR", "$VALUE"]
+                .iter()
+                .filter(|value| value.starts_with(&line[start..pos]))
+                .map(|value| Suggestion {
+                    value: value.to_string(),
+                    span: Span::new(start, end),
+                    ..Default::default()
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn complete_at_returns_the_span_in_the_middle_of_the_line() {
+        let (span, suggestions) = VariableCompleter.complete_at("echo $VA rest", 7);
+
+        assert_eq!(span, Span::new(5, 8));
+        assert_eq!(
+            suggestions
+                .iter()
+                .map(|suggestion| suggestion.value.as_str())
+                .collect::<Vec<_>>(),
+            vec!["$VAR", "$VALUE"]
+        );
+    }
+
+    #[test]
+    fn complete_at_defaults_to_the_span_of_the_suggestions() {
+        let mut completer = crate::DefaultCompleter::new(vec!["echo".into()]);
+
+        assert_eq!(completer.complete_at("ec", 2).0, Span::new(0, 2));
+        assert_eq!(completer.complete_at("ls", 2), (Span::new(2, 2), vec![]));
+    }
 }
@@ -0,0 +1,118 @@
+use crate::{Completer, Span, Suggestion};
+
+/// A completer giving another completer only the line the cursor is on, up to the cursor
+///
+/// The completers are given the whole buffer, which can span several lines, and the cursor
+/// position in it. This wraps a completer written for a single line: it gets the text before
+/// the cursor on its line, and the position in that text. The spans of its suggestions are
+/// moved back to the buffer.
+///
+/// # Example
+///
+/// ```rust
+/// use reedline::{Completer, CurrentLineCompleter, DefaultCompleter, Span};
+///
+/// let mut completer = CurrentLineCompleter::new(Box::new(DefaultCompleter::new(vec![
+///     "cargo".into(),
+/// ])));
+///
+/// let suggestions = completer.complete("ls\ncar", 6);
+/// assert_eq!(suggestions[0].value, "cargo");
+/// assert_eq!(suggestions[0].span, Span::new(3, 6));
+/// ```
+pub struct CurrentLineCompleter {
+    completer: Box<dyn Completer>,
+}
+
+impl CurrentLineCompleter {
+    /// Wraps `completer`, giving it the current line up to the cursor
+    pub fn new(completer: Box<dyn Completer>) -> Self {
+        Self { completer }
+    }
+}
+
+/// The current line of `line` up to `pos`, and the start of that line
+fn current_line(line: &str, pos: usize) -> (&str, usize) {
+    let line = if line.len() > pos { &line[..pos] } else { line };
+    let start = line.rfind('\n').map_or(0, |index| index + 1);
+    (&line[start..], start)
+}
+
+fn shift(suggestions: Vec<Suggestion>, start: usize) -> Vec<Suggestion> {
+    suggestions
+        .into_iter()
+        .map(|mut suggestion| {
+            suggestion.span = Span::new(suggestion.span.start + start, suggestion.span.end + start);
+            suggestion
+        })
+        .collect()
+}
+
+impl Completer for CurrentLineCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let (current, start) = current_line(line, pos);
+        shift(self.completer.complete(current, pos - start), start)
+    }
+
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        let (current, line_start) = current_line(line, pos);
+        let suggestions = self
+            .completer
+            .partial_complete(current, pos - line_start, start, offset);
+        shift(suggestions, line_start)
+    }
+
+    fn total_completions(&mut self, line: &str, pos: usize) -> usize {
+        let (current, start) = current_line(line, pos);
+        self.completer.total_completions(current, pos - start)
+    }
+
+    fn is_pending(&self) -> bool {
+        self.completer.is_pending()
+    }
+
+    fn poll_pending(&mut self) -> bool {
+        self.completer.poll_pending()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Completes `line` with itself, to see what the wrapped completer is given
+    struct EchoCompleter;
+
+    impl Completer for EchoCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            vec![Suggestion {
+                value: line.to_string(),
+                span: Span::new(0, pos),
+                ..Suggestion::default()
+            }]
+        }
+    }
+
+    #[test]
+    fn only_the_current_line_is_completed() {
+        let mut completer = CurrentLineCompleter::new(Box::new(EchoCompleter));
+
+        let suggestions = completer.complete("first\nsecond\nthird line", 18);
+        assert_eq!(suggestions[0].value, "third");
+        assert_eq!(suggestions[0].span, Span::new(13, 18));
+
+        let suggestions = completer.complete("first\r\nsecond", 7);
+        assert_eq!(suggestions[0].value, "");
+        assert_eq!(suggestions[0].span, Span::new(7, 7));
+
+        assert_eq!(completer.complete("same line", 4)[0].value, "same");
+        assert_eq!(completer.total_completions("first\nsecond", 8), 1);
+    }
+}
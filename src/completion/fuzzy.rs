@@ -0,0 +1,274 @@
+use super::base::current_word_start;
+use crate::{Completer, Span, Suggestion};
+use std::{cmp::Reverse, collections::BTreeSet};
+
+/// Score given to each matched character
+const SCORE_MATCH: i64 = 16;
+/// Bonus for a match at the start of a word of the candidate
+const BONUS_BOUNDARY: i64 = 8;
+/// Bonus for a match directly following the previous one
+const BONUS_CONSECUTIVE: i64 = 8;
+/// Penalty for each character skipped between two matches
+const PENALTY_GAP: i64 = 1;
+
+/// A completer matching a list of words against the current word using a fuzzy
+/// subsequence search, similar to `fzf`
+///
+/// The characters of the current word must appear in order in a word, but not necessarily
+/// next to each other. Suggestions are ranked by score (consecutive characters and characters at
+/// the start of a word score higher), ties favoring the shorter words.
+/// Each [`Suggestion`] carries its score and the indices of the matched characters.
+///
+/// The search is case-insensitive, unless the current word contains an uppercase character.
+///
+/// # Example
+///
+/// ```rust
+/// use reedline::{Completer, FuzzyCompleter};
+///
+/// let mut completer = FuzzyCompleter::new(vec![
+///     "checkout".into(),
+///     "cherry-pick".into(),
+///     "commit".into(),
+/// ]);
+///
+/// let suggestions = completer.complete("git cho", 7);
+/// assert_eq!(suggestions.len(), 1);
+/// assert_eq!(suggestions[0].value, "checkout");
+/// assert_eq!(suggestions[0].match_indices, Some(vec![0, 1, 5]));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FuzzyCompleter {
+    words: BTreeSet<String>,
+}
+
+impl Completer for FuzzyCompleter {
+    /// Returns the words matching the word under the cursor, best matches first
+    ///
+    /// The replacement span covers the current word, delimited by whitespace.
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        // Trimming in case someone passes in text containing stuff after the cursor
+        let line = if line.len() > pos { &line[..pos] } else { line };
+        let offset = pos - line.len();
+
+        let start = current_word_start(line);
+
+        let word = &line[start..];
+        let span = Span::new(offset + start, pos);
+
+        let mut matches = self
+            .words
+            .iter()
+            .filter_map(|value| {
+                fuzzy_match(word, value).map(|(score, indices)| (score, indices, value))
+            })
+            .collect::<Vec<_>>();
+
+        matches.sort_by_key(|(score, _, value)| (Reverse(*score), value.chars().count()));
+
+        matches
+            .into_iter()
+            .map(|(score, indices, value)| Suggestion {
+                value: value.clone(),
+                description: None,
+                style: None,
+                extra: None,
+                span,
+                append_whitespace: false,
+                match_indices: Some(indices),
+                score: Some(score),
+                preview: None,
+                cursor_offset: None,
+                source: None,
+                continue_completion: false,
+            })
+            .collect()
+    }
+}
+
+impl FuzzyCompleter {
+    /// Construct the fuzzy completer with a list of words to complete
+    pub fn new(words: Vec<String>) -> Self {
+        let mut completer = Self::default();
+        completer.insert(words);
+        completer
+    }
+
+    /// Add `words` to the list of words to complete
+    pub fn insert(&mut self, words: Vec<String>) {
+        self.words.extend(words);
+    }
+
+    /// Remove all the words from the completer
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+}
+
+/// Searches `pattern` as a subsequence of `candidate`
+///
+/// Returns the best score with the indices of the matched characters of `candidate`,
+/// or `None` if `candidate` doesn't contain every character of `pattern` in order.
+pub(crate) fn fuzzy_match(pattern: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let candidate = candidate.chars().collect::<Vec<_>>();
+
+    if pattern.is_empty() {
+        return Some((0, vec![]));
+    }
+
+    if pattern.len() > candidate.len() {
+        return None;
+    }
+
+    let eq =
+        |a: char, b: char| a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()));
+
+    let bonus = |j: usize| match j.checked_sub(1).map(|i| candidate[i]) {
+        None => BONUS_BOUNDARY,
+        Some(prev) if !prev.is_alphanumeric() => BONUS_BOUNDARY,
+        Some(prev) if prev.is_lowercase() && candidate[j].is_uppercase() => BONUS_BOUNDARY,
+        Some(_) => 0,
+    };
+
+    // Best score of the pattern so far, with its last character matched at each index of the candidate
+    let mut scores = vec![None; candidate.len()];
+    // Index at which the previous character of the pattern was matched, for each row and index
+    let mut parents = vec![];
+
+    for (i, &p) in pattern.iter().enumerate() {
+        let mut row = vec![None; candidate.len()];
+        let mut row_parents = vec![0; candidate.len()];
+        // Best previous match followed by a gap, along with its index
+        let mut best_gap: Option<(i64, usize)> = None;
+
+        for (j, &c) in candidate.iter().enumerate() {
+            if i > 0 {
+                best_gap = best_gap.map(|(score, k)| (score - PENALTY_GAP, k));
+
+                if let Some(score) = j.checked_sub(2).and_then(|k| scores[k]) {
+                    if best_gap.map_or(true, |(best, _)| score - PENALTY_GAP > best) {
+                        best_gap = Some((score - PENALTY_GAP, j - 2));
+                    }
+                }
+            }
+
+            if !eq(p, c) {
+                continue;
+            }
+
+            let previous = if i == 0 {
+                Some((0, 0))
+            } else {
+                let consecutive = j
+                    .checked_sub(1)
+                    .and_then(|k| scores[k].map(|score| (score + BONUS_CONSECUTIVE, k)));
+
+                match (consecutive, best_gap) {
+                    (Some(consecutive), Some(gap)) if gap.0 > consecutive.0 => Some(gap),
+                    (Some(consecutive), _) => Some(consecutive),
+                    (None, gap) => gap,
+                }
+            };
+
+            if let Some((score, k)) = previous {
+                row[j] = Some(score + SCORE_MATCH + bonus(j));
+                row_parents[j] = k;
+            }
+        }
+
+        scores = row;
+        parents.push(row_parents);
+    }
+
+    let (mut j, score) = scores
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .fold(None, |best: Option<(usize, i64)>, (j, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((j, score)),
+        })?;
+
+    let mut indices = vec![j];
+    for row_parents in parents.iter().skip(1).rev() {
+        j = row_parents[j];
+        indices.push(j);
+    }
+    indices.reverse();
+
+    Some((score, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn completer(words: &[&str]) -> FuzzyCompleter {
+        FuzzyCompleter::new(words.iter().map(|word| word.to_string()).collect())
+    }
+
+    fn values(suggestions: &[Suggestion]) -> Vec<&str> {
+        suggestions
+            .iter()
+            .map(|suggestion| suggestion.value.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn matches_subsequences() {
+        assert_eq!(fuzzy_match("fb", "foobar").unwrap().1, vec![0, 3]);
+        assert_eq!(fuzzy_match("oba", "foobar").unwrap().1, vec![2, 3, 4]);
+        assert_eq!(fuzzy_match("rb", "foobar"), None);
+        assert_eq!(fuzzy_match("foobarz", "foobar"), None);
+        assert_eq!(fuzzy_match("", "foobar"), Some((0, vec![])));
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(fuzzy_match("fb", "FooBar").is_some());
+        assert!(fuzzy_match("FB", "FooBar").is_some());
+        assert_eq!(fuzzy_match("Fb", "FooBar"), None);
+    }
+
+    #[test]
+    fn prefers_consecutive_and_boundary_matches() {
+        let mut completer = completer(&["axbxc", "abc-d", "xabc", "a-b-c"]);
+
+        assert_eq!(
+            values(&completer.complete("abc", 3)),
+            vec!["abc-d", "a-b-c", "xabc", "axbxc"]
+        );
+    }
+
+    #[test]
+    fn ties_favor_shorter_words() {
+        let mut completer = completer(&["status-long", "status", "stash"]);
+        let suggestions = completer.complete("st", 2);
+
+        assert_eq!(values(&suggestions), vec!["stash", "status", "status-long"]);
+        assert_eq!(suggestions[0].score, suggestions[2].score);
+    }
+
+    #[test]
+    fn replaces_the_current_word() {
+        let mut completer = completer(&["checkout", "switch"]);
+
+        let suggestions = completer.complete("git sw", 6);
+        assert_eq!(values(&suggestions), vec!["switch"]);
+        assert_eq!(suggestions[0].span, Span::new(4, 6));
+        assert_eq!(suggestions[0].match_indices, Some(vec![0, 1]));
+
+        // Text after the cursor isn't part of the word
+        let suggestions = completer.complete("git co --force", 6);
+        assert_eq!(values(&suggestions), vec!["checkout"]);
+        assert_eq!(suggestions[0].span, Span::new(4, 6));
+
+        // Right after a space, every word is suggested
+        let suggestions = completer.complete("git ", 4);
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].span, Span::new(4, 4));
+    }
+}
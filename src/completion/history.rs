@@ -32,17 +32,25 @@ fn search_unique(
 
 impl<'menu> Completer for HistoryCompleter<'menu> {
     fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        // Trimming in case someone passes in text containing stuff after the cursor
+        let line = if line.len() > pos { &line[..pos] } else { line };
         match search_unique(self, line) {
             Err(_) => vec![],
-            Ok(search_results) => search_results
-                .map(|value| self.create_suggestion(line, pos, value.command_line.deref()))
-                .collect(),
+            Ok(search_results) => {
+                let search = parse_selection_char(line, SELECTION_CHAR).remainder;
+                search_results
+                    .map(|value| {
+                        self.create_suggestion(line, pos, value.command_line.deref(), search)
+                    })
+                    .collect()
+            }
         }
     }
 
     // TODO: Implement `fn partial_complete()`
 
-    fn total_completions(&mut self, line: &str, _pos: usize) -> usize {
+    fn total_completions(&mut self, line: &str, pos: usize) -> usize {
+        let line = if line.len() > pos { &line[..pos] } else { line };
         search_unique(self, line).map(|i| i.count()).unwrap_or(0)
     }
 }
@@ -52,7 +60,7 @@ impl<'menu> HistoryCompleter<'menu> {
         Self(history)
     }
 
-    fn create_suggestion(&self, line: &str, pos: usize, value: &str) -> Suggestion {
+    fn create_suggestion(&self, line: &str, pos: usize, value: &str, search: &str) -> Suggestion {
         let span = Span {
             start: pos - line.len(),
             end: pos,
@@ -65,13 +73,33 @@ impl<'menu> HistoryCompleter<'menu> {
             extra: None,
             span,
             append_whitespace: false,
+            match_indices: substring_match_indices(value, search),
+            score: None,
+            preview: None,
+            cursor_offset: None,
+            source: None,
+            continue_completion: false,
         }
     }
 }
 
+/// Indices of the characters of the first occurrence of `search` in `value`, the start of
+/// `value` for a prefix
+fn substring_match_indices(value: &str, search: &str) -> Option<Vec<usize>> {
+    if search.is_empty() {
+        return None;
+    }
+    let start = value.find(search)?;
+    let first = value[..start].chars().count();
+    Some((first..first + search.chars().count()).collect())
+}
+
 #[cfg(test)]
 mod tests {
-    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicI64, Ordering},
+    };
 
     use rstest::rstest;
 
@@ -91,6 +119,7 @@ mod tests {
             duration: None,
             exit_status: None,
             more_info: None,
+            tags: HashMap::new(),
         }
     }
 
@@ -159,4 +188,29 @@ mod tests {
         assert_eq!(actual, expected);
         Ok(())
     }
+
+    #[rstest]
+    #[case("git status", "git", Some(vec![0, 1, 2]))]
+    #[case("echo été", "té", Some(vec![6, 7]))]
+    #[case("ls ls", "ls", Some(vec![0, 1]))]
+    #[case("git status", "", None)]
+    fn test_substring_match_indices(
+        #[case] value: &str,
+        #[case] search: &str,
+        #[case] expected: Option<Vec<usize>>,
+    ) {
+        assert_eq!(substring_match_indices(value, search), expected);
+    }
+
+    #[test]
+    fn complete_returns_the_matched_characters() -> Result<()> {
+        let mut history = FileBackedHistory::new(2)?;
+        history.save(&new_history_item("git status"))?;
+        let mut sut = HistoryCompleter::new(&history);
+
+        let actual = sut.complete("tat", 3);
+
+        assert_eq!(actual[0].match_indices, Some(vec![5, 6, 7]));
+        Ok(())
+    }
 }
@@ -1,6 +1,19 @@
+mod async_completer;
 mod base;
+mod current_line;
 mod default;
+mod fuzzy;
 pub(crate) mod history;
+mod multi;
+mod path;
+mod quote;
 
+pub use async_completer::{AsyncCompleter, AsyncCompleterAdapter, CompletionFuture};
 pub use base::{Completer, Span, Suggestion};
+pub use current_line::CurrentLineCompleter;
 pub use default::DefaultCompleter;
+pub(crate) use fuzzy::fuzzy_match;
+pub use fuzzy::FuzzyCompleter;
+pub use multi::MultiCompleter;
+pub use path::PathCompleter;
+pub use quote::{CompletionTransformer, QuoteContext};
@@ -0,0 +1,167 @@
+use crate::{Completer, Suggestion};
+
+/// A completer merging the suggestions of several completers, each labeled with its source
+///
+/// The suggestions are returned in the order of the completers, then in the order each
+/// completer returned them. Every [`Suggestion`] without a [`Suggestion::source`] gets the
+/// name of its completer. A suggestion with the same value and span as an earlier one is left
+/// out, so it keeps the label of the first completer returning it.
+///
+/// # Example
+///
+/// ```rust
+/// use reedline::{Completer, DefaultCompleter, MultiCompleter, PathCompleter, Reedline};
+///
+/// let mut completer = MultiCompleter::new()
+///     .with_completer("commands", Box::new(DefaultCompleter::new(vec!["cargo".into()])))
+///     .with_completer("paths", Box::new(PathCompleter::new()));
+///
+/// let suggestions = completer.complete("car", 3);
+/// assert_eq!(suggestions[0].value, "cargo");
+/// assert_eq!(suggestions[0].source.as_deref(), Some("commands"));
+///
+/// let mut line_editor = Reedline::create().with_completer(Box::new(completer));
+/// ```
+#[derive(Default)]
+pub struct MultiCompleter {
+    completers: Vec<(String, Box<dyn Completer>)>,
+}
+
+impl MultiCompleter {
+    /// Creates a completer without any sub-completer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder appending a completer, whose suggestions are labeled with `source`
+    #[must_use]
+    pub fn with_completer(mut self, source: &str, completer: Box<dyn Completer>) -> Self {
+        self.completers.push((source.to_string(), completer));
+        self
+    }
+}
+
+impl Completer for MultiCompleter {
+    /// Returns the suggestions of all the completers, labeled with their source
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let mut suggestions: Vec<Suggestion> = Vec::new();
+        for (source, completer) in &mut self.completers {
+            for mut suggestion in completer.complete(line, pos) {
+                let duplicate = suggestions.iter().any(|existing| {
+                    existing.value == suggestion.value && existing.span == suggestion.span
+                });
+                if !duplicate {
+                    suggestion.source.get_or_insert_with(|| source.clone());
+                    suggestions.push(suggestion);
+                }
+            }
+        }
+        suggestions
+    }
+
+    fn is_pending(&self) -> bool {
+        self.completers
+            .iter()
+            .any(|(_, completer)| completer.is_pending())
+    }
+
+    fn poll_pending(&mut self) -> bool {
+        // Every completer makes progress, even once one of them is done
+        self.completers
+            .iter_mut()
+            .fold(false, |done, (_, completer)| {
+                completer.poll_pending() || done
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DefaultCompleter, Span};
+    use pretty_assertions::assert_eq;
+
+    struct FixedCompleter(Vec<Suggestion>);
+
+    impl Completer for FixedCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            self.0.clone()
+        }
+    }
+
+    fn suggestion(value: &str, span: Span) -> Suggestion {
+        Suggestion {
+            value: value.to_string(),
+            span,
+            ..Suggestion::default()
+        }
+    }
+
+    #[test]
+    fn merges_the_suggestions_in_completer_order() {
+        let mut completer = MultiCompleter::new()
+            .with_completer(
+                "history",
+                Box::new(DefaultCompleter::new(vec!["git push".into()])),
+            )
+            .with_completer(
+                "commands",
+                Box::new(DefaultCompleter::new(vec!["git".into(), "grep".into()])),
+            );
+
+        let suggestions = completer.complete("g", 1);
+
+        assert_eq!(
+            suggestions
+                .iter()
+                .map(|suggestion| (suggestion.value.as_str(), suggestion.source.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("git push", Some("history")),
+                ("git", Some("commands")),
+                ("grep", Some("commands")),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_keep_the_first_source() {
+        let span = Span::new(0, 2);
+        let labeled = Suggestion {
+            source: Some("git".to_string()),
+            ..suggestion("ls", span)
+        };
+        let mut completer = MultiCompleter::new()
+            .with_completer(
+                "first",
+                Box::new(FixedCompleter(vec![suggestion("cd", span), labeled])),
+            )
+            .with_completer(
+                "second",
+                Box::new(FixedCompleter(vec![
+                    suggestion("ls", span),
+                    suggestion("cd", span),
+                    // Replaces another part of the line
+                    suggestion("cd", Span::new(0, 0)),
+                ])),
+            );
+
+        let suggestions = completer.complete("xx", 2);
+
+        assert_eq!(
+            suggestions
+                .iter()
+                .map(|suggestion| (
+                    suggestion.value.as_str(),
+                    suggestion.span,
+                    suggestion.source.as_deref()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("cd", span, Some("first")),
+                ("ls", span, Some("git")),
+                ("cd", Span::new(0, 0), Some("second")),
+            ]
+        );
+    }
+}
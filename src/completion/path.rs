@@ -0,0 +1,281 @@
+use super::base::current_word_start;
+use crate::{Completer, Span, Suggestion};
+use std::path::{is_separator, Path, PathBuf};
+
+/// A completer listing the files and directories matching the path under the cursor
+///
+/// Only the last component of the path is replaced, so that directories (suggested with a trailing `/`)
+/// can be completed one after the other. A leading `~` refers to the home directory of the user.
+///
+/// Hidden files are only suggested if the last component starts with a `.`
+///
+/// # Example
+///
+/// ```rust
+/// use reedline::{PathCompleter, Reedline};
+///
+/// let completer = Box::new(PathCompleter::new());
+///
+/// let mut line_editor = Reedline::create().with_completer(completer);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PathCompleter {
+    base_dir: Option<PathBuf>,
+}
+
+impl Completer for PathCompleter {
+    /// Returns the entries of the directory of the path under the cursor matching its last component
+    ///
+    /// If the directory can't be read, no suggestion is returned.
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        // Trimming in case someone passes in text containing stuff after the cursor
+        let line = if line.len() > pos { &line[..pos] } else { line };
+        let offset = pos - line.len();
+
+        let token = &line[current_word_start(line)..];
+
+        // The home directory itself must first be completed as a directory
+        if token == "~" {
+            return vec![path_suggestion(
+                "~".to_string(),
+                true,
+                Span::new(pos - token.len(), pos),
+            )];
+        }
+
+        let (dir, prefix) = match token.rfind(is_separator) {
+            Some(index) => (&token[..=index], &token[index + 1..]),
+            None => ("", token),
+        };
+
+        let Some(dir) = self.resolve_dir(dir, home_dir().as_deref()) else {
+            return vec![];
+        };
+
+        let Ok(entries) = dir.read_dir() else {
+            return vec![];
+        };
+
+        let span = Span::new(offset + line.len() - prefix.len(), pos);
+        let show_hidden = prefix.starts_with('.');
+
+        let mut suggestions = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name().into_string().ok()?;
+
+                if !name.starts_with(prefix) || (name.starts_with('.') && !show_hidden) {
+                    return None;
+                }
+
+                // Following symbolic links, so that links to directories are completed as such
+                Some(path_suggestion(name, entry.path().is_dir(), span))
+            })
+            .collect::<Vec<_>>();
+
+        suggestions.sort_by(|a, b| a.value.cmp(&b.value));
+        suggestions
+    }
+}
+
+impl PathCompleter {
+    /// Construct a path completer resolving relative paths from the current directory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve relative paths from `base_dir` instead of the current directory
+    #[must_use]
+    pub fn with_base_dir(mut self, base_dir: PathBuf) -> Self {
+        self.base_dir = Some(base_dir);
+        self
+    }
+
+    /// Directory in which to look for entries, for the directory part of a token
+    fn resolve_dir(&self, dir: &str, home: Option<&Path>) -> Option<PathBuf> {
+        let dir = match dir.strip_prefix('~') {
+            Some(rest) if rest.starts_with(is_separator) => {
+                home?.join(rest.trim_start_matches(is_separator))
+            }
+            _ => PathBuf::from(dir),
+        };
+
+        if dir.is_absolute() {
+            return Some(dir);
+        }
+
+        match &self.base_dir {
+            Some(base_dir) => Some(base_dir.join(dir)),
+            None => std::env::current_dir().ok().map(|cwd| cwd.join(dir)),
+        }
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let home = std::env::var_os("USERPROFILE");
+    #[cfg(not(windows))]
+    let home = std::env::var_os("HOME");
+
+    home.filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+fn path_suggestion(mut value: String, is_dir: bool, span: Span) -> Suggestion {
+    if is_dir {
+        value.push('/');
+    }
+
+    Suggestion {
+        value,
+        description: None,
+        style: None,
+        extra: None,
+        span,
+        // Directories are followed by the next path component
+        append_whitespace: !is_dir,
+        match_indices: None,
+        score: None,
+        preview: None,
+        cursor_offset: None,
+        source: None,
+        continue_completion: is_dir,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn setup() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join("src")).unwrap();
+        fs::write(tmp.path().join("src").join("lib.rs"), "").unwrap();
+        fs::write(tmp.path().join("src").join("main.rs"), "").unwrap();
+        fs::write(tmp.path().join("Cargo.toml"), "").unwrap();
+        fs::write(tmp.path().join(".gitignore"), "").unwrap();
+        fs::create_dir(tmp.path().join(".git")).unwrap();
+        tmp
+    }
+
+    fn complete(tmp: &tempfile::TempDir, line: &str) -> Vec<(String, Span, bool)> {
+        PathCompleter::new()
+            .with_base_dir(tmp.path().to_path_buf())
+            .complete(line, line.len())
+            .into_iter()
+            .map(|suggestion| {
+                // Only the directories are followed by more completions
+                assert_eq!(
+                    suggestion.continue_completion,
+                    !suggestion.append_whitespace
+                );
+                (
+                    suggestion.value,
+                    suggestion.span,
+                    suggestion.append_whitespace,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lists_matching_entries() {
+        let tmp = setup();
+
+        assert_eq!(
+            complete(&tmp, "cat "),
+            vec![
+                ("Cargo.toml".to_string(), Span::new(4, 4), true),
+                ("src/".to_string(), Span::new(4, 4), false),
+            ]
+        );
+        assert_eq!(
+            complete(&tmp, "cat sr"),
+            vec![("src/".to_string(), Span::new(4, 6), false)]
+        );
+    }
+
+    #[test]
+    fn replaces_only_the_last_component() {
+        let tmp = setup();
+
+        assert_eq!(
+            complete(&tmp, "cat src/m"),
+            vec![("main.rs".to_string(), Span::new(8, 9), true)]
+        );
+
+        let absolute = format!("cat {}/src/", tmp.path().display());
+        assert_eq!(
+            complete(&tmp, &absolute)
+                .into_iter()
+                .map(|(value, span, _)| (value, span))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "lib.rs".to_string(),
+                    Span::new(absolute.len(), absolute.len())
+                ),
+                (
+                    "main.rs".to_string(),
+                    Span::new(absolute.len(), absolute.len())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hidden_entries_require_a_dot() {
+        let tmp = setup();
+
+        assert_eq!(
+            complete(&tmp, "cat .git")
+                .into_iter()
+                .map(|(value, _, _)| value)
+                .collect::<Vec<_>>(),
+            vec![".git/", ".gitignore"]
+        );
+    }
+
+    #[test]
+    fn unreadable_directories_give_no_suggestion() {
+        let tmp = setup();
+
+        assert_eq!(complete(&tmp, "cat missing/"), vec![]);
+        assert_eq!(complete(&tmp, "cat Cargo.toml/"), vec![]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn expands_home() {
+        let completer = PathCompleter::new().with_base_dir(PathBuf::from("/base"));
+        let home = Path::new("/home/user");
+
+        assert_eq!(
+            completer.resolve_dir("~/", Some(home)),
+            Some(PathBuf::from("/home/user"))
+        );
+        assert_eq!(
+            completer.resolve_dir("~/src/", Some(home)),
+            Some(PathBuf::from("/home/user/src/"))
+        );
+        assert_eq!(completer.resolve_dir("~/", None), None);
+        assert_eq!(
+            completer.resolve_dir("~user/", Some(home)),
+            Some(PathBuf::from("/base/~user/"))
+        );
+        assert_eq!(
+            completer.resolve_dir("", Some(home)),
+            Some(PathBuf::from("/base"))
+        );
+
+        assert_eq!(
+            PathCompleter::new()
+                .complete("cd ~", 4)
+                .into_iter()
+                .map(|suggestion| (suggestion.value, suggestion.span))
+                .collect::<Vec<_>>(),
+            vec![("~/".to_string(), Span::new(3, 4))]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+/// The quotes the cursor is in, for a [`CompletionTransformer`] to escape the inserted value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteContext {
+    /// Outside of any quotes
+    Unquoted,
+    /// Inside single quotes
+    SingleQuoted,
+    /// Inside double quotes
+    DoubleQuoted,
+}
+
+impl QuoteContext {
+    /// The quotes still open at the end of `line`
+    ///
+    /// Like in a shell, a backslash escapes the next character outside of single quotes, and
+    /// nothing is escaped in single quotes.
+    pub fn at_end_of(line: &str) -> Self {
+        let mut context = Self::Unquoted;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            context = match (context, c) {
+                (Self::Unquoted | Self::DoubleQuoted, '\\') => {
+                    chars.next();
+                    context
+                }
+                (Self::Unquoted, '\'') => Self::SingleQuoted,
+                (Self::Unquoted, '"') => Self::DoubleQuoted,
+                (Self::SingleQuoted, '\'') | (Self::DoubleQuoted, '"') => Self::Unquoted,
+                _ => context,
+            };
+        }
+        context
+    }
+}
+
+/// Transforms the value of a chosen suggestion before it is inserted, knowing the quotes the
+/// cursor is in, see `Reedline::with_completion_transformer`
+pub type CompletionTransformer = dyn Fn(&str, &QuoteContext) -> String + Send;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("cat ", QuoteContext::Unquoted)]
+    #[case("cat '", QuoteContext::SingleQuoted)]
+    #[case("cat \"", QuoteContext::DoubleQuoted)]
+    #[case("cat 'a' \"b\" ", QuoteContext::Unquoted)]
+    #[case("cat \"it's ", QuoteContext::DoubleQuoted)]
+    #[case("cat 'say \"", QuoteContext::SingleQuoted)]
+    // Escaped quotes don't open or close anything
+    #[case("cat \\' ", QuoteContext::Unquoted)]
+    #[case("cat \"a\\\" ", QuoteContext::DoubleQuoted)]
+    // But a backslash is a character like another in single quotes
+    #[case("cat 'a\\' ", QuoteContext::Unquoted)]
+    fn test_quote_context(#[case] line: &str, #[case] expected: QuoteContext) {
+        assert_eq!(QuoteContext::at_end_of(line), expected);
+    }
+}
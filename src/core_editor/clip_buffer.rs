@@ -28,7 +28,7 @@ pub enum ClipboardMode {
 }
 
 /// Simple buffer that provides a clipboard only usable within the application/library.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct LocalClipboard {
     content: String,
     mode: ClipboardMode,
@@ -53,6 +53,7 @@ impl Clipboard for LocalClipboard {
 }
 
 /// Creates a local clipboard
+#[allow(dead_code)]
 pub fn get_local_clipboard() -> Box<dyn Clipboard> {
     Box::new(LocalClipboard::new())
 }
@@ -1,9 +1,15 @@
-use super::{edit_stack::EditStack, Clipboard, ClipboardMode, LineBuffer};
+use super::{
+    edit_stack::EditStack,
+    kill_ring::{KillDirection, KillRing},
+    Clipboard, ClipboardMode, LineBuffer, LocalClipboard, VisualLayout, WordChars,
+};
 #[cfg(feature = "system_clipboard")]
 use crate::core_editor::get_system_clipboard;
-use crate::enums::{EditType, UndoBehavior};
-use crate::{core_editor::get_local_clipboard, EditCommand};
+use crate::enums::{EditType, SelectionMode, UndoBehavior, UndoGranularity};
+use crate::{CompletionTransformer, EditCommand, QuoteContext};
+#[cfg(feature = "system_clipboard")]
 use std::ops::DerefMut;
+use std::{collections::HashMap, ops::Range, time::Instant};
 
 /// Stateful editor executing changes to the underlying [`LineBuffer`]
 ///
@@ -11,24 +17,63 @@ use std::ops::DerefMut;
 /// the undo/redo history and has facilities for cut/copy/yank/paste
 pub struct Editor {
     line_buffer: LineBuffer,
-    cut_buffer: Box<dyn Clipboard>,
+    kill_ring: KillRing,
+    last_kill_ring_command: Option<KillRingCommand>,
+    registers: HashMap<char, LocalClipboard>,
+    selected_register: Option<char>,
     #[cfg(feature = "system_clipboard")]
     system_clipboard: Box<dyn Clipboard>,
     edit_stack: EditStack<LineBuffer>,
     last_undo_behavior: UndoBehavior,
+    undo_granularity: UndoGranularity,
+    last_edit_time: Option<Instant>,
     selection_anchor: Option<usize>,
+    selection_mode: SelectionMode,
+    auto_pairs: bool,
+    completion_transformer: Option<Box<CompletionTransformer>>,
+    visual_layout: VisualLayout,
+    secondary_cursors: Vec<usize>,
+}
+
+/// Last edit command using the kill ring, as long as no other command ran since
+#[derive(Clone, Debug)]
+enum KillRingCommand {
+    /// Killed text, successive kills in the same direction and to the same register are
+    /// appended together
+    Kill {
+        direction: KillDirection,
+        register: Option<char>,
+    },
+    /// Yanked text in that range, which can be replaced by a yank-pop
+    Yank(Range<usize>),
 }
 
+/// Inserted at the start of the lines by [`EditCommand::IndentSelection`]
+const INDENT: &str = "    ";
+
+/// Opening and closing characters completed by each other when auto-pairing is enabled
+const AUTO_PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('`', '`')];
+
 impl Default for Editor {
     fn default() -> Self {
         Editor {
             line_buffer: LineBuffer::new(),
-            cut_buffer: get_local_clipboard(),
+            kill_ring: KillRing::default(),
+            last_kill_ring_command: None,
+            registers: HashMap::new(),
+            selected_register: None,
             #[cfg(feature = "system_clipboard")]
             system_clipboard: get_system_clipboard(),
             edit_stack: EditStack::new(),
             last_undo_behavior: UndoBehavior::CreateUndoPoint,
+            undo_granularity: UndoGranularity::default(),
+            last_edit_time: None,
             selection_anchor: None,
+            selection_mode: SelectionMode::Exclusive,
+            auto_pairs: false,
+            completion_transformer: None,
+            visual_layout: VisualLayout::default(),
+            secondary_cursors: Vec::new(),
         }
     }
 }
@@ -42,11 +87,84 @@ impl Editor {
     /// Set the current [`LineBuffer`].
     /// [`UndoBehavior`] specifies how this change should be reflected on the undo stack.
     pub(crate) fn set_line_buffer(&mut self, line_buffer: LineBuffer, undo_behavior: UndoBehavior) {
-        self.line_buffer = line_buffer;
+        self.replace_line_buffer(line_buffer);
+        self.last_kill_ring_command = None;
         self.update_undo_state(undo_behavior);
     }
 
+    /// Replace the current [`LineBuffer`], keeping the configured [`WordChars`]
+    fn replace_line_buffer(&mut self, mut line_buffer: LineBuffer) {
+        line_buffer.set_word_chars(self.line_buffer.word_chars().clone());
+        self.line_buffer = line_buffer;
+        self.secondary_cursors.clear();
+    }
+
+    /// The cursors added by [`EditCommand::AddCursorsAtWord`] besides the insertion point, in
+    /// increasing order
+    pub(crate) fn secondary_cursors(&self) -> &[usize] {
+        &self.secondary_cursors
+    }
+
+    /// Go back to editing at the insertion point alone
+    pub(crate) fn clear_secondary_cursors(&mut self) {
+        self.secondary_cursors.clear();
+    }
+
+    /// Set which characters join or break the words of the word motions and deletions
+    pub(crate) fn set_word_chars(&mut self, word_chars: WordChars) {
+        self.line_buffer.set_word_chars(word_chars);
+    }
+
+    /// Set how successive edits are grouped into undo steps
+    pub(crate) fn set_undo_granularity(&mut self, undo_granularity: UndoGranularity) {
+        self.undo_granularity = undo_granularity;
+    }
+
+    /// Set whether typed brackets and quotes are auto-paired with their closing character
+    pub(crate) fn set_auto_pairs(&mut self, enable: bool) {
+        self.auto_pairs = enable;
+    }
+
+    /// Where the lines of the buffer wrap on the screen, as last painted
+    pub(crate) fn visual_layout(&self) -> &VisualLayout {
+        &self.visual_layout
+    }
+
+    /// Set where the lines of the buffer wrap on the screen, once they are painted
+    pub(crate) fn set_visual_layout(&mut self, visual_layout: VisualLayout) {
+        self.visual_layout = visual_layout;
+    }
+
+    /// Set the transformer of the completions inserted by the menus
+    pub(crate) fn set_completion_transformer(
+        &mut self,
+        transformer: Option<Box<CompletionTransformer>>,
+    ) {
+        self.completion_transformer = transformer;
+    }
+
+    /// Remove the transformer of the completions, to set it back afterwards
+    pub(crate) fn take_completion_transformer(&mut self) -> Option<Box<CompletionTransformer>> {
+        self.completion_transformer.take()
+    }
+
+    /// The text to insert for a completion `value` replacing the buffer from `start`, once
+    /// transformed with the [`QuoteContext`] of the buffer before it
+    pub(crate) fn transform_completion(&self, value: String, start: usize) -> String {
+        match &self.completion_transformer {
+            Some(transformer) => {
+                let end = start.min(self.line_buffer.len());
+                let context = QuoteContext::at_end_of(&self.line_buffer.get_buffer()[..end]);
+                transformer(&value, &context)
+            }
+            None => value,
+        }
+    }
+
     pub(crate) fn run_edit_command(&mut self, command: &EditCommand) {
+        if !keeps_secondary_cursors(command) {
+            self.secondary_cursors.clear();
+        }
         match command {
             EditCommand::MoveToStart { select } => self.move_to_start(*select),
             EditCommand::MoveToLineStart { select } => self.move_to_line_start(*select),
@@ -66,14 +184,14 @@ impl Editor {
             }
             EditCommand::MoveWordRightEnd { select } => self.move_word_right_end(*select),
             EditCommand::MoveBigWordRightEnd { select } => self.move_big_word_right_end(*select),
-            EditCommand::InsertChar(c) => self.insert_char(*c),
+            EditCommand::InsertChar(c) => self.at_every_cursor(|editor| editor.insert_char(*c)),
             EditCommand::Complete => {}
-            EditCommand::InsertString(str) => self.insert_str(str),
+            EditCommand::InsertString(str) => self.at_every_cursor(|editor| editor.insert_str(str)),
             EditCommand::InsertNewline => self.insert_newline(),
             EditCommand::ReplaceChar(chr) => self.replace_char(*chr),
             EditCommand::ReplaceChars(n_chars, str) => self.replace_chars(*n_chars, str),
-            EditCommand::Backspace => self.backspace(),
-            EditCommand::Delete => self.delete(),
+            EditCommand::Backspace => self.at_every_cursor(Self::backspace),
+            EditCommand::Delete => self.at_every_cursor(Self::delete),
             EditCommand::CutChar => self.cut_char(),
             EditCommand::BackspaceWord => self.line_buffer.delete_word_left(),
             EditCommand::DeleteWord => self.line_buffer.delete_word_right(),
@@ -84,6 +202,7 @@ impl Editor {
             EditCommand::CutFromLineStart => self.cut_from_line_start(),
             EditCommand::CutToEnd => self.cut_from_end(),
             EditCommand::CutToLineEnd => self.cut_to_line_end(),
+            EditCommand::CutToVisualLineEnd => self.cut_to_visual_line_end(),
             EditCommand::CutWordLeft => self.cut_word_left(),
             EditCommand::CutBigWordLeft => self.cut_big_word_left(),
             EditCommand::CutWordRight => self.cut_word_right(),
@@ -92,10 +211,12 @@ impl Editor {
             EditCommand::CutBigWordRightToNext => self.cut_big_word_right_to_next(),
             EditCommand::PasteCutBufferBefore => self.insert_cut_buffer_before(),
             EditCommand::PasteCutBufferAfter => self.insert_cut_buffer_after(),
+            EditCommand::YankPop => self.yank_pop(),
             EditCommand::UppercaseWord => self.line_buffer.uppercase_word(),
             EditCommand::LowercaseWord => self.line_buffer.lowercase_word(),
             EditCommand::SwitchcaseChar => self.line_buffer.switchcase_char(),
             EditCommand::CapitalizeChar => self.line_buffer.capitalize_char(),
+            EditCommand::CapitalizeWord => self.line_buffer.capitalize_word(),
             EditCommand::SwapWords => self.line_buffer.swap_words(),
             EditCommand::SwapGraphemes => self.line_buffer.swap_graphemes(),
             EditCommand::Undo => self.undo(),
@@ -116,10 +237,45 @@ impl Editor {
             EditCommand::MoveLeftBefore { c, select } => {
                 self.move_left_until_char(*c, true, true, *select)
             }
+            EditCommand::MoveRightBeforeNext { c, select } => {
+                self.update_selection_anchor(*select);
+                self.line_buffer.move_right_before_next(*c, true);
+            }
+            EditCommand::MoveLeftBeforeNext { c, select } => {
+                self.update_selection_anchor(*select);
+                self.line_buffer.move_left_before_next(*c, true);
+            }
             EditCommand::SelectAll => self.select_all(),
             EditCommand::CutSelection => self.cut_selection_to_cut_buffer(),
             EditCommand::CopySelection => self.copy_selection_to_cut_buffer(),
             EditCommand::Paste => self.paste_cut_buffer(),
+            EditCommand::CopyCurrentLine => self.copy_current_line(),
+            EditCommand::CopyFromLineStart => self.copy_from_line_start(),
+            EditCommand::CopyToLineEnd => self.copy_to_line_end(),
+            EditCommand::CopyWordLeft => self.copy_left_to(self.line_buffer.word_left_index()),
+            EditCommand::CopyBigWordLeft => {
+                self.copy_left_to(self.line_buffer.big_word_left_index())
+            }
+            EditCommand::CopyWordRight => self.copy_right_to(self.line_buffer.word_right_index()),
+            EditCommand::CopyBigWordRight => self.copy_right_to(self.line_buffer.next_whitespace()),
+            EditCommand::CopyWordRightToNext => {
+                self.copy_right_to(self.line_buffer.word_right_start_index())
+            }
+            EditCommand::CopyBigWordRightToNext => {
+                self.copy_right_to(self.line_buffer.big_word_right_start_index())
+            }
+            EditCommand::CopyLeft => self.copy_left_to(self.line_buffer.grapheme_left_index()),
+            EditCommand::CopyRight => self.copy_right_to(self.line_buffer.grapheme_right_index()),
+            EditCommand::CopyRightUntil(c) => self.copy_right_until_char(*c, false, true),
+            EditCommand::CopyRightBefore(c) => self.copy_right_until_char(*c, true, true),
+            EditCommand::CopyLeftUntil(c) => self.copy_left_until_char(*c, false, true),
+            EditCommand::CopyLeftBefore(c) => self.copy_left_until_char(*c, true, true),
+            EditCommand::SelectRegister(register) => self.selected_register = Some(*register),
+            EditCommand::StartSelection(mode) => self.start_selection(*mode),
+            EditCommand::SwapSelectionEnds => self.swap_selection_ends(),
+            EditCommand::ClearSelection => {}
+            EditCommand::IndentSelection => self.indent_selection(),
+            EditCommand::AddCursorsAtWord => self.add_cursors_at_word(),
             #[cfg(feature = "system_clipboard")]
             EditCommand::CutSelectionSystem => self.cut_selection_to_system(),
             #[cfg(feature = "system_clipboard")]
@@ -127,10 +283,25 @@ impl Editor {
             #[cfg(feature = "system_clipboard")]
             EditCommand::PasteSystem => self.paste_from_system(),
         }
-        if !matches!(command.edit_type(), EditType::MoveCursor { select: true }) {
+        // The register is selected before cutting or copying the selection
+        if !matches!(command.edit_type(), EditType::MoveCursor { select: true })
+            && !matches!(command, EditCommand::SelectRegister(_))
+        {
             self.selection_anchor = None;
         }
+        // Any other command interrupts the successive kills and yanks
+        if !uses_kill_ring(command) {
+            self.last_kill_ring_command = None;
+        }
+        // The register only applies to the command right after its selection
+        if !matches!(command, EditCommand::SelectRegister(_)) {
+            self.selected_register = None;
+        }
         if let EditType::MoveCursor { select: true } = command.edit_type() {}
+        // Nothing to undo after copying the text
+        if command.edit_type() == EditType::NoOp {
+            return;
+        }
 
         let new_undo_behavior = match (command, command.edit_type()) {
             (_, EditType::MoveCursor { .. }) => UndoBehavior::MoveCursor,
@@ -150,6 +321,9 @@ impl Editor {
         self.update_undo_state(new_undo_behavior);
     }
     fn update_selection_anchor(&mut self, select: bool) {
+        if select && self.selection_anchor.is_none() {
+            self.selection_mode = SelectionMode::Exclusive;
+        }
         self.selection_anchor = if select {
             self.selection_anchor
                 .or_else(|| Some(self.insertion_point()))
@@ -157,6 +331,53 @@ impl Editor {
             None
         };
     }
+
+    fn start_selection(&mut self, mode: SelectionMode) {
+        self.update_selection_anchor(true);
+        self.selection_mode = mode;
+    }
+
+    fn swap_selection_ends(&mut self) {
+        if let Some(anchor) = self.selection_anchor {
+            self.selection_anchor = Some(self.insertion_point());
+            self.line_buffer.set_insertion_point(anchor);
+        }
+    }
+
+    fn indent_selection(&mut self) {
+        let (start, end) = self.get_selection().unwrap_or_else(|| {
+            let insertion_point = self.insertion_point();
+            (insertion_point, insertion_point)
+        });
+        let buffer = self.line_buffer.get_buffer();
+        let first_line_start = buffer[..start].rfind('\n').map_or(0, |index| index + 1);
+        // Like vi, empty lines are left alone
+        let line_starts = std::iter::once(first_line_start)
+            .chain(
+                buffer[first_line_start..end]
+                    .match_indices('\n')
+                    .map(|(index, _)| first_line_start + index + 1)
+                    .filter(|&line_start| line_start < end),
+            )
+            .filter(|&line_start| {
+                !buffer[line_start..].starts_with('\n') && line_start < buffer.len()
+            })
+            .collect::<Vec<_>>();
+
+        for &line_start in line_starts.iter().rev() {
+            self.line_buffer
+                .replace_range(line_start..line_start, INDENT);
+        }
+
+        // The cursor goes to the first non-blank of the first line, as in vi
+        if let Some(&line_start) = line_starts.first() {
+            let indentation = self.line_buffer.get_buffer()[line_start..]
+                .find(|c: char| c == '\n' || !c.is_whitespace())
+                .unwrap_or(0);
+            self.line_buffer
+                .set_insertion_point(line_start + indentation);
+        }
+    }
     fn move_to_position(&mut self, position: usize, select: bool) {
         self.update_selection_anchor(select);
         self.line_buffer.set_insertion_point(position)
@@ -183,6 +404,7 @@ impl Editor {
         F: FnOnce(&mut LineBuffer),
     {
         self.update_undo_state(undo_behavior);
+        self.last_kill_ring_command = None;
         func(&mut self.line_buffer);
     }
 
@@ -190,6 +412,7 @@ impl Editor {
     /// Insertion point update to the end of the buffer.
     pub(crate) fn set_buffer(&mut self, buffer: String, undo_behavior: UndoBehavior) {
         self.line_buffer.set_buffer(buffer);
+        self.last_kill_ring_command = None;
         self.update_undo_state(undo_behavior);
     }
 
@@ -238,13 +461,13 @@ impl Editor {
     }
 
     fn undo(&mut self) {
-        let val = self.edit_stack.undo();
-        self.line_buffer = val.clone();
+        let val = self.edit_stack.undo().clone();
+        self.replace_line_buffer(val);
     }
 
     fn redo(&mut self) {
-        let val = self.edit_stack.redo();
-        self.line_buffer = val.clone();
+        let val = self.edit_stack.redo().clone();
+        self.replace_line_buffer(val);
     }
 
     pub(crate) fn update_undo_state(&mut self, undo_behavior: UndoBehavior) {
@@ -252,19 +475,145 @@ impl Editor {
             self.last_undo_behavior = UndoBehavior::UndoRedo;
             return;
         }
-        if !undo_behavior.create_undo_point_after(&self.last_undo_behavior) {
+        if !self.create_undo_point(&undo_behavior) {
             self.edit_stack.undo();
         }
         self.edit_stack.insert(self.line_buffer.clone());
+        if !matches!(undo_behavior, UndoBehavior::MoveCursor) {
+            self.last_edit_time = Some(Instant::now());
+        }
         self.last_undo_behavior = undo_behavior;
     }
 
+    /// Whether the edit starts a new undo step rather than joining the previous one
+    fn create_undo_point(&self, undo_behavior: &UndoBehavior) -> bool {
+        let ends_group = match self.undo_granularity {
+            UndoGranularity::Character => true,
+            UndoGranularity::Word => false,
+            UndoGranularity::WordOrPause(pause) => self
+                .last_edit_time
+                .map_or(false, |last_edit_time| last_edit_time.elapsed() >= pause),
+        };
+
+        undo_behavior.create_undo_point_after(&self.last_undo_behavior)
+            || (ends_group && undo_behavior.is_character_edit())
+    }
+
+    /// Kill the text in `range`, appending it to the previous kill if it was in the same `direction`
+    ///
+    /// Kills without a direction are never appended to. Unless a register is selected, kills
+    /// within a line are also written to the small delete register `-`.
+    fn kill(&mut self, range: Range<usize>, mode: ClipboardMode, direction: Option<KillDirection>) {
+        let text = &self.line_buffer.get_buffer()[range];
+        let register = self
+            .selected_register
+            .take()
+            .filter(|register| *register != '"');
+
+        let append = direction.filter(|direction| {
+            matches!(
+                &self.last_kill_ring_command,
+                Some(KillRingCommand::Kill { direction: previous, register: previous_register })
+                    if previous == direction && *previous_register == register
+            )
+        });
+
+        match append {
+            Some(direction) => self.kill_ring.append(text, direction),
+            None => self.kill_ring.set(text, mode),
+        }
+
+        let target = register.or_else(|| (!text.contains('\n')).then_some('-'));
+        if let Some(target) = target {
+            write_register(&mut self.registers, target, text, mode, append);
+        }
+
+        self.last_kill_ring_command = direction.map(|direction| KillRingCommand::Kill {
+            direction,
+            register,
+        });
+    }
+
+    /// Copy the text in `range`, also to the yank register `0` unless a register is selected
+    fn copy(&mut self, range: Range<usize>, mode: ClipboardMode) {
+        let text = &self.line_buffer.get_buffer()[range];
+        let register = self
+            .selected_register
+            .take()
+            .filter(|register| *register != '"');
+
+        self.kill_ring.set(text, mode);
+        write_register(
+            &mut self.registers,
+            register.unwrap_or('0'),
+            text,
+            mode,
+            None,
+        );
+    }
+
+    fn copy_current_line(&mut self) {
+        let range = self.line_buffer.current_line_range();
+        self.copy(range, ClipboardMode::Lines);
+    }
+
+    fn copy_from_line_start(&mut self) {
+        let start = self.line_buffer.current_line_range().start;
+        self.copy(
+            start..self.line_buffer.insertion_point(),
+            ClipboardMode::Normal,
+        );
+    }
+
+    fn copy_to_line_end(&mut self) {
+        let end = self.line_buffer.find_current_line_end();
+        self.copy(
+            self.line_buffer.insertion_point()..end,
+            ClipboardMode::Normal,
+        );
+    }
+
+    fn copy_left_to(&mut self, left_index: usize) {
+        let insertion_offset = self.line_buffer.insertion_point();
+        if left_index < insertion_offset {
+            self.copy(left_index..insertion_offset, ClipboardMode::Normal);
+        }
+    }
+
+    fn copy_right_to(&mut self, right_index: usize) {
+        let insertion_offset = self.line_buffer.insertion_point();
+        if right_index > insertion_offset {
+            self.copy(insertion_offset..right_index, ClipboardMode::Normal);
+        }
+    }
+
+    fn copy_right_until_char(&mut self, c: char, before_char: bool, current_line: bool) {
+        if let Some(index) = self.line_buffer.find_char_right(c, current_line) {
+            let end = if before_char {
+                index
+            } else {
+                self.line_buffer.grapheme_end_index(index)
+            };
+            self.copy_right_to(end);
+        }
+    }
+
+    fn copy_left_until_char(&mut self, c: char, before_char: bool, current_line: bool) {
+        if let Some(index) = self.line_buffer.find_char_left(c, current_line) {
+            let start = if before_char {
+                self.line_buffer.grapheme_end_index(index)
+            } else {
+                index
+            };
+            self.copy_left_to(start);
+        }
+    }
+
     fn cut_current_line(&mut self) {
         let deletion_range = self.line_buffer.current_line_range();
 
-        let cut_slice = &self.line_buffer.get_buffer()[deletion_range.clone()];
-        if !cut_slice.is_empty() {
-            self.cut_buffer.set(cut_slice, ClipboardMode::Lines);
+        if !deletion_range.is_empty() {
+            self.kill(deletion_range.clone(), ClipboardMode::Lines, None);
             self.line_buffer.set_insertion_point(deletion_range.start);
             self.line_buffer.clear_range(deletion_range);
         }
@@ -273,9 +622,10 @@ impl Editor {
     fn cut_from_start(&mut self) {
         let insertion_offset = self.line_buffer.insertion_point();
         if insertion_offset > 0 {
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[..insertion_offset],
+            self.kill(
+                0..insertion_offset,
                 ClipboardMode::Normal,
+                Some(KillDirection::Backward),
             );
             self.line_buffer.clear_to_insertion_point();
         }
@@ -285,52 +635,75 @@ impl Editor {
         let previous_offset = self.line_buffer.insertion_point();
         self.line_buffer.move_to_line_start();
         let deletion_range = self.line_buffer.insertion_point()..previous_offset;
-        let cut_slice = &self.line_buffer.get_buffer()[deletion_range.clone()];
-        if !cut_slice.is_empty() {
-            self.cut_buffer.set(cut_slice, ClipboardMode::Normal);
+        if !deletion_range.is_empty() {
+            self.kill(
+                deletion_range.clone(),
+                ClipboardMode::Normal,
+                Some(KillDirection::Backward),
+            );
             self.line_buffer.clear_range(deletion_range);
         }
     }
 
     fn cut_from_end(&mut self) {
-        let cut_slice = &self.line_buffer.get_buffer()[self.line_buffer.insertion_point()..];
-        if !cut_slice.is_empty() {
-            self.cut_buffer.set(cut_slice, ClipboardMode::Normal);
+        let cut_range = self.line_buffer.insertion_point()..self.line_buffer.get_buffer().len();
+        if !cut_range.is_empty() {
+            self.kill(
+                cut_range,
+                ClipboardMode::Normal,
+                Some(KillDirection::Forward),
+            );
             self.line_buffer.clear_to_end();
         }
     }
 
     fn cut_to_line_end(&mut self) {
-        let cut_slice = &self.line_buffer.get_buffer()
-            [self.line_buffer.insertion_point()..self.line_buffer.find_current_line_end()];
-        if !cut_slice.is_empty() {
-            self.cut_buffer.set(cut_slice, ClipboardMode::Normal);
+        let cut_range =
+            self.line_buffer.insertion_point()..self.line_buffer.find_current_line_end();
+        if !cut_range.is_empty() {
+            self.kill(
+                cut_range,
+                ClipboardMode::Normal,
+                Some(KillDirection::Forward),
+            );
             self.line_buffer.clear_to_line_end();
         }
     }
 
-    fn cut_word_left(&mut self) {
-        let insertion_offset = self.line_buffer.insertion_point();
-        let left_index = self.line_buffer.word_left_index();
-        if left_index < insertion_offset {
-            let cut_range = left_index..insertion_offset;
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[cut_range.clone()],
+    fn cut_to_visual_line_end(&mut self) {
+        let insertion_point = self.line_buffer.insertion_point();
+        let row_end = self
+            .visual_layout
+            .row_end(self.line_buffer.get_buffer(), insertion_point);
+        let cut_range = insertion_point..row_end;
+        if !cut_range.is_empty() {
+            self.kill(
+                cut_range.clone(),
                 ClipboardMode::Normal,
+                Some(KillDirection::Forward),
             );
             self.line_buffer.clear_range(cut_range);
-            self.line_buffer.set_insertion_point(left_index);
         }
     }
 
+    fn cut_word_left(&mut self) {
+        let left_index = self.line_buffer.word_left_index();
+        self.cut_left_to(left_index);
+    }
+
     fn cut_big_word_left(&mut self) {
-        let insertion_offset = self.line_buffer.insertion_point();
         let left_index = self.line_buffer.big_word_left_index();
+        self.cut_left_to(left_index);
+    }
+
+    fn cut_left_to(&mut self, left_index: usize) {
+        let insertion_offset = self.line_buffer.insertion_point();
         if left_index < insertion_offset {
             let cut_range = left_index..insertion_offset;
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[cut_range.clone()],
+            self.kill(
+                cut_range.clone(),
                 ClipboardMode::Normal,
+                Some(KillDirection::Backward),
             );
             self.line_buffer.clear_range(cut_range);
             self.line_buffer.set_insertion_point(left_index);
@@ -338,52 +711,33 @@ impl Editor {
     }
 
     fn cut_word_right(&mut self) {
-        let insertion_offset = self.line_buffer.insertion_point();
         let right_index = self.line_buffer.word_right_index();
-        if right_index > insertion_offset {
-            let cut_range = insertion_offset..right_index;
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[cut_range.clone()],
-                ClipboardMode::Normal,
-            );
-            self.line_buffer.clear_range(cut_range);
-        }
+        self.cut_right_to(right_index);
     }
 
     fn cut_big_word_right(&mut self) {
-        let insertion_offset = self.line_buffer.insertion_point();
         let right_index = self.line_buffer.next_whitespace();
-        if right_index > insertion_offset {
-            let cut_range = insertion_offset..right_index;
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[cut_range.clone()],
-                ClipboardMode::Normal,
-            );
-            self.line_buffer.clear_range(cut_range);
-        }
+        self.cut_right_to(right_index);
     }
 
     fn cut_word_right_to_next(&mut self) {
-        let insertion_offset = self.line_buffer.insertion_point();
         let right_index = self.line_buffer.word_right_start_index();
-        if right_index > insertion_offset {
-            let cut_range = insertion_offset..right_index;
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[cut_range.clone()],
-                ClipboardMode::Normal,
-            );
-            self.line_buffer.clear_range(cut_range);
-        }
+        self.cut_right_to(right_index);
     }
 
     fn cut_big_word_right_to_next(&mut self) {
-        let insertion_offset = self.line_buffer.insertion_point();
         let right_index = self.line_buffer.big_word_right_start_index();
+        self.cut_right_to(right_index);
+    }
+
+    fn cut_right_to(&mut self, right_index: usize) {
+        let insertion_offset = self.line_buffer.insertion_point();
         if right_index > insertion_offset {
             let cut_range = insertion_offset..right_index;
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[cut_range.clone()],
+            self.kill(
+                cut_range.clone(),
                 ClipboardMode::Normal,
+                Some(KillDirection::Forward),
             );
             self.line_buffer.clear_range(cut_range);
         }
@@ -394,36 +748,81 @@ impl Editor {
         let right_index = self.line_buffer.grapheme_right_index();
         if right_index > insertion_offset {
             let cut_range = insertion_offset..right_index;
-            self.cut_buffer.set(
-                &self.line_buffer.get_buffer()[cut_range.clone()],
-                ClipboardMode::Normal,
-            );
+            self.kill(cut_range.clone(), ClipboardMode::Normal, None);
             self.line_buffer.clear_range(cut_range);
         }
     }
 
     fn insert_cut_buffer_before(&mut self) {
         self.delete_selection();
-        insert_clipboard_content_before(&mut self.line_buffer, self.cut_buffer.deref_mut())
+        self.yank(|line_buffer, kill_ring| insert_clipboard_content_before(line_buffer, kill_ring));
     }
 
     fn insert_cut_buffer_after(&mut self) {
         self.delete_selection();
-        match self.cut_buffer.get() {
+        self.yank(|line_buffer, kill_ring| match kill_ring.get() {
             (content, ClipboardMode::Normal) => {
-                self.line_buffer.move_right();
-                self.line_buffer.insert_str(&content);
+                line_buffer.move_right();
+                line_buffer.insert_str(&content);
             }
             (mut content, ClipboardMode::Lines) => {
                 // TODO: Simplify that?
-                self.line_buffer.move_to_line_start();
-                self.line_buffer.move_line_down();
+                line_buffer.move_to_line_start();
+                line_buffer.move_line_down();
                 if !content.ends_with('\n') {
                     // TODO: Make sure platform requirements are met
                     content.push('\n');
                 }
-                self.line_buffer.insert_str(&content);
+                line_buffer.insert_str(&content);
             }
+        });
+    }
+
+    /// Insert the latest kill with `insert`, remembering where it was inserted to yank-pop it
+    ///
+    /// If a register is selected, its content is inserted instead.
+    /// The insertion point must end up right after the inserted text.
+    fn yank(&mut self, insert: impl FnOnce(&mut LineBuffer, &mut dyn Clipboard)) {
+        let register = self
+            .selected_register
+            .take()
+            .filter(|register| *register != '"');
+        if let Some(register) = register {
+            if let Some(clipboard) = self.registers.get_mut(&register.to_ascii_lowercase()) {
+                insert(&mut self.line_buffer, clipboard);
+            }
+            return;
+        }
+
+        let previous_len = self.line_buffer.len();
+        insert(&mut self.line_buffer, &mut self.kill_ring);
+
+        let end = self.line_buffer.insertion_point();
+        let start = end - (self.line_buffer.len() - previous_len);
+
+        self.last_kill_ring_command = Some(KillRingCommand::Yank(start..end));
+    }
+
+    /// Whether [`EditCommand::YankPop`] has another kill to replace the one just yanked
+    pub(crate) fn can_yank_pop(&self) -> bool {
+        matches!(self.last_kill_ring_command, Some(KillRingCommand::Yank(_)))
+            && self.kill_ring.can_rotate()
+    }
+
+    /// Replace the text that was just yanked with the previous kill
+    fn yank_pop(&mut self) {
+        let Some(KillRingCommand::Yank(range)) = self.last_kill_ring_command.clone() else {
+            return;
+        };
+
+        if let Some((content, _)) = self.kill_ring.rotate() {
+            self.line_buffer.replace_range(range.clone(), &content);
+            self.line_buffer
+                .set_insertion_point(range.start + content.len());
+
+            self.last_kill_ring_command = Some(KillRingCommand::Yank(
+                range.start..range.start + content.len(),
+            ));
         }
     }
 
@@ -461,12 +860,19 @@ impl Editor {
         if let Some(index) = self.line_buffer.find_char_right(c, current_line) {
             // Saving the section of the string that will be deleted to be
             // stored into the buffer
-            let extra = if before_char { 0 } else { c.len_utf8() };
-            let cut_slice =
-                &self.line_buffer.get_buffer()[self.line_buffer.insertion_point()..index + extra];
+            let end = if before_char {
+                index
+            } else {
+                self.line_buffer.grapheme_end_index(index)
+            };
+            let cut_range = self.line_buffer.insertion_point()..end;
 
-            if !cut_slice.is_empty() {
-                self.cut_buffer.set(cut_slice, ClipboardMode::Normal);
+            if !cut_range.is_empty() {
+                self.kill(
+                    cut_range,
+                    ClipboardMode::Normal,
+                    Some(KillDirection::Forward),
+                );
 
                 if before_char {
                     self.line_buffer.delete_right_before_char(c, current_line);
@@ -481,12 +887,19 @@ impl Editor {
         if let Some(index) = self.line_buffer.find_char_left(c, current_line) {
             // Saving the section of the string that will be deleted to be
             // stored into the buffer
-            let extra = if before_char { c.len_utf8() } else { 0 };
-            let cut_slice =
-                &self.line_buffer.get_buffer()[index + extra..self.line_buffer.insertion_point()];
+            let start = if before_char {
+                self.line_buffer.grapheme_end_index(index)
+            } else {
+                index
+            };
+            let cut_range = start..self.line_buffer.insertion_point();
 
-            if !cut_slice.is_empty() {
-                self.cut_buffer.set(cut_slice, ClipboardMode::Normal);
+            if !cut_range.is_empty() {
+                self.kill(
+                    cut_range,
+                    ClipboardMode::Normal,
+                    Some(KillDirection::Backward),
+                );
 
                 if before_char {
                     self.line_buffer.delete_left_before_char(c, current_line);
@@ -538,8 +951,7 @@ impl Editor {
 
     fn cut_selection_to_cut_buffer(&mut self) {
         if let Some((start, end)) = self.get_selection() {
-            let cut_slice = &self.line_buffer.get_buffer()[start..end];
-            self.cut_buffer.set(cut_slice, ClipboardMode::Normal);
+            self.kill(start..end, self.selection_clipboard_mode(), None);
             self.line_buffer.clear_range_safe(start, end);
             self.selection_anchor = None;
         }
@@ -555,20 +967,38 @@ impl Editor {
 
     fn copy_selection_to_cut_buffer(&mut self) {
         if let Some((start, end)) = self.get_selection() {
-            let cut_slice = &self.line_buffer.get_buffer()[start..end];
-            self.cut_buffer.set(cut_slice, ClipboardMode::Normal);
+            self.copy(start..end, self.selection_clipboard_mode());
+        }
+    }
+
+    /// Line selections are pasted as lines
+    fn selection_clipboard_mode(&self) -> ClipboardMode {
+        match self.selection_mode {
+            SelectionMode::Exclusive | SelectionMode::Inclusive => ClipboardMode::Normal,
+            SelectionMode::Lines => ClipboardMode::Lines,
         }
     }
 
     /// If a selection is active returns the selected range, otherwise None.
-    /// The range is guaranteed to be ascending.
+    /// The range is guaranteed to be ascending, and extended according to the [`SelectionMode`].
     pub fn get_selection(&self) -> Option<(usize, usize)> {
-        self.selection_anchor.map(|selection_anchor| {
-            if self.insertion_point() > selection_anchor {
-                (selection_anchor, self.insertion_point())
-            } else {
-                (self.insertion_point(), selection_anchor)
-            }
+        let selection_anchor = self.selection_anchor?;
+        let (start, end) = if self.insertion_point() > selection_anchor {
+            (selection_anchor, self.insertion_point())
+        } else {
+            (self.insertion_point(), selection_anchor)
+        };
+
+        let buffer = self.line_buffer.get_buffer();
+        Some(match self.selection_mode {
+            SelectionMode::Exclusive => (start, end),
+            SelectionMode::Inclusive => (start, self.line_buffer.grapheme_end_index(end)),
+            SelectionMode::Lines => (
+                buffer[..start].rfind('\n').map_or(0, |index| index + 1),
+                buffer[end..]
+                    .find('\n')
+                    .map_or(buffer.len(), |index| end + index + 1),
+            ),
         })
     }
 
@@ -583,10 +1013,22 @@ impl Editor {
         if self.selection_anchor.is_some() {
             self.delete_selection();
         } else {
+            if self.auto_pairs && self.is_between_pair() {
+                self.line_buffer.delete_right_grapheme();
+            }
             self.line_buffer.delete_left_grapheme();
         }
     }
 
+    /// Whether the cursor is right between an opening character and its closing one
+    fn is_between_pair(&self) -> bool {
+        let left = self.line_buffer.grapheme_left();
+        let right = self.line_buffer.grapheme_right();
+        AUTO_PAIRS
+            .iter()
+            .any(|(open, close)| is_char(left, *open) && is_char(right, *close))
+    }
+
     fn delete(&mut self) {
         if self.selection_anchor.is_some() {
             self.delete_selection();
@@ -624,15 +1066,135 @@ impl Editor {
     }
 
     fn insert_char(&mut self, c: char) {
+        if self.auto_pairs && self.selection_anchor.is_none() && self.insert_auto_paired(c) {
+            return;
+        }
         self.delete_selection();
         self.line_buffer.insert_char(c);
     }
 
+    /// Type over a closing character already next to the cursor, or insert an opening one
+    /// along with its closing character and put the cursor between them.
+    ///
+    /// Only pairs in front of whitespace, a closing character or the end of the buffer, and
+    /// not quotes right after a word, to leave existing text and apostrophes alone.
+    /// Returns `false` if `c` has to be inserted normally.
+    fn insert_auto_paired(&mut self, c: char) -> bool {
+        let next = self.line_buffer.grapheme_right();
+        if is_char(next, c) && AUTO_PAIRS.iter().any(|(_, close)| *close == c) {
+            self.line_buffer.move_right();
+            return true;
+        }
+
+        let Some((open, close)) = AUTO_PAIRS.iter().find(|(open, _)| *open == c) else {
+            return false;
+        };
+        let pairs_before_next = next.is_empty()
+            || next.chars().all(char::is_whitespace)
+            || AUTO_PAIRS.iter().any(|(_, close)| is_char(next, *close));
+        let after_word = open == close
+            && self
+                .line_buffer
+                .grapheme_left()
+                .chars()
+                .any(char::is_alphanumeric);
+        if !pairs_before_next || after_word {
+            return false;
+        }
+
+        self.line_buffer.insert_char(*open);
+        self.line_buffer.insert_char(*close);
+        self.line_buffer.move_left();
+        true
+    }
+
     fn insert_str(&mut self, str: &str) {
         self.delete_selection();
         self.line_buffer.insert_str(str);
     }
 
+    /// Put a secondary cursor at the offset of the insertion point in the word under it, in
+    /// every other occurrence of that whole word
+    ///
+    /// The insertion point right after a word, or right before it, is also in that word, a
+    /// word being preferred to punctuation.
+    fn add_cursors_at_word(&mut self) {
+        let insertion_point = self.line_buffer.insertion_point();
+        let segments = self
+            .line_buffer
+            .word_chars()
+            .split_word_bound_indices(self.line_buffer.get_buffer());
+        let Some(&(word_start, word)) = segments
+            .iter()
+            .filter(|(start, segment)| {
+                !segment.trim().is_empty()
+                    && (*start..=start + segment.len()).contains(&insertion_point)
+            })
+            .max_by_key(|(start, segment)| {
+                (
+                    segment.chars().any(char::is_alphanumeric),
+                    start + segment.len() > insertion_point,
+                )
+            })
+        else {
+            return;
+        };
+
+        let offset = insertion_point - word_start;
+        self.secondary_cursors = segments
+            .iter()
+            .filter(|(start, segment)| *segment == word && *start != word_start)
+            .map(|(start, _)| start + offset)
+            .collect();
+    }
+
+    /// Run `edit` at the insertion point, then at each secondary cursor
+    ///
+    /// The edits go from the last cursor to the first one, so the text changed at a cursor
+    /// only shifts the cursors already edited. Cursors meeting after a deletion are merged.
+    fn at_every_cursor(&mut self, edit: impl Fn(&mut Self)) {
+        if self.secondary_cursors.is_empty() {
+            edit(self);
+            return;
+        }
+
+        let insertion_point = self.line_buffer.insertion_point();
+        let mut cursors = self.secondary_cursors.clone();
+        cursors.push(insertion_point);
+        cursors.sort_unstable();
+        cursors.dedup();
+
+        // The cursors once edited, with whether it is the insertion point
+        let mut edited: Vec<(usize, bool)> = Vec::with_capacity(cursors.len());
+        for cursor in cursors.into_iter().rev() {
+            let len = self.line_buffer.len();
+            self.line_buffer.set_insertion_point(cursor);
+            edit(self);
+            let new_len = self.line_buffer.len();
+            for (position, _) in &mut edited {
+                // Never below the text deleted before it
+                *position = *position + new_len - len;
+            }
+            edited.push((
+                self.line_buffer.insertion_point(),
+                cursor == insertion_point,
+            ));
+        }
+
+        let new_insertion_point = edited
+            .iter()
+            .find_map(|(position, is_insertion_point)| is_insertion_point.then_some(*position))
+            .unwrap_or(insertion_point);
+        self.line_buffer.set_insertion_point(new_insertion_point);
+        self.secondary_cursors = edited
+            .into_iter()
+            .map(|(position, _)| position)
+            .filter(|position| *position != new_insertion_point)
+            .collect();
+        self.secondary_cursors.sort_unstable();
+        self.secondary_cursors.dedup();
+    }
+
     fn insert_newline(&mut self) {
         self.delete_selection();
         self.line_buffer.insert_newline();
@@ -646,7 +1208,79 @@ impl Editor {
 
     fn paste_cut_buffer(&mut self) {
         self.delete_selection();
-        insert_clipboard_content_before(&mut self.line_buffer, self.cut_buffer.deref_mut());
+        self.yank(|line_buffer, kill_ring| insert_clipboard_content_before(line_buffer, kill_ring));
+    }
+}
+
+/// Whether the command edits at every cursor, otherwise it goes back to the insertion point alone
+fn keeps_secondary_cursors(command: &EditCommand) -> bool {
+    matches!(
+        command,
+        EditCommand::InsertChar(_)
+            | EditCommand::InsertString(_)
+            | EditCommand::Backspace
+            | EditCommand::Delete
+            | EditCommand::AddCursorsAtWord
+    )
+}
+
+/// Whether the command kills or yanks text, otherwise it interrupts successive kills and yanks
+fn uses_kill_ring(command: &EditCommand) -> bool {
+    matches!(
+        command,
+        EditCommand::CutChar
+            | EditCommand::CutCurrentLine
+            | EditCommand::CutFromStart
+            | EditCommand::CutFromLineStart
+            | EditCommand::CutToEnd
+            | EditCommand::CutToLineEnd
+            | EditCommand::CutToVisualLineEnd
+            | EditCommand::CutWordLeft
+            | EditCommand::CutBigWordLeft
+            | EditCommand::CutWordRight
+            | EditCommand::CutBigWordRight
+            | EditCommand::CutWordRightToNext
+            | EditCommand::CutBigWordRightToNext
+            | EditCommand::CutRightUntil(_)
+            | EditCommand::CutRightBefore(_)
+            | EditCommand::CutLeftUntil(_)
+            | EditCommand::CutLeftBefore(_)
+            | EditCommand::CutSelection
+            | EditCommand::PasteCutBufferBefore
+            | EditCommand::PasteCutBufferAfter
+            | EditCommand::Paste
+            | EditCommand::YankPop
+    )
+}
+
+/// Write `text` to the vi `register`, appending it in the `append` direction if any
+///
+/// Uppercase letters always append to the register of the lowercase letter.
+fn write_register(
+    registers: &mut HashMap<char, LocalClipboard>,
+    register: char,
+    text: &str,
+    mode: ClipboardMode,
+    append: Option<KillDirection>,
+) {
+    let clipboard = registers.entry(register.to_ascii_lowercase()).or_default();
+
+    let append = if register.is_ascii_uppercase() {
+        Some(KillDirection::Forward)
+    } else {
+        append
+    };
+
+    match append {
+        Some(direction) => {
+            let (mut content, previous_mode) = clipboard.get();
+            match direction {
+                KillDirection::Forward => content.push_str(text),
+                KillDirection::Backward => content.insert_str(0, text),
+            }
+            clipboard.set(&content, previous_mode);
+        }
+        None => clipboard.set(text, mode),
     }
 }
 
@@ -668,6 +1302,12 @@ fn insert_clipboard_content_before(line_buffer: &mut LineBuffer, clipboard: &mut
     }
 }
 
+/// Whether the grapheme is exactly the character `c`
+fn is_char(grapheme: &str, c: char) -> bool {
+    let mut chars = grapheme.chars();
+    chars.next() == Some(c) && chars.next().is_none()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -693,6 +1333,18 @@ mod test {
         assert_eq!(editor.get_buffer(), expected);
     }
 
+    #[test]
+    fn test_word_chars_survive_undo() {
+        let mut editor = editor_with("abc def-ghi");
+        editor.set_word_chars(WordChars::new().with_joining("-"));
+
+        editor.run_edit_command(&EditCommand::InsertString(" jkl".to_string()));
+        editor.run_edit_command(&EditCommand::Undo);
+        editor.cut_word_left();
+
+        assert_eq!(editor.get_buffer(), "abc ");
+    }
+
     #[rstest]
     #[case("abc def ghi", 11, "abc def ")]
     #[case("abc def-ghi", 11, "abc ")]
@@ -756,6 +1408,64 @@ mod test {
         assert_eq!(editor.get_buffer(), expected);
     }
 
+    #[rstest]
+    #[case("", 0, "(", "()", 1)]
+    #[case("", 0, "()", "()", 2)]
+    #[case("", 0, "[{\"", "[{\"\"}]", 3)]
+    #[case("", 0, "f(a, `b`)", "f(a, `b`)", 9)]
+    #[case("", 0, "(a))", "(a))", 4)]
+    #[case("foo", 0, "(", "(foo", 1)]
+    #[case("foo bar", 3, "(", "foo() bar", 4)]
+    #[case("don", 3, "\"", "don\"", 4)]
+    #[case("", 0, "'", "'", 1)]
+    #[case("", 0, "(\u{8}", "", 0)]
+    #[case("(a)", 1, "\u{8}", "a)", 0)]
+    #[case("())", 1, "\u{8}", ")", 0)]
+    fn test_auto_pairs(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] typed: &str,
+        #[case] expected: &str,
+        #[case] expected_position: usize,
+    ) {
+        let mut editor = editor_with(input);
+        editor.set_auto_pairs(true);
+        editor.line_buffer.set_insertion_point(position);
+
+        // Backspace is written '\u{8}'
+        for c in typed.chars() {
+            let command = match c {
+                '\u{8}' => EditCommand::Backspace,
+                c => EditCommand::InsertChar(c),
+            };
+            editor.run_edit_command(&command);
+        }
+
+        assert_eq!(editor.get_buffer(), expected);
+        assert_eq!(editor.insertion_point(), expected_position);
+    }
+
+    #[test]
+    fn test_auto_pairs_leave_pasted_text_alone() {
+        let mut editor = editor_with("");
+        editor.set_auto_pairs(true);
+
+        editor.run_edit_command(&EditCommand::InsertString("f(\"".to_string()));
+
+        assert_eq!(editor.get_buffer(), "f(\"");
+    }
+
+    #[test]
+    fn test_auto_pairs_disabled() {
+        let mut editor = editor_with("");
+
+        for command in str_to_edit_commands("(\"") {
+            editor.run_edit_command(&command);
+        }
+
+        assert_eq!(editor.get_buffer(), "(\"");
+    }
+
     fn str_to_edit_commands(s: &str) -> Vec<EditCommand> {
         s.chars().map(EditCommand::InsertChar).collect()
     }
@@ -773,6 +1483,84 @@ mod test {
         assert_eq!(editor.get_buffer(), "This is  a test");
     }
 
+    #[test]
+    fn test_undo_sentence_removes_last_word() {
+        let mut editor = editor_with("");
+        for cmd in str_to_edit_commands("undo the last word") {
+            editor.run_edit_command(&cmd);
+        }
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "undo the last");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "undo the");
+    }
+
+    #[test]
+    fn test_undo_groups_separately_inserts_and_deletions() {
+        let mut editor = editor_with("");
+        for cmd in str_to_edit_commands("abcd") {
+            editor.run_edit_command(&cmd);
+        }
+        editor.run_edit_command(&EditCommand::Backspace);
+        editor.run_edit_command(&EditCommand::Backspace);
+        editor.run_edit_command(&EditCommand::InsertChar('x'));
+        assert_eq!(editor.get_buffer(), "abx");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "ab");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "abcd");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "");
+    }
+
+    #[test]
+    fn test_undo_groups_end_at_cursor_jumps() {
+        let mut editor = editor_with("");
+        for cmd in str_to_edit_commands("word") {
+            editor.run_edit_command(&cmd);
+        }
+        editor.run_edit_command(&EditCommand::MoveToStart { select: false });
+        for cmd in str_to_edit_commands("a") {
+            editor.run_edit_command(&cmd);
+        }
+        assert_eq!(editor.get_buffer(), "aword");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "word");
+    }
+
+    #[test]
+    fn test_undo_character_granularity() {
+        let mut editor = editor_with("");
+        editor.set_undo_granularity(UndoGranularity::Character);
+        for cmd in str_to_edit_commands("ab cd") {
+            editor.run_edit_command(&cmd);
+        }
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "ab c");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "ab ");
+    }
+
+    #[test]
+    fn test_undo_pause_granularity() {
+        let mut editor = editor_with("");
+        editor.set_undo_granularity(UndoGranularity::WordOrPause(
+            std::time::Duration::from_millis(20),
+        ));
+        for cmd in str_to_edit_commands("abc") {
+            editor.run_edit_command(&cmd);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(40));
+        for cmd in str_to_edit_commands("def") {
+            editor.run_edit_command(&cmd);
+        }
+        assert_eq!(editor.get_buffer(), "abcdef");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "abc");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "");
+    }
+
     #[test]
     fn test_undo_backspace_works_on_word_boundaries() {
         let mut editor = editor_with("This is  a test");
@@ -876,6 +1664,242 @@ mod test {
         editor.run_edit_command(&EditCommand::Undo);
         assert_eq!(editor.get_buffer(), "This \r\n is a test");
     }
+
+    #[test]
+    fn test_successive_kills_are_appended() {
+        let mut editor = editor_with("one two three four");
+        editor.run_edit_command(&EditCommand::CutWordLeft);
+        editor.run_edit_command(&EditCommand::CutWordLeft);
+        assert_eq!(editor.get_buffer(), "one two ");
+
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        assert_eq!(editor.get_buffer(), "one two three four");
+
+        // Moving the cursor or killing in another direction starts a new kill
+        editor.run_edit_command(&EditCommand::MoveToStart { select: false });
+        editor.run_edit_command(&EditCommand::CutWordRight);
+        editor.run_edit_command(&EditCommand::MoveToEnd { select: false });
+        editor.run_edit_command(&EditCommand::CutWordLeft);
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        assert_eq!(editor.get_buffer(), " two three four");
+        editor.run_edit_command(&EditCommand::YankPop);
+        assert_eq!(editor.get_buffer(), " two three one");
+    }
+
+    #[test]
+    fn test_yank_pop_cycles_through_kills() {
+        let mut editor = editor_with("");
+        for word in ["one", "two", "three"] {
+            editor.run_edit_command(&EditCommand::InsertString(word.into()));
+            editor.run_edit_command(&EditCommand::CutFromStart);
+        }
+
+        editor.run_edit_command(&EditCommand::InsertString("> ".into()));
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        assert_eq!(editor.get_buffer(), "> three");
+        editor.run_edit_command(&EditCommand::YankPop);
+        assert_eq!(editor.get_buffer(), "> two");
+        editor.run_edit_command(&EditCommand::YankPop);
+        assert_eq!(editor.get_buffer(), "> one");
+        editor.run_edit_command(&EditCommand::YankPop);
+        assert_eq!(editor.get_buffer(), "> three");
+        assert_eq!(editor.insertion_point(), 7);
+
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "> one");
+    }
+
+    #[rstest]
+    #[case(SelectionMode::Exclusive, 4, 6, (4, 6))]
+    #[case(SelectionMode::Inclusive, 4, 6, (4, 7))]
+    #[case(SelectionMode::Inclusive, 6, 4, (4, 7))]
+    #[case(SelectionMode::Inclusive, 0, 1, (0, 4))] // Up to the end of the accented grapheme
+    #[case(SelectionMode::Lines, 6, 9, (5, 13))]
+    #[case(SelectionMode::Lines, 1, 1, (0, 5))]
+    fn test_selection_mode(
+        #[case] mode: SelectionMode,
+        #[case] anchor: usize,
+        #[case] position: usize,
+        #[case] expected: (usize, usize),
+    ) {
+        let mut editor = editor_with("ae\u{301}\ndef\nghi\njkl");
+        editor.run_edit_command(&EditCommand::MoveToPosition {
+            position: anchor,
+            select: false,
+        });
+        editor.run_edit_command(&EditCommand::StartSelection(mode));
+        editor.run_edit_command(&EditCommand::MoveToPosition {
+            position,
+            select: true,
+        });
+
+        assert_eq!(editor.get_selection(), Some(expected));
+    }
+
+    #[test]
+    fn test_swap_selection_ends() {
+        let mut editor = editor_with("one two three");
+        editor.run_edit_command(&EditCommand::MoveToLineStart { select: false });
+        editor.run_edit_command(&EditCommand::StartSelection(SelectionMode::Inclusive));
+        editor.run_edit_command(&EditCommand::MoveWordRightEnd { select: true });
+        editor.run_edit_command(&EditCommand::SwapSelectionEnds);
+        assert_eq!(editor.insertion_point(), 0);
+        assert_eq!(editor.get_selection(), Some((0, 3)));
+
+        editor.run_edit_command(&EditCommand::ClearSelection);
+        assert_eq!(editor.insertion_point(), 0);
+        assert_eq!(editor.get_selection(), None);
+    }
+
+    #[test]
+    fn test_cut_line_selection() {
+        let mut editor = editor_with("one\ntwo\nthree");
+        editor.run_edit_command(&EditCommand::MoveToPosition {
+            position: 5,
+            select: false,
+        });
+        editor.run_edit_command(&EditCommand::StartSelection(SelectionMode::Lines));
+        editor.run_edit_command(&EditCommand::CutSelection);
+        assert_eq!(editor.get_buffer(), "one\nthree");
+
+        // Pasted as a line
+        editor.run_edit_command(&EditCommand::MoveToPosition {
+            position: 1,
+            select: false,
+        });
+        editor.run_edit_command(&EditCommand::PasteCutBufferAfter);
+        assert_eq!(editor.get_buffer(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn test_indent_selection() {
+        let mut editor = editor_with("one\n\n two\nthree");
+        editor.run_edit_command(&EditCommand::MoveToStart { select: false });
+        editor.run_edit_command(&EditCommand::StartSelection(SelectionMode::Inclusive));
+        editor.run_edit_command(&EditCommand::MoveToPosition {
+            position: 6,
+            select: true,
+        });
+        editor.run_edit_command(&EditCommand::IndentSelection);
+        assert_eq!(editor.get_buffer(), "    one\n\n     two\nthree");
+        assert_eq!(editor.insertion_point(), 4);
+        assert_eq!(editor.get_selection(), None);
+
+        // Without a selection, the current line
+        editor.run_edit_command(&EditCommand::MoveToEnd { select: false });
+        editor.run_edit_command(&EditCommand::IndentSelection);
+        assert_eq!(editor.get_buffer(), "    one\n\n     two\n    three");
+        assert_eq!(editor.insertion_point(), 22);
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut editor = editor_with("one two three");
+        editor.run_edit_command(&EditCommand::SelectRegister('a'));
+        editor.run_edit_command(&EditCommand::CopyWordLeft);
+        editor.run_edit_command(&EditCommand::MoveWordLeft { select: false });
+        editor.run_edit_command(&EditCommand::SelectRegister('A'));
+        editor.run_edit_command(&EditCommand::CutWordLeft);
+        assert_eq!(editor.get_buffer(), "one three");
+
+        // Small deletes without a register go to `-`
+        editor.run_edit_command(&EditCommand::CutWordLeft);
+        assert_eq!(editor.get_buffer(), "three");
+
+        editor.run_edit_command(&EditCommand::MoveToEnd { select: false });
+        editor.run_edit_command(&EditCommand::SelectRegister('a'));
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        assert_eq!(editor.get_buffer(), "threethreetwo ");
+        editor.run_edit_command(&EditCommand::SelectRegister('-'));
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        assert_eq!(editor.get_buffer(), "threethreetwo one ");
+
+        // An empty register pastes nothing
+        editor.run_edit_command(&EditCommand::SelectRegister('z'));
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        assert_eq!(editor.get_buffer(), "threethreetwo one ");
+    }
+
+    #[test]
+    fn test_yank_pop_requires_a_yank() {
+        let mut editor = editor_with("one two");
+        editor.run_edit_command(&EditCommand::CutWordLeft);
+        editor.run_edit_command(&EditCommand::CutWordLeft);
+        editor.run_edit_command(&EditCommand::YankPop);
+        assert_eq!(editor.get_buffer(), "");
+
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        editor.run_edit_command(&EditCommand::InsertChar('!'));
+        editor.run_edit_command(&EditCommand::YankPop);
+        assert_eq!(editor.get_buffer(), "one two!");
+    }
+
+    #[test]
+    fn test_cut_to_visual_line_end() {
+        // "> echo abc" is painted on a first row of 10 columns, then "defgh"
+        let mut editor = editor_with("echo abcdefgh\nls");
+        editor.set_visual_layout(VisualLayout::new(10, vec![2]));
+        editor.line_buffer.set_insertion_point(5);
+
+        editor.run_edit_command(&EditCommand::CutToVisualLineEnd);
+        assert_eq!(editor.get_buffer(), "echo defgh\nls");
+        assert_eq!(editor.insertion_point(), 5);
+
+        editor.run_edit_command(&EditCommand::PasteCutBufferBefore);
+        assert_eq!(editor.get_buffer(), "echo abcdefgh\nls");
+
+        // The last row ends with the line
+        editor.line_buffer.set_insertion_point(10);
+        editor.run_edit_command(&EditCommand::CutToVisualLineEnd);
+        assert_eq!(editor.get_buffer(), "echo abcde\nls");
+    }
+
+    #[test]
+    fn edits_apply_at_every_cursor() {
+        let mut editor = editor_with("let foo = foo + bar(foo)");
+        editor.line_buffer.set_insertion_point(12);
+
+        editor.run_edit_command(&EditCommand::AddCursorsAtWord);
+        assert_eq!(editor.secondary_cursors(), &[6, 22]);
+
+        editor.run_edit_command(&EditCommand::Backspace);
+        editor.run_edit_command(&EditCommand::InsertString("ix".into()));
+        assert_eq!(editor.get_buffer(), "let fixo = fixo + bar(fixo)");
+        assert_eq!(editor.insertion_point(), 14);
+        assert_eq!(editor.secondary_cursors(), &[7, 25]);
+
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "let fo = fo + bar(fo)");
+        assert!(editor.secondary_cursors().is_empty());
+    }
+
+    #[test]
+    fn other_commands_collapse_the_cursors() {
+        let mut editor = editor_with("ab ab abc");
+        editor.line_buffer.set_insertion_point(2);
+
+        editor.run_edit_command(&EditCommand::AddCursorsAtWord);
+        assert_eq!(editor.secondary_cursors(), &[5]);
+
+        editor.run_edit_command(&EditCommand::MoveLeft { select: false });
+        assert!(editor.secondary_cursors().is_empty());
+        editor.run_edit_command(&EditCommand::Delete);
+        assert_eq!(editor.get_buffer(), "a ab abc");
+    }
+
+    #[test]
+    fn deleting_between_cursors_merges_them() {
+        let mut editor = editor_with("a a a");
+        editor.line_buffer.set_insertion_point(5);
+        editor.run_edit_command(&EditCommand::AddCursorsAtWord);
+
+        for _ in 0..2 {
+            editor.run_edit_command(&EditCommand::Backspace);
+        }
+        assert_eq!(editor.get_buffer(), "");
+        assert_eq!(editor.insertion_point(), 0);
+        assert!(editor.secondary_cursors().is_empty());
+    }
     #[cfg(feature = "system_clipboard")]
     mod without_system_clipboard {
         use super::*;
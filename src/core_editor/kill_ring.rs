@@ -0,0 +1,137 @@
+use super::{Clipboard, ClipboardMode};
+use std::collections::VecDeque;
+
+/// Default number of killed texts remembered by the [`KillRing`]
+pub const DEFAULT_KILL_RING_CAPACITY: usize = 60;
+
+/// Side of the insertion point a text was killed from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum KillDirection {
+    /// Killed text was left of the insertion point
+    Backward,
+    /// Killed text was right of the insertion point
+    Forward,
+}
+
+/// Ring of the last killed texts, like the Emacs kill ring
+///
+/// Setting its content as a [`Clipboard`] pushes a new entry, evicting the oldest one once the
+/// ring is full, while getting it yanks the most recent entry. Right after a yank the older
+/// entries can be cycled through with [`KillRing::rotate`].
+pub(crate) struct KillRing {
+    entries: VecDeque<(String, ClipboardMode)>,
+    capacity: usize,
+    yank_index: usize,
+}
+
+impl Default for KillRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_KILL_RING_CAPACITY)
+    }
+}
+
+impl KillRing {
+    /// Create an empty kill ring holding at most `capacity` entries (at least one)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+            yank_index: 0,
+        }
+    }
+
+    /// Add `text` to the most recent entry, after it if it was killed forward or before it otherwise
+    ///
+    /// Pushes a new entry if the ring is empty.
+    pub fn append(&mut self, text: &str, direction: KillDirection) {
+        self.yank_index = 0;
+
+        match self.entries.front_mut() {
+            Some((entry, _)) => match direction {
+                KillDirection::Forward => entry.push_str(text),
+                KillDirection::Backward => entry.insert_str(0, text),
+            },
+            None => self.set(text, ClipboardMode::Normal),
+        }
+    }
+
+    /// Whether [`KillRing::rotate`] yanks another entry than the last yanked one
+    pub fn can_rotate(&self) -> bool {
+        self.entries.len() > 1
+    }
+
+    /// Move to the entry preceding the last yanked one, wrapping around to the most recent one
+    ///
+    /// Returns the entry to yank instead, if any.
+    pub fn rotate(&mut self) -> Option<(String, ClipboardMode)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        self.yank_index = (self.yank_index + 1) % self.entries.len();
+        self.entries.get(self.yank_index).cloned()
+    }
+}
+
+impl Clipboard for KillRing {
+    fn set(&mut self, content: &str, mode: ClipboardMode) {
+        self.yank_index = 0;
+        self.entries.push_front((content.to_string(), mode));
+        self.entries.truncate(self.capacity);
+    }
+
+    fn get(&mut self) -> (String, ClipboardMode) {
+        self.yank_index = 0;
+        self.entries.front().cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn texts(ring: &KillRing) -> Vec<&str> {
+        ring.entries.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted() {
+        let mut ring = KillRing::new(2);
+        ring.set("one", ClipboardMode::Normal);
+        ring.set("two", ClipboardMode::Normal);
+        ring.set("three", ClipboardMode::Normal);
+
+        assert_eq!(texts(&ring), vec!["three", "two"]);
+        assert_eq!(ring.get().0, "three");
+    }
+
+    #[test]
+    fn append_extends_the_latest_entry() {
+        let mut ring = KillRing::default();
+        ring.append("foo", KillDirection::Forward);
+        ring.append(" bar", KillDirection::Forward);
+        ring.append("baz ", KillDirection::Backward);
+
+        assert_eq!(texts(&ring), vec!["baz foo bar"]);
+    }
+
+    #[test]
+    fn rotate_cycles_through_entries() {
+        let mut ring = KillRing::default();
+        assert!(ring.rotate().is_none());
+
+        ring.set("one", ClipboardMode::Normal);
+        ring.set("two", ClipboardMode::Normal);
+        ring.set("three", ClipboardMode::Normal);
+
+        assert_eq!(ring.get().0, "three");
+        assert_eq!(ring.rotate().unwrap().0, "two");
+        assert_eq!(ring.rotate().unwrap().0, "one");
+        assert_eq!(ring.rotate().unwrap().0, "three");
+
+        // Yanking again starts over from the most recent entry
+        ring.rotate();
+        assert_eq!(ring.get().0, "three");
+    }
+}
@@ -1,14 +1,22 @@
 use {
+    super::WordChars,
     itertools::Itertools,
     std::{convert::From, ops::Range},
     unicode_segmentation::UnicodeSegmentation,
 };
 
 /// In memory representation of the entered line(s) including a cursor position to facilitate cursor based editing.
+///
+/// Offsets are byte indices into the buffer. As long as the insertion point is on a grapheme
+/// boundary, the offsets returned by the `grapheme_*_index` and `word_*_index` methods are on a
+/// grapheme boundary as well: the buffer can be sliced at them and they are valid insertion
+/// points. Use [`LineBuffer::replace_range_safe`] and [`LineBuffer::clear_range_safe`] to edit
+/// the buffer while keeping the insertion point on such a boundary.
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct LineBuffer {
     lines: String,
     insertion_point: usize,
+    word_chars: WordChars,
 }
 
 impl From<&str> for LineBuffer {
@@ -25,6 +33,16 @@ impl LineBuffer {
         Self::default()
     }
 
+    /// Set which characters join or break the words of the word motions and deletions
+    pub fn set_word_chars(&mut self, word_chars: WordChars) {
+        self.word_chars = word_chars;
+    }
+
+    /// Characters joining or breaking the words of the word motions and deletions
+    pub fn word_chars(&self) -> &WordChars {
+        &self.word_chars
+    }
+
     /// Check to see if the line buffer is empty
     pub fn is_empty(&self) -> bool {
         self.lines.is_empty()
@@ -170,8 +188,9 @@ impl LineBuffer {
 
     /// Cursor position *behind* the next word to the right
     pub fn word_right_index(&self) -> usize {
-        self.lines[self.insertion_point..]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[self.insertion_point..])
+            .into_iter()
             .find(|(_, word)| !is_whitespace_str(word))
             .map(|(i, word)| self.insertion_point + i + word.len())
             .unwrap_or_else(|| self.lines.len())
@@ -181,8 +200,9 @@ impl LineBuffer {
     pub fn big_word_right_index(&self) -> usize {
         let mut found_ws = false;
 
-        self.lines[self.insertion_point..]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[self.insertion_point..])
+            .into_iter()
             .find(|(_, word)| {
                 found_ws = found_ws || is_whitespace_str(word);
                 found_ws && !is_whitespace_str(word)
@@ -193,8 +213,9 @@ impl LineBuffer {
 
     /// Cursor position *at end of* the next word to the right
     pub fn word_right_end_index(&self) -> usize {
-        self.lines[self.insertion_point..]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[self.insertion_point..])
+            .into_iter()
             .find_map(|(i, word)| {
                 word.grapheme_indices(true)
                     .next_back()
@@ -212,8 +233,9 @@ impl LineBuffer {
 
     /// Cursor position *at end of* the next WORD to the right
     pub fn big_word_right_end_index(&self) -> usize {
-        self.lines[self.insertion_point..]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[self.insertion_point..])
+            .into_iter()
             .tuple_windows()
             .find_map(|((prev_i, prev_word), (_, word))| {
                 if is_whitespace_str(word) {
@@ -237,8 +259,9 @@ impl LineBuffer {
 
     /// Cursor position *in front of* the next word to the right
     pub fn word_right_start_index(&self) -> usize {
-        self.lines[self.insertion_point..]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[self.insertion_point..])
+            .into_iter()
             .find(|(i, word)| *i != 0 && !is_whitespace_str(word))
             .map(|(i, _)| self.insertion_point + i)
             .unwrap_or_else(|| self.lines.len())
@@ -248,8 +271,9 @@ impl LineBuffer {
     pub fn big_word_right_start_index(&self) -> usize {
         let mut found_ws = false;
 
-        self.lines[self.insertion_point..]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[self.insertion_point..])
+            .into_iter()
             .find(|(i, word)| {
                 found_ws = found_ws || *i != 0 && is_whitespace_str(word);
                 found_ws && *i != 0 && !is_whitespace_str(word)
@@ -260,8 +284,9 @@ impl LineBuffer {
 
     /// Cursor position *in front of* the next word to the left
     pub fn word_left_index(&self) -> usize {
-        self.lines[..self.insertion_point]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[..self.insertion_point])
+            .into_iter()
             .filter(|(_, word)| !is_whitespace_str(word))
             .last()
             .map(|(i, _)| i)
@@ -270,8 +295,9 @@ impl LineBuffer {
 
     /// Cursor position *in front of* the next WORD to the left
     pub fn big_word_left_index(&self) -> usize {
-        self.lines[..self.insertion_point]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[..self.insertion_point])
+            .into_iter()
             .fold(None, |last_word_index, (i, word)| {
                 match (last_word_index, is_whitespace_str(word)) {
                     (None, true) => None,
@@ -291,8 +317,9 @@ impl LineBuffer {
 
     /// Cursor position on the next whitespace
     pub fn next_whitespace(&self) -> usize {
-        self.lines[self.insertion_point..]
-            .split_word_bound_indices()
+        self.word_chars
+            .split_word_bound_indices(&self.lines[self.insertion_point..])
+            .into_iter()
             .find(|(i, word)| *i != 0 && is_whitespace_str(word))
             .map(|(i, _)| self.insertion_point + i)
             .unwrap_or_else(|| self.lines.len())
@@ -427,9 +454,65 @@ impl LineBuffer {
         self.replace_range(range, "");
     }
 
+    /// Substitute the text covered by `range` with `replace_with`, and change insertion point if
+    /// necessary.
+    ///
+    /// If the cursor is located inside of `range` it is moved behind the replacement.
+    /// If the cursor is located after `range` it is adjusted to stay at its current char boundary.
+    /// The cursor is then moved to the end of its grapheme if the replacement joined it with the
+    /// text around (e.g. when followed by a combining character).
+    ///
+    /// # Panics
+    ///
+    /// If the bounds of `range` are not on char boundaries or are out of bounds, like
+    /// [`String::replace_range`]
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use reedline::LineBuffer;
+    ///
+    /// let mut line_buffer = LineBuffer::from("echo 'héllo' world");
+    /// line_buffer.set_insertion_point(9);
+    ///
+    /// // Replace the text inside of the quotes around the cursor
+    /// let start = line_buffer.get_buffer()[..9].rfind('\'').unwrap() + 1;
+    /// let end = line_buffer.get_buffer()[9..].find('\'').unwrap() + 9;
+    /// line_buffer.replace_range_safe(start..end, "bye");
+    ///
+    /// assert_eq!(line_buffer.get_buffer(), "echo 'bye' world");
+    /// assert_eq!(line_buffer.insertion_point(), 9);
+    /// ```
+    pub fn replace_range_safe(&mut self, range: Range<usize>, replace_with: &str) {
+        let Range { start, end } = range;
+        if self.insertion_point <= start {
+            // No action necessary
+        } else if self.insertion_point < end {
+            self.insertion_point = start + replace_with.len();
+        } else {
+            // Insertion point after end
+            self.insertion_point = self.insertion_point - (end - start) + replace_with.len();
+        }
+        self.replace_range(start..end, replace_with);
+
+        let on_grapheme_boundary = self.insertion_point == self.lines.len()
+            || self
+                .lines
+                .grapheme_indices(true)
+                .any(|(i, _)| i == self.insertion_point);
+        if !on_grapheme_boundary {
+            let grapheme_start = self.lines[..self.insertion_point]
+                .grapheme_indices(true)
+                .next_back()
+                .map_or(0, |(i, _)| i);
+            self.insertion_point = self.grapheme_end_index(grapheme_start);
+        }
+    }
+
     /// Substitute text covered by `range` in the current line
     ///
     /// Safety: Does not change the insertion point/offset and is thus not unicode safe!
+    /// See [`LineBuffer::replace_range_safe`] for a version keeping the insertion point valid.
     pub fn replace_range<R>(&mut self, range: R, replace_with: &str)
     where
         R: std::ops::RangeBounds<usize>,
@@ -459,8 +542,10 @@ impl LineBuffer {
     /// Gets the range of the word the current edit position is pointing to
     pub fn current_word_range(&self) -> Range<usize> {
         let right_index = self.word_right_index();
-        let left_index = self.lines[..right_index]
-            .split_word_bound_indices()
+        let left_index = self
+            .word_chars
+            .split_word_bound_indices(&self.lines[..right_index])
+            .into_iter()
             .filter(|(_, word)| !is_whitespace_str(word))
             .last()
             .map(|(i, _)| i)
@@ -542,6 +627,29 @@ impl LineBuffer {
         }
     }
 
+    /// Capitalize the first character of the word from the insertion point (skipping the
+    /// whitespace at the insertion point), lowercase the rest of the word and move the
+    /// insertion point behind it.
+    pub fn capitalize_word(&mut self) {
+        let change_range = self.insertion_point..self.word_right_index();
+
+        let mut capitalized = String::with_capacity(change_range.len());
+        let mut found_first = false;
+        for c in self.lines[change_range.clone()].chars() {
+            if found_first {
+                capitalized.extend(c.to_lowercase());
+            } else if c.is_alphanumeric() {
+                capitalized.extend(c.to_uppercase());
+                found_first = true;
+            } else {
+                capitalized.push(c);
+            }
+        }
+
+        self.replace_range(change_range.clone(), &capitalized);
+        self.insertion_point = change_range.start + capitalized.len();
+    }
+
     /// Deletes on grapheme to the left
     pub fn delete_left_grapheme(&mut self) {
         let left_index = self.grapheme_left_index();
@@ -574,7 +682,7 @@ impl LineBuffer {
         self.clear_range(self.insertion_point()..right_word_index);
     }
 
-    /// Swaps current word with word on right
+    /// Swaps current word with word on right, and moves the insertion point behind them
     pub fn swap_words(&mut self) {
         let word_1_range = self.current_word_range();
         self.move_word_right();
@@ -585,8 +693,9 @@ impl LineBuffer {
             let insertion_line = self.get_buffer();
             let word_1 = insertion_line[word_1_range.clone()].to_string();
             let word_2 = insertion_line[word_2_range.clone()].to_string();
-            self.replace_range(word_2_range, &word_1);
+            self.replace_range(word_2_range.clone(), &word_1);
             self.replace_range(word_1_range, &word_2);
+            self.insertion_point = word_2_range.end;
         }
     }
 
@@ -679,7 +788,7 @@ impl LineBuffer {
         !self.get_buffer()[self.insertion_point()..].contains('\n')
     }
 
-    /// Finds index for the first occurrence of a char to the right of offset
+    /// Finds index for the first grapheme starting with a char to the right of offset
     pub fn find_char_right(&self, c: char, current_line: bool) -> Option<usize> {
         // Skip current grapheme
         let char_offset = self.grapheme_right_index();
@@ -688,17 +797,32 @@ impl LineBuffer {
         } else {
             char_offset..self.lines.len()
         };
-        self.lines[range].find(c).map(|index| index + char_offset)
+        self.lines[range]
+            .grapheme_indices(true)
+            .find(|(_, grapheme)| grapheme.starts_with(c))
+            .map(|(index, _)| index + char_offset)
     }
 
-    /// Finds index for the first occurrence of a char to the left of offset
+    /// Finds index for the first grapheme starting with a char to the left of offset
     pub fn find_char_left(&self, c: char, current_line: bool) -> Option<usize> {
         let range = if current_line {
             self.current_line_range().start..self.insertion_point()
         } else {
             0..self.insertion_point()
         };
-        self.lines[range.clone()].rfind(c).map(|i| i + range.start)
+        self.lines[range.clone()]
+            .grapheme_indices(true)
+            .rev()
+            .find(|(_, grapheme)| grapheme.starts_with(c))
+            .map(|(index, _)| index + range.start)
+    }
+
+    /// Index *behind* the grapheme starting at `index`
+    pub fn grapheme_end_index(&self, index: usize) -> usize {
+        self.lines[index..]
+            .graphemes(true)
+            .next()
+            .map_or(index, |grapheme| index + grapheme.len())
     }
 
     /// Moves the insertion point until the next char to the right
@@ -732,16 +856,52 @@ impl LineBuffer {
     /// Moves the insertion point before the next char to the left of offset
     pub fn move_left_before(&mut self, c: char, current_line: bool) -> usize {
         if let Some(index) = self.find_char_left(c, current_line) {
-            self.insertion_point = index + c.len_utf8();
+            self.insertion_point = self.grapheme_end_index(index);
         }
 
         self.insertion_point
     }
 
+    /// Moves the insertion point before the next char to the right.
+    ///
+    /// Unlike [`LineBuffer::move_right_before`] this doesn't get stuck when the char is directly
+    /// to the right of the cursor, but moves before the following occurrence instead.
+    pub fn move_right_before_next(&mut self, c: char, current_line: bool) -> usize {
+        let start = self.insertion_point;
+        if self.find_char_right(c, current_line) == Some(self.grapheme_right_index()) {
+            self.insertion_point = self.grapheme_right_index();
+        }
+
+        if self.find_char_right(c, current_line).is_some() {
+            self.move_right_before(c, current_line)
+        } else {
+            self.insertion_point = start;
+            start
+        }
+    }
+
+    /// Moves the insertion point before the next char to the left of offset.
+    ///
+    /// Unlike [`LineBuffer::move_left_before`] this doesn't get stuck when the char is directly
+    /// to the left of the cursor, but moves before the preceding occurrence instead.
+    pub fn move_left_before_next(&mut self, c: char, current_line: bool) -> usize {
+        let start = self.insertion_point;
+        if self.find_char_left(c, current_line) == Some(self.grapheme_left_index()) {
+            self.insertion_point = self.grapheme_left_index();
+        }
+
+        if self.find_char_left(c, current_line).is_some() {
+            self.move_left_before(c, current_line)
+        } else {
+            self.insertion_point = start;
+            start
+        }
+    }
+
     /// Deletes until first character to the right of offset
     pub fn delete_right_until_char(&mut self, c: char, current_line: bool) {
         if let Some(index) = self.find_char_right(c, current_line) {
-            self.clear_range(self.insertion_point()..index + c.len_utf8());
+            self.clear_range(self.insertion_point()..self.grapheme_end_index(index));
         }
     }
 
@@ -763,8 +923,9 @@ impl LineBuffer {
     /// Deletes before first character to the left of offset
     pub fn delete_left_before_char(&mut self, c: char, current_line: bool) {
         if let Some(index) = self.find_char_left(c, current_line) {
-            self.clear_range(index + c.len_utf8()..self.insertion_point());
-            self.insertion_point = index + c.len_utf8();
+            let end = self.grapheme_end_index(index);
+            self.clear_range(end..self.insertion_point());
+            self.insertion_point = end;
         }
     }
 }
@@ -889,6 +1050,41 @@ mod test {
         line_buffer.assert_valid();
     }
 
+    #[rstest]
+    #[case(WordChars::new(), vec!["cp foo-", "cp foo", "cp ", ""])]
+    #[case(WordChars::new().with_joining("-_."), vec!["cp ", ""])]
+    #[case(
+        WordChars::new().with_breaking("_."),
+        vec!["cp foo-bar_baz.", "cp foo-bar_baz", "cp foo-bar_", "cp foo-bar", "cp foo-"]
+    )]
+    fn delete_word_left_uses_word_chars(
+        #[case] word_chars: WordChars,
+        #[case] expected: Vec<&str>,
+    ) {
+        let mut line_buffer = buffer_with("cp foo-bar_baz.qux");
+        line_buffer.set_word_chars(word_chars);
+
+        for expected in expected {
+            line_buffer.delete_word_left();
+            assert_eq!(line_buffer.get_buffer(), expected);
+            line_buffer.assert_valid();
+        }
+    }
+
+    #[rstest]
+    #[case(WordChars::new(), "-baz qux")]
+    #[case(WordChars::new().with_joining("-"), " qux")]
+    #[case(WordChars::new().with_breaking("_"), "_bar-baz qux")]
+    fn delete_word_right_uses_word_chars(#[case] word_chars: WordChars, #[case] expected: &str) {
+        let mut line_buffer = buffer_with("foo_bar-baz qux");
+        line_buffer.set_word_chars(word_chars);
+        line_buffer.move_to_start();
+        line_buffer.delete_word_right();
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        line_buffer.assert_valid();
+    }
+
     #[test]
     fn delete_word_right_works() {
         let mut line_buffer = buffer_with("This is a test");
@@ -951,6 +1147,31 @@ mod test {
         line_buffer.assert_valid();
     }
 
+    #[rstest]
+    #[case("", 0, "", 0)]
+    #[case("this IS a test", 5, "this Is a test", 7)]
+    #[case("this IS a test", 4, "this Is a test", 7)]
+    #[case("this IS a test", 6, "this IS a test", 7)]
+    #[case("hello wORLD", 7, "hello wOrld", 11)]
+    #[case("  über", 0, "  Über", 7)]
+    #[case("owned", 5, "owned", 5)]
+    fn capitalize_word_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] output: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+        line_buffer.capitalize_word();
+
+        let mut expected = buffer_with(output);
+        expected.set_insertion_point(out_location);
+
+        assert_eq!(expected, line_buffer);
+        line_buffer.assert_valid();
+    }
+
     #[rstest]
     #[case("This is a test", 13, "This is a TEST", 14)]
     #[case("This is a test", 10, "This is a TEST", 14)]
@@ -1047,8 +1268,9 @@ mod test {
     }
 
     #[rstest]
-    #[case("This is a test", 8, "This is test a", 8)]
-    #[case("This is a test", 0, "is This a test", 0)]
+    #[case("This is a test", 8, "This is test a", 14)]
+    #[case("This is a test", 0, "is This a test", 7)]
+    #[case("This is a test", 6, "This a is test", 9)]
     #[case("This is a test", 14, "This is a test", 14)]
     fn swap_words_works(
         #[case] input: &str,
@@ -1169,6 +1391,8 @@ mod test {
     #[case("abc\ndef", 3, 'f', true, 3)]
     #[case("abc\ndef", 0, 'f', false, 6)]
     #[case("abc\ndef", 3, 'f', false, 6)]
+    #[case("ae\u{301}b", 0, 'e', true, 1)]
+    #[case("👨\u{200d}👩 👩", 0, '👩', true, 12)] // Not inside the ZWJ sequence
     fn test_move_right_until(
         #[case] input: &str,
         #[case] position: usize,
@@ -1211,6 +1435,7 @@ mod test {
     #[case("abc def ghi", 0, 'i', true, "")]
     #[case("abc def ghi", 0, 'z', true, "abc def ghi")]
     #[case("abc def ghi", 0, 'a', true, "abc def ghi")]
+    #[case("ae\u{301}b", 0, 'e', true, "b")] // With the combining accent
     fn test_delete_until(
         #[case] input: &str,
         #[case] position: usize,
@@ -1287,6 +1512,45 @@ mod test {
         line_buffer.assert_valid();
     }
 
+    #[rstest]
+    #[case("axbxc", 0, 'x', 2)]
+    #[case("abxc", 0, 'x', 1)]
+    #[case("axbc", 0, 'x', 0)]
+    #[case("axbc", 2, 'x', 2)]
+    fn test_move_right_before_next(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] c: char,
+        #[case] expected: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(position);
+
+        line_buffer.move_right_before_next(c, true);
+
+        assert_eq!(line_buffer.insertion_point(), expected);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("axbxc", 4, 'x', 2)]
+    #[case("axbc", 4, 'x', 2)]
+    #[case("axbc", 2, 'x', 2)]
+    fn test_move_left_before_next(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] c: char,
+        #[case] expected: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(position);
+
+        line_buffer.move_left_before_next(c, true);
+
+        assert_eq!(line_buffer.insertion_point(), expected);
+        line_buffer.assert_valid();
+    }
+
     #[rstest]
     #[case("abc def ghi", 5, 'b', true, "aef ghi")]
     #[case("abc def ghi", 5, 'e', true, "abc def ghi")]
@@ -1313,6 +1577,7 @@ mod test {
     #[case("abc def ghi", 5, 'b', true, "abef ghi")]
     #[case("abc def ghi", 5, 'e', true, "abc def ghi")]
     #[case("abc def ghi", 10, 'a', true, "ai")]
+    #[case("e\u{301}bc", 4, 'e', true, "e\u{301}c")]
     fn test_delete_before_left(
         #[case] input: &str,
         #[case] position: usize,
@@ -1597,4 +1862,27 @@ mod test {
 
         assert_eq!(index, expected);
     }
+
+    #[rstest]
+    #[case("hello world", 2, 6..11, "there", "hello there", 2)]
+    #[case("hello world", 8, 6..11, "you", "hello you", 9)]
+    #[case("hello world", 11, 0..5, "hi", "hi world", 8)]
+    #[case("héllo wörld", 13, 0..6, "ü", "ü wörld", 9)]
+    #[case("eb", 1, 1..2, "\u{301}", "e\u{301}", 3)]
+    fn replace_range_safe_keeps_the_cursor_on_a_grapheme(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] range: Range<usize>,
+        #[case] replace_with: &str,
+        #[case] expected: &str,
+        #[case] expected_position: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(position);
+
+        line_buffer.replace_range_safe(range, replace_with);
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), expected_position);
+    }
 }
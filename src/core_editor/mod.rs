@@ -1,10 +1,15 @@
 mod clip_buffer;
 mod edit_stack;
 mod editor;
+mod kill_ring;
 mod line_buffer;
+mod visual_layout;
+mod word_chars;
 
 #[cfg(feature = "system_clipboard")]
 pub(crate) use clip_buffer::get_system_clipboard;
-pub(crate) use clip_buffer::{get_local_clipboard, Clipboard, ClipboardMode};
+pub(crate) use clip_buffer::{Clipboard, ClipboardMode, LocalClipboard};
 pub use editor::Editor;
 pub use line_buffer::LineBuffer;
+pub(crate) use visual_layout::VisualLayout;
+pub use word_chars::WordChars;
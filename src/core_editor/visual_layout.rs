@@ -0,0 +1,166 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Where the lines of the buffer wrap on the screen, as last painted
+///
+/// Records the width of the screen and the column each line of the buffer starts at, after
+/// the prompt or its multiline indicator. The commands working on the wrapped rows, like
+/// [`crate::EditCommand::CutToVisualLineEnd`], need it: until the buffer is painted, the
+/// screen width is unknown and every line is a single row.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct VisualLayout {
+    screen_width: usize,
+    start_columns: Vec<usize>,
+}
+
+impl VisualLayout {
+    /// `start_columns` are the columns of the first lines of the buffer, the lines after them
+    /// starting at the same column as the last one
+    pub fn new(screen_width: usize, start_columns: Vec<usize>) -> Self {
+        Self {
+            screen_width,
+            start_columns,
+        }
+    }
+
+    fn start_column(&self, line_index: usize) -> usize {
+        self.start_columns
+            .get(line_index)
+            .or_else(|| self.start_columns.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// End of the row of `buffer` that `position` is on, once the line wraps on the screen
+    ///
+    /// A position at the start of a row is on that row, like the cursor painted there. A
+    /// character too wide for the end of a row goes to the next one, like in the terminal.
+    pub fn row_end(&self, buffer: &str, position: usize) -> usize {
+        let line_start = buffer[..position].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = buffer[position..]
+            .find('\n')
+            .map_or(buffer.len(), |index| position + index);
+        if self.screen_width == 0 {
+            return line_end;
+        }
+
+        let line_index = buffer[..line_start].matches('\n').count();
+        let mut column = self.start_column(line_index) % self.screen_width;
+        for (offset, grapheme) in buffer[line_start..line_end].grapheme_indices(true) {
+            let width = grapheme.width();
+            if column > 0 && column + width > self.screen_width {
+                if line_start + offset > position {
+                    return line_start + offset;
+                }
+                column = 0;
+            }
+            column += width;
+        }
+        line_end
+    }
+
+    /// Position of `buffer` painted at `column` of the `row` counted from the first row of the
+    /// buffer, `None` below its last row
+    ///
+    /// Like a click in the terminal, a position in the prompt before the line is its start, a
+    /// position after the end of a row is that end, and the second column of a wide character
+    /// is that character.
+    pub fn position_at(&self, buffer: &str, row: usize, column: usize) -> Option<usize> {
+        let mut line_row = 0;
+        let mut line_start = 0;
+        for (line_index, line) in buffer.split('\n').enumerate() {
+            let mut current_row = line_row;
+            let mut current_column = if self.screen_width == 0 {
+                self.start_column(line_index)
+            } else {
+                self.start_column(line_index) % self.screen_width
+            };
+            if current_row == row && column < current_column {
+                return Some(line_start);
+            }
+            for (offset, grapheme) in line.grapheme_indices(true) {
+                let width = grapheme.width();
+                if self.screen_width > 0
+                    && current_column > 0
+                    && current_column + width > self.screen_width
+                {
+                    if current_row == row {
+                        return Some(line_start + offset);
+                    }
+                    current_row += 1;
+                    current_column = 0;
+                }
+                if current_row == row && column < current_column + width {
+                    return Some(line_start + offset);
+                }
+                current_column += width;
+            }
+            if current_row == row {
+                return Some(line_start + line.trim_end_matches('\r').len());
+            }
+            line_row = current_row + 1;
+            line_start += line.len() + 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    // The prompt takes 4 columns of the first row
+    #[case("abcdefghijklmn", 0, 6)]
+    #[case("abcdefghijklmn", 5, 6)]
+    #[case("abcdefghijklmn", 6, 14)]
+    #[case("abcdefghijklmn", 14, 14)]
+    // The continuation lines start after a 2 columns indicator
+    #[case("ab\ncdefghijklmn", 3, 11)]
+    #[case("ab\ncdefghijklmn", 11, 15)]
+    // A 2 columns wide character does not fit in the last column
+    #[case("abc漢字", 0, 6)]
+    #[case("abc漢字漢字漢字", 6, 21)]
+    fn test_row_end(#[case] buffer: &str, #[case] position: usize, #[case] expected: usize) {
+        let layout = VisualLayout::new(10, vec![4, 2]);
+
+        assert_eq!(layout.row_end(buffer, position), expected);
+    }
+
+    #[test]
+    fn unknown_width_keeps_the_lines_whole() {
+        let layout = VisualLayout::default();
+
+        assert_eq!(layout.row_end("abcdefghijklmnop\nq", 3), 16);
+    }
+
+    #[rstest]
+    // The prompt takes 4 columns of the first row
+    #[case("abcdefghijklmn", 0, 0, Some(0))]
+    #[case("abcdefghijklmn", 0, 5, Some(1))]
+    #[case("abcdefghijklmn", 1, 3, Some(9))]
+    #[case("abcdefghijklmn", 1, 9, Some(14))]
+    #[case("abcdefghijklmn", 2, 0, None)]
+    // The continuation lines start after a 2 columns indicator
+    #[case("ab\ncdefghijklmn", 0, 8, Some(2))]
+    #[case("ab\ncdefghijklmn", 1, 0, Some(3))]
+    #[case("ab\ncdefghijklmn", 1, 4, Some(5))]
+    #[case("ab\ncdefghijklmn", 2, 1, Some(12))]
+    // Both columns of a wide character are on it, and the column it leaves empty is before it
+    #[case("abc漢字", 0, 7, Some(3))]
+    #[case("abc漢字", 0, 8, Some(3))]
+    #[case("abc漢字", 0, 9, Some(6))]
+    #[case("abc漢字", 1, 1, Some(6))]
+    fn test_position_at(
+        #[case] buffer: &str,
+        #[case] row: usize,
+        #[case] column: usize,
+        #[case] expected: Option<usize>,
+    ) {
+        let layout = VisualLayout::new(10, vec![4, 2]);
+
+        assert_eq!(layout.position_at(buffer, row, column), expected);
+    }
+}
@@ -0,0 +1,136 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Characters joining or breaking the words of the word motions and deletions, like zsh's
+/// `WORDCHARS`
+///
+/// Words are found with the Unicode word boundaries (so `foo-bar` is made of the `foo`, `-` and
+/// `bar` words while `foo_bar` or `foo.bar` are a single word), then adjusted:
+///
+/// - A joining character is part of the words right next to it, e.g. `foo-bar` is a single word
+///   when `-` is joining.
+/// - A breaking character is always a word on its own, e.g. `foo_bar` is made of the `foo`, `_`
+///   and `bar` words when `_` is breaking. It takes precedence over a joining character.
+///
+/// By default, no character is joining or breaking.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordChars {
+    joining: String,
+    breaking: String,
+}
+
+impl WordChars {
+    /// Only use the Unicode word boundaries
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder that sets the characters joining the words next to them
+    #[must_use]
+    pub fn with_joining(mut self, joining: &str) -> Self {
+        self.joining = joining.to_string();
+        self
+    }
+
+    /// A builder that sets the characters that always are a word on their own
+    #[must_use]
+    pub fn with_breaking(mut self, breaking: &str) -> Self {
+        self.breaking = breaking.to_string();
+        self
+    }
+
+    /// Like [`UnicodeSegmentation::split_word_bound_indices`], the word boundaries being
+    /// adjusted with the joining and breaking characters
+    pub(crate) fn split_word_bound_indices<'a>(&self, s: &'a str) -> Vec<(usize, &'a str)> {
+        let mut pieces = Vec::new();
+        for (index, segment) in s.split_word_bound_indices() {
+            let mut start = 0;
+            for (offset, c) in segment.char_indices() {
+                if self.breaking.contains(c) {
+                    if start < offset {
+                        pieces.push((index + start, &segment[start..offset]));
+                    }
+                    start = offset + c.len_utf8();
+                    pieces.push((index + offset, &segment[offset..start]));
+                }
+            }
+            if start < segment.len() {
+                pieces.push((index + start, &segment[start..]));
+            }
+        }
+
+        if self.joining.is_empty() {
+            return pieces;
+        }
+
+        let mut words: Vec<(usize, &str)> = Vec::new();
+        for (index, piece) in pieces {
+            match words.last_mut() {
+                Some((start, word)) if self.joins(word, piece) => {
+                    *word = &s[*start..index + piece.len()];
+                }
+                _ => words.push((index, piece)),
+            }
+        }
+        words
+    }
+
+    fn is_joining(&self, c: char) -> bool {
+        self.joining.contains(c) && !self.breaking.contains(c)
+    }
+
+    /// Whether two successive pieces of text are part of the same word
+    fn joins(&self, left: &str, right: &str) -> bool {
+        // Leaves out the whitespace, the punctuation and the breaking characters
+        let is_word = |piece: &str| {
+            piece
+                .chars()
+                .any(|c| c.is_alphanumeric() || self.is_joining(c))
+                && !piece.chars().any(|c| self.breaking.contains(c))
+        };
+
+        is_word(left)
+            && is_word(right)
+            && (left.ends_with(|c| self.is_joining(c)) || right.starts_with(|c| self.is_joining(c)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(WordChars::new(), vec!["foo", "-", "bar_baz.qux", " ", "(", "x", ")"])]
+    #[case(WordChars::new().with_joining("-"), vec!["foo-bar_baz.qux", " ", "(", "x", ")"])]
+    #[case(
+        WordChars::new().with_breaking("_."),
+        vec!["foo", "-", "bar", "_", "baz", ".", "qux", " ", "(", "x", ")"]
+    )]
+    #[case(
+        WordChars::new().with_joining("-_").with_breaking("_"),
+        vec!["foo-bar", "_", "baz.qux", " ", "(", "x", ")"]
+    )]
+    fn test_split_word_bound_indices(#[case] word_chars: WordChars, #[case] expected: Vec<&str>) {
+        let line = "foo-bar_baz.qux (x)";
+        let words = word_chars.split_word_bound_indices(line);
+
+        assert_eq!(
+            words.iter().map(|(_, word)| *word).collect::<Vec<_>>(),
+            expected
+        );
+        for (index, word) in words {
+            assert_eq!(&line[index..index + word.len()], word);
+        }
+    }
+
+    #[test]
+    fn test_joining_keeps_unicode_words_apart() {
+        let word_chars = WordChars::new().with_joining("-");
+
+        assert_eq!(
+            word_chars.split_word_bound_indices("漢字 é-ü"),
+            vec![(0, "漢"), (3, "字"), (6, " "), (7, "é-ü")]
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
-    enums::{ReedlineEvent, ReedlineRawEvent},
+    enums::{EditCommand, ReedlineEvent, ReedlineRawEvent},
     PromptEditMode,
 };
+use std::time::Duration;
 
 /// Define the style of parsing for the edit events
 /// Available default options:
@@ -13,4 +14,32 @@ pub trait EditMode: Send {
 
     /// What to display in the prompt indicator
     fn edit_mode(&self) -> PromptEditMode;
+
+    /// The keys typed so far of an unfinished command, e.g. the `d` operator waiting for its
+    /// motion in Vi normal mode, for the prompt to show
+    fn pending_command(&self) -> Option<String> {
+        None
+    }
+
+    /// Time left for the next key of a partially typed key sequence,
+    /// or `None` if no key sequence is being typed
+    fn pending_sequence_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Handles the keys of the partially typed key sequence on their own,
+    /// once no other key was typed before its timeout
+    fn flush_pending_sequence(&mut self) -> ReedlineEvent {
+        ReedlineEvent::None
+    }
+
+    /// Switches to a mode inserting text, before [`crate::Reedline::insert_text`] inserts some,
+    /// returning the commands moving the cursor to the insert position
+    fn prepare_insertion(&mut self) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    /// Forgets the keys, the count and the operator typed so far of an unfinished command, once
+    /// [`crate::Reedline::set_edit_mode`] switches to this edit mode
+    fn reset_pending(&mut self) {}
 }
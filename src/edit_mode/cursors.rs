@@ -1,13 +1,28 @@
 use crossterm::cursor::SetCursorStyle;
 
-/// Maps cursor shapes to each edit mode (emacs, vi normal & vi insert).
+/// Maps cursor shapes to each edit mode (emacs, vi normal, vi insert & vi visual).
 /// If any of the fields is `None`, the cursor won't get changed by Reedline for that mode.
-#[derive(Default)]
+///
+/// The default uses a block in vi normal and visual modes and a bar in vi insert mode,
+/// leaving the cursor of emacs mode alone.
 pub struct CursorConfig {
     /// The cursor to be used when in vi insert mode
     pub vi_insert: Option<SetCursorStyle>,
     /// The cursor to be used when in vi normal mode
     pub vi_normal: Option<SetCursorStyle>,
+    /// The cursor to be used when in vi visual mode
+    pub vi_visual: Option<SetCursorStyle>,
     /// The cursor to be used when in emacs mode
     pub emacs: Option<SetCursorStyle>,
 }
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            vi_insert: Some(SetCursorStyle::SteadyBar),
+            vi_normal: Some(SetCursorStyle::SteadyBlock),
+            vi_visual: Some(SetCursorStyle::SteadyBlock),
+            emacs: None,
+        }
+    }
+}
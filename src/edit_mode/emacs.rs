@@ -2,14 +2,16 @@ use crate::{
     edit_mode::{
         keybindings::{
             add_common_control_bindings, add_common_edit_bindings, add_common_navigation_bindings,
-            add_common_selection_bindings, edit_bind, Keybindings,
+            add_common_selection_bindings, combine_events, edit_bind, KeyCombination,
+            KeySequenceMatcher, Keybindings, SequenceOutput,
         },
         EditMode,
     },
-    enums::{EditCommand, ReedlineEvent, ReedlineRawEvent},
+    enums::{mouse_event, EditCommand, ReedlineEvent, ReedlineRawEvent},
     PromptEditMode,
 };
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use std::time::Duration;
 
 /// Returns the current default emacs keybindings
 pub fn default_emacs_keybindings() -> Keybindings {
@@ -53,8 +55,8 @@ pub fn default_emacs_keybindings() -> Keybindings {
     );
     kb.add_binding(KM::CONTROL, KC::Char('w'), edit_bind(EC::CutWordLeft));
     kb.add_binding(KM::CONTROL, KC::Char('k'), edit_bind(EC::CutToEnd));
-    kb.add_binding(KM::CONTROL, KC::Char('u'), edit_bind(EC::CutFromStart));
     kb.add_binding(KM::ALT, KC::Char('d'), edit_bind(EC::CutWordRight));
+    kb.add_binding(KM::ALT, KC::Char('y'), edit_bind(EC::YankPop));
     // Edits
     kb.add_binding(KM::CONTROL, KC::Char('t'), edit_bind(EC::SwapGraphemes));
 
@@ -97,21 +99,92 @@ pub fn default_emacs_keybindings() -> Keybindings {
     // Case changes
     kb.add_binding(KM::ALT, KC::Char('u'), edit_bind(EC::UppercaseWord));
     kb.add_binding(KM::ALT, KC::Char('l'), edit_bind(EC::LowercaseWord));
-    kb.add_binding(KM::ALT, KC::Char('c'), edit_bind(EC::CapitalizeChar));
+    kb.add_binding(KM::ALT, KC::Char('c'), edit_bind(EC::CapitalizeWord));
+    kb.add_binding(KM::ALT, KC::Char('t'), edit_bind(EC::SwapWords));
+
+    // Numeric argument repeating the next event
+    kb.add_binding(KM::CONTROL, KC::Char('u'), ReedlineEvent::UniversalArgument);
+    for digit in 0..=9 {
+        kb.add_binding(
+            KM::ALT,
+            KC::Char(char::from(b'0' + digit)),
+            ReedlineEvent::DigitArgument(digit),
+        );
+    }
+
+    // Keyboard macro, recorded in the register `e`
+    // The parentheses are typed with shift on most keyboard layouts
+    for modifier in [KM::NONE, KM::SHIFT] {
+        kb.add_sequence(
+            &[(KM::CONTROL, KC::Char('x')), (modifier, KC::Char('('))],
+            ReedlineEvent::RecordMacro('e'),
+        );
+        kb.add_sequence(
+            &[(KM::CONTROL, KC::Char('x')), (modifier, KC::Char(')'))],
+            ReedlineEvent::StopMacro,
+        );
+    }
+    kb.add_sequence(
+        &[(KM::CONTROL, KC::Char('x')), (KM::NONE, KC::Char('e'))],
+        ReedlineEvent::PlayLastMacro,
+    );
+
+    kb.add_sequence(
+        &[(KM::CONTROL, KC::Char('x')), (KM::CONTROL, KC::Char('e'))],
+        ReedlineEvent::OpenEditor,
+    );
 
     kb
 }
 
+/// Largest repeat count of a numeric argument
+const MAX_NUMERIC_ARGUMENT: usize = 10_000;
+
+/// The numeric argument being typed, repeating the next event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NumericArgument {
+    /// Count of the `Ctrl-u` presses alone: 4 to the power of the presses
+    universal: usize,
+    /// The typed digits, replacing the count of the `Ctrl-u` presses
+    digits: Option<usize>,
+    /// Whether plain digits are still part of the argument rather than inserted
+    accepts_digits: bool,
+}
+
+impl NumericArgument {
+    fn count(&self) -> usize {
+        self.digits
+            .unwrap_or(self.universal)
+            .min(MAX_NUMERIC_ARGUMENT)
+    }
+
+    fn push_digit(&mut self, digit: u8) {
+        let digits = self.digits.unwrap_or(0);
+        self.digits = Some(
+            digits
+                .saturating_mul(10)
+                .saturating_add(usize::from(digit))
+                .min(MAX_NUMERIC_ARGUMENT),
+        );
+        self.accepts_digits = true;
+    }
+}
+
 /// This parses the incoming Events like a emacs style-editor
+///
+/// Like in Emacs, the [`ReedlineEvent::UniversalArgument`] (`Ctrl-u`) and
+/// [`ReedlineEvent::DigitArgument`] (`Alt-<digit>`) events type a numeric argument repeating
+/// the next event: `Ctrl-u` alone repeats it 4 times, `Ctrl-u Ctrl-u` 16 times, while
+/// `Ctrl-u 2 0` or `Alt-2 0` repeat it 20 times.
 pub struct Emacs {
     keybindings: Keybindings,
+    sequence: KeySequenceMatcher,
+    argument: Option<NumericArgument>,
 }
 
 impl Default for Emacs {
     fn default() -> Self {
-        Emacs {
-            keybindings: default_emacs_keybindings(),
-        }
+        Emacs::new(default_emacs_keybindings())
     }
 }
 
@@ -120,76 +193,189 @@ impl EditMode for Emacs {
         match event.into() {
             Event::Key(KeyEvent {
                 code, modifiers, ..
-            }) => match (modifiers, code) {
-                (modifier, KeyCode::Char(c)) => {
-                    // Note. The modifier can also be a combination of modifiers, for
-                    // example:
-                    //     KeyModifiers::CONTROL | KeyModifiers::ALT
-                    //     KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
-                    //
-                    // Mixed modifiers are used by non american keyboards that have extra
-                    // keys like 'alt gr'. Keep this in mind if in the future there are
-                    // cases where an event is not being captured
-                    let c = match modifier {
-                        KeyModifiers::NONE => c,
-                        _ => c.to_ascii_lowercase(),
-                    };
-
-                    self.keybindings
-                        .find_binding(modifier, KeyCode::Char(c))
-                        .unwrap_or_else(|| {
-                            if modifier == KeyModifiers::NONE
-                                || modifier == KeyModifiers::SHIFT
-                                || modifier == KeyModifiers::CONTROL | KeyModifiers::ALT
-                                || modifier
-                                    == KeyModifiers::CONTROL
-                                        | KeyModifiers::ALT
-                                        | KeyModifiers::SHIFT
-                            {
-                                ReedlineEvent::Edit(vec![EditCommand::InsertChar(
-                                    if modifier == KeyModifiers::SHIFT {
-                                        c.to_ascii_uppercase()
-                                    } else {
-                                        c
-                                    },
-                                )])
-                            } else {
-                                ReedlineEvent::None
-                            }
-                        })
-                }
-                _ => self
-                    .keybindings
-                    .find_binding(modifiers, code)
-                    .unwrap_or(ReedlineEvent::None),
-            },
+            }) => {
+                let key = KeyCombination {
+                    modifier: modifiers,
+                    key_code: code,
+                };
+                let outputs = self.sequence.push(&self.keybindings, key);
+                let event = self.sequence_event(outputs);
+                self.apply_argument(event)
+            }
 
-            Event::Mouse(_) => ReedlineEvent::Mouse,
+            Event::Mouse(event) => mouse_event(event),
             Event::Resize(width, height) => ReedlineEvent::Resize(width, height),
             Event::FocusGained => ReedlineEvent::None,
             Event::FocusLost => ReedlineEvent::None,
-            Event::Paste(body) => ReedlineEvent::Edit(vec![EditCommand::InsertString(
-                body.replace("\r\n", "\n").replace('\r', "\n"),
-            )]),
+            Event::Paste(body) => ReedlineEvent::Paste(body),
         }
     }
 
     fn edit_mode(&self) -> PromptEditMode {
         PromptEditMode::Emacs
     }
+
+    fn pending_sequence_timeout(&self) -> Option<Duration> {
+        self.sequence.timeout(&self.keybindings)
+    }
+
+    fn flush_pending_sequence(&mut self) -> ReedlineEvent {
+        let outputs = self.sequence.flush(&self.keybindings);
+        let event = self.sequence_event(outputs);
+        self.apply_argument(event)
+    }
+
+    fn reset_pending(&mut self) {
+        self.sequence = KeySequenceMatcher::new();
+        self.argument = None;
+    }
 }
 
 impl Emacs {
     /// Emacs style input parsing constructor if you want to use custom keybindings
     pub const fn new(keybindings: Keybindings) -> Self {
-        Emacs { keybindings }
+        Emacs {
+            keybindings,
+            sequence: KeySequenceMatcher::new(),
+            argument: None,
+        }
+    }
+
+    /// Types the numeric argument, or repeats the event with the typed argument
+    fn apply_argument(&mut self, event: ReedlineEvent) -> ReedlineEvent {
+        match (event, self.argument.as_mut()) {
+            (ReedlineEvent::UniversalArgument, None) => {
+                self.argument = Some(NumericArgument {
+                    universal: 4,
+                    digits: None,
+                    accepts_digits: true,
+                });
+                ReedlineEvent::None
+            }
+            (ReedlineEvent::UniversalArgument, Some(argument)) => {
+                if argument.digits.is_none() {
+                    argument.universal = argument
+                        .universal
+                        .saturating_mul(4)
+                        .min(MAX_NUMERIC_ARGUMENT);
+                } else {
+                    // Ends the digits, the next ones are inserted
+                    argument.accepts_digits = false;
+                }
+                ReedlineEvent::None
+            }
+            (ReedlineEvent::DigitArgument(digit), _) => {
+                self.argument
+                    .get_or_insert(NumericArgument {
+                        universal: 1,
+                        digits: None,
+                        accepts_digits: true,
+                    })
+                    .push_digit(digit);
+                ReedlineEvent::None
+            }
+            (ReedlineEvent::Edit(commands), Some(argument))
+                if argument.accepts_digits && is_digit_insertion(&commands) =>
+            {
+                if let [EditCommand::InsertChar(c)] = commands[..] {
+                    argument.push_digit(c as u8 - b'0');
+                }
+                ReedlineEvent::None
+            }
+            // Keeps the argument for the event completing a key sequence
+            (ReedlineEvent::None, _) => ReedlineEvent::None,
+            (event, Some(_)) => {
+                let count = self.argument.take().map_or(1, |argument| argument.count());
+                repeat_event(event, count)
+            }
+            (event, None) => event,
+        }
+    }
+
+    /// Event of the keys handled by the key sequence matcher
+    fn sequence_event(&self, outputs: Vec<SequenceOutput>) -> ReedlineEvent {
+        combine_events(
+            outputs
+                .into_iter()
+                .map(|output| match output {
+                    SequenceOutput::Event(event) => event,
+                    SequenceOutput::Key(key) => self.parse_key(key.modifier, key.key_code),
+                })
+                .collect(),
+        )
+    }
+
+    /// Event bound to a single key
+    fn parse_key(&self, modifiers: KeyModifiers, code: KeyCode) -> ReedlineEvent {
+        if let Some(key) = self.keybindings.legacy_fallback(modifiers, code) {
+            return self.parse_key(key.modifier, key.key_code);
+        }
+
+        match (modifiers, code) {
+            (modifier, KeyCode::Char(c)) => {
+                // Note. The modifier can also be a combination of modifiers, for
+                // example:
+                //     KeyModifiers::CONTROL | KeyModifiers::ALT
+                //     KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
+                //
+                // Mixed modifiers are used by non american keyboards that have extra
+                // keys like 'alt gr'. Keep this in mind if in the future there are
+                // cases where an event is not being captured
+                let c = match modifier {
+                    KeyModifiers::NONE => c,
+                    _ => c.to_ascii_lowercase(),
+                };
+
+                self.keybindings
+                    .find_binding(modifier, KeyCode::Char(c))
+                    .unwrap_or_else(|| {
+                        if modifier == KeyModifiers::NONE
+                            || modifier == KeyModifiers::SHIFT
+                            || modifier == KeyModifiers::CONTROL | KeyModifiers::ALT
+                            || modifier
+                                == KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
+                        {
+                            ReedlineEvent::Edit(vec![EditCommand::InsertChar(
+                                if modifier == KeyModifiers::SHIFT {
+                                    c.to_ascii_uppercase()
+                                } else {
+                                    c
+                                },
+                            )])
+                        } else {
+                            ReedlineEvent::None
+                        }
+                    })
+            }
+            _ => self
+                .keybindings
+                .find_binding(modifiers, code)
+                .unwrap_or(ReedlineEvent::None),
+        }
+    }
+}
+
+fn is_digit_insertion(commands: &[EditCommand]) -> bool {
+    matches!(commands, [EditCommand::InsertChar(c)] if c.is_ascii_digit())
+}
+
+/// The event repeated `count` times, its edit commands being kept in a single event
+fn repeat_event(event: ReedlineEvent, count: usize) -> ReedlineEvent {
+    match event {
+        ReedlineEvent::Edit(commands) => {
+            ReedlineEvent::Edit(std::iter::repeat(commands).take(count).flatten().collect())
+        }
+        event if count == 1 => event,
+        event => ReedlineEvent::Multiple(vec![event; count]),
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
 
     #[test]
     fn ctrl_l_leads_to_clear_screen_event() {
@@ -288,4 +474,185 @@ mod test {
             ReedlineEvent::Edit(vec![EditCommand::InsertChar('😀')])
         );
     }
+
+    fn key(modifiers: KeyModifiers, code: KeyCode) -> ReedlineRawEvent {
+        ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(code, modifiers))).unwrap()
+    }
+
+    #[test]
+    fn disambiguated_keys_fall_back_to_their_legacy_key() {
+        let mut keybindings = default_emacs_keybindings();
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Enter,
+            ReedlineEvent::Edit(vec![EditCommand::InsertNewline]),
+        );
+        let mut emacs = Emacs::new(keybindings);
+
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::SHIFT, KeyCode::Enter)),
+            ReedlineEvent::Enter
+        );
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::CONTROL, KeyCode::Enter)),
+            ReedlineEvent::Edit(vec![EditCommand::InsertNewline])
+        );
+    }
+
+    #[test]
+    fn key_sequence_works() {
+        let mut keybindings = default_emacs_keybindings();
+        keybindings.add_sequence(
+            &[
+                (KeyModifiers::CONTROL, KeyCode::Char('x')),
+                (KeyModifiers::CONTROL, KeyCode::Char('s')),
+            ],
+            ReedlineEvent::Submit,
+        );
+
+        let mut emacs = Emacs::new(keybindings);
+
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::CONTROL, KeyCode::Char('x'))),
+            ReedlineEvent::None
+        );
+        assert!(emacs.pending_sequence_timeout().is_some());
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::CONTROL, KeyCode::Char('s'))),
+            ReedlineEvent::Submit
+        );
+        assert_eq!(emacs.pending_sequence_timeout(), None);
+    }
+
+    #[test]
+    fn interrupted_key_sequence_handles_keys_on_their_own() {
+        let mut keybindings = default_emacs_keybindings();
+        keybindings.add_sequence(
+            &[
+                (KeyModifiers::CONTROL, KeyCode::Char('l')),
+                (KeyModifiers::NONE, KeyCode::Char('l')),
+            ],
+            ReedlineEvent::Submit,
+        );
+
+        let mut emacs = Emacs::new(keybindings);
+
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::CONTROL, KeyCode::Char('l'))),
+            ReedlineEvent::None
+        );
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::NONE, KeyCode::Char('a'))),
+            ReedlineEvent::Multiple(vec![
+                ReedlineEvent::ClearScreen,
+                ReedlineEvent::Edit(vec![EditCommand::InsertChar('a')])
+            ])
+        );
+
+        // After the timeout, the single key binding applies
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::CONTROL, KeyCode::Char('l'))),
+            ReedlineEvent::None
+        );
+        assert_eq!(emacs.flush_pending_sequence(), ReedlineEvent::ClearScreen);
+        assert_eq!(emacs.pending_sequence_timeout(), None);
+    }
+
+    #[test]
+    fn numeric_argument_repeats_the_delete() {
+        let mut emacs = Emacs::default();
+
+        for event in [
+            key(KeyModifiers::CONTROL, KeyCode::Char('u')),
+            key(KeyModifiers::NONE, KeyCode::Char('2')),
+            key(KeyModifiers::NONE, KeyCode::Char('0')),
+        ] {
+            assert_eq!(emacs.parse_event(event), ReedlineEvent::None);
+        }
+
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::NONE, KeyCode::Delete)),
+            ReedlineEvent::Edit(vec![EditCommand::Delete; 20])
+        );
+        // The argument only applies to a single event
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::NONE, KeyCode::Delete)),
+            ReedlineEvent::Edit(vec![EditCommand::Delete])
+        );
+    }
+
+    #[test]
+    fn digit_argument_repeats_the_forward_word() {
+        let mut emacs = Emacs::default();
+
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::ALT, KeyCode::Char('3'))),
+            ReedlineEvent::None
+        );
+        assert_eq!(
+            emacs.parse_event(key(KeyModifiers::ALT, KeyCode::Char('f'))),
+            ReedlineEvent::Multiple(vec![
+                ReedlineEvent::UntilFound(vec![
+                    ReedlineEvent::HistoryHintWordComplete,
+                    ReedlineEvent::Edit(vec![EditCommand::MoveWordRight { select: false }]),
+                ]);
+                3
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case(MouseEventKind::Down(MouseButton::Left), ReedlineEvent::MouseClick { column: 4, row: 2 })]
+    #[case(MouseEventKind::Drag(MouseButton::Left), ReedlineEvent::MouseDrag { column: 4, row: 2 })]
+    #[case(MouseEventKind::Down(MouseButton::Right), ReedlineEvent::Mouse)]
+    #[case(MouseEventKind::Moved, ReedlineEvent::Mouse)]
+    fn left_button_mouse_events_are_kept(
+        #[case] kind: MouseEventKind,
+        #[case] expected: ReedlineEvent,
+    ) {
+        let mut emacs = Emacs::default();
+        let event = Event::Mouse(MouseEvent {
+            kind,
+            column: 4,
+            row: 2,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        assert_eq!(
+            emacs.parse_event(ReedlineRawEvent::convert_from(event).unwrap()),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(&[(KeyModifiers::CONTROL, 'u')], "aaaa")]
+    #[case(&[(KeyModifiers::CONTROL, 'u'), (KeyModifiers::CONTROL, 'u')], "aaaaaaaaaaaaaaaa")]
+    #[case(&[(KeyModifiers::CONTROL, 'u'), (KeyModifiers::NONE, '3')], "aaa")]
+    #[case(&[(KeyModifiers::ALT, '1'), (KeyModifiers::NONE, '2')], "aaaaaaaaaaaa")]
+    // The second `Ctrl-u` ends the digits
+    #[case(&[(KeyModifiers::CONTROL, 'u'), (KeyModifiers::NONE, '2'), (KeyModifiers::CONTROL, 'u')], "aa")]
+    fn numeric_argument_repeats_the_self_insert(
+        #[case] argument: &[(KeyModifiers, char)],
+        #[case] expected: &str,
+    ) {
+        let mut emacs = Emacs::default();
+        for (modifiers, c) in argument {
+            assert_eq!(
+                emacs.parse_event(key(*modifiers, KeyCode::Char(*c))),
+                ReedlineEvent::None
+            );
+        }
+
+        let inserted = match emacs.parse_event(key(KeyModifiers::NONE, KeyCode::Char('a'))) {
+            ReedlineEvent::Edit(commands) => commands
+                .into_iter()
+                .map(|command| match command {
+                    EditCommand::InsertChar(c) => c,
+                    command => panic!("unexpected {command:?}"),
+                })
+                .collect::<String>(),
+            event => panic!("unexpected {event:?}"),
+        };
+        assert_eq!(inserted, expected);
+    }
 }
@@ -1,21 +1,388 @@
 use {
     crate::{enums::ReedlineEvent, EditCommand},
-    crossterm::event::{KeyCode, KeyModifiers},
-    serde::{Deserialize, Serialize},
-    std::collections::HashMap,
+    crossterm::event::{KeyCode, KeyModifiers, MediaKeyCode, ModifierKeyCode},
+    serde::{
+        de::{self, IntoDeserializer, MapAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::{self, Display},
+        marker::PhantomData,
+        str::FromStr,
+        time::{Duration, Instant},
+    },
+    thiserror::Error,
 };
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+/// Default max delay between two keys of a key sequence
+const DEFAULT_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// A key along with the modifiers held while it is pressed
+///
+/// Its string form, used to serialize it, is made of the modifiers followed by the key,
+/// separated by dashes, e.g. `ctrl-shift-a`, `alt-enter`, `f5` or `ctrl--`.
+/// See [`KeyCombination::from_str`] for the names of the keys.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct KeyCombination {
+    /// Modifiers held, such as `Ctrl`
     pub modifier: KeyModifiers,
+    /// Key pressed
     pub key_code: KeyCode,
 }
 
+/// Names of the modifiers in the string form of a [`KeyCombination`], in their order
+const MODIFIER_NAMES: [(KeyModifiers, &str); 6] = [
+    (KeyModifiers::CONTROL, "ctrl"),
+    (KeyModifiers::ALT, "alt"),
+    (KeyModifiers::SHIFT, "shift"),
+    (KeyModifiers::SUPER, "super"),
+    (KeyModifiers::HYPER, "hyper"),
+    (KeyModifiers::META, "meta"),
+];
+
+/// Names of the keys in the string form of a [`KeyCombination`], besides characters,
+/// function keys, media keys and modifier keys
+const KEY_NAMES: [(KeyCode, &str); 24] = [
+    (KeyCode::Char(' '), "space"),
+    (KeyCode::Backspace, "backspace"),
+    (KeyCode::Enter, "enter"),
+    (KeyCode::Left, "left"),
+    (KeyCode::Right, "right"),
+    (KeyCode::Up, "up"),
+    (KeyCode::Down, "down"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::PageUp, "pageup"),
+    (KeyCode::PageDown, "pagedown"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::BackTab, "backtab"),
+    (KeyCode::Delete, "delete"),
+    (KeyCode::Insert, "insert"),
+    (KeyCode::Null, "null"),
+    (KeyCode::Esc, "esc"),
+    (KeyCode::CapsLock, "capslock"),
+    (KeyCode::ScrollLock, "scrolllock"),
+    (KeyCode::NumLock, "numlock"),
+    (KeyCode::PrintScreen, "printscreen"),
+    (KeyCode::Pause, "pause"),
+    (KeyCode::Menu, "menu"),
+    (KeyCode::KeypadBegin, "keypadbegin"),
+];
+
+/// Error returned when parsing an invalid [`KeyCombination`] or key sequence
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid key combination `{0}`")]
+pub struct ParseKeyError(String);
+
+impl Display for KeyCombination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in MODIFIER_NAMES {
+            if self.modifier.contains(modifier) {
+                write!(f, "{name}-")?;
+            }
+        }
+
+        if let Some((_, name)) = KEY_NAMES.iter().find(|(code, _)| *code == self.key_code) {
+            return f.write_str(name);
+        }
+
+        match self.key_code {
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::Media(code) => write!(f, "media-{code:?}"),
+            KeyCode::Modifier(code) => write!(f, "modifier-{code:?}"),
+            key_code => unreachable!("{key_code:?} should have a name"),
+        }
+    }
+}
+
+impl FromStr for KeyCombination {
+    type Err = ParseKeyError;
+
+    /// Parses the string form of a key combination
+    ///
+    /// The modifiers are `ctrl` (or `control`), `alt`, `shift`, `super`, `hyper` and `meta`.
+    /// The key is either a single character, `space`, a function key from `f1`, one of `backspace`,
+    /// `enter`, `left`, `right`, `up`, `down`, `home`, `end`, `pageup`, `pagedown`, `tab`, `backtab`,
+    /// `delete`, `insert`, `null`, `esc`, `capslock`, `scrolllock`, `numlock`, `printscreen`,
+    /// `pause`, `menu` and `keypadbegin`, or a media or modifier key such as `media-PlayPause` or
+    /// `modifier-LeftShift`, named after [`MediaKeyCode`] and [`ModifierKeyCode`].
+    ///
+    /// Only the characters and the media and modifier keys are case sensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifier = KeyModifiers::NONE;
+        let mut key = s;
+
+        // A single character is the key, even if it is a dash
+        while key.chars().count() > 1 {
+            let Some((name, rest)) = key.split_once('-') else {
+                break;
+            };
+
+            let flag = MODIFIER_NAMES
+                .iter()
+                .chain(&[(KeyModifiers::CONTROL, "control")])
+                .find(|(_, modifier_name)| modifier_name.eq_ignore_ascii_case(name));
+
+            match flag {
+                Some((flag, _)) => modifier |= *flag,
+                None => break,
+            }
+
+            key = rest;
+        }
+
+        let key_code = parse_key_code(key).ok_or_else(|| ParseKeyError(s.to_string()))?;
+
+        Ok(Self { modifier, key_code })
+    }
+}
+
+fn parse_key_code(key: &str) -> Option<KeyCode> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    if let Some((code, _)) = KEY_NAMES
+        .iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(key))
+    {
+        return Some(*code);
+    }
+
+    if let Some(code) = key.strip_prefix("media-") {
+        return MediaKeyCode::deserialize(code.into_deserializer())
+            .map(KeyCode::Media)
+            .map_err(|_: de::value::Error| ())
+            .ok();
+    }
+
+    if let Some(code) = key.strip_prefix("modifier-") {
+        return ModifierKeyCode::deserialize(code.into_deserializer())
+            .map(KeyCode::Modifier)
+            .map_err(|_: de::value::Error| ())
+            .ok();
+    }
+
+    key.strip_prefix(['f', 'F'])
+        .and_then(|n| n.parse().ok())
+        .filter(|n| *n > 0)
+        .map(KeyCode::F)
+}
+
+impl Serialize for KeyCombination {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyCombination {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        key.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Keys of a sequence, whose string form is made of the keys separated by spaces
+struct KeySequence(Vec<KeyCombination>);
+
+impl Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, key) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{key}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.split_whitespace().next().is_none() {
+            return Err(ParseKeyError(s.to_string()));
+        }
+
+        s.split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(KeySequence)
+    }
+}
+
+/// Node of the trie of key sequences, reached by typing the keys of the path leading to it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct KeySequenceNode {
+    event: Option<ReedlineEvent>,
+    next: HashMap<KeyCombination, KeySequenceNode>,
+}
+
+impl KeySequenceNode {
+    fn insert(&mut self, keys: Vec<KeyCombination>, event: ReedlineEvent) {
+        let node = keys
+            .into_iter()
+            .fold(self, |node, key| node.next.entry(key).or_default());
+
+        node.event = Some(event);
+    }
+
+    /// Every sequence leading to an event from this node, after the `prefix` keys
+    fn sequences<'a>(
+        &'a self,
+        prefix: &mut Vec<KeyCombination>,
+        sequences: &mut Vec<(KeySequence, &'a ReedlineEvent)>,
+    ) {
+        if let Some(event) = &self.event {
+            sequences.push((KeySequence(prefix.clone()), event));
+        }
+
+        for (key, node) in &self.next {
+            prefix.push(key.clone());
+            node.sequences(prefix, sequences);
+            prefix.pop();
+        }
+    }
+}
+
+/// How the keys typed so far match the key sequences of [`Keybindings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceMatch {
+    /// The keys start longer sequences, with the event bound to these keys themselves if any
+    Partial(Option<ReedlineEvent>),
+    /// The keys form a whole sequence, bound to the event
+    Complete(ReedlineEvent),
+    /// No sequence starts with the keys
+    NoMatch,
+}
+
 /// Main definition of editor keybindings
-#[derive(Serialize, Deserialize, Clone, Debug)]
+///
+/// Keybindings can be written to and read from configuration files: both the keys of the
+/// bindings and the key sequences are written in their string form (see [`KeyCombination`]),
+/// the keys of a sequence being separated by spaces.
+///
+/// ```rust
+/// use reedline::Keybindings;
+///
+/// let keybindings: Keybindings = serde_json::from_str(
+///     r#"{
+///         "bindings": { "ctrl-l": "ClearScreen", "alt-enter": { "Edit": ["InsertNewline"] } },
+///         "sequences": { "ctrl-x ctrl-s": "Submit" }
+///     }"#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(keybindings.get_keybindings().len(), 2);
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Keybindings {
     /// Defines a keybinding for a reedline event
+    #[serde(
+        serialize_with = "serialize_bindings",
+        deserialize_with = "deserialize_bindings"
+    )]
     pub bindings: HashMap<KeyCombination, ReedlineEvent>,
+    /// Defines the bindings of key sequences
+    #[serde(
+        default,
+        serialize_with = "serialize_sequences",
+        deserialize_with = "deserialize_sequences"
+    )]
+    sequences: KeySequenceNode,
+    /// Max delay between two keys of a sequence
+    #[serde(default = "default_sequence_timeout")]
+    sequence_timeout: Duration,
+}
+
+fn default_sequence_timeout() -> Duration {
+    DEFAULT_SEQUENCE_TIMEOUT
+}
+
+/// The bindings are sorted by key for a stable output
+fn serialize_bindings<S: Serializer>(
+    bindings: &HashMap<KeyCombination, ReedlineEvent>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        bindings
+            .iter()
+            .map(|(key, event)| (key.to_string(), event))
+            .collect::<BTreeMap<_, _>>(),
+    )
+}
+
+fn deserialize_bindings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<KeyCombination, ReedlineEvent>, D::Error> {
+    let bindings = deserializer.deserialize_map(BindingsVisitor::<KeyCombination>(PhantomData))?;
+    Ok(bindings.into_iter().collect())
+}
+
+fn serialize_sequences<S: Serializer>(
+    sequences: &KeySequenceNode,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut all_sequences = vec![];
+    sequences.sequences(&mut vec![], &mut all_sequences);
+
+    serializer.collect_map(
+        all_sequences
+            .into_iter()
+            .map(|(keys, event)| (keys.to_string(), event))
+            .collect::<BTreeMap<_, _>>(),
+    )
+}
+
+fn deserialize_sequences<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<KeySequenceNode, D::Error> {
+    let sequences = deserializer.deserialize_map(BindingsVisitor::<KeySequence>(PhantomData))?;
+
+    let mut node = KeySequenceNode::default();
+    for (keys, event) in sequences {
+        node.insert(keys.0, event);
+    }
+
+    Ok(node)
+}
+
+/// Reads a map of keys to events, the errors naming the keys of the invalid bindings
+struct BindingsVisitor<K>(PhantomData<K>);
+
+impl<'de, K: FromStr<Err = ParseKeyError>> Visitor<'de> for BindingsVisitor<K> {
+    type Value = Vec<(K, ReedlineEvent)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of keys to reedline events")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut bindings = vec![];
+
+        while let Some(key) = map.next_key::<String>()? {
+            let keys = key.parse().map_err(de::Error::custom)?;
+
+            let event = map.next_value::<ReedlineEvent>().map_err(|err| {
+                de::Error::custom(format!("invalid event bound to `{key}`: {err}"))
+            })?;
+
+            if matches!(&event, ReedlineEvent::UntilFound(events) if events.is_empty()) {
+                return Err(de::Error::custom(format!(
+                    "invalid event bound to `{key}`: UntilFound should contain a series of potential events to handle"
+                )));
+            }
+
+            bindings.push((keys, event));
+        }
+
+        Ok(bindings)
+    }
 }
 
 impl Default for Keybindings {
@@ -29,6 +396,8 @@ impl Keybindings {
     pub fn new() -> Self {
         Self {
             bindings: HashMap::new(),
+            sequences: KeySequenceNode::default(),
+            sequence_timeout: DEFAULT_SEQUENCE_TIMEOUT,
         }
     }
 
@@ -65,6 +434,43 @@ impl Keybindings {
         self.bindings.get(&key_combo).cloned()
     }
 
+    /// Key that terminals without the kitty keyboard protocol report instead of an unbound key
+    ///
+    /// The protocol distinguishes keys such as `Shift-Enter`, `Ctrl-Tab` or `Ctrl-i` from `Enter`
+    /// and `Tab`. Without a binding of their own, they are handled like the legacy key so that
+    /// enabling the protocol doesn't lose them. Returns `None` if the key is bound or has no
+    /// legacy equivalent.
+    pub fn legacy_fallback(
+        &self,
+        modifier: KeyModifiers,
+        key_code: KeyCode,
+    ) -> Option<KeyCombination> {
+        if self.find_binding(modifier, key_code).is_some() {
+            return None;
+        }
+
+        let stripped = modifier - KeyModifiers::SHIFT - KeyModifiers::CONTROL;
+        let key_code = match key_code {
+            KeyCode::Enter | KeyCode::Tab | KeyCode::Backspace | KeyCode::Esc
+                if modifier != stripped =>
+            {
+                key_code
+            }
+            KeyCode::Char(c) if modifier.contains(KeyModifiers::CONTROL) => match c {
+                'i' | 'I' => KeyCode::Tab,
+                'm' | 'M' => KeyCode::Enter,
+                '[' => KeyCode::Esc,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(KeyCombination {
+            modifier: stripped,
+            key_code,
+        })
+    }
+
     /// Remove a keybinding
     ///
     /// Returns `Some(ReedlineEvent)` if the key combination was previously bound to a particular [`ReedlineEvent`]
@@ -81,6 +487,223 @@ impl Keybindings {
     pub fn get_keybindings(&self) -> &HashMap<KeyCombination, ReedlineEvent> {
         &self.bindings
     }
+
+    /// Adds a binding to a sequence of keys typed one after the other, such as `g g` or `Ctrl-x Ctrl-s`
+    ///
+    /// Once the first keys of a sequence have been typed, the next key is matched against the
+    /// sequences before the single key bindings. Keys that don't continue a sequence, or that
+    /// aren't followed by another key before the timeout (see [`Self::set_sequence_timeout`]),
+    /// are handled on their own as if no sequence was bound: through the single key bindings,
+    /// the character insertion of Emacs and the insert mode of Vi, or the command parser of Vi's
+    /// normal mode. If the keys typed before the timeout form a sequence of their own, its
+    /// event is sent instead.
+    ///
+    /// In Vi, each mode has its own sequences, like its own keybindings.
+    ///
+    /// # Panics
+    ///
+    /// If `keys` is empty or `command` is an empty [`ReedlineEvent::UntilFound`]
+    pub fn add_sequence(&mut self, keys: &[(KeyModifiers, KeyCode)], command: ReedlineEvent) {
+        assert!(!keys.is_empty(), "A key sequence should contain keys");
+
+        if let ReedlineEvent::UntilFound(subcommands) = &command {
+            assert!(
+                !subcommands.is_empty(),
+                "UntilFound should contain a series of potential events to handle"
+            );
+        }
+
+        let keys = keys
+            .iter()
+            .map(|&(modifier, key_code)| KeyCombination { modifier, key_code })
+            .collect();
+
+        self.sequences.insert(keys, command);
+    }
+
+    /// Remove the binding of a key sequence
+    ///
+    /// Returns `Some(ReedlineEvent)` if the key sequence was previously bound to a particular [`ReedlineEvent`]
+    pub fn remove_sequence(&mut self, keys: &[(KeyModifiers, KeyCode)]) -> Option<ReedlineEvent> {
+        fn remove(node: &mut KeySequenceNode, keys: &[KeyCombination]) -> Option<ReedlineEvent> {
+            let Some((key, rest)) = keys.split_first() else {
+                return node.event.take();
+            };
+
+            let next = node.next.get_mut(key)?;
+            let event = remove(next, rest);
+
+            // Dropping the nodes that don't lead to any sequence anymore
+            if next.event.is_none() && next.next.is_empty() {
+                node.next.remove(key);
+            }
+
+            event
+        }
+
+        let keys = keys
+            .iter()
+            .map(|&(modifier, key_code)| KeyCombination { modifier, key_code })
+            .collect::<Vec<_>>();
+
+        if keys.is_empty() {
+            return None;
+        }
+
+        remove(&mut self.sequences, &keys)
+    }
+
+    /// Find how the keys typed so far match the key sequences
+    pub fn find_sequence(&self, keys: &[KeyCombination]) -> SequenceMatch {
+        let node = keys
+            .iter()
+            .try_fold(&self.sequences, |node, key| node.next.get(key));
+
+        match node {
+            Some(node) if keys.is_empty() => SequenceMatch::Partial(node.event.clone()),
+            Some(node) if node.next.is_empty() => match &node.event {
+                Some(event) => SequenceMatch::Complete(event.clone()),
+                None => SequenceMatch::NoMatch,
+            },
+            Some(node) => SequenceMatch::Partial(node.event.clone()),
+            None => SequenceMatch::NoMatch,
+        }
+    }
+
+    /// Set the max delay between two keys of a key sequence, one second by default
+    pub fn set_sequence_timeout(&mut self, timeout: Duration) {
+        self.sequence_timeout = timeout;
+    }
+
+    /// Get the max delay between two keys of a key sequence
+    pub fn sequence_timeout(&self) -> Duration {
+        self.sequence_timeout
+    }
+
+    /// Bind `Up` and `Down`, as well as `Ctrl-p` and `Ctrl-n`, to the history entries starting
+    /// with the text before the cursor, once no menu takes them
+    ///
+    /// See [`ReedlineEvent::HistoryPrefixUp`] and [`ReedlineEvent::HistoryPrefixDown`].
+    pub fn add_history_prefix_bindings(&mut self) {
+        let up =
+            ReedlineEvent::UntilFound(vec![ReedlineEvent::MenuUp, ReedlineEvent::HistoryPrefixUp]);
+        let down = ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::MenuDown,
+            ReedlineEvent::HistoryPrefixDown,
+        ]);
+        self.add_binding(KeyModifiers::NONE, KeyCode::Up, up.clone());
+        self.add_binding(KeyModifiers::CONTROL, KeyCode::Char('p'), up);
+        self.add_binding(KeyModifiers::NONE, KeyCode::Down, down.clone());
+        self.add_binding(KeyModifiers::CONTROL, KeyCode::Char('n'), down);
+    }
+}
+
+/// What to do with a key given to a [`KeySequenceMatcher`]
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SequenceOutput {
+    /// A sequence was typed, bound to the event
+    Event(ReedlineEvent),
+    /// The key isn't part of a sequence, and has to be handled on its own
+    Key(KeyCombination),
+}
+
+/// Matches the key sequences of [`Keybindings`] as keys are typed
+#[derive(Debug, Default)]
+pub(crate) struct KeySequenceMatcher {
+    pending: Vec<KeyCombination>,
+    last_key: Option<Instant>,
+}
+
+impl KeySequenceMatcher {
+    pub(crate) const fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            last_key: None,
+        }
+    }
+
+    /// Matches the typed key after the pending ones
+    ///
+    /// Returns nothing while the keys typed so far start a longer sequence.
+    pub(crate) fn push(
+        &mut self,
+        keybindings: &Keybindings,
+        key: KeyCombination,
+    ) -> Vec<SequenceOutput> {
+        self.pending.push(key);
+
+        match keybindings.find_sequence(&normalized(&self.pending)) {
+            SequenceMatch::Partial(_) => {
+                self.last_key = Some(Instant::now());
+                vec![]
+            }
+            SequenceMatch::Complete(event) => {
+                self.clear();
+                vec![SequenceOutput::Event(event)]
+            }
+            SequenceMatch::NoMatch => {
+                let key = self.pending.pop().expect("the key was just pushed");
+
+                if self.pending.is_empty() {
+                    return vec![SequenceOutput::Key(key)];
+                }
+
+                // The key may start a new sequence once the pending ones are handled
+                let mut outputs = self.flush(keybindings);
+                outputs.extend(self.push(keybindings, key));
+                outputs
+            }
+        }
+    }
+
+    /// Gives back the pending keys, as the event of the sequence they form if any
+    pub(crate) fn flush(&mut self, keybindings: &Keybindings) -> Vec<SequenceOutput> {
+        let keys = std::mem::take(&mut self.pending);
+        self.last_key = None;
+
+        match keybindings.find_sequence(&normalized(&keys)) {
+            SequenceMatch::Partial(Some(event)) | SequenceMatch::Complete(event) => {
+                vec![SequenceOutput::Event(event)]
+            }
+            _ => keys.into_iter().map(SequenceOutput::Key).collect(),
+        }
+    }
+
+    /// Time left before the pending keys have to be flushed, if any
+    pub(crate) fn timeout(&self, keybindings: &Keybindings) -> Option<Duration> {
+        self.last_key.map(|last_key| {
+            keybindings
+                .sequence_timeout
+                .saturating_sub(last_key.elapsed())
+        })
+    }
+
+    fn clear(&mut self) {
+        self.pending.clear();
+        self.last_key = None;
+    }
+}
+
+/// Keys as they are looked up in the keybindings: the characters typed with a modifier are lowercase
+fn normalized(keys: &[KeyCombination]) -> Vec<KeyCombination> {
+    keys.iter()
+        .map(|key| match key.key_code {
+            KeyCode::Char(c) if key.modifier != KeyModifiers::NONE => KeyCombination {
+                modifier: key.modifier,
+                key_code: KeyCode::Char(c.to_ascii_lowercase()),
+            },
+            _ => key.clone(),
+        })
+        .collect()
+}
+
+/// Combines the events obtained for several keys into a single one
+pub(crate) fn combine_events(mut events: Vec<ReedlineEvent>) -> ReedlineEvent {
+    match events.len() {
+        0 => ReedlineEvent::None,
+        1 => events.pop().expect("there is one event"),
+        _ => ReedlineEvent::Multiple(events),
+    }
 }
 
 pub fn edit_bind(command: EditCommand) -> ReedlineEvent {
@@ -89,7 +712,7 @@ pub fn edit_bind(command: EditCommand) -> ReedlineEvent {
 
 /// Add the basic special keybindings
 ///
-/// `Ctrl-C`, `Ctrl-D`, `Ctrl-O`, `Ctrl-R`
+/// `Ctrl-C`, `Ctrl-D`, `Ctrl-O`, `Ctrl-R`, `Ctrl-S`
 /// + `Esc`
 /// + `Ctrl-O` to open the external editor
 pub fn add_common_control_bindings(kb: &mut Keybindings) {
@@ -101,6 +724,11 @@ pub fn add_common_control_bindings(kb: &mut Keybindings) {
     kb.add_binding(KM::CONTROL, KC::Char('d'), ReedlineEvent::CtrlD);
     kb.add_binding(KM::CONTROL, KC::Char('l'), ReedlineEvent::ClearScreen);
     kb.add_binding(KM::CONTROL, KC::Char('r'), ReedlineEvent::SearchHistory);
+    kb.add_binding(
+        KM::CONTROL,
+        KC::Char('s'),
+        ReedlineEvent::SearchHistoryForward,
+    );
     kb.add_binding(KM::CONTROL, KC::Char('o'), ReedlineEvent::OpenEditor);
 }
 /// Add the arrow navigation and its `Ctrl` variants
@@ -202,11 +830,12 @@ pub fn add_common_navigation_bindings(kb: &mut Keybindings) {
 
 /// Add basic functionality to edit
 ///
-/// `Delete`, `Backspace` and the basic variants do delete words
+/// `Delete`, `Backspace` and the basic variants do delete words, `Alt-Enter` inserts a newline
 pub fn add_common_edit_bindings(kb: &mut Keybindings) {
     use EditCommand as EC;
     use KeyCode as KC;
     use KeyModifiers as KM;
+    kb.add_binding(KM::ALT, KC::Enter, ReedlineEvent::InsertNewline);
     kb.add_binding(KM::NONE, KC::Backspace, edit_bind(EC::Backspace));
     kb.add_binding(KM::NONE, KC::Delete, edit_bind(EC::Delete));
     kb.add_binding(KM::CONTROL, KC::Backspace, edit_bind(EC::BackspaceWord));
@@ -285,3 +914,249 @@ pub fn add_common_selection_bindings(kb: &mut Keybindings) {
         edit_bind(EC::SelectAll),
     );
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings,
+    };
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(KeyModifiers::SHIFT, KeyCode::Enter, Some((KeyModifiers::NONE, KeyCode::Enter)))]
+    #[case(
+        KeyModifiers::CONTROL | KeyModifiers::ALT,
+        KeyCode::Enter,
+        Some((KeyModifiers::ALT, KeyCode::Enter))
+    )]
+    #[case(KeyModifiers::CONTROL, KeyCode::Tab, Some((KeyModifiers::NONE, KeyCode::Tab)))]
+    #[case(KeyModifiers::CONTROL, KeyCode::Char('i'), Some((KeyModifiers::NONE, KeyCode::Tab)))]
+    #[case(KeyModifiers::CONTROL, KeyCode::Char('m'), Some((KeyModifiers::NONE, KeyCode::Enter)))]
+    #[case(KeyModifiers::NONE, KeyCode::Enter, None)]
+    #[case(KeyModifiers::ALT, KeyCode::Enter, None)]
+    #[case(KeyModifiers::CONTROL, KeyCode::Char('a'), None)]
+    // Bound keys are kept
+    #[case(KeyModifiers::SHIFT, KeyCode::Tab, None)]
+    fn test_legacy_fallback(
+        #[case] modifier: KeyModifiers,
+        #[case] key_code: KeyCode,
+        #[case] expected: Option<(KeyModifiers, KeyCode)>,
+    ) {
+        let mut keybindings = Keybindings::new();
+        keybindings.add_binding(
+            KeyModifiers::SHIFT,
+            KeyCode::Tab,
+            ReedlineEvent::MenuPrevious,
+        );
+
+        assert_eq!(
+            keybindings.legacy_fallback(modifier, key_code),
+            expected.map(|(modifier, key_code)| KeyCombination { modifier, key_code })
+        );
+    }
+
+    fn combination(c: char) -> KeyCombination {
+        KeyCombination {
+            modifier: KeyModifiers::NONE,
+            key_code: KeyCode::Char(c),
+        }
+    }
+
+    #[test]
+    fn find_sequence_walks_the_trie() {
+        let mut keybindings = Keybindings::new();
+        let g = (KeyModifiers::NONE, KeyCode::Char('g'));
+        let d = (KeyModifiers::NONE, KeyCode::Char('d'));
+
+        keybindings.add_sequence(&[g, g], ReedlineEvent::Up);
+        keybindings.add_sequence(&[g, d], ReedlineEvent::Down);
+        keybindings.add_sequence(&[g, d, d], ReedlineEvent::Left);
+
+        let find = |keys: &str| {
+            keybindings.find_sequence(&keys.chars().map(combination).collect::<Vec<_>>())
+        };
+
+        assert_eq!(find("g"), SequenceMatch::Partial(None));
+        assert_eq!(find("gg"), SequenceMatch::Complete(ReedlineEvent::Up));
+        assert_eq!(
+            find("gd"),
+            SequenceMatch::Partial(Some(ReedlineEvent::Down))
+        );
+        assert_eq!(find("gdd"), SequenceMatch::Complete(ReedlineEvent::Left));
+        assert_eq!(find("gx"), SequenceMatch::NoMatch);
+        assert_eq!(find("x"), SequenceMatch::NoMatch);
+
+        assert_eq!(
+            keybindings.remove_sequence(&[g, d, d]),
+            Some(ReedlineEvent::Left)
+        );
+        assert_eq!(keybindings.remove_sequence(&[g, d, d]), None);
+        assert_eq!(
+            keybindings.find_sequence(&[combination('g'), combination('d')]),
+            SequenceMatch::Complete(ReedlineEvent::Down)
+        );
+    }
+
+    #[test]
+    fn matcher_flushes_interrupted_sequences() {
+        let mut keybindings = Keybindings::new();
+        let g = (KeyModifiers::NONE, KeyCode::Char('g'));
+        keybindings.add_sequence(&[g, g], ReedlineEvent::Up);
+
+        let mut matcher = KeySequenceMatcher::new();
+
+        assert_eq!(matcher.push(&keybindings, combination('g')), vec![]);
+        assert!(matcher.timeout(&keybindings).is_some());
+
+        // The interrupting key may start a sequence again
+        assert_eq!(
+            matcher.push(&keybindings, combination('x')),
+            vec![
+                SequenceOutput::Key(combination('g')),
+                SequenceOutput::Key(combination('x'))
+            ]
+        );
+        assert_eq!(matcher.timeout(&keybindings), None);
+
+        assert_eq!(matcher.push(&keybindings, combination('g')), vec![]);
+        assert_eq!(
+            matcher.push(&keybindings, combination('g')),
+            vec![SequenceOutput::Event(ReedlineEvent::Up)]
+        );
+
+        assert_eq!(matcher.push(&keybindings, combination('g')), vec![]);
+        assert_eq!(
+            matcher.flush(&keybindings),
+            vec![SequenceOutput::Key(combination('g'))]
+        );
+        assert_eq!(matcher.timeout(&keybindings), None);
+    }
+
+    #[rstest]
+    #[case(KeyModifiers::NONE, KeyCode::Char('a'), "a")]
+    #[case(KeyModifiers::CONTROL | KeyModifiers::SHIFT, KeyCode::Char('A'), "ctrl-shift-A")]
+    #[case(KeyModifiers::CONTROL, KeyCode::Char('-'), "ctrl--")]
+    #[case(KeyModifiers::ALT, KeyCode::Char(' '), "alt-space")]
+    #[case(KeyModifiers::NONE, KeyCode::F(12), "f12")]
+    #[case(KeyModifiers::SHIFT, KeyCode::BackTab, "shift-backtab")]
+    #[case(
+        KeyModifiers::NONE,
+        KeyCode::Media(MediaKeyCode::PlayPause),
+        "media-PlayPause"
+    )]
+    #[case(
+        KeyModifiers::NONE,
+        KeyCode::Modifier(ModifierKeyCode::LeftShift),
+        "modifier-LeftShift"
+    )]
+    fn key_combination_string_form(
+        #[case] modifier: KeyModifiers,
+        #[case] key_code: KeyCode,
+        #[case] expected: &str,
+    ) {
+        let combination = KeyCombination { modifier, key_code };
+
+        assert_eq!(combination.to_string(), expected);
+        assert_eq!(expected.parse(), Ok(combination));
+    }
+
+    #[rstest]
+    #[case("Control-Enter", KeyModifiers::CONTROL, KeyCode::Enter)]
+    #[case("ALT-PageUp", KeyModifiers::ALT, KeyCode::PageUp)]
+    #[case("-", KeyModifiers::NONE, KeyCode::Char('-'))]
+    fn key_combination_alternative_names(
+        #[case] input: &str,
+        #[case] modifier: KeyModifiers,
+        #[case] key_code: KeyCode,
+    ) {
+        assert_eq!(input.parse(), Ok(KeyCombination { modifier, key_code }));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("ctrl-")]
+    #[case("f0")]
+    #[case("foo-a")]
+    #[case("media-Unknown")]
+    fn invalid_key_combination(#[case] input: &str) {
+        assert_eq!(
+            input.parse::<KeyCombination>(),
+            Err(ParseKeyError(input.to_string()))
+        );
+    }
+
+    #[rstest]
+    #[case(default_emacs_keybindings())]
+    #[case(default_vi_insert_keybindings())]
+    #[case(default_vi_normal_keybindings())]
+    fn default_keybindings_round_trip(#[case] keybindings: Keybindings) {
+        let serialized = serde_json::to_string(&keybindings).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Keybindings>(&serialized).unwrap(),
+            keybindings
+        );
+    }
+
+    #[test]
+    fn sequences_round_trip() {
+        let mut keybindings = Keybindings::new();
+        keybindings.add_sequence(
+            &[
+                (KeyModifiers::CONTROL, KeyCode::Char('x')),
+                (KeyModifiers::CONTROL, KeyCode::Char('s')),
+            ],
+            ReedlineEvent::Submit,
+        );
+        keybindings.add_sequence(
+            &[(KeyModifiers::NONE, KeyCode::Char('j'))],
+            ReedlineEvent::Esc,
+        );
+        keybindings.set_sequence_timeout(Duration::from_millis(300));
+
+        let serialized = serde_json::to_value(&keybindings).unwrap();
+        assert_eq!(
+            serialized["sequences"],
+            serde_json::json!({ "ctrl-x ctrl-s": "Submit", "j": "Esc" })
+        );
+
+        assert_eq!(
+            serde_json::from_value::<Keybindings>(serialized).unwrap(),
+            keybindings
+        );
+    }
+
+    #[test]
+    fn history_prefix_bindings_come_after_the_menus() {
+        let mut keybindings = default_emacs_keybindings();
+        keybindings.add_history_prefix_bindings();
+
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::CONTROL, KeyCode::Char('p')),
+            Some(ReedlineEvent::UntilFound(vec![
+                ReedlineEvent::MenuUp,
+                ReedlineEvent::HistoryPrefixUp,
+            ]))
+        );
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::NONE, KeyCode::Down),
+            Some(ReedlineEvent::UntilFound(vec![
+                ReedlineEvent::MenuDown,
+                ReedlineEvent::HistoryPrefixDown,
+            ]))
+        );
+    }
+
+    #[rstest]
+    #[case(r#"{ "bindings": { "ctrl-l": "Unknown" } }"#, "`ctrl-l`")]
+    #[case(r#"{ "bindings": { "ctrl-l": { "UntilFound": [] } } }"#, "`ctrl-l`")]
+    #[case(r#"{ "bindings": { "ctrl-foo": "ClearScreen" } }"#, "`ctrl-foo`")]
+    #[case(r#"{ "bindings": {}, "sequences": { "g ctrl-": "Esc" } }"#, "`ctrl-`")]
+    fn invalid_keybindings_name_the_key(#[case] input: &str, #[case] key: &str) {
+        let err = serde_json::from_str::<Keybindings>(input).unwrap_err();
+
+        assert!(err.to_string().contains(key), "{err}");
+    }
+}
@@ -7,5 +7,5 @@ mod vi;
 pub use base::EditMode;
 pub use cursors::CursorConfig;
 pub use emacs::{default_emacs_keybindings, Emacs};
-pub use keybindings::Keybindings;
+pub use keybindings::{KeyCombination, Keybindings, ParseKeyError, SequenceMatch};
 pub use vi::{default_vi_insert_keybindings, default_vi_normal_keybindings, Vi};
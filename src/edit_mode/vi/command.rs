@@ -1,5 +1,5 @@
-use super::{motion::Motion, motion::ViCharSearch, parser::ReedlineOption};
-use crate::{EditCommand, ReedlineEvent, Vi};
+use super::{motion::Motion, motion::ViCharSearch, parser::ReedlineOption, ViMode};
+use crate::{EditCommand, ReedlineEvent, SelectionMode, Vi};
 use std::iter::Peekable;
 
 pub fn parse_command<'iter, I>(input: &mut Peekable<I>) -> Option<Command>
@@ -11,6 +11,10 @@ where
             let _ = input.next();
             Some(Command::Delete)
         }
+        Some('y') => {
+            let _ = input.next();
+            Some(Command::Yank)
+        }
         Some('p') => {
             let _ = input.next();
             Some(Command::PasteAfter)
@@ -82,6 +86,37 @@ where
             let _ = input.next();
             Some(Command::RepeatLastAction)
         }
+        Some('v') => {
+            let _ = input.next();
+            Some(Command::EnterVisual)
+        }
+        Some('V') => {
+            let _ = input.next();
+            Some(Command::EnterVisualLine)
+        }
+        Some('o') => {
+            let _ = input.next();
+            Some(Command::SwapVisualEnds)
+        }
+        Some('>') => {
+            let _ = input.next();
+            Some(Command::Indent)
+        }
+        Some('q') => {
+            let _ = input.next();
+            match input.next() {
+                Some(c) => Some(Command::RecordMacro(*c)),
+                None => Some(Command::Incomplete),
+            }
+        }
+        Some('@') => {
+            let _ = input.next();
+            match input.next() {
+                Some('@') => Some(Command::PlayLastMacro),
+                Some(c) => Some(Command::PlayMacro(*c)),
+                None => Some(Command::Incomplete),
+            }
+        }
         _ => None,
     }
 }
@@ -90,6 +125,7 @@ where
 pub enum Command {
     Incomplete,
     Delete,
+    Yank,
     DeleteChar,
     ReplaceChar(char),
     SubstituteCharWithInsert,
@@ -107,22 +143,52 @@ pub enum Command {
     HistorySearch,
     Switchcase,
     RepeatLastAction,
+    EnterVisual,
+    EnterVisualLine,
+    SwapVisualEnds,
+    Indent,
+    RecordMacro(char),
+    PlayMacro(char),
+    PlayLastMacro,
 }
 
 impl Command {
     pub fn whole_line_char(&self) -> Option<char> {
         match self {
             Command::Delete => Some('d'),
+            Command::Yank => Some('y'),
             Command::Change => Some('c'),
+            Command::Indent => Some('>'),
             _ => None,
         }
     }
 
+    /// Whether the command changes the buffer, to be repeated by `.`
+    pub fn is_change(&self) -> bool {
+        !matches!(
+            self,
+            Command::Incomplete
+                | Command::Yank
+                | Command::Undo
+                | Command::HistorySearch
+                | Command::RepeatLastAction
+                | Command::EnterVisual
+                | Command::EnterVisualLine
+                | Command::SwapVisualEnds
+                | Command::RecordMacro(_)
+                | Command::PlayMacro(_)
+                | Command::PlayLastMacro
+        )
+    }
+
     pub fn requires_motion(&self) -> bool {
-        matches!(self, Command::Delete | Command::Change)
+        matches!(
+            self,
+            Command::Delete | Command::Yank | Command::Change | Command::Indent
+        )
     }
 
-    pub fn to_reedline(&self, vi_state: &mut Vi) -> Vec<ReedlineOption> {
+    pub fn to_reedline(&self) -> Vec<ReedlineOption> {
         match self {
             Self::EnterViInsert => vec![ReedlineOption::Event(ReedlineEvent::Repaint)],
             Self::EnterViAppend => vec![ReedlineOption::Edit(EditCommand::MoveRight {
@@ -147,15 +213,93 @@ impl Command {
             Self::SubstituteCharWithInsert => vec![ReedlineOption::Edit(EditCommand::CutChar)],
             Self::HistorySearch => vec![ReedlineOption::Event(ReedlineEvent::SearchHistory)],
             Self::Switchcase => vec![ReedlineOption::Edit(EditCommand::SwitchcaseChar)],
+            Self::EnterVisual => vec![ReedlineOption::Edit(EditCommand::StartSelection(
+                SelectionMode::Inclusive,
+            ))],
+            Self::EnterVisualLine => vec![ReedlineOption::Edit(EditCommand::StartSelection(
+                SelectionMode::Lines,
+            ))],
+            // Only meaningful in visual mode
+            Self::SwapVisualEnds => vec![],
+            Self::RecordMacro(register) => {
+                vec![ReedlineOption::Event(ReedlineEvent::RecordMacro(*register))]
+            }
+            Self::PlayMacro(register) => {
+                vec![ReedlineOption::Event(ReedlineEvent::PlayMacro(*register))]
+            }
+            Self::PlayLastMacro => vec![ReedlineOption::Event(ReedlineEvent::PlayLastMacro)],
             // Mark a command as incomplete whenever a motion is required to finish the command
-            Self::Delete | Self::Change | Self::Incomplete => vec![ReedlineOption::Incomplete],
-            Command::RepeatLastAction => match &vi_state.previous {
-                Some(event) => vec![ReedlineOption::Event(event.clone())],
-                None => vec![],
-            },
+            Self::Delete | Self::Yank | Self::Change | Self::Indent | Self::Incomplete => {
+                vec![ReedlineOption::Incomplete]
+            }
+            // Repeated by the parser, which knows the count to repeat it with
+            Command::RepeatLastAction => vec![],
         }
     }
 
+    /// Events of the command acting on the selection of the visual mode, which it may leave
+    pub fn to_visual_reedline(&self, vi_state: &mut Vi) -> Vec<ReedlineOption> {
+        let ViMode::Visual(selection_mode) = vi_state.mode else {
+            return vec![];
+        };
+
+        let (events, mode) = match self {
+            Self::Delete | Self::DeleteChar => (
+                vec![ReedlineOption::Edit(EditCommand::CutSelection)],
+                ViMode::Normal,
+            ),
+            Self::Yank => (
+                vec![ReedlineOption::Edit(EditCommand::CopySelection)],
+                ViMode::Normal,
+            ),
+            Self::Change | Self::SubstituteCharWithInsert => (
+                vec![
+                    ReedlineOption::Edit(EditCommand::CutSelection),
+                    ReedlineOption::Event(ReedlineEvent::Repaint),
+                ],
+                ViMode::Insert,
+            ),
+            Self::Indent => (
+                vec![ReedlineOption::Edit(EditCommand::IndentSelection)],
+                ViMode::Normal,
+            ),
+            // The selection is replaced by the pasted text
+            Self::PasteAfter | Self::PasteBefore => (
+                vec![ReedlineOption::Edit(EditCommand::PasteCutBufferBefore)],
+                ViMode::Normal,
+            ),
+            Self::EnterVisual | Self::EnterVisualLine => {
+                let new_selection_mode = if *self == Self::EnterVisual {
+                    SelectionMode::Inclusive
+                } else {
+                    SelectionMode::Lines
+                };
+                // Like in vim, the key of the current visual mode leaves it
+                if new_selection_mode == selection_mode {
+                    (
+                        vec![ReedlineOption::Edit(EditCommand::ClearSelection)],
+                        ViMode::Normal,
+                    )
+                } else {
+                    (
+                        vec![ReedlineOption::Edit(EditCommand::StartSelection(
+                            new_selection_mode,
+                        ))],
+                        ViMode::Visual(new_selection_mode),
+                    )
+                }
+            }
+            Self::SwapVisualEnds => (
+                vec![ReedlineOption::Edit(EditCommand::SwapSelectionEnds)],
+                vi_state.mode,
+            ),
+            _ => (vec![], vi_state.mode),
+        };
+
+        vi_state.mode = mode;
+        events
+    }
+
     pub fn to_reedline_with_motion(
         &self,
         motion: &Motion,
@@ -209,6 +353,57 @@ impl Command {
                     .as_ref()
                     .map(|char_search| vec![ReedlineOption::Edit(char_search.reverse().to_cut())]),
             },
+            Self::Yank => match motion {
+                Motion::End => Some(vec![ReedlineOption::Edit(EditCommand::CopyToLineEnd)]),
+                Motion::Line => Some(vec![ReedlineOption::Edit(EditCommand::CopyCurrentLine)]),
+                Motion::NextWord => {
+                    Some(vec![ReedlineOption::Edit(EditCommand::CopyWordRightToNext)])
+                }
+                Motion::NextBigWord => Some(vec![ReedlineOption::Edit(
+                    EditCommand::CopyBigWordRightToNext,
+                )]),
+                Motion::NextWordEnd => Some(vec![ReedlineOption::Edit(EditCommand::CopyWordRight)]),
+                Motion::NextBigWordEnd => {
+                    Some(vec![ReedlineOption::Edit(EditCommand::CopyBigWordRight)])
+                }
+                Motion::PreviousWord => Some(vec![ReedlineOption::Edit(EditCommand::CopyWordLeft)]),
+                Motion::PreviousBigWord => {
+                    Some(vec![ReedlineOption::Edit(EditCommand::CopyBigWordLeft)])
+                }
+                Motion::RightUntil(c) => {
+                    vi_state.last_char_search = Some(ViCharSearch::ToRight(*c));
+                    Some(vec![ReedlineOption::Edit(EditCommand::CopyRightUntil(*c))])
+                }
+                Motion::RightBefore(c) => {
+                    vi_state.last_char_search = Some(ViCharSearch::TillRight(*c));
+                    Some(vec![ReedlineOption::Edit(EditCommand::CopyRightBefore(*c))])
+                }
+                Motion::LeftUntil(c) => {
+                    vi_state.last_char_search = Some(ViCharSearch::ToLeft(*c));
+                    Some(vec![ReedlineOption::Edit(EditCommand::CopyLeftUntil(*c))])
+                }
+                Motion::LeftBefore(c) => {
+                    vi_state.last_char_search = Some(ViCharSearch::TillLeft(*c));
+                    Some(vec![ReedlineOption::Edit(EditCommand::CopyLeftBefore(*c))])
+                }
+                Motion::Start => Some(vec![ReedlineOption::Edit(EditCommand::CopyFromLineStart)]),
+                Motion::Left => Some(vec![ReedlineOption::Edit(EditCommand::CopyLeft)]),
+                Motion::Right => Some(vec![ReedlineOption::Edit(EditCommand::CopyRight)]),
+                Motion::Up => None,
+                Motion::Down => None,
+                Motion::ReplayCharSearch => vi_state
+                    .last_char_search
+                    .as_ref()
+                    .map(|char_search| vec![ReedlineOption::Edit(char_search.to_copy())]),
+                Motion::ReverseCharSearch => vi_state
+                    .last_char_search
+                    .as_ref()
+                    .map(|char_search| vec![ReedlineOption::Edit(char_search.reverse().to_copy())]),
+            },
+            Self::Indent => match motion {
+                Motion::Line => Some(vec![ReedlineOption::Edit(EditCommand::IndentSelection)]),
+                _ => None,
+            },
             Self::Change => {
                 let op = match motion {
                     Motion::End => Some(vec![ReedlineOption::Edit(EditCommand::ClearToLineEnd)]),
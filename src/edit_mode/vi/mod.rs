@@ -4,14 +4,20 @@ mod parser;
 mod vi_keybindings;
 
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use std::time::Duration;
 pub use vi_keybindings::{default_vi_insert_keybindings, default_vi_normal_keybindings};
 
 use self::motion::ViCharSearch;
 
 use super::EditMode;
 use crate::{
-    edit_mode::{keybindings::Keybindings, vi::parser::parse},
-    enums::{EditCommand, ReedlineEvent, ReedlineRawEvent},
+    edit_mode::{
+        keybindings::{
+            combine_events, KeyCombination, KeySequenceMatcher, Keybindings, SequenceOutput,
+        },
+        vi::parser::{parse, ViChange},
+    },
+    enums::{mouse_event, EditCommand, ReedlineEvent, ReedlineRawEvent, SelectionMode},
     PromptEditMode, PromptViMode,
 };
 
@@ -19,6 +25,8 @@ use crate::{
 enum ViMode {
     Normal,
     Insert,
+    /// Selecting with the motions, by graphemes or by lines
+    Visual(SelectionMode),
 }
 
 /// This parses incoming input `Event`s like a Vi-Style editor
@@ -27,9 +35,14 @@ pub struct Vi {
     insert_keybindings: Keybindings,
     normal_keybindings: Keybindings,
     mode: ViMode,
-    previous: Option<ReedlineEvent>,
+    previous: Option<ViChange>,
+    // whether the keys typed in insert mode belong to the previous change
+    recording_insert: bool,
     // last f, F, t, T motion for ; and ,
     last_char_search: Option<ViCharSearch>,
+    // whether a macro is being recorded, to stop it with q
+    recording_macro: bool,
+    sequence: KeySequenceMatcher,
 }
 
 impl Default for Vi {
@@ -40,7 +53,10 @@ impl Default for Vi {
             cache: Vec::new(),
             mode: ViMode::Insert,
             previous: None,
+            recording_insert: false,
             last_char_search: None,
+            recording_macro: false,
+            sequence: KeySequenceMatcher::new(),
         }
     }
 }
@@ -61,112 +77,259 @@ impl EditMode for Vi {
         match event.into() {
             Event::Key(KeyEvent {
                 code, modifiers, ..
-            }) => match (self.mode, modifiers, code) {
-                (ViMode::Normal, modifier, KeyCode::Char(c)) => {
-                    let c = c.to_ascii_lowercase();
-
-                    if let Some(event) = self
-                        .normal_keybindings
-                        .find_binding(modifiers, KeyCode::Char(c))
-                    {
-                        event
-                    } else if modifier == KeyModifiers::NONE || modifier == KeyModifiers::SHIFT {
-                        self.cache.push(if modifier == KeyModifiers::SHIFT {
-                            c.to_ascii_uppercase()
-                        } else {
-                            c
-                        });
+            }) => {
+                let key = KeyCombination {
+                    modifier: modifiers,
+                    key_code: code,
+                };
+                let outputs = match self.mode {
+                    ViMode::Normal | ViMode::Visual(_) => {
+                        self.sequence.push(&self.normal_keybindings, key)
+                    }
+                    ViMode::Insert => self.sequence.push(&self.insert_keybindings, key),
+                };
+                self.sequence_event(outputs)
+            }
 
-                        let res = parse(&mut self.cache.iter().peekable());
+            Event::Mouse(event) => mouse_event(event),
+            Event::Resize(width, height) => ReedlineEvent::Resize(width, height),
+            Event::FocusGained => ReedlineEvent::None,
+            Event::FocusLost => ReedlineEvent::None,
+            Event::Paste(body) => ReedlineEvent::Paste(body),
+        }
+    }
 
-                        if !res.is_valid() {
-                            self.cache.clear();
-                            ReedlineEvent::None
-                        } else if res.is_complete() {
-                            if res.enters_insert_mode() {
-                                self.mode = ViMode::Insert;
-                            }
+    fn edit_mode(&self) -> PromptEditMode {
+        match self.mode {
+            ViMode::Normal => PromptEditMode::Vi(PromptViMode::Normal),
+            ViMode::Insert => PromptEditMode::Vi(PromptViMode::Insert),
+            ViMode::Visual(_) => PromptEditMode::Vi(PromptViMode::Visual),
+        }
+    }
+
+    fn pending_command(&self) -> Option<String> {
+        (!self.cache.is_empty()).then(|| self.cache.iter().collect())
+    }
+
+    fn pending_sequence_timeout(&self) -> Option<Duration> {
+        self.sequence.timeout(self.keybindings())
+    }
+
+    fn flush_pending_sequence(&mut self) -> ReedlineEvent {
+        let outputs = match self.mode {
+            ViMode::Normal | ViMode::Visual(_) => self.sequence.flush(&self.normal_keybindings),
+            ViMode::Insert => self.sequence.flush(&self.insert_keybindings),
+        };
+        self.sequence_event(outputs)
+    }
+
+    /// Appends after the cursor like `a`, leaving the visual mode first
+    fn prepare_insertion(&mut self) -> Vec<EditCommand> {
+        let previous_mode = self.mode;
+        self.cache.clear();
+        self.mode = ViMode::Insert;
+        match previous_mode {
+            ViMode::Insert => vec![],
+            ViMode::Normal => vec![EditCommand::MoveRight { select: false }],
+            ViMode::Visual(_) => vec![
+                EditCommand::ClearSelection,
+                EditCommand::MoveRight { select: false },
+            ],
+        }
+    }
+
+    fn reset_pending(&mut self) {
+        self.cache.clear();
+        self.sequence = KeySequenceMatcher::new();
+    }
+}
+
+impl Vi {
+    /// Keybindings of the current mode
+    fn keybindings(&self) -> &Keybindings {
+        match self.mode {
+            ViMode::Normal | ViMode::Visual(_) => &self.normal_keybindings,
+            ViMode::Insert => &self.insert_keybindings,
+        }
+    }
+
+    /// Event of the keys handled by the key sequence matcher
+    ///
+    /// The keys are parsed one after the other, as each of them may change the mode
+    fn sequence_event(&mut self, outputs: Vec<SequenceOutput>) -> ReedlineEvent {
+        combine_events(
+            outputs
+                .into_iter()
+                .map(|output| {
+                    let inserting = self.mode == ViMode::Insert;
+                    let event = match output {
+                        SequenceOutput::Event(event) => event,
+                        SequenceOutput::Key(key) => self.parse_key(key.modifier, key.key_code),
+                    };
+                    if inserting {
+                        self.record_insert(&event);
+                    }
+                    event
+                })
+                .collect(),
+        )
+    }
+
+    /// Remember the edits typed in insert mode, to repeat them along with the change entering it
+    ///
+    /// The recording stops when leaving insert mode or submitting the line.
+    fn record_insert(&mut self, event: &ReedlineEvent) {
+        fn edits(event: &ReedlineEvent, inserted: &mut Vec<EditCommand>) {
+            match event {
+                ReedlineEvent::Edit(commands) => inserted.extend(commands.iter().cloned()),
+                ReedlineEvent::Multiple(events) => {
+                    events.iter().for_each(|event| edits(event, inserted))
+                }
+                _ => {}
+            }
+        }
+
+        if !self.recording_insert {
+            return;
+        }
+
+        if self.mode == ViMode::Normal || *event == ReedlineEvent::Enter {
+            self.recording_insert = false;
+        } else if let Some(change) = &mut self.previous {
+            edits(event, &mut change.inserted);
+        }
+    }
+
+    /// Event of a single key, in the current mode
+    fn parse_key(&mut self, modifiers: KeyModifiers, code: KeyCode) -> ReedlineEvent {
+        let keybindings = match self.mode {
+            ViMode::Normal | ViMode::Visual(_) => &self.normal_keybindings,
+            ViMode::Insert => &self.insert_keybindings,
+        };
+        if let Some(key) = keybindings.legacy_fallback(modifiers, code) {
+            return self.parse_key(key.modifier, key.key_code);
+        }
+
+        match (self.mode, modifiers, code) {
+            (ViMode::Normal | ViMode::Visual(_), modifier, KeyCode::Char(c)) => {
+                let c = c.to_ascii_lowercase();
+
+                if self.recording_macro
+                    && self.cache.is_empty()
+                    && c == 'q'
+                    && modifier == KeyModifiers::NONE
+                {
+                    self.recording_macro = false;
+                    ReedlineEvent::StopMacro
+                } else if let Some(event) = self
+                    .normal_keybindings
+                    .find_binding(modifiers, KeyCode::Char(c))
+                {
+                    // A bound key completes the command instead, dropping its count
+                    self.cache.clear();
+                    event
+                } else if modifier == KeyModifiers::NONE || modifier == KeyModifiers::SHIFT {
+                    self.cache.push(if modifier == KeyModifiers::SHIFT {
+                        c.to_ascii_uppercase()
+                    } else {
+                        c
+                    });
+
+                    let res = parse(&mut self.cache.iter().peekable());
 
-                            let event = res.to_reedline_event(self);
+                    if !res.is_valid() {
+                        self.cache.clear();
+                        ReedlineEvent::None
+                    } else if let ViMode::Visual(_) = self.mode {
+                        if res.is_complete_in_visual() {
+                            let event = res.to_visual_event(self);
                             self.cache.clear();
                             event
                         } else {
                             ReedlineEvent::None
                         }
+                    } else if res.is_complete() {
+                        if res.enters_insert_mode() {
+                            self.mode = ViMode::Insert;
+                        } else if let Some(selection_mode) = res.enters_visual_mode() {
+                            self.mode = ViMode::Visual(selection_mode);
+                        } else if res.starts_macro_recording() {
+                            self.recording_macro = true;
+                        }
+
+                        let event = res.to_reedline_event(self);
+                        self.cache.clear();
+                        event
                     } else {
                         ReedlineEvent::None
                     }
+                } else {
+                    ReedlineEvent::None
                 }
-                (ViMode::Insert, modifier, KeyCode::Char(c)) => {
-                    // Note. The modifier can also be a combination of modifiers, for
-                    // example:
-                    //     KeyModifiers::CONTROL | KeyModifiers::ALT
-                    //     KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
-                    //
-                    // Mixed modifiers are used by non american keyboards that have extra
-                    // keys like 'alt gr'. Keep this in mind if in the future there are
-                    // cases where an event is not being captured
-                    let c = match modifier {
-                        KeyModifiers::NONE => c,
-                        _ => c.to_ascii_lowercase(),
-                    };
+            }
+            (ViMode::Insert, modifier, KeyCode::Char(c)) => {
+                // Note. The modifier can also be a combination of modifiers, for
+                // example:
+                //     KeyModifiers::CONTROL | KeyModifiers::ALT
+                //     KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
+                //
+                // Mixed modifiers are used by non american keyboards that have extra
+                // keys like 'alt gr'. Keep this in mind if in the future there are
+                // cases where an event is not being captured
+                let c = match modifier {
+                    KeyModifiers::NONE => c,
+                    _ => c.to_ascii_lowercase(),
+                };
 
-                    self.insert_keybindings
-                        .find_binding(modifier, KeyCode::Char(c))
-                        .unwrap_or_else(|| {
-                            if modifier == KeyModifiers::NONE
-                                || modifier == KeyModifiers::SHIFT
-                                || modifier == KeyModifiers::CONTROL | KeyModifiers::ALT
-                                || modifier
-                                    == KeyModifiers::CONTROL
-                                        | KeyModifiers::ALT
-                                        | KeyModifiers::SHIFT
-                            {
-                                ReedlineEvent::Edit(vec![EditCommand::InsertChar(
-                                    if modifier == KeyModifiers::SHIFT {
-                                        c.to_ascii_uppercase()
-                                    } else {
-                                        c
-                                    },
-                                )])
-                            } else {
-                                ReedlineEvent::None
-                            }
-                        })
-                }
-                (_, KeyModifiers::NONE, KeyCode::Esc) => {
-                    self.cache.clear();
-                    self.mode = ViMode::Normal;
+                self.insert_keybindings
+                    .find_binding(modifier, KeyCode::Char(c))
+                    .unwrap_or_else(|| {
+                        if modifier == KeyModifiers::NONE
+                            || modifier == KeyModifiers::SHIFT
+                            || modifier == KeyModifiers::CONTROL | KeyModifiers::ALT
+                            || modifier
+                                == KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
+                        {
+                            ReedlineEvent::Edit(vec![EditCommand::InsertChar(
+                                if modifier == KeyModifiers::SHIFT {
+                                    c.to_ascii_uppercase()
+                                } else {
+                                    c
+                                },
+                            )])
+                        } else {
+                            ReedlineEvent::None
+                        }
+                    })
+            }
+            (_, KeyModifiers::NONE, KeyCode::Esc) => {
+                self.cache.clear();
+                let visual = matches!(self.mode, ViMode::Visual(_));
+                self.mode = ViMode::Normal;
+                if visual {
+                    ReedlineEvent::Multiple(vec![
+                        ReedlineEvent::Edit(vec![EditCommand::ClearSelection]),
+                        ReedlineEvent::Esc,
+                        ReedlineEvent::Repaint,
+                    ])
+                } else {
                     ReedlineEvent::Multiple(vec![ReedlineEvent::Esc, ReedlineEvent::Repaint])
                 }
-                (_, KeyModifiers::NONE, KeyCode::Enter) => {
-                    self.mode = ViMode::Insert;
-                    ReedlineEvent::Enter
-                }
-                (ViMode::Normal, _, _) => self
-                    .normal_keybindings
-                    .find_binding(modifiers, code)
-                    .unwrap_or(ReedlineEvent::None),
-                (ViMode::Insert, _, _) => self
-                    .insert_keybindings
+            }
+            (_, KeyModifiers::NONE, KeyCode::Enter) => {
+                self.mode = ViMode::Insert;
+                ReedlineEvent::Enter
+            }
+            (ViMode::Normal | ViMode::Visual(_), _, _) => {
+                self.cache.clear();
+                self.normal_keybindings
                     .find_binding(modifiers, code)
-                    .unwrap_or(ReedlineEvent::None),
-            },
-
-            Event::Mouse(_) => ReedlineEvent::Mouse,
-            Event::Resize(width, height) => ReedlineEvent::Resize(width, height),
-            Event::FocusGained => ReedlineEvent::None,
-            Event::FocusLost => ReedlineEvent::None,
-            Event::Paste(body) => ReedlineEvent::Edit(vec![EditCommand::InsertString(
-                body.replace("\r\n", "\n").replace('\r', "\n"),
-            )]),
-        }
-    }
-
-    fn edit_mode(&self) -> PromptEditMode {
-        match self.mode {
-            ViMode::Normal => PromptEditMode::Vi(PromptViMode::Normal),
-            ViMode::Insert => PromptEditMode::Vi(PromptViMode::Insert),
+                    .unwrap_or(ReedlineEvent::None)
+            }
+            (ViMode::Insert, _, _) => self
+                .insert_keybindings
+                .find_binding(modifiers, code)
+                .unwrap_or(ReedlineEvent::None),
         }
     }
 }
@@ -174,7 +337,9 @@ impl EditMode for Vi {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{enums::UndoBehavior, Editor};
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
 
     #[test]
     fn esc_leads_to_normal_mode_test() {
@@ -264,4 +429,383 @@ mod test {
 
         assert_eq!(result, ReedlineEvent::None);
     }
+
+    #[test]
+    fn key_sequence_in_normal_mode_test() {
+        let mut keybindings = default_vi_normal_keybindings();
+        keybindings.add_sequence(
+            &[
+                (KeyModifiers::NONE, KeyCode::Char('g')),
+                (KeyModifiers::NONE, KeyCode::Char('g')),
+            ],
+            ReedlineEvent::Edit(vec![EditCommand::MoveToStart { select: false }]),
+        );
+
+        let mut vi = Vi {
+            mode: ViMode::Normal,
+            ..Vi::new(default_vi_insert_keybindings(), keybindings)
+        };
+
+        let g = || {
+            ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(
+                KeyCode::Char('g'),
+                KeyModifiers::NONE,
+            )))
+            .unwrap()
+        };
+
+        assert_eq!(vi.parse_event(g()), ReedlineEvent::None);
+        assert_eq!(
+            vi.parse_event(g()),
+            ReedlineEvent::Edit(vec![EditCommand::MoveToStart { select: false }])
+        );
+
+        // Keys not continuing the sequence go to the command parser, here `g` then `i`
+        assert_eq!(vi.parse_event(g()), ReedlineEvent::None);
+        let i = ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(
+            KeyCode::Char('i'),
+            KeyModifiers::NONE,
+        )))
+        .unwrap();
+        vi.parse_event(i);
+        assert!(vi.cache.is_empty());
+        assert_eq!(vi.pending_sequence_timeout(), None);
+    }
+
+    /// Type `keys`, `\x1b` being Esc, running the resulting edits on `editor`
+    fn run_keys(vi: &mut Vi, editor: &mut Editor, keys: &str) {
+        fn run(editor: &mut Editor, event: ReedlineEvent) {
+            match event {
+                ReedlineEvent::Multiple(events) => {
+                    events.into_iter().for_each(|event| run(editor, event))
+                }
+                ReedlineEvent::Edit(commands) => commands
+                    .iter()
+                    .for_each(|command| editor.run_edit_command(command)),
+                // Without menus nor hints, the editor handles the last event
+                ReedlineEvent::UntilFound(mut events) => {
+                    if let Some(event) = events.pop() {
+                        run(editor, event);
+                    }
+                }
+                ReedlineEvent::Left => {
+                    editor.run_edit_command(&EditCommand::MoveLeft { select: false })
+                }
+                ReedlineEvent::Right => {
+                    editor.run_edit_command(&EditCommand::MoveRight { select: false })
+                }
+                ReedlineEvent::Up => editor.move_line_up(),
+                ReedlineEvent::Down => editor.move_line_down(),
+                _ => {}
+            }
+        }
+
+        for c in keys.chars() {
+            let code = match c {
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            let modifiers = if c.is_ascii_uppercase() {
+                KeyModifiers::SHIFT
+            } else {
+                KeyModifiers::NONE
+            };
+            let key =
+                ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(code, modifiers))).unwrap();
+            let event = vi.parse_event(key);
+            run(editor, event);
+        }
+    }
+
+    #[test]
+    fn pending_command_is_the_typed_operator() {
+        let mut vi = Vi {
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+        let mut editor = Editor::default();
+
+        run_keys(&mut vi, &mut editor, "2d");
+        assert_eq!(vi.pending_command(), Some("2d".to_string()));
+
+        run_keys(&mut vi, &mut editor, "w");
+        assert_eq!(vi.pending_command(), None);
+    }
+
+    #[test]
+    fn yank_and_paste_with_registers_test() {
+        let mut vi = Vi {
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+        let mut editor = Editor::default();
+        editor.set_buffer("hello world".to_string(), UndoBehavior::CreateUndoPoint);
+        editor.run_edit_command(&EditCommand::MoveToStart { select: false });
+
+        // Yank into the unnamed register then delete into `a`
+        run_keys(&mut vi, &mut editor, "yw");
+        run_keys(&mut vi, &mut editor, "w\"adw");
+        assert_eq!(editor.get_buffer(), "hello ");
+
+        // The yank register still holds the yanked word
+        run_keys(&mut vi, &mut editor, "\"0P");
+        assert_eq!(editor.get_buffer(), "hello hello ");
+        run_keys(&mut vi, &mut editor, "\"aP");
+        assert_eq!(editor.get_buffer(), "hello hello world");
+    }
+
+    fn normal_mode_editor(buffer: &str) -> (Vi, Editor) {
+        let vi = Vi {
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+        let mut editor = Editor::default();
+        editor.set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+        editor.run_edit_command(&EditCommand::MoveToStart { select: false });
+        (vi, editor)
+    }
+
+    #[test]
+    fn repeat_delete_test() {
+        let (mut vi, mut editor) = normal_mode_editor("one two three four");
+
+        run_keys(&mut vi, &mut editor, "dw");
+        assert_eq!(editor.get_buffer(), "two three four");
+        run_keys(&mut vi, &mut editor, ".");
+        assert_eq!(editor.get_buffer(), "three four");
+
+        // Yanks and motions aren't changes
+        run_keys(&mut vi, &mut editor, "yww.");
+        assert_eq!(editor.get_buffer(), "three ");
+    }
+
+    #[test]
+    fn repeat_change_test() {
+        let (mut vi, mut editor) = normal_mode_editor("one two three");
+
+        run_keys(&mut vi, &mut editor, "cwfoo\x1b");
+        assert_eq!(editor.get_buffer(), "foo two three");
+        assert!(matches!(vi.mode, ViMode::Normal));
+
+        run_keys(&mut vi, &mut editor, "w.");
+        assert_eq!(editor.get_buffer(), "foo foo three");
+        assert!(matches!(vi.mode, ViMode::Normal));
+
+        // Only the keys typed before leaving insert mode are repeated
+        run_keys(&mut vi, &mut editor, "wx.");
+        assert_eq!(editor.get_buffer(), "foo foo ree");
+    }
+
+    #[test]
+    fn repeat_with_count_test() {
+        let (mut vi, mut editor) = normal_mode_editor("a b c d e f g h");
+
+        run_keys(&mut vi, &mut editor, "2dw");
+        assert_eq!(editor.get_buffer(), "c d e f g h");
+        run_keys(&mut vi, &mut editor, "3.");
+        assert_eq!(editor.get_buffer(), "f g h");
+
+        // The new count is kept for the next repeats
+        run_keys(&mut vi, &mut editor, ".");
+        assert_eq!(editor.get_buffer(), "");
+    }
+
+    #[test]
+    fn visual_mode_test() {
+        let (mut vi, mut editor) = normal_mode_editor("one two three");
+
+        run_keys(&mut vi, &mut editor, "vw");
+        assert_eq!(editor.get_selection(), Some((0, 5)));
+        run_keys(&mut vi, &mut editor, "d");
+        assert_eq!(editor.get_buffer(), "wo three");
+        assert_eq!(vi.mode, ViMode::Normal);
+        assert_eq!(editor.get_selection(), None);
+
+        // Motions move the cursor again once the selection is done
+        run_keys(&mut vi, &mut editor, "w");
+        assert_eq!(editor.insertion_point(), 3);
+        assert_eq!(editor.get_selection(), None);
+
+        run_keys(&mut vi, &mut editor, "v\x1b");
+        assert_eq!(vi.mode, ViMode::Normal);
+        assert_eq!(editor.get_selection(), None);
+    }
+
+    #[test]
+    fn visual_mode_swap_ends_test() {
+        let (mut vi, mut editor) = normal_mode_editor("one two three");
+
+        run_keys(&mut vi, &mut editor, "wve");
+        assert_eq!(editor.get_selection(), Some((4, 7)));
+        run_keys(&mut vi, &mut editor, "o");
+        assert_eq!(editor.insertion_point(), 4);
+        assert_eq!(editor.get_selection(), Some((4, 7)));
+        run_keys(&mut vi, &mut editor, "b");
+        assert_eq!(editor.get_selection(), Some((0, 7)));
+
+        run_keys(&mut vi, &mut editor, "\"ay");
+        assert_eq!(vi.mode, ViMode::Normal);
+        run_keys(&mut vi, &mut editor, "$\"aP");
+        assert_eq!(editor.get_buffer(), "one two threeone two");
+    }
+
+    #[test]
+    fn visual_mode_change_test() {
+        let (mut vi, mut editor) = normal_mode_editor("one two");
+
+        run_keys(&mut vi, &mut editor, "vec1\x1b");
+        assert_eq!(editor.get_buffer(), "1 two");
+        assert_eq!(vi.mode, ViMode::Normal);
+    }
+
+    #[test]
+    fn visual_line_mode_test() {
+        let (mut vi, mut editor) = normal_mode_editor("one\ntwo\nthree");
+
+        run_keys(&mut vi, &mut editor, "V");
+        assert_eq!(vi.mode, ViMode::Visual(SelectionMode::Lines));
+        assert_eq!(editor.get_selection(), Some((0, 4)));
+
+        // Switching between the visual modes keeps the selection
+        run_keys(&mut vi, &mut editor, "lv");
+        assert_eq!(vi.mode, ViMode::Visual(SelectionMode::Inclusive));
+        assert_eq!(editor.get_selection(), Some((0, 2)));
+        run_keys(&mut vi, &mut editor, "V>");
+        assert_eq!(editor.get_buffer(), "    one\ntwo\nthree");
+        assert_eq!(vi.mode, ViMode::Normal);
+
+        run_keys(&mut vi, &mut editor, "VV");
+        assert_eq!(vi.mode, ViMode::Normal);
+        assert_eq!(editor.get_selection(), None);
+
+        run_keys(&mut vi, &mut editor, "Vd");
+        assert_eq!(editor.get_buffer(), "two\nthree");
+    }
+
+    #[rstest]
+    // Motions
+    #[case(
+        "one\ntwo\nthree\nfour\nfive\nsix",
+        "5j",
+        "one\ntwo\nthree\nfour\nfive\nsix",
+        24
+    )]
+    #[case("abcdefghijklmnop", "10l", "abcdefghijklmnop", 10)]
+    #[case("one two three four", "2w", "one two three four", 8)]
+    #[case("one two three four", "12w", "one two three four", 18)]
+    // Operators
+    #[case("abcdef", "4x", "ef", 0)]
+    #[case("one two three four", "3dw", "four", 0)]
+    #[case("one two three four", "2cwX\x1b", "X three four", 1)]
+    #[case("abcdef", "3~", "ABCdef", 3)]
+    // Counts of the operator and the motion multiply
+    #[case("a b c d e f g h", "2d3w", "g h", 0)]
+    #[case("a b c d e f g h", "d2w", "c d e f g h", 0)]
+    #[case("a b c d e f g h", "2d2w", "e f g h", 0)]
+    // Counts are dropped by Esc and after each command
+    #[case("a b c d e f g h", "3\x1bdw", "b c d e f g h", 0)]
+    #[case("a b c d e f g h", "3wdw", "a b c e f g h", 6)]
+    fn count_test(
+        #[case] buffer: &str,
+        #[case] keys: &str,
+        #[case] expected_buffer: &str,
+        #[case] expected_position: usize,
+    ) {
+        let (mut vi, mut editor) = normal_mode_editor(buffer);
+
+        run_keys(&mut vi, &mut editor, keys);
+
+        assert_eq!(editor.get_buffer(), expected_buffer);
+        assert_eq!(editor.insertion_point(), expected_position);
+    }
+
+    #[test]
+    fn count_dropped_by_bound_key_test() {
+        let (mut vi, mut editor) = normal_mode_editor("a b c d e f g h");
+
+        run_keys(&mut vi, &mut editor, "3");
+        let backspace = ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(
+            KeyCode::Backspace,
+            KeyModifiers::NONE,
+        )))
+        .unwrap();
+        vi.parse_event(backspace);
+        run_keys(&mut vi, &mut editor, "dw");
+
+        assert_eq!(editor.get_buffer(), "b c d e f g h");
+    }
+
+    #[test]
+    fn char_search_test() {
+        let (mut vi, mut editor) = normal_mode_editor("a(b, c), (d, e), (f, g)");
+
+        run_keys(&mut vi, &mut editor, "3f,");
+        assert_eq!(editor.insertion_point(), 11);
+        run_keys(&mut vi, &mut editor, ",");
+        assert_eq!(editor.insertion_point(), 7);
+        run_keys(&mut vi, &mut editor, "0;");
+        assert_eq!(editor.insertion_point(), 3);
+
+        // Repeated till searches don't get stuck in front of the char
+        run_keys(&mut vi, &mut editor, "0t(");
+        assert_eq!(editor.insertion_point(), 0);
+        run_keys(&mut vi, &mut editor, ";");
+        assert_eq!(editor.insertion_point(), 8);
+        run_keys(&mut vi, &mut editor, "$2T(");
+        assert_eq!(editor.insertion_point(), 10);
+        run_keys(&mut vi, &mut editor, "0");
+        run_keys(&mut vi, &mut editor, "3t(");
+        assert_eq!(editor.insertion_point(), 16);
+    }
+
+    #[test]
+    fn visual_mode_has_its_own_prompt_mode_test() {
+        let (mut vi, mut editor) = normal_mode_editor("abc");
+
+        run_keys(&mut vi, &mut editor, "v");
+        assert!(matches!(
+            vi.edit_mode(),
+            PromptEditMode::Vi(PromptViMode::Visual)
+        ));
+        run_keys(&mut vi, &mut editor, "\x1b");
+        assert!(matches!(
+            vi.edit_mode(),
+            PromptEditMode::Vi(PromptViMode::Normal)
+        ));
+    }
+
+    #[test]
+    fn q_stops_macro_recording_test() {
+        let (mut vi, _) = normal_mode_editor("");
+        let mut key = |c| {
+            vi.parse_event(
+                ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(
+                    KeyCode::Char(c),
+                    KeyModifiers::NONE,
+                )))
+                .unwrap(),
+            )
+        };
+
+        assert_eq!(key('q'), ReedlineEvent::None);
+        assert_eq!(
+            key('a'),
+            ReedlineEvent::Multiple(vec![ReedlineEvent::RecordMacro('a')])
+        );
+        assert_eq!(key('q'), ReedlineEvent::StopMacro);
+        // Not recording anymore, `q` waits for a register again
+        assert_eq!(key('q'), ReedlineEvent::None);
+    }
+
+    #[test]
+    fn char_search_operator_test() {
+        let (mut vi, mut editor) = normal_mode_editor("call(a, b) + c");
+
+        run_keys(&mut vi, &mut editor, "2fadt)");
+        assert_eq!(editor.get_buffer(), "call() + c");
+
+        let (mut vi, mut editor) = normal_mode_editor("a, b, c, d");
+        run_keys(&mut vi, &mut editor, "d2f,");
+        assert_eq!(editor.get_buffer(), " c, d");
+    }
 }
@@ -2,7 +2,10 @@ use std::iter::Peekable;
 
 use crate::{EditCommand, ReedlineEvent, Vi};
 
-use super::parser::{ParseResult, ReedlineOption};
+use super::{
+    parser::{ParseResult, ReedlineOption},
+    ViMode,
+};
 
 pub fn parse_motion<'iter, I>(
     input: &mut Peekable<I>,
@@ -142,7 +145,15 @@ pub enum Motion {
 
 impl Motion {
     pub fn to_reedline(&self, vi_state: &mut Vi) -> Vec<ReedlineOption> {
+        // The motions extend the selection in visual mode
+        let select = matches!(vi_state.mode, ViMode::Visual(_));
         match self {
+            Motion::Left if select => {
+                vec![ReedlineOption::Edit(EditCommand::MoveLeft { select })]
+            }
+            Motion::Right if select => {
+                vec![ReedlineOption::Edit(EditCommand::MoveRight { select })]
+            }
             Motion::Left => vec![ReedlineOption::Event(ReedlineEvent::UntilFound(vec![
                 ReedlineEvent::MenuLeft,
                 ReedlineEvent::Left,
@@ -161,76 +172,105 @@ impl Motion {
                 ReedlineEvent::Down,
             ]))],
             Motion::NextWord => vec![ReedlineOption::Edit(EditCommand::MoveWordRightStart {
-                select: false,
+                select,
             })],
             Motion::NextBigWord => vec![ReedlineOption::Edit(EditCommand::MoveBigWordRightStart {
-                select: false,
+                select,
             })],
             Motion::NextWordEnd => vec![ReedlineOption::Edit(EditCommand::MoveWordRightEnd {
-                select: false,
+                select,
             })],
             Motion::NextBigWordEnd => {
                 vec![ReedlineOption::Edit(EditCommand::MoveBigWordRightEnd {
-                    select: false,
+                    select,
                 })]
             }
-            Motion::PreviousWord => vec![ReedlineOption::Edit(EditCommand::MoveWordLeft {
-                select: false,
-            })],
+            Motion::PreviousWord => {
+                vec![ReedlineOption::Edit(EditCommand::MoveWordLeft { select })]
+            }
             Motion::PreviousBigWord => vec![ReedlineOption::Edit(EditCommand::MoveBigWordLeft {
-                select: false,
+                select,
             })],
             Motion::Line => vec![], // Placeholder as unusable standalone motion
             Motion::Start => vec![ReedlineOption::Edit(EditCommand::MoveToLineStart {
-                select: false,
-            })],
-            Motion::End => vec![ReedlineOption::Edit(EditCommand::MoveToLineEnd {
-                select: false,
+                select,
             })],
+            Motion::End => vec![ReedlineOption::Edit(EditCommand::MoveToLineEnd { select })],
             Motion::RightUntil(ch) => {
                 vi_state.last_char_search = Some(ViCharSearch::ToRight(*ch));
                 vec![ReedlineOption::Edit(EditCommand::MoveRightUntil {
                     c: *ch,
-                    select: false,
+                    select,
                 })]
             }
             Motion::RightBefore(ch) => {
                 vi_state.last_char_search = Some(ViCharSearch::TillRight(*ch));
                 vec![ReedlineOption::Edit(EditCommand::MoveRightBefore {
                     c: *ch,
-                    select: false,
+                    select,
                 })]
             }
             Motion::LeftUntil(ch) => {
                 vi_state.last_char_search = Some(ViCharSearch::ToLeft(*ch));
                 vec![ReedlineOption::Edit(EditCommand::MoveLeftUntil {
                     c: *ch,
-                    select: false,
+                    select,
                 })]
             }
             Motion::LeftBefore(ch) => {
                 vi_state.last_char_search = Some(ViCharSearch::TillLeft(*ch));
                 vec![ReedlineOption::Edit(EditCommand::MoveLeftBefore {
                     c: *ch,
-                    select: false,
+                    select,
                 })]
             }
             Motion::ReplayCharSearch => {
                 if let Some(char_search) = vi_state.last_char_search.as_ref() {
-                    vec![ReedlineOption::Edit(char_search.to_move())]
+                    vec![ReedlineOption::Edit(char_search.to_repeated_move(select))]
                 } else {
                     vec![]
                 }
             }
             Motion::ReverseCharSearch => {
                 if let Some(char_search) = vi_state.last_char_search.as_ref() {
-                    vec![ReedlineOption::Edit(char_search.reverse().to_move())]
+                    vec![ReedlineOption::Edit(
+                        char_search.reverse().to_repeated_move(select),
+                    )]
                 } else {
                     vec![]
                 }
             }
         }
     }
+
+    /// Events of the motion repeated `count` times, further char searches skipping the
+    /// character found by the previous one like `;` does
+    pub fn to_reedline_with_count(&self, vi_state: &mut Vi, count: usize) -> Vec<ReedlineOption> {
+        let mut events = self.to_reedline(vi_state);
+        let select = matches!(vi_state.mode, ViMode::Visual(_));
+        let repeated = match (self, vi_state.last_char_search.as_ref()) {
+            (
+                Motion::RightUntil(_)
+                | Motion::RightBefore(_)
+                | Motion::LeftUntil(_)
+                | Motion::LeftBefore(_)
+                | Motion::ReplayCharSearch,
+                Some(char_search),
+            ) => vec![ReedlineOption::Edit(char_search.to_repeated_move(select))],
+            (Motion::ReverseCharSearch, Some(char_search)) => {
+                vec![ReedlineOption::Edit(
+                    char_search.reverse().to_repeated_move(select),
+                )]
+            }
+            _ => events.clone(),
+        };
+
+        for _ in 1..count {
+            events.extend(repeated.iter().cloned());
+        }
+
+        events
+    }
 }
 
 /// Vi left-right motions to or till a character.
@@ -257,24 +297,23 @@ impl ViCharSearch {
         }
     }
 
-    pub fn to_move(&self) -> EditCommand {
+    pub fn to_move(&self, select: bool) -> EditCommand {
+        match self {
+            ViCharSearch::ToRight(c) => EditCommand::MoveRightUntil { c: *c, select },
+            ViCharSearch::ToLeft(c) => EditCommand::MoveLeftUntil { c: *c, select },
+            ViCharSearch::TillRight(c) => EditCommand::MoveRightBefore { c: *c, select },
+            ViCharSearch::TillLeft(c) => EditCommand::MoveLeftBefore { c: *c, select },
+        }
+    }
+
+    /// Move for a search repeated from where the last one ended.
+    ///
+    /// A till search would not move from there, so it skips the character next to the cursor.
+    pub fn to_repeated_move(&self, select: bool) -> EditCommand {
         match self {
-            ViCharSearch::ToRight(c) => EditCommand::MoveRightUntil {
-                c: *c,
-                select: false,
-            },
-            ViCharSearch::ToLeft(c) => EditCommand::MoveLeftUntil {
-                c: *c,
-                select: false,
-            },
-            ViCharSearch::TillRight(c) => EditCommand::MoveRightBefore {
-                c: *c,
-                select: false,
-            },
-            ViCharSearch::TillLeft(c) => EditCommand::MoveLeftBefore {
-                c: *c,
-                select: false,
-            },
+            ViCharSearch::ToRight(_) | ViCharSearch::ToLeft(_) => self.to_move(select),
+            ViCharSearch::TillRight(c) => EditCommand::MoveRightBeforeNext { c: *c, select },
+            ViCharSearch::TillLeft(c) => EditCommand::MoveLeftBeforeNext { c: *c, select },
         }
     }
 
@@ -286,4 +325,13 @@ impl ViCharSearch {
             ViCharSearch::TillLeft(c) => EditCommand::CutLeftBefore(*c),
         }
     }
+
+    pub fn to_copy(&self) -> EditCommand {
+        match self {
+            ViCharSearch::ToRight(c) => EditCommand::CopyRightUntil(*c),
+            ViCharSearch::ToLeft(c) => EditCommand::CopyLeftUntil(*c),
+            ViCharSearch::TillRight(c) => EditCommand::CopyRightBefore(*c),
+            ViCharSearch::TillLeft(c) => EditCommand::CopyLeftBefore(*c),
+        }
+    }
 }
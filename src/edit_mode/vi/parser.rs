@@ -1,9 +1,9 @@
 use super::command::{parse_command, Command};
 use super::motion::{parse_motion, Motion};
-use crate::{EditCommand, ReedlineEvent, Vi};
+use crate::{EditCommand, ReedlineEvent, SelectionMode, Vi};
 use std::iter::Peekable;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ReedlineOption {
     Event(ReedlineEvent),
     Edit(EditCommand),
@@ -39,6 +39,7 @@ impl<T> ParseResult<T> {
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ParsedViSequence {
+    register: Option<char>,
     multiplier: Option<usize>,
     command: Option<Command>,
     count: Option<usize>,
@@ -61,6 +62,16 @@ impl ParsedViSequence {
         }
     }
 
+    /// Whether the sequence is complete in visual mode, where the operators act on the
+    /// selection instead of waiting for a motion
+    pub fn is_complete_in_visual(&self) -> bool {
+        match (&self.command, &self.motion) {
+            (Some(Command::Incomplete), _) => false,
+            (Some(_), ParseResult::Incomplete) => true,
+            _ => self.is_complete(),
+        }
+    }
+
     /// Combine `multiplier` and `count` as vim only considers the product
     ///
     /// Default return value: 1
@@ -74,23 +85,34 @@ impl ParsedViSequence {
 
     fn apply_multiplier(&self, raw_events: Option<Vec<ReedlineOption>>) -> ReedlineEvent {
         if let Some(raw_events) = raw_events {
-            let events = std::iter::repeat(raw_events)
-                .take(self.total_multiplier())
-                .flatten()
-                .filter_map(ReedlineOption::into_reedline_event)
-                .collect::<Vec<ReedlineEvent>>();
-
-            if events.is_empty() || events.contains(&ReedlineEvent::None) {
-                // TODO: Clarify if the `contains(ReedlineEvent::None)` path is relevant
-                ReedlineEvent::None
-            } else {
-                ReedlineEvent::Multiple(events)
-            }
+            repeat_events(raw_events, self.register, self.total_multiplier())
         } else {
             ReedlineEvent::None
         }
     }
 
+    /// Events of the `command`, remembered as the last change to be repeated by `.` if it is one
+    fn change_events(
+        &self,
+        command: &Command,
+        raw_events: Option<Vec<ReedlineOption>>,
+        vi_state: &mut Vi,
+    ) -> ReedlineEvent {
+        let events = self.apply_multiplier(raw_events.clone());
+
+        if command.is_change() && events != ReedlineEvent::None {
+            vi_state.previous = Some(ViChange {
+                raw_events: raw_events.unwrap_or_default(),
+                register: self.register,
+                multiplier: self.total_multiplier(),
+                inserted: vec![],
+            });
+            vi_state.recording_insert = self.enters_insert_mode();
+        }
+
+        events
+    }
+
     pub fn enters_insert_mode(&self) -> bool {
         matches!(
             (&self.command, &self.motion),
@@ -109,34 +131,125 @@ impl ParsedViSequence {
         )
     }
 
+    pub fn starts_macro_recording(&self) -> bool {
+        matches!(
+            (&self.command, &self.motion),
+            (Some(Command::RecordMacro(_)), ParseResult::Incomplete)
+        )
+    }
+
+    pub fn enters_visual_mode(&self) -> Option<SelectionMode> {
+        match (&self.command, &self.motion) {
+            (Some(Command::EnterVisual), ParseResult::Incomplete) => Some(SelectionMode::Inclusive),
+            (Some(Command::EnterVisualLine), ParseResult::Incomplete) => Some(SelectionMode::Lines),
+            _ => None,
+        }
+    }
+
+    /// Event of the sequence in visual mode, where the motions extend the selection
+    pub fn to_visual_event(&self, vi_state: &mut Vi) -> ReedlineEvent {
+        match (&self.command, &self.motion) {
+            (Some(command), ParseResult::Incomplete) => {
+                repeat_events(command.to_visual_reedline(vi_state), self.register, 1)
+            }
+            (None, ParseResult::Valid(motion)) => repeat_events(
+                motion.to_reedline_with_count(vi_state, self.total_multiplier()),
+                None,
+                1,
+            ),
+            _ => ReedlineEvent::None,
+        }
+    }
+
     pub fn to_reedline_event(&self, vi_state: &mut Vi) -> ReedlineEvent {
         match (&self.multiplier, &self.command, &self.count, &self.motion) {
-            (_, Some(command), None, ParseResult::Incomplete) => {
-                let events = self.apply_multiplier(Some(command.to_reedline(vi_state)));
-                match &events {
-                    ReedlineEvent::None => {}
-                    event => vi_state.previous = Some(event.clone()),
+            (_, Some(Command::RepeatLastAction), None, ParseResult::Incomplete) => {
+                match &mut vi_state.previous {
+                    Some(change) => {
+                        // A new count replaces the one of the change, also for the next repeats
+                        if let Some(multiplier) = self.multiplier {
+                            change.multiplier = multiplier;
+                        }
+                        change.to_reedline_event(self.register)
+                    }
+                    None => ReedlineEvent::None,
                 }
-                events
+            }
+            (_, Some(command), None, ParseResult::Incomplete) => {
+                let raw_events = command.to_reedline();
+                self.change_events(command, Some(raw_events), vi_state)
             }
             // This case handles all combinations of commands and motions that could exist
             (_, Some(command), _, ParseResult::Valid(motion)) => {
-                let events =
-                    self.apply_multiplier(command.to_reedline_with_motion(motion, vi_state));
-                match &events {
-                    ReedlineEvent::None => {}
-                    event => vi_state.previous = Some(event.clone()),
-                }
-                events
-            }
-            (_, None, _, ParseResult::Valid(motion)) => {
-                self.apply_multiplier(Some(motion.to_reedline(vi_state)))
+                let raw_events = command.to_reedline_with_motion(motion, vi_state);
+                self.change_events(command, raw_events, vi_state)
             }
+            (_, None, _, ParseResult::Valid(motion)) => repeat_events(
+                motion.to_reedline_with_count(vi_state, self.total_multiplier()),
+                self.register,
+                1,
+            ),
             _ => ReedlineEvent::None,
         }
     }
 }
 
+/// Last change made in normal mode, repeated by `.`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViChange {
+    raw_events: Vec<ReedlineOption>,
+    register: Option<char>,
+    multiplier: usize,
+    /// Edits typed in insert mode, if the change entered it
+    pub inserted: Vec<EditCommand>,
+}
+
+impl ViChange {
+    /// Events repeating the change, in the `register` if any instead of the one of the change
+    fn to_reedline_event(&self, register: Option<char>) -> ReedlineEvent {
+        let events = repeat_events(
+            self.raw_events.clone(),
+            register.or(self.register),
+            self.multiplier,
+        );
+
+        match events {
+            ReedlineEvent::Multiple(mut events) if !self.inserted.is_empty() => {
+                events.push(ReedlineEvent::Edit(self.inserted.clone()));
+                ReedlineEvent::Multiple(events)
+            }
+            events => events,
+        }
+    }
+}
+
+fn repeat_events(
+    mut raw_events: Vec<ReedlineOption>,
+    register: Option<char>,
+    multiplier: usize,
+) -> ReedlineEvent {
+    // The register is selected again before each repetition, as it only applies to one edit
+    if let Some(register) = register {
+        raw_events.insert(
+            0,
+            ReedlineOption::Edit(EditCommand::SelectRegister(register)),
+        );
+    }
+
+    let events = std::iter::repeat(raw_events)
+        .take(multiplier)
+        .flatten()
+        .filter_map(ReedlineOption::into_reedline_event)
+        .collect::<Vec<ReedlineEvent>>();
+
+    if events.is_empty() || events.contains(&ReedlineEvent::None) {
+        // TODO: Clarify if the `contains(ReedlineEvent::None)` path is relevant
+        ReedlineEvent::None
+    } else {
+        ReedlineEvent::Multiple(events)
+    }
+}
+
 fn parse_number<'iter, I>(input: &mut Peekable<I>) -> Option<usize>
 where
     I: Iterator<Item = &'iter char>,
@@ -161,16 +274,63 @@ where
     }
 }
 
+/// Parse the `"<char>` selection of a register
+fn parse_register<'iter, I>(input: &mut Peekable<I>) -> ParseResult<Option<char>>
+where
+    I: Iterator<Item = &'iter char>,
+{
+    if input.next_if_eq(&&'"').is_none() {
+        return ParseResult::Valid(None);
+    }
+
+    match input.next() {
+        Some(&c) if c.is_ascii_alphanumeric() || c == '"' || c == '-' => {
+            ParseResult::Valid(Some(c))
+        }
+        Some(_) => ParseResult::Invalid,
+        None => ParseResult::Incomplete,
+    }
+}
+
 pub fn parse<'iter, I>(input: &mut Peekable<I>) -> ParsedViSequence
 where
     I: Iterator<Item = &'iter char>,
 {
+    // The register may be selected before or after the multiplier
+    let mut register = parse_register(input);
     let multiplier = parse_number(input);
+    if matches!(register, ParseResult::Valid(None)) {
+        register = parse_register(input);
+    }
+
+    let register = match register {
+        ParseResult::Valid(register) => register,
+        ParseResult::Incomplete => {
+            return ParsedViSequence {
+                register: None,
+                multiplier,
+                command: Some(Command::Incomplete),
+                count: None,
+                motion: ParseResult::Incomplete,
+            }
+        }
+        ParseResult::Invalid => {
+            return ParsedViSequence {
+                register: None,
+                multiplier,
+                command: None,
+                count: None,
+                motion: ParseResult::Invalid,
+            }
+        }
+    };
+
     let command = parse_command(input);
     let count = parse_number(input);
     let motion = parse_motion(input, command.as_ref().and_then(Command::whole_line_char));
 
     ParsedViSequence {
+        register,
         multiplier,
         command,
         count,
@@ -196,6 +356,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: None,
                 command: Some(Command::Delete),
                 count: None,
@@ -214,6 +375,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: Some(2),
                 command: Some(Command::Delete),
                 count: None,
@@ -232,6 +394,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: Some(2),
                 command: Some(Command::Delete),
                 count: Some(2),
@@ -250,6 +413,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: Some(2),
                 command: Some(Command::Delete),
                 count: Some(20),
@@ -268,6 +432,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: Some(2),
                 command: Some(Command::Delete),
                 count: None,
@@ -286,6 +451,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: None,
                 command: Some(Command::Delete),
                 count: None,
@@ -304,6 +470,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: Some(2),
                 command: Some(Command::Delete),
                 count: None,
@@ -321,6 +488,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: None,
                 command: Some(Command::Incomplete),
                 count: None,
@@ -340,6 +508,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: None,
                 command: None,
                 count: None,
@@ -358,6 +527,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: None,
                 command: Some(Command::ReplaceChar('k')),
                 count: None,
@@ -377,6 +547,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: Some(2),
                 command: None,
                 count: None,
@@ -395,6 +566,7 @@ mod tests {
         assert_eq!(
             output,
             ParsedViSequence {
+                register: None,
                 multiplier: Some(2),
                 command: None,
                 count: None,
@@ -405,6 +577,56 @@ mod tests {
         assert_eq!(output.is_complete(), true);
     }
 
+    #[test]
+    fn test_register_before_multiplier() {
+        let input = ['"', 'a', '2', 'y', 'y'];
+        let output = vi_parse(&input);
+
+        assert_eq!(
+            output,
+            ParsedViSequence {
+                register: Some('a'),
+                multiplier: Some(2),
+                command: Some(Command::Yank),
+                count: None,
+                motion: ParseResult::Valid(Motion::Line),
+            }
+        );
+        assert_eq!(output.is_valid(), true);
+        assert_eq!(output.is_complete(), true);
+    }
+
+    #[test]
+    fn test_register_after_multiplier() {
+        let input = ['2', '"', 'a', 'p'];
+        let output = vi_parse(&input);
+
+        assert_eq!(
+            output,
+            ParsedViSequence {
+                register: Some('a'),
+                multiplier: Some(2),
+                command: Some(Command::PasteAfter),
+                count: None,
+                motion: ParseResult::Incomplete,
+            }
+        );
+        assert_eq!(output.is_valid(), true);
+        assert_eq!(output.is_complete(), true);
+    }
+
+    #[rstest]
+    #[case(&['"'], true)]
+    #[case(&['"', 'a'], true)]
+    #[case(&['"', 'a', 'd'], true)]
+    #[case(&['"', '!'], false)]
+    fn test_partial_register(#[case] input: &[char], #[case] is_valid: bool) {
+        let output = vi_parse(input);
+
+        assert_eq!(output.is_valid(), is_valid);
+        assert_eq!(output.is_complete(), false);
+    }
+
     #[rstest]
     #[case(&['2', 'k'], ReedlineEvent::Multiple(vec![ReedlineEvent::UntilFound(vec![
                 ReedlineEvent::MenuUp,
@@ -456,6 +678,22 @@ mod tests {
     #[case(&['d', 'e'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CutWordRight])]))]
     #[case(&['d', 'b'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CutWordLeft])]))]
     #[case(&['d', 'B'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CutBigWordLeft])]))]
+    #[case(&['y', 'y'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CopyCurrentLine])]))]
+    #[case(&['y', 'w'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CopyWordRightToNext])]))]
+    #[case(&['"', 'a', 'y', 'y'], ReedlineEvent::Multiple(vec![
+        ReedlineEvent::Edit(vec![EditCommand::SelectRegister('a')]),
+        ReedlineEvent::Edit(vec![EditCommand::CopyCurrentLine])]))]
+    #[case(&['2', '"', 'a', 'p'], ReedlineEvent::Multiple(vec![
+        ReedlineEvent::Edit(vec![EditCommand::SelectRegister('a')]),
+        ReedlineEvent::Edit(vec![EditCommand::PasteCutBufferAfter]),
+        ReedlineEvent::Edit(vec![EditCommand::SelectRegister('a')]),
+        ReedlineEvent::Edit(vec![EditCommand::PasteCutBufferAfter])]))]
+    #[case(&['q', 'a'], ReedlineEvent::Multiple(vec![ReedlineEvent::RecordMacro('a')]))]
+    #[case(&['@', 'a'], ReedlineEvent::Multiple(vec![ReedlineEvent::PlayMacro('a')]))]
+    #[case(&['2', '@', 'a'], ReedlineEvent::Multiple(vec![
+        ReedlineEvent::PlayMacro('a'),
+        ReedlineEvent::PlayMacro('a')]))]
+    #[case(&['@', '@'], ReedlineEvent::Multiple(vec![ReedlineEvent::PlayLastMacro]))]
     fn test_reedline_move(#[case] input: &[char], #[case] expected: ReedlineEvent) {
         let mut vi = Vi::default();
         let res = vi_parse(input);
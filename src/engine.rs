@@ -1,8 +1,11 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use itertools::Itertools;
 use nu_ansi_term::{Color, Style};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 
+#[cfg(all(unix, feature = "suspend"))]
+use crate::terminal_extensions::suspend::SuspendGuard;
 use crate::{enums::ReedlineRawEvent, CursorConfig};
 #[cfg(feature = "bashisms")]
 use crate::{
@@ -17,33 +20,42 @@ use {
 };
 use {
     crate::{
-        completion::{Completer, DefaultCompleter},
+        completion::{Completer, CompletionTransformer, DefaultCompleter},
         core_editor::Editor,
         edit_mode::{EditMode, Emacs},
-        enums::{EventStatus, ReedlineEvent},
-        highlighter::SimpleMatchHighlighter,
+        enums::{CompletionStrategy, EnterBehavior, EventStatus, LineOverflow, ReedlineEvent},
+        highlighter::{HighlightCache, SimpleMatchHighlighter},
         hinter::Hinter,
         history::{
             FileBackedHistory, History, HistoryCursor, HistoryItem, HistoryItemId,
             HistoryNavigationQuery, HistorySessionId, SearchDirection, SearchQuery,
         },
-        painting::{Painter, PainterSuspendedState, PromptLines},
-        prompt::{PromptEditMode, PromptHistorySearchStatus},
+        menu_functions::replace_in_buffer,
+        painting::{strip_ansi, Painter, PainterSuspendedState, PromptLines},
+        prompt::{PromptContext, PromptEditMode, PromptHistorySearchStatus},
         result::{ReedlineError, ReedlineErrorVariants},
-        terminal_extensions::{bracketed_paste::BracketedPasteGuard, kitty::KittyProtocolGuard},
+        terminal_extensions::{
+            bracketed_paste::BracketedPasteGuard, kitty::KittyProtocolGuard,
+            mouse_capture::MouseCaptureGuard,
+        },
         utils::text_manipulation,
         EditCommand, ExampleHighlighter, Highlighter, LineBuffer, Menu, MenuEvent, Prompt,
-        PromptHistorySearch, ReedlineMenu, Signal, UndoBehavior, ValidationResult, Validator,
+        PromptHistorySearch, ReedlineMenu, Signal, Span, Suggestion, UndoBehavior, UndoGranularity,
+        ValidationResult, Validator, WordChars,
     },
     crossterm::{
-        cursor::{SetCursorStyle, Show},
+        cursor::Show,
         event,
         event::{Event, KeyCode, KeyEvent, KeyModifiers},
-        terminal, QueueableCommand,
+        terminal,
+        tty::IsTty,
+        QueueableCommand,
     },
     std::{
-        fs::File, io, io::Result, io::Write, process::Command, time::Duration, time::SystemTime,
+        collections::HashMap, fs::File, io, io::BufRead, io::Result, io::Write, process::Command,
+        time::Duration, time::SystemTime,
     },
+    unicode_segmentation::UnicodeSegmentation,
 };
 
 // The POLL_WAIT is used to specify for how long the POLL should wait for
@@ -57,6 +69,29 @@ const POLL_WAIT: u64 = 10;
 // a paste. 10 events in 10 milliseconds is conservative enough (unlikely somebody
 // will type more than 10 characters in 10 milliseconds)
 const EVENTS_THRESHOLD: usize = 10;
+// How long the events are waited for before checking the suspend and resume signals again
+#[cfg(all(unix, feature = "suspend"))]
+const SIGNAL_POLL_WAIT: u64 = 100;
+
+/// Redacts the command lines before they are added to the history, see
+/// [`Reedline::with_history_redactor`]
+pub type HistoryRedactor = dyn Fn(&str) -> Option<String> + Send;
+
+/// Edits the buffer when its [`ReedlineEvent::RunCommand`] is bound to a key, see
+/// [`Reedline::with_command`]
+pub type CustomCommand = dyn Fn(&mut LineBuffer) + Send;
+
+/// How an action that can't be done is signalled, see [`Reedline::with_bell`]
+#[derive(Default)]
+pub enum Bell {
+    /// Nothing signals it
+    #[default]
+    Silent,
+    /// The terminal bell rings, audibly or with a flash depending on the terminal
+    Terminal,
+    /// The callback is run, e.g. to flash a part of the application
+    Custom(Box<dyn Fn() + Send>),
+}
 
 /// Determines if inputs should be used to extend the regular line buffer,
 /// traverse the history in the standard prompt or edit the search string in the
@@ -105,8 +140,15 @@ pub struct Reedline {
     // none if history doesn't support this
     history_last_run_id: Option<HistoryItemId>,
     history_exclusion_prefix: Option<String>,
+    history_redactor: Option<Box<HistoryRedactor>>,
+    history_cwd_scoped: bool,
+
+    // Commands run by `ReedlineEvent::RunCommand`, by name
+    commands: HashMap<String, Box<CustomCommand>>,
     history_excluded_item: Option<HistoryItem>,
     history_cursor_on_excluded: bool,
+    // Last match of the reverse history search, kept while the search string matches nothing
+    history_search_match: Option<String>,
     input_mode: InputMode,
 
     // State of the painter after a `ReedlineEvent::ExecuteHostCommand` was requested, used after
@@ -121,29 +163,50 @@ pub struct Reedline {
 
     transient_prompt: Option<Box<dyn Prompt>>,
 
+    // What the prompt gets to know about the previous commands
+    prompt_context: PromptContext,
+
     // Edit Mode: Vi, Emacs
     edit_mode: Box<dyn EditMode>,
 
     // Provides the tab completions
     completer: Box<dyn Completer>,
     quick_completions: bool,
+
+    // Whether `ReedlineEvent::Enter` submits or inserts a newline
+    enter_behavior: EnterBehavior,
     partial_completions: bool,
+    completion_strategy: CompletionStrategy,
+    accept_best_completion: bool,
+    // Last suggestion accepted from a completion menu, for `ReedlineEvent::RepeatLastCompletion`
+    last_completion: Option<AcceptedCompletion>,
 
     // Highlight the edit buffer
     highlighter: Box<dyn Highlighter>,
 
+    // Styles of the last highlighted buffer, for the incremental highlighters
+    highlight_cache: HighlightCache,
+
     // Style used for visual selection
     visual_selection_style: Style,
 
+    // Style of the characters under the secondary cursors
+    secondary_cursor_style: Style,
+
     // Showcase hints based on various strategies (history, language-completion, spellcheck, etc)
     hinter: Option<Box<dyn Hinter>>,
+    hinter_enabled: bool,
     hide_hints: bool,
 
     // Use ansi coloring or not
     use_ansi_coloring: bool,
 
+    // Read the lines of stdin as they are when it isn't a terminal
+    piped_input: bool,
+
     // Engine Menus
     menus: Vec<ReedlineMenu>,
+    completer_enabled: bool,
 
     // Text editor used to open the line buffer for editing
     buffer_editor: Option<BufferEditor>,
@@ -151,12 +214,40 @@ pub struct Reedline {
     // Use different cursors depending on the current edit mode
     cursor_shapes: Option<CursorConfig>,
 
+    // Keyboard macros by register, the one being recorded and the ones being played
+    macros: HashMap<char, Vec<ReedlineEvent>>,
+    recording_macro: Option<(char, Vec<ReedlineEvent>)>,
+    last_macro: Option<char>,
+    playing_macros: Vec<char>,
+
+    // Events to handle at the start of the next `read_line`
+    scripted_events: Vec<ReedlineEvent>,
+
+    // Buffer the next `read_line` starts with
+    seeded_buffer: Option<LineBuffer>,
+
+    // Buffer of the last line aborted with `Ctrl+C`
+    interrupted_buffer: Option<String>,
+
+    // Line edited before browsing the history by prefix, restored past the newest entry
+    history_prefix_line: Option<LineBuffer>,
+
+    // Signals the actions that can't be done
+    bell: Bell,
+
     // Manage bracketed paste mode
     bracketed_paste: BracketedPasteGuard,
 
     // Manage optional kitty protocol
     kitty_protocol: KittyProtocolGuard,
 
+    // Manage the optional mouse capture
+    mouse_capture: MouseCaptureGuard,
+
+    // Manage the optional suspend and resume signals
+    #[cfg(all(unix, feature = "suspend"))]
+    suspend: SuspendGuard,
+
     #[cfg(feature = "external_printer")]
     external_printer: Option<ExternalPrinter<String>>,
 }
@@ -166,12 +257,104 @@ struct BufferEditor {
     temp_file: PathBuf,
 }
 
+/// A suggestion accepted from a completion menu, with the text it replaced before and after
+/// the cursor
+struct AcceptedCompletion {
+    suggestion: Suggestion,
+    before_cursor: String,
+    after_cursor: String,
+}
+
+impl AcceptedCompletion {
+    /// The suggestion selected in `menu`, about to replace its span of the buffer of `editor`
+    fn selected(menu: &ReedlineMenu, editor: &Editor) -> Option<Self> {
+        // The history entries are whole command lines rather than completions
+        if matches!(menu, ReedlineMenu::HistoryMenu(_)) {
+            return None;
+        }
+        let suggestion = menu.get_selected_value()?;
+        let buffer = editor.get_buffer();
+        let end = suggestion.span.end.min(buffer.len());
+        let start = suggestion.span.start.min(end);
+        let cursor = editor.insertion_point().clamp(start, end);
+        Some(Self {
+            before_cursor: buffer.get(start..cursor)?.to_string(),
+            after_cursor: buffer.get(cursor..end)?.to_string(),
+            suggestion,
+        })
+    }
+
+    /// The suggestion replacing the same text around the cursor of `editor`, if it is still
+    /// there
+    fn at_cursor(&self, editor: &Editor) -> Option<Suggestion> {
+        let buffer = editor.get_buffer();
+        let cursor = editor.insertion_point();
+        if !buffer[..cursor].ends_with(&self.before_cursor)
+            || !buffer[cursor..].starts_with(&self.after_cursor)
+        {
+            return None;
+        }
+        Some(Suggestion {
+            span: Span::new(
+                cursor - self.before_cursor.len(),
+                cursor + self.after_cursor.len(),
+            ),
+            ..self.suggestion.clone()
+        })
+    }
+}
+
+impl BufferEditor {
+    /// The editor of the environment, editing a temporary file
+    fn from_env() -> Self {
+        let editor = ["EDITOR", "VISUAL"].iter().find_map(|name| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        });
+        // Another user could create a file with a predictable name before us
+        let temp_file = std::env::temp_dir().join(format!(
+            "reedline-buffer-{}-{:016x}.txt",
+            std::process::id(),
+            SmallRng::from_entropy().gen::<u64>()
+        ));
+        let mut command = editor_command(editor.as_deref());
+        command.arg(&temp_file);
+        BufferEditor { command, temp_file }
+    }
+}
+
+/// Create the temporary file of the environment editor, failing if anything (e.g. a symlink)
+/// already exists at its path, and only letting the user access it since the buffer may hold
+/// secrets
+fn create_temp_file(path: &Path) -> Result<File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Whether the output of the painter, on stderr, supports the ANSI colors
+fn ansi_colors_supported() -> bool {
+    io::stderr().is_tty() && std::env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+/// The command of an editor like `$EDITOR`, which may have arguments, or else of `vi`
+fn editor_command(editor: Option<&str>) -> Command {
+    let mut words = editor.unwrap_or_default().split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or("vi"));
+    command.args(words);
+    command
+}
+
 impl Drop for Reedline {
     fn drop(&mut self) {
-        if self.cursor_shapes.is_some() {
+        // Restore the cursor shape if `read_line` did not get to do it
+        if self.painter.cursor_shape_changed() {
             let _ignore = terminal::enable_raw_mode();
+            let _ignore = self.painter.restore_cursor_shape();
             let mut stdout = std::io::stdout();
-            let _ignore = stdout.queue(SetCursorStyle::DefaultUserShape);
             let _ignore = stdout.queue(Show);
             let _ignore = stdout.flush();
         }
@@ -192,6 +375,7 @@ impl Reedline {
         let painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
         let buffer_highlighter = Box::<ExampleHighlighter>::default();
         let visual_selection_style = Style::new().on(Color::LightGray);
+        let secondary_cursor_style = Style::new().reverse();
         let completer = Box::<DefaultCompleter>::default();
         let hinter = None;
         let validator = None;
@@ -208,27 +392,53 @@ impl Reedline {
             history_session_id: hist_session_id,
             history_last_run_id: None,
             history_exclusion_prefix: None,
+            history_redactor: None,
+            history_cwd_scoped: false,
+            commands: HashMap::new(),
             history_excluded_item: None,
             history_cursor_on_excluded: false,
+            history_search_match: None,
             input_mode: InputMode::Regular,
             suspended_state: None,
             painter,
             transient_prompt: None,
+            prompt_context: PromptContext::default(),
             edit_mode,
             completer,
             quick_completions: false,
+            enter_behavior: EnterBehavior::default(),
             partial_completions: false,
+            completion_strategy: CompletionStrategy::default(),
+            accept_best_completion: false,
+            last_completion: None,
             highlighter: buffer_highlighter,
+            highlight_cache: HighlightCache::default(),
             visual_selection_style,
+            secondary_cursor_style,
             hinter,
+            hinter_enabled: true,
             hide_hints: false,
             validator,
-            use_ansi_coloring: true,
+            use_ansi_coloring: ansi_colors_supported(),
+            piped_input: false,
             menus: Vec::new(),
+            completer_enabled: true,
             buffer_editor: None,
-            cursor_shapes: None,
+            cursor_shapes: Some(CursorConfig::default()),
+            macros: HashMap::new(),
+            recording_macro: None,
+            last_macro: None,
+            playing_macros: Vec::new(),
+            scripted_events: Vec::new(),
+            seeded_buffer: None,
+            interrupted_buffer: None,
+            history_prefix_line: None,
+            bell: Bell::default(),
             bracketed_paste: BracketedPasteGuard::default(),
+            mouse_capture: MouseCaptureGuard::default(),
             kitty_protocol: KittyProtocolGuard::default(),
+            #[cfg(all(unix, feature = "suspend"))]
+            suspend: SuspendGuard::default(),
             #[cfg(feature = "external_printer")]
             external_printer: None,
         }
@@ -246,24 +456,100 @@ impl Reedline {
 
     /// Toggle whether reedline enables bracketed paste to reed copied content
     ///
-    /// This currently alters the behavior for multiline pastes as pasting of regular text will
-    /// execute after every complete new line as determined by the [`Validator`]. With enabled
-    /// bracketed paste all lines will appear in the buffer and can then be submitted with a
-    /// separate enter.
+    /// With bracketed paste the terminal sends the copied content as a whole, handled as a
+    /// single [`ReedlineEvent::Paste`]: all lines appear in the buffer and can then be
+    /// submitted with a separate enter. Without it, pasted text is typed in key by key and
+    /// executes after every complete new line as determined by the [`Validator`].
     ///
-    /// At this point most terminals should support it or ignore the setting of the necessary
-    /// flags. For full compatibility, keep it disabled.
+    /// Most terminals support it or ignore the setting of the necessary flags. Enabled by
+    /// default, disable it if your terminal misbehaves with pastes.
     pub fn use_bracketed_paste(mut self, enable: bool) -> Self {
         self.bracketed_paste.set(enable);
         self
     }
 
+    /// Toggle whether reedline captures the mouse while a line is edited
+    ///
+    /// A click moves the cursor to the clicked character of the buffer, taking the prompt, the
+    /// wrapped lines and the wide characters into account, or selects the clicked value of the
+    /// completion menu. Dragging the mouse selects the buffer from the click. Clicks are ignored
+    /// while the buffer is taller than the screen, or with [`LineOverflow::Scroll`].
+    ///
+    /// The terminal doesn't select the text on its own anymore while the mouse is captured,
+    /// which is given back to it once the line is read.
+    ///
+    /// Disabled by default
+    pub fn use_mouse_capture(mut self, enable: bool) -> Self {
+        self.mouse_capture.set(enable);
+        self
+    }
+
+    /// A builder that configures how successive edits are grouped into the steps of
+    /// [`EditCommand::Undo`] and [`EditCommand::Redo`]
+    ///
+    /// Defaults to [`UndoGranularity::Word`]
+    #[must_use]
+    pub fn with_undo_granularity(mut self, undo_granularity: UndoGranularity) -> Self {
+        self.editor.set_undo_granularity(undo_granularity);
+        self
+    }
+
+    /// A builder that configures which characters join or break the words of the word
+    /// motions and deletions (e.g. `Alt-f`, `Ctrl-w` or vi's `dw`), see [`WordChars`]
+    #[must_use]
+    pub fn with_word_chars(mut self, word_chars: WordChars) -> Self {
+        self.editor.set_word_chars(word_chars);
+        self
+    }
+
+    /// A builder that transforms the suggestions chosen in the completion menus before they are
+    /// inserted, e.g. to escape or quote them depending on the quotes the cursor is in
+    ///
+    /// The transformer is given the value of the suggestion and the [`crate::QuoteContext`] of
+    /// the line before the text it replaces, and returns the text to insert. The entries of
+    /// the history menu are inserted as is.
+    /// # Example
+    /// ```rust
+    /// // Escapes the spaces outside of quotes, e.g. inserting `my\ file`
+    ///
+    /// use reedline::{QuoteContext, Reedline};
+    ///
+    /// let mut line_editor =
+    ///     Reedline::create().with_completion_transformer(Box::new(|value, context| {
+    ///         match context {
+    ///             QuoteContext::Unquoted => value.replace(' ', "\\ "),
+    ///             QuoteContext::SingleQuoted | QuoteContext::DoubleQuoted => value.to_string(),
+    ///         }
+    ///     }));
+    /// ```
+    #[must_use]
+    pub fn with_completion_transformer(mut self, transformer: Box<CompletionTransformer>) -> Self {
+        self.editor.set_completion_transformer(Some(transformer));
+        self
+    }
+
+    /// Toggle whether typing `(`, `[`, `{`, `"` or `` ` `` also inserts its closing character
+    ///
+    /// Typing a closing character already next to the cursor moves over it instead, and
+    /// backspacing an opening character right in front of its closing one deletes both.
+    /// Text pasted with [`Reedline::use_bracketed_paste`] is inserted as is.
+    ///
+    /// Disabled by default
+    pub fn use_auto_pairs(mut self, enable: bool) -> Self {
+        self.editor.set_auto_pairs(enable);
+        self
+    }
+
     /// Toggle whether reedline uses the kitty keyboard enhancement protocol
     ///
-    /// This allows us to disambiguate more events than the traditional standard
+    /// This allows us to disambiguate more events than the traditional standard, so that keys
+    /// such as `Ctrl-Enter`, `Shift-Enter` or `Ctrl-i` can be bound apart from `Enter` and
+    /// `Tab`. When unbound they still behave like the key they are reported as without the
+    /// protocol (see [`crate::Keybindings::legacy_fallback`]).
     /// Only available with a few terminal emulators.
     /// You can check for that with [`crate::kitty_protocol_available`]
-    /// `Reedline` will perform this check internally
+    /// `Reedline` will perform this check internally, staying with the traditional keys
+    /// otherwise. The protocol is disabled again when [`Reedline::read_line`] returns.
     ///
     /// Read more: <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
     pub fn use_kitty_keyboard_enhancement(mut self, enable: bool) -> Self {
@@ -271,11 +557,40 @@ impl Reedline {
         self
     }
 
+    /// Toggle whether reedline catches the signals stopping and resuming the process while a
+    /// line is edited
+    ///
+    /// A `SIGTSTP` (e.g. `kill -TSTP`) restores the terminal before the process stops, and a
+    /// `SIGCONT` after a `SIGSTOP` re-enters the raw mode. Either way the prompt is painted
+    /// again once resumed. The handlers are added after the ones of the host, which keep being
+    /// called, and only act while a line is edited: `SIGTSTP` stops the process right away
+    /// otherwise. With [`Reedline::begin_line`], the signals are handled along the next
+    /// terminal event.
+    ///
+    /// The `Ctrl-z` key doesn't send a signal in the raw mode, bind it to
+    /// [`ReedlineEvent::Suspend`] to suspend the process.
+    ///
+    /// Disabled by default
+    ///
+    /// ## Required feature:
+    /// `suspend`, on unix
+    #[cfg(all(unix, feature = "suspend"))]
+    pub fn use_suspend_handling(mut self, enable: bool) -> Self {
+        self.suspend.set(enable);
+        self
+    }
+
     /// Return the previously generated history session id
     pub fn get_history_session_id(&self) -> Option<HistorySessionId> {
         self.history_session_id
     }
 
+    /// Set the exit status of the last command run by the host, passed to the [`Prompt`] in
+    /// its [`PromptContext`] until set again
+    pub fn set_last_command_status(&mut self, status: i32) {
+        self.prompt_context.last_command_status = Some(status);
+    }
+
     /// Set a new history session id
     /// This should be used in situations where the user initially did not have a history_session_id
     /// and then later realized they want to have one without restarting the application.
@@ -313,6 +628,17 @@ impl Reedline {
         self
     }
 
+    /// Stop showing and completing hints, keeping the configured [`Hinter`] to
+    /// [`Reedline::enable_hinter`] it back later
+    pub fn disable_hinter(&mut self) {
+        self.hinter_enabled = false;
+    }
+
+    /// Show hints again with the configured [`Hinter`] after [`Reedline::disable_hinter`]
+    pub fn enable_hinter(&mut self) {
+        self.hinter_enabled = true;
+    }
+
     /// A builder to configure the tab completion
     /// # Example
     /// ```rust
@@ -352,8 +678,71 @@ impl Reedline {
         self
     }
 
+    /// Turn on accepting the best completion. [`ReedlineEvent::AcceptTopCompletion`] then
+    /// accepts the first suggestion of the completer even when there are several, instead of
+    /// opening the menu
+    #[must_use]
+    pub fn with_accept_best_completion(mut self, accept_best_completion: bool) -> Self {
+        self.accept_best_completion = accept_best_completion;
+        self
+    }
+
+    /// A builder that configures whether opening a completion menu first completes the
+    /// longest common prefix of the suggestions, see [`CompletionStrategy`]
+    ///
+    /// Defaults to [`CompletionStrategy::MenuFirst`].
+    /// # Example
+    /// ```rust
+    /// // The first Tab completes "fo" to "for", the next one lists the suggestions
+    ///
+    /// use reedline::{CompletionStrategy, DefaultCompleter, Reedline};
+    ///
+    /// let completer = DefaultCompleter::new(vec!["format".into(), "forward".into()]);
+    ///
+    /// let mut line_editor = Reedline::create()
+    ///     .with_completer(Box::new(completer))
+    ///     .with_completion_strategy(CompletionStrategy::LongestCommonPrefix);
+    /// ```
+    #[must_use]
+    pub fn with_completion_strategy(mut self, completion_strategy: CompletionStrategy) -> Self {
+        self.completion_strategy = completion_strategy;
+        self
+    }
+
+    /// A builder that makes [`Reedline::read_line`] read the lines of stdin as they are when
+    /// it isn't a terminal, e.g. with piped or scripted input
+    ///
+    /// The prompt is printed, then the next line of stdin is returned without being edited,
+    /// and the end of the input returns [`Signal::CtrlD`].
+    ///
+    /// Disabled by default: the keys are read from the terminal even when stdin is piped, so a
+    /// host run as `data | app` can still edit its lines and read the data itself.
+    #[must_use]
+    pub fn with_piped_input(mut self, piped_input: bool) -> Self {
+        self.piped_input = piped_input;
+        self
+    }
+
+    /// A builder that configures how the lines of the buffer wider than the terminal are
+    /// painted: wrapped on the next rows, or on a single row scrolled horizontally
+    ///
+    /// Defaults to [`LineOverflow::Wrap`].
+    #[must_use]
+    pub fn with_line_overflow(mut self, line_overflow: LineOverflow) -> Self {
+        self.painter.set_line_overflow(line_overflow);
+        self
+    }
+
     /// A builder which enables or disables the use of ansi coloring in the prompt
     /// and in the command line syntax highlighting.
+    ///
+    /// When disabled, the painted text is plain: the escape sequences of the prompt, the
+    /// highlighter and the hinter are removed, the cursor shapes aren't changed, and the menus
+    /// mark the selected value without colors. Only the cursor movements remain.
+    ///
+    /// Defaults to whether stderr, where the line is painted, is a terminal, unless `TERM` is
+    /// `dumb`. The colors used to be enabled by default: a host redirecting stderr while
+    /// drawing on the terminal some other way has to enable them again.
     #[must_use]
     pub fn with_ansi_colors(mut self, use_ansi_coloring: bool) -> Self {
         self.use_ansi_coloring = use_ansi_coloring;
@@ -379,6 +768,38 @@ impl Reedline {
     #[must_use]
     pub fn with_highlighter(mut self, highlighter: Box<dyn Highlighter>) -> Self {
         self.highlighter = highlighter;
+        self.highlight_cache.clear();
+        self
+    }
+
+    /// A builder that sets how the actions that can't be done are signalled, silently by default
+    ///
+    /// The bell rings when:
+    ///
+    /// - [`ReedlineEvent::Left`] is at the start of the buffer, or [`ReedlineEvent::Right`] at
+    ///   its end, e.g. with the arrow keys once no menu or hint took them
+    /// - [`EditCommand::YankPop`] has no other kill to replace the one just yanked
+    /// - The text typed in a history search makes it fail, or keeps it failing
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use reedline::{Bell, Reedline};
+    ///
+    /// let mut line_editor = Reedline::create().with_bell(Bell::Terminal);
+    /// ```
+    #[must_use]
+    pub fn with_bell(mut self, bell: Bell) -> Self {
+        self.bell = bell;
+        self
+    }
+
+    /// A builder that starts the next line with `buffer` already typed, the cursor at its end
+    ///
+    /// See [`Reedline::set_buffer`].
+    #[must_use]
+    pub fn with_buffer(mut self, buffer: String) -> Self {
+        self.seeded_buffer = Some(LineBuffer::from(buffer.as_str()));
         self
     }
 
@@ -389,6 +810,16 @@ impl Reedline {
         self
     }
 
+    /// A builder that configures the style of the characters under the secondary cursors of
+    /// [`EditCommand::AddCursorsAtWord`]
+    ///
+    /// Reversed by default. A secondary cursor at the end of a line has no character to show it.
+    #[must_use]
+    pub fn with_secondary_cursor_style(mut self, style: Style) -> Self {
+        self.secondary_cursor_style = style;
+        self
+    }
+
     /// A builder which configures the history for your instance of the Reedline engine
     /// # Example
     /// ```rust,no_run
@@ -430,6 +861,123 @@ impl Reedline {
         self
     }
 
+    /// A builder which redacts the submitted command lines before they are added to the history,
+    /// e.g. to scrub secrets. The redactor returns the command line to store, or `None` to
+    /// leave it out of the history
+    ///
+    /// It runs for any [`History`] backend, before the exclusion prefix and the history's own
+    /// filtering and deduplication. As it runs synchronously when a line is submitted, it should
+    /// be fast.
+    /// # Example
+    /// ```rust
+    /// use reedline::Reedline;
+    ///
+    /// let mut line_editor = Reedline::create().with_history_redactor(Box::new(|command_line| {
+    ///     match command_line.split_once(" --token ") {
+    ///         Some((command, _)) => Some(format!("{command} --token <redacted>")),
+    ///         None if command_line.contains("password") => None,
+    ///         None => Some(command_line.to_string()),
+    ///     }
+    /// }));
+    /// ```
+    #[must_use]
+    pub fn with_history_redactor(mut self, redactor: Box<HistoryRedactor>) -> Self {
+        self.history_redactor = Some(redactor);
+        self
+    }
+
+    /// A builder scoping the history browsed with the up and down arrows to the current working
+    /// directory
+    ///
+    /// The submitted lines are saved with the directory they were run in, and only the ones run
+    /// in the current directory or below it are browsed, matching the
+    /// [`SearchFilter::cwd_prefix`](crate::SearchFilter::cwd_prefix) of the history.
+    /// The items saved without a directory aren't browsed. The history must support filtering
+    /// by directory: the [`crate::FileBackedHistory`] only keeps the directories once
+    /// [`crate::FileBackedHistory::with_cwd`] is enabled.
+    ///
+    /// Disabled by default
+    #[must_use]
+    pub fn with_cwd_scoped_history(mut self, enable: bool) -> Self {
+        self.history_cwd_scoped = enable;
+        self
+    }
+
+    /// The working directory scoping the history, if [`Reedline::with_cwd_scoped_history`] is
+    /// enabled
+    fn history_cwd(&self) -> Option<String> {
+        if !self.history_cwd_scoped {
+            return None;
+        }
+        std::env::current_dir()
+            .ok()
+            .map(|cwd| cwd.to_string_lossy().to_string())
+    }
+
+    /// A builder registering a command editing the buffer, run when a key bound to
+    /// [`ReedlineEvent::RunCommand`] with its name is pressed
+    ///
+    /// The command edits a copy of the [`LineBuffer`], replacing the buffer afterwards: its
+    /// edits, however many, are undone in a single step. Registering another command with the
+    /// same name replaces it.
+    /// # Example
+    /// ```rust
+    /// use crossterm::event::{KeyCode, KeyModifiers};
+    /// use reedline::{default_emacs_keybindings, Emacs, Reedline, ReedlineEvent};
+    ///
+    /// let mut keybindings = default_emacs_keybindings();
+    /// keybindings.add_binding(
+    ///     KeyModifiers::ALT,
+    ///     KeyCode::Char('s'),
+    ///     ReedlineEvent::RunCommand("sudo".to_string()),
+    /// );
+    ///
+    /// let mut line_editor = Reedline::create()
+    ///     .with_edit_mode(Box::new(Emacs::new(keybindings)))
+    ///     .with_command(
+    ///         "sudo",
+    ///         Box::new(|line_buffer| {
+    ///             if !line_buffer.get_buffer().starts_with("sudo ") {
+    ///                 line_buffer.move_to_start();
+    ///                 line_buffer.insert_str("sudo ");
+    ///                 line_buffer.move_to_end();
+    ///             }
+    ///         }),
+    ///     );
+    /// ```
+    #[must_use]
+    pub fn with_command(mut self, name: &str, command: Box<CustomCommand>) -> Self {
+        self.commands.insert(name.to_string(), command);
+        self
+    }
+
+    /// A builder that configures whether [`ReedlineEvent::Enter`] submits the buffer or inserts
+    /// a newline
+    ///
+    /// Defaults to [`EnterBehavior::Validate`], the [`Validator`] deciding. The validator is
+    /// only run for `Enter` and [`ReedlineEvent::SubmitOrNewline`]: the keys bound to
+    /// [`ReedlineEvent::Submit`] or [`ReedlineEvent::InsertNewline`] (`Alt-Enter` by default)
+    /// always submit or insert a newline.
+    /// # Example
+    /// ```rust
+    /// // Enter inserts a newline, Ctrl-s submits
+    ///
+    /// use crossterm::event::{KeyCode, KeyModifiers};
+    /// use reedline::{default_emacs_keybindings, Emacs, EnterBehavior, Reedline, ReedlineEvent};
+    ///
+    /// let mut keybindings = default_emacs_keybindings();
+    /// keybindings.add_binding(KeyModifiers::CONTROL, KeyCode::Char('s'), ReedlineEvent::Submit);
+    ///
+    /// let mut line_editor = Reedline::create()
+    ///     .with_edit_mode(Box::new(Emacs::new(keybindings)))
+    ///     .with_enter_behavior(EnterBehavior::Newline);
+    /// ```
+    #[must_use]
+    pub fn with_enter_behavior(mut self, enter_behavior: EnterBehavior) -> Self {
+        self.enter_behavior = enter_behavior;
+        self
+    }
+
     /// A builder that configures the validator for your instance of the Reedline engine
     /// # Example
     /// ```rust
@@ -448,7 +996,9 @@ impl Reedline {
 
     /// A builder that configures the alternate text editor used to edit the line buffer
     ///
-    /// You are responsible for providing a file path that is unique to this reedline session
+    /// You are responsible for providing a file path that is unique to this reedline session.
+    /// Without it, [`ReedlineEvent::OpenEditor`] runs `$EDITOR`, `$VISUAL`, or else `vi` on a
+    /// temporary file.
     ///
     /// # Example
     /// ```rust,no_run
@@ -487,6 +1037,11 @@ impl Reedline {
     }
 
     /// Set a different prompt to be used after submitting each line
+    ///
+    /// Once the entry is accepted, all of its lines are painted again with this prompt before
+    /// moving on, usually to keep the scrollback compact. The prompt given to
+    /// [`Reedline::read_line`] is still used while editing, including the new lines requested
+    /// by the [`Validator`].
     #[must_use]
     pub fn with_transient_prompt(mut self, transient_prompt: Box<dyn Prompt>) -> Self {
         self.transient_prompt = Some(transient_prompt);
@@ -500,6 +1055,28 @@ impl Reedline {
         self
     }
 
+    /// Switch to another edit mode, e.g. for `set -o vi` and `set -o emacs`, returning the
+    /// previous one to switch back to it later
+    ///
+    /// The command typed so far in the previous edit mode is dropped, and the new one starts
+    /// without a pending one. The cursor takes the shape of the new mode in the
+    /// [`CursorConfig`] from the next paint.
+    pub fn set_edit_mode(&mut self, mut edit_mode: Box<dyn EditMode>) -> Box<dyn EditMode> {
+        edit_mode.reset_pending();
+        std::mem::replace(&mut self.edit_mode, edit_mode)
+    }
+
+    /// Name of the current edit mode: `emacs`, `vi`, the name of a
+    /// [`PromptEditMode::Custom`] mode, or `default`
+    pub fn current_edit_mode_name(&self) -> String {
+        match self.prompt_edit_mode() {
+            PromptEditMode::Default => "default".to_string(),
+            PromptEditMode::Emacs => "emacs".to_string(),
+            PromptEditMode::Vi(_) => "vi".to_string(),
+            PromptEditMode::Custom(name) => name,
+        }
+    }
+
     /// A builder that appends a menu to the engine
     #[must_use]
     pub fn with_menu(mut self, menu: ReedlineMenu) -> Self {
@@ -514,6 +1091,19 @@ impl Reedline {
         self
     }
 
+    /// Stop activating the menus, closing the active one, so the [`Completer`] isn't run.
+    /// The completer and the menus are kept to [`Reedline::enable_completer`] them back later
+    pub fn disable_completer(&mut self) {
+        self.completer_enabled = false;
+        self.deactivate_menus();
+    }
+
+    /// Activate the menus with the configured [`Completer`] again after
+    /// [`Reedline::disable_completer`]
+    pub fn enable_completer(&mut self) {
+        self.completer_enabled = true;
+    }
+
     /// A builder that adds the history item id
     #[must_use]
     pub fn with_history_session_id(mut self, session: Option<HistorySessionId>) -> Self {
@@ -521,19 +1111,53 @@ impl Reedline {
         self
     }
 
-    /// A builder that enables reedline changing the cursor shape based on the current edit mode.
-    /// The current implementation sets the cursor shape when drawing the prompt.
-    /// Do not use this if the cursor shape is set elsewhere, e.g. in the terminal settings or by ansi escape sequences.
+    /// A builder that sets the cursor shape reedline uses for each edit mode.
+    /// The current implementation sets the cursor shape when drawing the prompt, and restores
+    /// the shape of the user when [`Reedline::read_line`] returns.
+    ///
+    /// Defaults to [`CursorConfig::default`]
     pub fn with_cursor_config(mut self, cursor_shapes: CursorConfig) -> Self {
         self.cursor_shapes = Some(cursor_shapes);
         self
     }
 
+    /// Toggle whether reedline changes the cursor shape based on the current edit mode
+    ///
+    /// Disable this if the cursor shape is set elsewhere, e.g. in the terminal settings or by
+    /// ansi escape sequences. Enabling it again uses the default [`CursorConfig`] if the
+    /// configuration was dropped.
+    ///
+    /// Enabled by default
+    pub fn use_cursor_shapes(mut self, enable: bool) -> Self {
+        if !enable {
+            self.cursor_shapes = None;
+        } else if self.cursor_shapes.is_none() {
+            self.cursor_shapes = Some(CursorConfig::default());
+        }
+        self
+    }
+
     /// Returns the corresponding expected prompt style for the given edit mode
     pub fn prompt_edit_mode(&self) -> PromptEditMode {
         self.edit_mode.edit_mode()
     }
 
+    /// The context given to the [`Prompt`], describing what the engine is doing
+    fn current_prompt_context(&self, history_search: Option<PromptHistorySearch>) -> PromptContext {
+        PromptContext {
+            edit_mode: self.prompt_edit_mode(),
+            history_search,
+            active_menu: self
+                .menus
+                .iter()
+                .find(|menu| menu.is_active())
+                .map(|menu| menu.name().to_string()),
+            pending_command: self.edit_mode.pending_command(),
+            multiline_depth: self.editor.get_buffer().matches('\n').count(),
+            ..self.prompt_context.clone()
+        }
+    }
+
     /// Output the complete [`History`] chronologically with numbering to the terminal
     pub fn print_history(&mut self) -> Result<()> {
         let history: Vec<_> = self
@@ -626,17 +1250,155 @@ impl Reedline {
     ///
     /// Returns a [`std::io::Result`] in which the `Err` type is [`std::io::Result`]
     /// and the `Ok` variant wraps a [`Signal`] which handles user inputs.
+    ///
+    /// With [`Reedline::with_piped_input`], the next line of stdin is returned as it is when
+    /// stdin isn't a terminal.
     pub fn read_line(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
+        if self.piped_input && !io::stdin().is_tty() && self.scripted_events.is_empty() {
+            return self.read_piped_line(prompt, &mut io::stdin().lock());
+        }
+
+        let result = self.begin_line(prompt).and_then(|signal| match signal {
+            Some(signal) => Ok(signal),
+            None => self.read_line_helper(prompt),
+        });
+
+        self.end_line()?;
+        result
+    }
+
+    /// Start editing a line driven by the terminal events passed to
+    /// [`Reedline::handle_terminal_event`], instead of blocking like [`Reedline::read_line`]
+    ///
+    /// Lets the application own the event source, e.g. to multiplex the editor with other I/O in
+    /// an async runtime. Enables the raw mode and paints the prompt, [`Reedline::end_line`] must
+    /// be called to restore the terminal once a [`Signal`] is returned.
+    ///
+    /// Returns a [`Signal`] right away if the events queued with [`Reedline::play_events`] end
+    /// the line.
+    pub fn begin_line(&mut self, prompt: &dyn Prompt) -> Result<Option<Signal>> {
+        self.enter_terminal()?;
+        #[cfg(all(unix, feature = "suspend"))]
+        self.suspend.enter()?;
+
+        self.start_editing(prompt)
+    }
+
+    /// Handle a terminal event of the line started with [`Reedline::begin_line`]
+    ///
+    /// Returns `None` while the line is being edited, and the [`Signal`] ending the line
+    /// otherwise. The `prompt` should be the one passed to [`Reedline::begin_line`].
+    ///
+    /// The keys of a partially typed key sequence (e.g. a vi `jk` escape) are only handled
+    /// once the next event arrives.
+    pub fn handle_terminal_event(
+        &mut self,
+        prompt: &dyn Prompt,
+        event: Event,
+    ) -> Result<Option<Signal>> {
+        #[cfg(all(unix, feature = "suspend"))]
+        self.handle_suspend_signals(prompt)?;
+
+        match event {
+            Event::Resize(x, y) => {
+                self.handle_terminal_events(prompt, vec![ReedlineEvent::Resize(x, y)], vec![], true)
+            }
+            event => self.handle_terminal_events(
+                prompt,
+                vec![],
+                ReedlineRawEvent::convert_from(event).into_iter().collect(),
+                true,
+            ),
+        }
+    }
+
+    /// Restore the terminal after the line started with [`Reedline::begin_line`] ended
+    pub fn end_line(&mut self) -> Result<()> {
+        #[cfg(all(unix, feature = "suspend"))]
+        self.suspend.exit();
+        self.leave_terminal()
+    }
+
+    /// Enter the raw mode and enable the terminal extensions
+    fn enter_terminal(&mut self) -> Result<()> {
         terminal::enable_raw_mode()?;
         self.bracketed_paste.enter();
         self.kitty_protocol.enter();
+        self.mouse_capture.enter();
+        Ok(())
+    }
 
-        let result = self.read_line_helper(prompt);
-
+    /// Restore the cursor shape, the cooked mode and disable the terminal extensions
+    fn leave_terminal(&mut self) -> Result<()> {
+        self.painter.restore_cursor_shape()?;
         self.bracketed_paste.exit();
         self.kitty_protocol.exit();
-        terminal::disable_raw_mode()?;
-        result
+        self.mouse_capture.exit();
+        terminal::disable_raw_mode()
+    }
+
+    /// Stop the process with the terminal restored, painting the prompt again once resumed
+    #[cfg(all(unix, feature = "suspend"))]
+    fn suspend(&mut self) -> Result<()> {
+        // The shell reports the stopped job below the input
+        self.painter.move_cursor_to_end()?;
+        self.leave_terminal()?;
+        let stopped = self.suspend.stop_process();
+        self.enter_terminal()?;
+        stopped?;
+
+        // The shell wrote over the screen in the meantime
+        self.painter.initialize_prompt_position(None)
+    }
+
+    /// Suspend on a `SIGTSTP`, and re-enter the raw mode after a `SIGCONT`
+    #[cfg(all(unix, feature = "suspend"))]
+    fn handle_suspend_signals(&mut self, prompt: &dyn Prompt) -> Result<()> {
+        if self.suspend.take_stopped() {
+            self.suspend()?;
+        } else if self.suspend.take_resumed() {
+            // Stopped by a `SIGSTOP`, the shell may have restored the cooked mode
+            self.enter_terminal()?;
+            self.painter.initialize_prompt_position(None)?;
+        } else {
+            return Ok(());
+        }
+        self.repaint(prompt)
+    }
+
+    /// Queue events to be handled as if they were typed at the start of the next
+    /// [`Reedline::read_line`] or [`Reedline::begin_line`], before any input
+    ///
+    /// Lets an application script the input, e.g. to insert some text or replay a macro.
+    pub fn play_events(&mut self, events: &[ReedlineEvent]) {
+        self.scripted_events.extend_from_slice(events);
+    }
+
+    /// Start the next [`Reedline::read_line`] or [`Reedline::begin_line`] with `buffer` already
+    /// typed and the cursor at the byte offset `cursor`, e.g. to edit a previous command
+    ///
+    /// The buffer is painted with its highlighting and hint right away, then edited like it was
+    /// typed. It only seeds the next line, the lines after it start empty again.
+    ///
+    /// Returns an error if `cursor` isn't on a grapheme boundary of `buffer`.
+    pub fn set_buffer(&mut self, buffer: String, cursor: usize) -> crate::Result<()> {
+        let mut line_buffer = LineBuffer::from(buffer.as_str());
+        line_buffer.set_insertion_point(cursor);
+        if !line_buffer.is_valid() {
+            return Err(ReedlineError(ReedlineErrorVariants::InvalidCursorPosition(
+                cursor,
+            )));
+        }
+        self.seeded_buffer = Some(line_buffer);
+        Ok(())
+    }
+
+    /// Replace the buffer with the one seeded by [`Reedline::set_buffer`], if any
+    fn take_seeded_buffer(&mut self) {
+        if let Some(line_buffer) = self.seeded_buffer.take() {
+            self.editor
+                .set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        }
     }
 
     /// Returns the current insertion point of the input buffer.
@@ -649,6 +1411,15 @@ impl Reedline {
         self.editor.get_buffer()
     }
 
+    /// Returns the buffer of the last line if it was aborted with [`Signal::CtrlC`], until the
+    /// next line starts
+    ///
+    /// The aborted text is still cleared and isn't saved to the history. The host can log it,
+    /// or offer to recall it with [`Reedline::set_buffer`].
+    pub fn last_interrupted_buffer(&self) -> Option<&str> {
+        self.interrupted_buffer.as_deref()
+    }
+
     /// Writes `msg` to the terminal with a following carriage return and newline
     fn print_line(&mut self, msg: &str) -> Result<()> {
         self.painter.paint_line(msg)
@@ -669,9 +1440,8 @@ impl Reedline {
         Ok(())
     }
 
-    /// Helper implementing the logic for [`Reedline::read_line()`] to be wrapped
-    /// in a `raw_mode` context.
-    fn read_line_helper(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
+    /// Paint the prompt of a new line and handle the events queued with [`Reedline::play_events`]
+    fn start_editing(&mut self, prompt: &dyn Prompt) -> Result<Option<Signal>> {
         self.painter
             .initialize_prompt_position(self.suspended_state.as_ref())?;
         if self.suspended_state.is_some() {
@@ -680,13 +1450,21 @@ impl Reedline {
             self.suspended_state = None;
         }
         self.hide_hints = false;
+        self.interrupted_buffer = None;
+        self.take_seeded_buffer();
 
         self.repaint(prompt)?;
 
-        let mut crossterm_events: Vec<ReedlineRawEvent> = vec![];
-        let mut reedline_events: Vec<ReedlineEvent> = vec![];
+        let scripted_events = std::mem::take(&mut self.scripted_events);
+        self.handle_terminal_events(prompt, scripted_events, vec![], true)
+    }
 
+    /// Helper implementing the logic for [`Reedline::read_line()`] to be wrapped
+    /// in a `raw_mode` context.
+    fn read_line_helper(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
         loop {
+            let mut crossterm_events: Vec<ReedlineRawEvent> = vec![];
+            let mut reedline_events: Vec<ReedlineEvent> = vec![];
             let mut paste_enter_state = false;
 
             #[cfg(feature = "external_printer")]
@@ -704,42 +1482,75 @@ impl Reedline {
                 }
             }
 
-            let mut latest_resize = None;
-            loop {
-                match event::read()? {
-                    Event::Resize(x, y) => {
-                        latest_resize = Some((x, y));
-                    }
-                    enter @ Event::Key(KeyEvent {
-                        code: KeyCode::Enter,
-                        modifiers: KeyModifiers::NONE,
-                        ..
-                    }) => {
-                        let enter = ReedlineRawEvent::convert_from(enter);
-                        if let Some(enter) = enter {
-                            crossterm_events.push(enter);
-                            // Break early to check if the input is complete and
-                            // can be send to the hosting application. If
-                            // multiple complete entries are submitted, events
-                            // are still in the crossterm queue for us to
-                            // process.
-                            paste_enter_state = crossterm_events.len() > EVENTS_THRESHOLD;
-                            break;
+            // Completions computed in the background are polled until the next event arrives
+            while self.completions_pending()
+                && !self.sequence_timed_out()
+                && !event::poll(Duration::from_millis(POLL_WAIT))?
+            {
+                if self.poll_completions() {
+                    self.repaint(prompt)?;
+                }
+            }
+
+            // The keys of a partially typed key sequence are handled on their own
+            // if the next key doesn't arrive in time
+            let flush_sequence = match self.edit_mode.pending_sequence_timeout() {
+                Some(timeout) => !event::poll(timeout)?,
+                None => false,
+            };
+
+            // The signals are caught while waiting for the next event
+            #[cfg(all(unix, feature = "suspend"))]
+            if self.suspend.is_active() && !flush_sequence {
+                self.handle_suspend_signals(prompt)?;
+                while !event::poll(Duration::from_millis(SIGNAL_POLL_WAIT))? {
+                    self.handle_suspend_signals(prompt)?;
+                }
+            }
+
+            let mut latest_resize = None;
+            if flush_sequence {
+                reedline_events.push(self.edit_mode.flush_pending_sequence());
+            } else {
+                loop {
+                    match event::read()? {
+                        Event::Resize(x, y) => {
+                            latest_resize = Some((x, y));
                         }
-                    }
-                    x => {
-                        let raw_event = ReedlineRawEvent::convert_from(x);
-                        if let Some(evt) = raw_event {
-                            crossterm_events.push(evt);
+                        enter @ Event::Key(KeyEvent {
+                            code: KeyCode::Enter,
+                            modifiers: KeyModifiers::NONE,
+                            ..
+                        }) => {
+                            let enter = ReedlineRawEvent::convert_from(enter);
+                            if let Some(enter) = enter {
+                                crossterm_events.push(enter);
+                                // Break early to check if the input is complete and
+                                // can be send to the hosting application. If
+                                // multiple complete entries are submitted, events
+                                // are still in the crossterm queue for us to
+                                // process.
+                                // The buffer is only repainted, and highlighted, once the
+                                // pasted lines still queued are handled
+                                paste_enter_state = crossterm_events.len() > EVENTS_THRESHOLD
+                                    || event::poll(Duration::ZERO)?;
+                                break;
+                            }
+                        }
+                        x => {
+                            let raw_event = ReedlineRawEvent::convert_from(x);
+                            if let Some(evt) = raw_event {
+                                crossterm_events.push(evt);
+                            }
                         }
                     }
-                }
 
-                // There could be multiple events queued up!
-                // pasting text, resizes, blocking this thread (e.g. during debugging)
-                // We should be able to handle all of them as quickly as possible without causing unnecessary output steps.
-                if !event::poll(Duration::from_millis(POLL_WAIT))? {
-                    break;
+                    // There could be multiple events queued up!
+                    // pasting text, resizes, blocking this thread (e.g. during debugging)
+                    // We should be able to handle all of them as quickly as possible without causing unnecessary output steps.
+                    if !event::poll(Duration::from_millis(POLL_WAIT))? {
+                        break;
+                    }
                 }
             }
 
@@ -747,58 +1558,86 @@ impl Reedline {
                 reedline_events.push(ReedlineEvent::Resize(x, y));
             }
 
-            // Accelerate pasted text by fusing `EditCommand`s
-            //
-            // (Text should only be `EditCommand::InsertChar`s)
-            let mut last_edit_commands = None;
-            for event in crossterm_events.drain(..) {
-                match (&mut last_edit_commands, self.edit_mode.parse_event(event)) {
-                    (None, ReedlineEvent::Edit(ec)) => {
-                        last_edit_commands = Some(ec);
-                    }
-                    (None, other_event) => {
-                        reedline_events.push(other_event);
-                    }
-                    (Some(ref mut last_ecs), ReedlineEvent::Edit(ec)) => {
-                        last_ecs.extend(ec);
-                    }
-                    (ref mut a @ Some(_), other_event) => {
-                        reedline_events.push(ReedlineEvent::Edit(a.take().unwrap()));
+            if let Some(signal) = self.handle_terminal_events(
+                prompt,
+                reedline_events,
+                crossterm_events,
+                !paste_enter_state,
+            )? {
+                return Ok(signal);
+            }
+        }
+    }
 
-                        reedline_events.push(other_event);
-                    }
+    /// Handle `reedline_events` followed by the events parsed from `crossterm_events`,
+    /// repainting after each of them if `repaint` is set
+    fn handle_terminal_events(
+        &mut self,
+        prompt: &dyn Prompt,
+        mut reedline_events: Vec<ReedlineEvent>,
+        crossterm_events: Vec<ReedlineRawEvent>,
+        repaint: bool,
+    ) -> Result<Option<Signal>> {
+        // Accelerate pasted text by fusing `EditCommand`s
+        //
+        // (Text should only be `EditCommand::InsertChar`s)
+        let mut last_edit_commands = None;
+        for event in crossterm_events {
+            match (&mut last_edit_commands, self.edit_mode.parse_event(event)) {
+                (None, ReedlineEvent::Edit(ec)) => {
+                    last_edit_commands = Some(ec);
+                }
+                (None, other_event) => {
+                    reedline_events.push(other_event);
+                }
+                (Some(ref mut last_ecs), ReedlineEvent::Edit(ec)) => {
+                    last_ecs.extend(ec);
+                }
+                (ref mut a @ Some(_), other_event) => {
+                    reedline_events.push(ReedlineEvent::Edit(a.take().unwrap()));
+
+                    reedline_events.push(other_event);
                 }
             }
-            if let Some(ec) = last_edit_commands {
-                reedline_events.push(ReedlineEvent::Edit(ec));
-            }
+        }
+        if let Some(ec) = last_edit_commands {
+            reedline_events.push(ReedlineEvent::Edit(ec));
+        }
 
-            for event in reedline_events.drain(..) {
-                match self.handle_event(prompt, event)? {
-                    EventStatus::Exits(signal) => {
-                        // Check if we are merely suspended (to process an ExecuteHostCommand event)
-                        // or if we're about to quit the editor.
-                        if self.suspended_state.is_none() {
-                            // We are about to quit the editor, move the cursor below the input
-                            // area, for external commands or new read_line call
-                            self.painter.move_cursor_to_end()?;
-                        }
-                        return Ok(signal);
-                    }
-                    EventStatus::Handled => {
-                        if !paste_enter_state {
-                            self.repaint(prompt)?;
-                        }
+        for event in reedline_events {
+            match self.handle_event(prompt, event)? {
+                EventStatus::Exits(signal) => {
+                    // Check if we are merely suspended (to process an ExecuteHostCommand event)
+                    // or if we're about to quit the editor.
+                    if self.suspended_state.is_none() {
+                        // We are about to quit the editor, move the cursor below the input
+                        // area, for external commands or new read_line call
+                        self.painter.move_cursor_to_end()?;
                     }
-                    EventStatus::Inapplicable => {
-                        // Nothing changed, no need to repaint
+                    return Ok(Some(signal));
+                }
+                EventStatus::Handled => {
+                    if repaint {
+                        self.repaint(prompt)?;
                     }
                 }
+                EventStatus::Inapplicable => {
+                    // Nothing changed, no need to repaint
+                }
             }
         }
+
+        Ok(None)
     }
 
     fn handle_event(&mut self, prompt: &dyn Prompt, event: ReedlineEvent) -> Result<EventStatus> {
+        // The events replayed by a macro are not recorded again, only the event replaying it
+        if let Some((_, events)) = &mut self.recording_macro {
+            if self.playing_macros.is_empty() && event != ReedlineEvent::StopMacro {
+                events.push(event.clone());
+            }
+        }
+
         if self.input_mode == InputMode::HistorySearch {
             self.handle_history_search_event(event)
         } else {
@@ -834,6 +1673,7 @@ impl Reedline {
             }
             ReedlineEvent::CtrlC => {
                 self.input_mode = InputMode::Regular;
+                self.interrupted_buffer = Some(self.editor.get_buffer().to_string());
                 Ok(EventStatus::Exits(Signal::CtrlC))
             }
             ReedlineEvent::ClearScreen => {
@@ -848,7 +1688,7 @@ impl Reedline {
             | ReedlineEvent::HistoryHintComplete
             | ReedlineEvent::Submit
             | ReedlineEvent::SubmitOrNewline => {
-                if let Some(string) = self.history_cursor.string_at_cursor() {
+                if let Some(string) = self.history_search_match.take() {
                     self.editor
                         .set_buffer(string, UndoBehavior::CreateUndoPoint);
                 }
@@ -862,24 +1702,50 @@ impl Reedline {
             }
             ReedlineEvent::Edit(commands) => {
                 self.run_history_commands(&commands);
+                self.ring_bell_if_search_fails(&commands)?;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Paste(text) => {
+                let commands: Vec<_> = pasted_text(&text)
+                    .chars()
+                    .map(EditCommand::InsertChar)
+                    .collect();
+                self.run_history_commands(&commands);
+                self.ring_bell_if_search_fails(&commands)?;
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::Mouse => Ok(EventStatus::Handled),
             ReedlineEvent::Resize(width, height) => {
+                // The lines are wrapped differently: repaint everything from the prompt start
                 self.painter.handle_resize(width, height);
-                Ok(EventStatus::Inapplicable)
+                Ok(EventStatus::Handled)
             }
             ReedlineEvent::Repaint => {
                 // A handled Event causes a repaint
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::PreviousHistory | ReedlineEvent::Up | ReedlineEvent::SearchHistory => {
+            ReedlineEvent::PreviousHistory | ReedlineEvent::Up | ReedlineEvent::HistoryPrefixUp => {
                 self.history_cursor
                     .back(self.history.as_ref())
                     .expect("todo: error handling");
+                self.update_history_search_match();
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::NextHistory | ReedlineEvent::Down => {
+            ReedlineEvent::SearchHistory => {
+                self.history_cursor
+                    .back_wrapping(self.history.as_ref())
+                    .expect("todo: error handling");
+                self.update_history_search_match();
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::SearchHistoryForward => {
+                self.history_cursor
+                    .forward_wrapping(self.history.as_ref())
+                    .expect("todo: error handling");
+                self.update_history_search_match();
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::NextHistory | ReedlineEvent::Down | ReedlineEvent::HistoryPrefixDown => {
                 self.history_cursor
                     .forward(self.history.as_ref())
                     .expect("todo: error handling");
@@ -889,9 +1755,12 @@ impl Reedline {
                         .back(self.history.as_ref())
                         .expect("todo: error handling");
                 }
+                self.update_history_search_match();
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::Esc => {
+                // The line being edited before the search is left untouched
+                self.history_search_match = None;
                 self.input_mode = InputMode::Regular;
                 Ok(EventStatus::Handled)
             }
@@ -902,7 +1771,21 @@ impl Reedline {
             | ReedlineEvent::None
             | ReedlineEvent::HistoryHintWordComplete
             | ReedlineEvent::OpenEditor
+            | ReedlineEvent::RunCommand(_)
+            | ReedlineEvent::Suspend
+            | ReedlineEvent::InsertNewline
+            | ReedlineEvent::RecordMacro(_)
+            | ReedlineEvent::StopMacro
+            | ReedlineEvent::PlayMacro(_)
+            | ReedlineEvent::PlayLastMacro
+            | ReedlineEvent::UniversalArgument
+            | ReedlineEvent::DigitArgument(_)
             | ReedlineEvent::Menu(_)
+            | ReedlineEvent::AcceptTopCompletion(_)
+            | ReedlineEvent::AcceptAndContinue
+            | ReedlineEvent::RepeatLastCompletion
+            | ReedlineEvent::MouseClick { .. }
+            | ReedlineEvent::MouseDrag { .. }
             | ReedlineEvent::MenuNext
             | ReedlineEvent::MenuPrevious
             | ReedlineEvent::MenuUp
@@ -921,30 +1804,38 @@ impl Reedline {
     ) -> io::Result<EventStatus> {
         match event {
             ReedlineEvent::Menu(name) => {
-                if self.active_menu().is_none() {
+                if self.completer_enabled && self.active_menu().is_none() {
                     if let Some(menu) = self.menus.iter_mut().find(|menu| menu.name() == name) {
                         menu.menu_event(MenuEvent::Activate(self.quick_completions));
 
-                        if self.quick_completions && menu.can_quick_complete() {
+                        let common_prefix =
+                            self.completion_strategy == CompletionStrategy::LongestCommonPrefix;
+                        let mut values_updated = false;
+                        if (self.quick_completions || common_prefix) && menu.can_quick_complete() {
                             menu.update_values(
                                 &mut self.editor,
                                 self.completer.as_mut(),
                                 self.history.as_ref(),
                             );
+                            values_updated = true;
 
                             if menu.get_values().len() == 1 {
                                 return self.handle_editor_event(prompt, ReedlineEvent::Enter);
                             }
                         }
 
-                        if self.partial_completions
+                        if (self.partial_completions || common_prefix)
                             && menu.can_partially_complete(
-                                self.quick_completions,
+                                values_updated,
                                 &mut self.editor,
                                 self.completer.as_mut(),
                                 self.history.as_ref(),
                             )
                         {
+                            // The menu is only opened once nothing more can be completed
+                            if common_prefix {
+                                menu.menu_event(MenuEvent::Deactivate);
+                            }
                             return Ok(EventStatus::Handled);
                         }
 
@@ -953,10 +1844,66 @@ impl Reedline {
                 }
                 Ok(EventStatus::Inapplicable)
             }
+            ReedlineEvent::AcceptTopCompletion(name) => {
+                if self.completer_enabled && self.active_menu().is_none() {
+                    if let Some(menu) = self.menus.iter_mut().find(|menu| menu.name() == name) {
+                        menu.menu_event(MenuEvent::Activate(false));
+                        menu.update_values(
+                            &mut self.editor,
+                            self.completer.as_mut(),
+                            self.history.as_ref(),
+                        );
+
+                        // The top suggestion is the selected one, and is replaced like the
+                        // menu does. Otherwise the menu stays open
+                        let suggestions = menu.get_values().len();
+                        if suggestions == 1 || (self.accept_best_completion && suggestions > 1) {
+                            return self.handle_editor_event(prompt, ReedlineEvent::Enter);
+                        }
+                        return Ok(EventStatus::Handled);
+                    }
+                }
+                Ok(EventStatus::Inapplicable)
+            }
+            ReedlineEvent::AcceptAndContinue => {
+                let Some(menu) = self.menus.iter_mut().find(|menu| menu.is_active()) else {
+                    return Ok(EventStatus::Inapplicable);
+                };
+
+                let continues = menu.get_selected_value().map_or(false, |suggestion| {
+                    suggestion.continue_completion && !suggestion.append_whitespace
+                });
+                self.last_completion = AcceptedCompletion::selected(menu, &self.editor);
+                menu.replace_in_buffer(&mut self.editor);
+                menu.menu_event(MenuEvent::Deactivate);
+
+                if continues {
+                    // Opened again like with its key, completing at the new cursor position
+                    let name = menu.name().to_string();
+                    self.handle_editor_event(prompt, ReedlineEvent::Menu(name))?;
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::RepeatLastCompletion => {
+                let suggestion = self
+                    .last_completion
+                    .as_ref()
+                    .and_then(|completion| completion.at_cursor(&self.editor));
+                match suggestion {
+                    Some(suggestion) => {
+                        self.deactivate_menus();
+                        replace_in_buffer(Some(suggestion), &mut self.editor);
+                        Ok(EventStatus::Handled)
+                    }
+                    None => Ok(EventStatus::Inapplicable),
+                }
+            }
             ReedlineEvent::MenuNext => match self.active_menu() {
                 None => Ok(EventStatus::Inapplicable),
                 Some(menu) => {
-                    if menu.get_values().len() == 1 && menu.can_quick_complete() {
+                    if (menu.get_values().len() == 1 && menu.can_quick_complete())
+                        || menu.accepts_on_next()
+                    {
                         self.handle_editor_event(prompt, ReedlineEvent::Enter)
                     } else {
                         menu.menu_event(MenuEvent::NextElement);
@@ -1016,7 +1963,9 @@ impl Reedline {
             ReedlineEvent::HistoryHintComplete => {
                 if let Some(hinter) = self.hinter.as_mut() {
                     let current_hint = hinter.complete_hint();
+                    let acceptable = hinter.is_hint_acceptable();
                     if self.hints_active()
+                        && acceptable
                         && self.editor.is_cursor_at_buffer_end()
                         && !current_hint.is_empty()
                         && self.active_menu().is_none()
@@ -1030,7 +1979,9 @@ impl Reedline {
             ReedlineEvent::HistoryHintWordComplete => {
                 if let Some(hinter) = self.hinter.as_mut() {
                     let current_hint_part = hinter.next_hint_token();
+                    let acceptable = hinter.is_hint_acceptable();
                     if self.hints_active()
+                        && acceptable
                         && self.editor.is_cursor_at_buffer_end()
                         && !current_hint_part.is_empty()
                         && self.active_menu().is_none()
@@ -1043,8 +1994,20 @@ impl Reedline {
             }
             ReedlineEvent::Esc => {
                 self.deactivate_menus();
+                self.editor.clear_secondary_cursors();
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::MouseClick { column, row } => {
+                if let Some((menu_row, available_lines)) = self.painter.menu_row_at(row) {
+                    if let Some(menu) = self.active_menu() {
+                        if menu.select_at(menu_row, column, available_lines) {
+                            return Ok(EventStatus::Handled);
+                        }
+                    }
+                }
+                Ok(self.move_to_mouse(column, row, false))
+            }
+            ReedlineEvent::MouseDrag { column, row } => Ok(self.move_to_mouse(column, row, true)),
             ReedlineEvent::CtrlD => {
                 if self.editor.is_empty() {
                     self.editor.reset_undo_stack();
@@ -1056,17 +2019,16 @@ impl Reedline {
             }
             ReedlineEvent::CtrlC => {
                 self.deactivate_menus();
+                self.interrupted_buffer = Some(self.editor.get_buffer().to_string());
                 self.run_edit_commands(&[EditCommand::Clear]);
                 self.editor.reset_undo_stack();
                 Ok(EventStatus::Exits(Signal::CtrlC))
             }
             ReedlineEvent::ClearScreen => {
-                self.deactivate_menus();
                 self.painter.clear_screen()?;
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::ClearScrollback => {
-                self.deactivate_menus();
                 self.painter.clear_scrollback()?;
                 Ok(EventStatus::Handled)
             }
@@ -1075,6 +2037,7 @@ impl Reedline {
             {
                 for menu in self.menus.iter_mut() {
                     if menu.is_active() {
+                        self.last_completion = AcceptedCompletion::selected(menu, &self.editor);
                         menu.replace_in_buffer(&mut self.editor);
                         menu.menu_event(MenuEvent::Deactivate);
 
@@ -1089,16 +2052,28 @@ impl Reedline {
                     return self.handle_editor_event(prompt, event);
                 }
 
-                let buffer = self.editor.get_buffer().to_string();
-                match self.validator.as_mut().map(|v| v.validate(&buffer)) {
-                    None | Some(ValidationResult::Complete) => Ok(self.submit_buffer(prompt)?),
-                    Some(ValidationResult::Incomplete) => {
-                        self.run_edit_commands(&[EditCommand::InsertNewline]);
-
-                        Ok(EventStatus::Handled)
+                let submit = match self.enter_behavior {
+                    EnterBehavior::Validate => {
+                        let buffer = self.editor.get_buffer().to_string();
+                        !matches!(
+                            self.validator.as_mut().map(|v| v.validate(&buffer)),
+                            Some(ValidationResult::Incomplete)
+                        )
                     }
+                    EnterBehavior::Submit => true,
+                    EnterBehavior::Newline => false,
+                };
+                if submit {
+                    Ok(self.submit_buffer(prompt)?)
+                } else {
+                    self.run_edit_commands(&[EditCommand::InsertNewline]);
+                    Ok(EventStatus::Handled)
                 }
             }
+            ReedlineEvent::InsertNewline => self.handle_editor_event(
+                prompt,
+                ReedlineEvent::Edit(vec![EditCommand::InsertNewline]),
+            ),
             ReedlineEvent::Submit => {
                 #[cfg(feature = "bashisms")]
                 if let Some(event) = self.parse_bang_command() {
@@ -1131,6 +2106,9 @@ impl Reedline {
                 Ok(EventStatus::Exits(Signal::Success(host_command)))
             }
             ReedlineEvent::Edit(commands) => {
+                if commands.contains(&EditCommand::YankPop) && !self.editor.can_yank_pop() {
+                    self.ring_bell()?;
+                }
                 self.run_edit_commands(&commands);
                 if let Some(menu) = self.menus.iter_mut().find(|men| men.is_active()) {
                     if self.quick_completions && menu.can_quick_complete() {
@@ -1173,10 +2151,48 @@ impl Reedline {
                 }
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::Paste(text) => {
+                // Completions are not updated for every pasted character, the active menu
+                // would show stale values
+                self.deactivate_menus();
+                self.run_edit_commands(&[EditCommand::InsertString(pasted_text(&text))]);
+                Ok(EventStatus::Handled)
+            }
             ReedlineEvent::OpenEditor => self.open_editor().map(|_| EventStatus::Handled),
+            #[cfg(all(unix, feature = "suspend"))]
+            ReedlineEvent::Suspend => self.suspend().map(|_| EventStatus::Handled),
+            #[cfg(not(all(unix, feature = "suspend")))]
+            ReedlineEvent::Suspend => Ok(EventStatus::Inapplicable),
+            ReedlineEvent::RunCommand(name) => match self.commands.get(&name) {
+                Some(command) => {
+                    let mut line_buffer = self.editor.line_buffer().clone();
+                    command(&mut line_buffer);
+                    // The menu values would be stale
+                    self.deactivate_menus();
+                    self.editor
+                        .set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+                    Ok(EventStatus::Handled)
+                }
+                None => Ok(EventStatus::Inapplicable),
+            },
+            ReedlineEvent::RecordMacro(register) => {
+                self.stop_macro();
+                self.recording_macro = Some((register, Vec::new()));
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::StopMacro => {
+                self.stop_macro();
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::PlayMacro(register) => self.play_macro(prompt, register),
+            ReedlineEvent::PlayLastMacro => match self.last_macro {
+                Some(register) => self.play_macro(prompt, register),
+                None => Ok(EventStatus::Inapplicable),
+            },
             ReedlineEvent::Resize(width, height) => {
+                // The lines are wrapped differently: repaint everything from the prompt start
                 self.painter.handle_resize(width, height);
-                Ok(EventStatus::Inapplicable)
+                Ok(EventStatus::Handled)
             }
             ReedlineEvent::Repaint => {
                 // A handled Event causes a repaint
@@ -1190,6 +2206,14 @@ impl Reedline {
                 self.next_history();
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::HistoryPrefixUp => {
+                self.browse_history_by_prefix(true);
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::HistoryPrefixDown => {
+                self.browse_history_by_prefix(false);
+                Ok(EventStatus::Handled)
+            }
             ReedlineEvent::Up => {
                 self.up_command();
                 Ok(EventStatus::Handled)
@@ -1199,14 +2223,14 @@ impl Reedline {
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::Left => {
-                self.run_edit_commands(&[EditCommand::MoveLeft { select: false }]);
+                self.run_edge_motion(EditCommand::MoveLeft { select: false })?;
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::Right => {
-                self.run_edit_commands(&[EditCommand::MoveRight { select: false }]);
+                self.run_edge_motion(EditCommand::MoveRight { select: false })?;
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::SearchHistory => {
+            ReedlineEvent::SearchHistory | ReedlineEvent::SearchHistoryForward => {
                 self.enter_history_search();
                 Ok(EventStatus::Handled)
             }
@@ -1245,10 +2269,33 @@ impl Reedline {
                 // Exhausting the event handlers is still considered handled
                 Ok(EventStatus::Inapplicable)
             }
+            // Only meaningful to the edit mode
+            ReedlineEvent::UniversalArgument | ReedlineEvent::DigitArgument(_) => {
+                Ok(EventStatus::Inapplicable)
+            }
             ReedlineEvent::None | ReedlineEvent::Mouse => Ok(EventStatus::Inapplicable),
         }
     }
 
+    /// Move the cursor to the character of the buffer painted at `column` and `row` of the
+    /// screen, if there is one
+    fn move_to_mouse(&mut self, column: u16, row: u16, select: bool) -> EventStatus {
+        let position = self.painter.buffer_row_at(row).and_then(|buffer_row| {
+            self.editor.visual_layout().position_at(
+                self.editor.get_buffer(),
+                buffer_row,
+                column.into(),
+            )
+        });
+        match position {
+            Some(position) => {
+                self.run_edit_commands(&[EditCommand::MoveToPosition { position, select }]);
+                EventStatus::Handled
+            }
+            None => EventStatus::Inapplicable,
+        }
+    }
+
     fn active_menu(&mut self) -> Option<&mut ReedlineMenu> {
         self.menus.iter_mut().find(|menu| menu.is_active())
     }
@@ -1265,10 +2312,12 @@ impl Reedline {
         }
         if self.input_mode != InputMode::HistoryTraversal {
             self.input_mode = InputMode::HistoryTraversal;
+            self.history_prefix_line = None;
             self.history_cursor = HistoryCursor::new(
                 self.get_history_navigation_based_on_line_buffer(),
                 self.get_history_session_id(),
-            );
+            )
+            .with_cwd_prefix(self.history_cwd());
 
             if self.history_excluded_item.is_some() {
                 self.history_cursor_on_excluded = true;
@@ -1292,10 +2341,12 @@ impl Reedline {
     fn next_history(&mut self) {
         if self.input_mode != InputMode::HistoryTraversal {
             self.input_mode = InputMode::HistoryTraversal;
+            self.history_prefix_line = None;
             self.history_cursor = HistoryCursor::new(
                 self.get_history_navigation_based_on_line_buffer(),
                 self.get_history_session_id(),
-            );
+            )
+            .with_cwd_prefix(self.history_cwd());
         }
 
         if self.history_cursor_on_excluded {
@@ -1323,6 +2374,54 @@ impl Reedline {
             .update_undo_state(UndoBehavior::HistoryNavigation)
     }
 
+    /// Move to the previous or next history entry starting with the text before the cursor,
+    /// for [`ReedlineEvent::HistoryPrefixUp`] and [`ReedlineEvent::HistoryPrefixDown`]
+    fn browse_history_by_prefix(&mut self, backward: bool) {
+        if self.input_mode != InputMode::HistoryTraversal || self.history_prefix_line.is_none() {
+            let prefix = self.editor.get_buffer()[..self.editor.insertion_point()].to_string();
+            self.input_mode = InputMode::HistoryTraversal;
+            self.history_cursor_on_excluded = false;
+            self.history_cursor = HistoryCursor::new(
+                HistoryNavigationQuery::PrefixSearch(prefix),
+                self.get_history_session_id(),
+            )
+            .with_cwd_prefix(self.history_cwd());
+            self.history_prefix_line = Some(self.editor.line_buffer().clone());
+        }
+
+        let prefix_len = match self.history_cursor.get_navigation() {
+            HistoryNavigationQuery::PrefixSearch(prefix) => prefix.len(),
+            _ => 0,
+        };
+        if backward {
+            self.history_cursor.back(self.history.as_ref())
+        } else {
+            self.history_cursor.forward(self.history.as_ref())
+        }
+        .expect("todo: error handling");
+
+        match self.history_cursor.string_at_cursor() {
+            Some(command_line) => {
+                self.editor
+                    .set_buffer(command_line, UndoBehavior::HistoryNavigation);
+                self.editor.run_edit_command(&EditCommand::MoveToPosition {
+                    position: prefix_len,
+                    select: false,
+                });
+                self.editor
+                    .update_undo_state(UndoBehavior::HistoryNavigation);
+            }
+            // No entry has the prefix, or the browsing went past the newest one
+            None => {
+                self.input_mode = InputMode::Regular;
+                if let Some(line_buffer) = self.history_prefix_line.take() {
+                    self.editor
+                        .set_line_buffer(line_buffer, UndoBehavior::HistoryNavigation);
+                }
+            }
+        }
+    }
+
     /// Enable the search and navigation through the history from the line buffer prompt
     ///
     /// Enables either prefix search with output in the line buffer or simple traversal
@@ -1344,6 +2443,41 @@ impl Reedline {
         }
     }
 
+    /// Store the macro being recorded, if any, in its register
+    fn stop_macro(&mut self) {
+        if let Some((register, events)) = self.recording_macro.take() {
+            self.macros.insert(register, events);
+            self.last_macro = Some(register);
+        }
+    }
+
+    /// Handle the events of the macro in `register` again
+    fn play_macro(&mut self, prompt: &dyn Prompt, register: char) -> io::Result<EventStatus> {
+        if self.playing_macros.contains(&register) {
+            return Ok(EventStatus::Inapplicable);
+        }
+        let Some(events) = self.macros.get(&register).cloned() else {
+            return Ok(EventStatus::Inapplicable);
+        };
+        self.last_macro = Some(register);
+
+        self.playing_macros.push(register);
+        let mut status = Ok(EventStatus::Inapplicable);
+        for event in events {
+            match self.handle_event(prompt, event) {
+                Ok(EventStatus::Handled) => status = Ok(EventStatus::Handled),
+                Ok(EventStatus::Inapplicable) => {}
+                exits_or_error => {
+                    status = exits_or_error;
+                    break;
+                }
+            }
+        }
+        self.playing_macros.pop();
+
+        status
+    }
+
     /// Switch into reverse history search mode
     ///
     /// This mode uses a separate prompt and handles keybindings slightly differently!
@@ -1352,9 +2486,58 @@ impl Reedline {
             HistoryNavigationQuery::SubstringSearch("".to_string()),
             self.get_history_session_id(),
         );
+        self.history_search_match = None;
         self.input_mode = InputMode::HistorySearch;
     }
 
+    /// Signals an action that can't be done with the [`Bell`]
+    fn ring_bell(&mut self) -> Result<()> {
+        match &self.bell {
+            Bell::Silent => Ok(()),
+            Bell::Terminal => self.painter.ring_bell(),
+            Bell::Custom(callback) => {
+                callback();
+                Ok(())
+            }
+        }
+    }
+
+    /// Runs a motion, ringing the bell if the cursor is at the edge of the buffer already
+    fn run_edge_motion(&mut self, command: EditCommand) -> Result<()> {
+        let insertion_point = self.editor.insertion_point();
+        self.run_edit_commands(&[command]);
+        if self.editor.insertion_point() == insertion_point {
+            self.ring_bell()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the history search finds nothing for its search string
+    fn history_search_failing(&self) -> bool {
+        matches!(
+            self.history_cursor.get_navigation(),
+            HistoryNavigationQuery::SubstringSearch(substring) if !substring.is_empty()
+        ) && self.history_cursor.string_at_cursor().is_none()
+    }
+
+    /// Rings the bell if the text typed by `commands` in the history search makes it fail
+    fn ring_bell_if_search_fails(&mut self, commands: &[EditCommand]) -> Result<()> {
+        let typed = commands
+            .iter()
+            .any(|command| matches!(command, EditCommand::InsertChar(_)));
+        if typed && self.history_search_failing() {
+            self.ring_bell()?;
+        }
+        Ok(())
+    }
+
+    /// Keep the match of the reverse history search, unless the search fails
+    fn update_history_search_match(&mut self) {
+        if let Some(string) = self.history_cursor.string_at_cursor() {
+            self.history_search_match = Some(string);
+        }
+    }
+
     /// Dispatches the applicable [`EditCommand`] actions for editing the history search string.
     ///
     /// Only modifies internal state, does not perform regular output!
@@ -1399,6 +2582,7 @@ impl Reedline {
                 }
             }
         }
+        self.update_history_search_match();
     }
 
     /// Set the buffer contents for history traversal/search in the standard prompt
@@ -1459,6 +2643,35 @@ impl Reedline {
         }
     }
 
+    /// Insert `text` at the cursor of the line started with [`Reedline::begin_line`] and repaint
+    /// it, e.g. for a path dropped onto the application from outside of the event loop
+    ///
+    /// The text is inserted like a [`ReedlineEvent::Paste`]: as a single undo step, with its
+    /// line endings turned into newlines and without any auto-paired character. An open menu is
+    /// closed first, and a history search is left with the line being edited before it. In Vi
+    /// normal or visual mode, the edit mode switches to insert mode and the text is appended after
+    /// the cursor, like with `a`.
+    ///
+    /// The line isn't submitted, even if the text ends with a line ending. Use
+    /// [`Reedline::play_events`] to insert text at the start of the next line instead.
+    pub fn insert_text(&mut self, prompt: &dyn Prompt, text: &str) -> Result<()> {
+        self.insert_at_cursor(text);
+        self.repaint(prompt)
+    }
+
+    /// The edit of [`Reedline::insert_text`], without painting it
+    fn insert_at_cursor(&mut self, text: &str) {
+        if self.input_mode == InputMode::HistorySearch {
+            self.history_search_match = None;
+            self.input_mode = InputMode::Regular;
+        }
+        self.deactivate_menus();
+
+        let mut commands = self.edit_mode.prepare_insertion();
+        commands.push(EditCommand::InsertString(pasted_text(text)));
+        self.run_edit_commands(&commands);
+    }
+
     fn up_command(&mut self) {
         // If we're at the top, then:
         if self.editor.is_cursor_at_first_line() {
@@ -1479,9 +2692,38 @@ impl Reedline {
         }
     }
 
+    /// Whether the next key of a partially typed key sequence didn't arrive in time
+    fn sequence_timed_out(&self) -> bool {
+        self.edit_mode
+            .pending_sequence_timeout()
+            .map_or(false, |timeout| timeout.is_zero())
+    }
+
+    /// Whether the completer of the active menu is computing completions in the background
+    fn completions_pending(&self) -> bool {
+        self.menus
+            .iter()
+            .any(|menu| menu.is_active() && menu.completions_pending(self.completer.as_ref()))
+    }
+
+    /// Polls the completions of the active menu computed in the background.
+    /// Once they are available, the menu values get updated on the next repaint
+    fn poll_completions(&mut self) -> bool {
+        let Some(menu) = self.menus.iter_mut().find(|menu| menu.is_active()) else {
+            return false;
+        };
+
+        if menu.poll_pending_completions(self.completer.as_mut()) {
+            menu.menu_event(MenuEvent::Edit(false));
+            true
+        } else {
+            false
+        }
+    }
+
     /// Checks if hints should be displayed and are able to be completed
     fn hints_active(&self) -> bool {
-        !self.hide_hints && matches!(self.input_mode, InputMode::Regular)
+        self.hinter_enabled && !self.hide_hints && matches!(self.input_mode, InputMode::Regular)
     }
 
     /// Repaint of either the buffer or the parts for reverse history search
@@ -1636,30 +2878,64 @@ impl Reedline {
         }
     }
 
+    /// Edit the buffer in the buffer editor, keeping it if the editor fails or its file can't be
+    /// written or read back
     fn open_editor(&mut self) -> Result<()> {
-        match &mut self.buffer_editor {
-            Some(BufferEditor {
-                ref mut command,
-                temp_file,
-            }) => {
-                {
-                    let mut file = File::create(&temp_file)?;
-                    write!(file, "{}", self.editor.get_buffer())?;
-                }
-                {
-                    let mut child = command.spawn()?;
-                    child.wait()?;
-                }
+        let temporary = self.buffer_editor.is_none();
+        let mut buffer_editor = match self.buffer_editor.take() {
+            Some(buffer_editor) => buffer_editor,
+            None => BufferEditor::from_env(),
+        };
 
-                let res = std::fs::read_to_string(temp_file)?;
-                let res = res.trim_end().to_string();
+        let edited = self.run_buffer_editor(&mut buffer_editor, temporary);
 
-                self.editor.set_buffer(res, UndoBehavior::CreateUndoPoint);
+        // The temporary file holds the buffer, which may contain secrets
+        if temporary {
+            let _ignore = std::fs::remove_file(&buffer_editor.temp_file);
+        } else {
+            self.buffer_editor = Some(buffer_editor);
+        }
 
-                Ok(())
-            }
-            _ => Ok(()),
+        if let Some(edited) = edited? {
+            // Editors end the file with a newline, which would submit the line
+            let edited = edited.trim_end_matches(['\n', '\r']).to_string();
+            self.editor
+                .set_buffer(edited, UndoBehavior::CreateUndoPoint);
+        }
+
+        Ok(())
+    }
+
+    /// Run the buffer editor on a file holding the buffer, returning its edited content unless
+    /// the editor failed or the file couldn't be written or read back
+    ///
+    /// Only the errors restoring the terminal are returned.
+    fn run_buffer_editor(
+        &mut self,
+        buffer_editor: &mut BufferEditor,
+        temporary: bool,
+    ) -> Result<Option<String>> {
+        let written = if temporary {
+            create_temp_file(&buffer_editor.temp_file)
+        } else {
+            File::create(&buffer_editor.temp_file)
         }
+        .and_then(|mut file| write!(file, "{}", self.editor.get_buffer()));
+        if written.is_err() {
+            return Ok(None);
+        }
+
+        // The editor expects the terminal to be in the cooked mode, without the extensions
+        self.leave_terminal()?;
+        let status = buffer_editor.command.status();
+        self.enter_terminal()?;
+
+        Ok(match status {
+            Ok(status) if status.success() => {
+                std::fs::read_to_string(&buffer_editor.temp_file).ok()
+            }
+            _ => None,
+        })
     }
 
     /// Repaint logic for the history reverse search
@@ -1670,16 +2946,17 @@ impl Reedline {
         let navigation = self.history_cursor.get_navigation();
 
         if let HistoryNavigationQuery::SubstringSearch(substring) = navigation {
-            let status =
-                if !substring.is_empty() && self.history_cursor.string_at_cursor().is_none() {
-                    PromptHistorySearchStatus::Failing
-                } else {
-                    PromptHistorySearchStatus::Passing
-                };
+            let status = if self.history_search_failing() {
+                PromptHistorySearchStatus::Failing
+            } else {
+                PromptHistorySearchStatus::Passing
+            };
 
             let prompt_history_search = PromptHistorySearch::new(status, substring.clone());
+            let prompt_context = self.current_prompt_context(Some(prompt_history_search.clone()));
 
-            let res_string = self.history_cursor.string_at_cursor().unwrap_or_default();
+            // A failing search keeps showing the last match
+            let res_string = self.history_search_match.clone().unwrap_or_default();
 
             // Highlight matches
             let res_string = if self.use_ansi_coloring {
@@ -1690,14 +2967,16 @@ impl Reedline {
                 res_string
             };
 
-            let lines = PromptLines::new(
+            let mut lines = PromptLines::new(
                 prompt,
+                &prompt_context,
                 self.prompt_edit_mode(),
                 Some(prompt_history_search),
                 &res_string,
                 "",
                 "",
             );
+            self.painter.scroll_horizontally(prompt, &mut lines);
 
             self.painter.repaint_buffer(
                 prompt,
@@ -1719,12 +2998,23 @@ impl Reedline {
         let cursor_position_in_buffer = self.editor.insertion_point();
         let buffer_to_paint = self.editor.get_buffer();
 
-        let mut styled_text = self
-            .highlighter
-            .highlight(buffer_to_paint, cursor_position_in_buffer);
+        let mut styled_text = self.highlight_cache.highlight(
+            self.highlighter.as_ref(),
+            buffer_to_paint,
+            cursor_position_in_buffer,
+            self.painter.screen_height() as usize,
+        );
         if let Some((from, to)) = self.editor.get_selection() {
             styled_text.style_range(from, to, self.visual_selection_style);
         }
+        for &cursor in self.editor.secondary_cursors() {
+            if let Some(grapheme) = buffer_to_paint[cursor..].graphemes(true).next() {
+                if !grapheme.starts_with(['\r', '\n']) {
+                    let end = cursor + grapheme.len();
+                    styled_text.style_range(cursor, end, self.secondary_cursor_style);
+                }
+            }
+        }
 
         let (before_cursor, after_cursor) = styled_text.render_around_insertion_point(
             cursor_position_in_buffer,
@@ -1748,14 +3038,17 @@ impl Reedline {
         // Needs to add return carriage to newlines because when not in raw mode
         // some OS don't fully return the carriage
 
+        let prompt_context = self.current_prompt_context(None);
         let mut lines = PromptLines::new(
             prompt,
+            &prompt_context,
             self.prompt_edit_mode(),
             None,
             &before_cursor,
             &after_cursor,
             &hint,
         );
+        self.painter.scroll_horizontally(prompt, &mut lines);
 
         // Updating the working details of the active menu
         for menu in self.menus.iter_mut() {
@@ -1774,6 +3067,10 @@ impl Reedline {
             }
         }
 
+        let line_count = self.editor.get_buffer().split('\n').count();
+        let visual_layout = self.painter.visual_layout(prompt, &lines, line_count);
+        self.editor.set_visual_layout(visual_layout);
+
         let menu = self.menus.iter().find(|menu| menu.is_active());
 
         self.painter.repaint_buffer(
@@ -1823,8 +3120,9 @@ impl Reedline {
     fn submit_buffer(&mut self, prompt: &dyn Prompt) -> io::Result<EventStatus> {
         let buffer = self.editor.get_buffer().to_string();
         self.hide_hints = true;
+        self.editor.run_edit_command(&EditCommand::ClearSelection);
 
-        // Additional repaint to show the content without hints etc.
+        // Additional repaint to show the content without hints, selection etc.
         if let Some(transient_prompt) = self.transient_prompt.take() {
             self.repaint(transient_prompt.as_ref())?;
             self.transient_prompt = Some(transient_prompt);
@@ -1833,35 +3131,98 @@ impl Reedline {
         }
 
         if !buffer.is_empty() {
-            let filtered = self
-                .history_exclusion_prefix
-                .as_ref()
-                .map_or(false, |prefix| buffer.starts_with(prefix));
+            self.save_to_history(&buffer);
+        }
 
-            let entry_id = if filtered {
-                Self::FILTERED_ITEM_ID
-            } else {
-                self.history.generate_id()
-            };
+        self.run_edit_commands(&[EditCommand::Clear]);
+        self.editor.reset_undo_stack();
 
-            let mut entry = HistoryItem::from_command_line(&buffer, entry_id);
+        Ok(EventStatus::Exits(Signal::Success(buffer)))
+    }
 
-            entry.session_id = self.get_history_session_id();
+    /// Reads a line of `input` without editing it, for the input that doesn't come from a
+    /// terminal
+    ///
+    /// The prompt and the line are printed like once submitted, for the output to read like
+    /// the session.
+    fn read_piped_line(&mut self, prompt: &dyn Prompt, input: &mut impl BufRead) -> Result<Signal> {
+        let context = self.current_prompt_context(None);
+        let mut prompt_text = format!(
+            "{}{}",
+            prompt.render_prompt_left_with_context(&context),
+            prompt.render_prompt_indicator_with_context(self.prompt_edit_mode(), &context)
+        );
+        if !self.use_ansi_coloring {
+            prompt_text = strip_ansi(&prompt_text);
+        }
 
-            if filtered {
-                self.history.replace(&entry).expect("todo: error handling");
-            } else {
-                self.history.save(&entry).expect("todo: error handling");
-            }
+        let mut line = String::new();
+        let mut stderr = io::stderr();
+        write!(stderr, "{prompt_text}")?;
+        stderr.flush()?;
+        if input.read_line(&mut line)? == 0 {
+            writeln!(stderr)?;
+            return Ok(Signal::CtrlD);
+        }
+        let line = line
+            .strip_suffix('\n')
+            .map_or(line.as_str(), |line| {
+                line.strip_suffix('\r').unwrap_or(line)
+            })
+            .to_string();
+        writeln!(stderr, "{line}")?;
 
-            self.history_last_run_id = Some(entry_id);
-            self.history_excluded_item = if filtered { Some(entry) } else { None };
+        if !line.is_empty() {
+            self.save_to_history(&line);
         }
+        Ok(Signal::Success(line))
+    }
 
-        self.run_edit_commands(&[EditCommand::Clear]);
-        self.editor.reset_undo_stack();
+    /// Adds the submitted buffer to the history, once redacted
+    fn save_to_history(&mut self, buffer: &str) {
+        let command_line = match &self.history_redactor {
+            Some(redactor) => redactor(buffer),
+            None => Some(buffer.to_string()),
+        };
+        let Some(command_line) = command_line else {
+            self.history_last_run_id = None;
+            self.history_excluded_item = None;
+            return;
+        };
 
-        Ok(EventStatus::Exits(Signal::Success(buffer)))
+        let filtered = self
+            .history_exclusion_prefix
+            .as_ref()
+            .map_or(false, |prefix| buffer.starts_with(prefix));
+
+        let entry_id = if filtered {
+            Self::FILTERED_ITEM_ID
+        } else {
+            self.history.generate_id()
+        };
+
+        let mut entry = HistoryItem::from_command_line(command_line, entry_id);
+
+        entry.session_id = self.get_history_session_id();
+        entry.cwd = self.history_cwd();
+
+        if filtered {
+            self.history.replace(&entry).expect("todo: error handling");
+        } else {
+            self.history.save(&entry).expect("todo: error handling");
+        }
+
+        self.history_last_run_id = Some(entry_id);
+        self.history_excluded_item = if filtered { Some(entry) } else { None };
+    }
+}
+
+/// Pasted text with its line endings turned into newlines, without the trailing one
+fn pasted_text(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    match text.strip_suffix('\n') {
+        Some(stripped) => stripped.to_string(),
+        None => text,
     }
 }
 
@@ -1870,3 +3231,1016 @@ fn thread_safe() {
     fn f<S: Send>(_: S) {}
     f(Reedline::create());
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ColumnarMenu, DefaultCompleter, DefaultHinter, DefaultPrompt, DefaultValidator,
+        FileBackedHistory, MenuBuilder, PlaceholderHinter, PromptViMode, Span, Suggestion, Vi,
+    };
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    fn handle(reedline: &mut Reedline, events: Vec<ReedlineEvent>) {
+        let prompt = DefaultPrompt::default();
+        for event in events {
+            reedline.handle_event(&prompt, event).unwrap();
+        }
+    }
+
+    fn insert(text: &str) -> ReedlineEvent {
+        ReedlineEvent::Edit(vec![EditCommand::InsertString(text.to_string())])
+    }
+
+    fn type_text(text: &str) -> ReedlineEvent {
+        ReedlineEvent::Edit(text.chars().map(EditCommand::InsertChar).collect())
+    }
+
+    fn reedline_with_history(command_lines: &[&str]) -> Reedline {
+        let mut history = FileBackedHistory::default();
+        for command_line in command_lines {
+            let id = history.generate_id();
+            history
+                .save(&HistoryItem::from_command_line(*command_line, id))
+                .unwrap();
+        }
+        Reedline::create().with_history(Box::new(history))
+    }
+
+    #[test]
+    fn piped_lines_are_read_as_they_are() {
+        let mut reedline = reedline_with_history(&[]);
+        let prompt = DefaultPrompt::default();
+        let mut input = io::Cursor::new("ls -la\r\n\necho done");
+
+        let lines = (0..4)
+            .map(|_| reedline.read_piped_line(&prompt, &mut input).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            &lines[..],
+            [
+                Signal::Success(first),
+                Signal::Success(empty),
+                Signal::Success(last),
+                Signal::CtrlD,
+            ] if first == "ls -la" && empty.is_empty() && last == "echo done"
+        ));
+
+        let command_lines = reedline
+            .history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect::<Vec<_>>();
+        assert_eq!(command_lines, vec!["ls -la", "echo done"]);
+    }
+
+    #[test]
+    fn history_redactor_runs_before_saving() {
+        let mut reedline =
+            reedline_with_history(&[]).with_history_redactor(Box::new(|command_line| {
+                match command_line.split_once(" --token ") {
+                    Some((command, _)) => Some(format!("{command} --token <redacted>")),
+                    None if command_line.contains("password") => None,
+                    None => Some(command_line.to_string()),
+                }
+            }));
+
+        reedline.save_to_history("curl --token abc123");
+        reedline.save_to_history("ls");
+        assert!(reedline.has_last_command_context());
+        reedline.save_to_history("echo password");
+        assert!(!reedline.has_last_command_context());
+
+        let command_lines = reedline
+            .history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect::<Vec<_>>();
+        assert_eq!(command_lines, vec!["curl --token <redacted>", "ls"]);
+    }
+
+    #[test]
+    fn seeded_buffers_start_the_next_line() {
+        let mut reedline = Reedline::create().with_buffer("git commit".to_string());
+        reedline.painter.handle_resize(80, 24);
+        let prompt = DefaultPrompt::default();
+
+        reedline.take_seeded_buffer();
+        assert_eq!(reedline.current_buffer_contents(), "git commit");
+        assert_eq!(reedline.current_insertion_point(), 10);
+        assert!(matches!(
+            reedline.handle_event(&prompt, ReedlineEvent::Enter),
+            Ok(EventStatus::Exits(Signal::Success(buffer))) if buffer == "git commit"
+        ));
+
+        // Only the next line is seeded
+        reedline.take_seeded_buffer();
+        assert_eq!(reedline.current_buffer_contents(), "");
+
+        reedline.set_buffer("git commit".to_string(), 3).unwrap();
+        reedline.take_seeded_buffer();
+        handle(&mut reedline, vec![insert(" --no-pager")]);
+        assert_eq!(reedline.current_buffer_contents(), "git --no-pager commit");
+
+        assert!(reedline.set_buffer("é".to_string(), 1).is_err());
+        assert!(reedline.set_buffer("ab".to_string(), 3).is_err());
+    }
+
+    #[test]
+    fn interrupted_buffers_are_kept_out_of_the_history() {
+        let mut reedline = reedline_with_history(&[]);
+        let prompt = DefaultPrompt::default();
+        assert_eq!(reedline.last_interrupted_buffer(), None);
+
+        handle(&mut reedline, vec![insert("git comm")]);
+        assert!(matches!(
+            reedline.handle_event(&prompt, ReedlineEvent::CtrlC),
+            Ok(EventStatus::Exits(Signal::CtrlC))
+        ));
+        assert_eq!(reedline.last_interrupted_buffer(), Some("git comm"));
+        assert_eq!(reedline.current_buffer_contents(), "");
+        assert_eq!(reedline.history().count_all().unwrap(), 0);
+    }
+
+    #[test]
+    fn bell_rings_for_the_actions_that_cannot_be_done() {
+        let rings = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&rings);
+        let mut reedline =
+            reedline_with_history(&["cargo"]).with_bell(Bell::Custom(Box::new(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })));
+        let mut rung = |events| {
+            let before = rings.load(Ordering::SeqCst);
+            handle(&mut reedline, events);
+            rings.load(Ordering::SeqCst) - before
+        };
+
+        assert_eq!(rung(vec![ReedlineEvent::Left]), 1);
+        assert_eq!(rung(vec![insert("ab"), ReedlineEvent::Left]), 0);
+        assert_eq!(rung(vec![ReedlineEvent::Right, ReedlineEvent::Right]), 1);
+        assert_eq!(
+            rung(vec![ReedlineEvent::Edit(vec![EditCommand::YankPop])]),
+            1
+        );
+        assert_eq!(rung(vec![ReedlineEvent::SearchHistory, type_text("ca")]), 0);
+        assert_eq!(rung(vec![type_text("x")]), 1);
+    }
+
+    #[test]
+    fn history_is_browsed_by_the_prefix_before_the_cursor() {
+        let mut reedline =
+            reedline_with_history(&["git status", "ls", "git commit", "cargo build", "git push"]);
+        let browse = |reedline: &mut Reedline, event: ReedlineEvent| {
+            handle(reedline, vec![event]);
+            (
+                reedline.current_buffer_contents().to_string(),
+                reedline.current_insertion_point(),
+            )
+        };
+        let state = |buffer: &str, position| (buffer.to_string(), position);
+
+        handle(&mut reedline, vec![insert("git")]);
+        for expected in ["git push", "git commit", "git status", "git status"] {
+            assert_eq!(
+                browse(&mut reedline, ReedlineEvent::HistoryPrefixUp),
+                state(expected, 3)
+            );
+        }
+        for expected in ["git commit", "git push", "git"] {
+            assert_eq!(
+                browse(&mut reedline, ReedlineEvent::HistoryPrefixDown),
+                state(expected, 3)
+            );
+        }
+
+        // Typing refines the prefix
+        browse(&mut reedline, ReedlineEvent::HistoryPrefixUp);
+        handle(&mut reedline, vec![type_text(" s")]);
+        assert_eq!(
+            browse(&mut reedline, ReedlineEvent::HistoryPrefixUp),
+            state("git status", 5)
+        );
+
+        // The text after the cursor is back past the newest entry
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::Edit(vec![EditCommand::Clear]),
+                insert("cargo test"),
+                ReedlineEvent::Edit(vec![EditCommand::MoveWordLeft { select: false }]),
+            ],
+        );
+        assert_eq!(
+            browse(&mut reedline, ReedlineEvent::HistoryPrefixUp),
+            state("cargo build", 6)
+        );
+        assert_eq!(
+            browse(&mut reedline, ReedlineEvent::HistoryPrefixDown),
+            state("cargo test", 6)
+        );
+        assert_eq!(
+            browse(&mut reedline, ReedlineEvent::HistoryPrefixUp),
+            state("cargo build", 6)
+        );
+    }
+
+    #[test]
+    fn cwd_scoped_history_browses_the_current_directory() {
+        let cwd = std::env::current_dir()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let below = format!("{cwd}{}below", std::path::MAIN_SEPARATOR);
+        let mut history = FileBackedHistory::default().with_cwd(true);
+        for (command_line, cwd) in [
+            ("legacy", None),
+            ("below", Some(below)),
+            ("sibling", Some(format!("{cwd}-sibling"))),
+            ("elsewhere", Some("/elsewhere".to_string())),
+        ] {
+            let mut item = HistoryItem::from_command_line(command_line, history.generate_id());
+            item.cwd = cwd;
+            history.save(&item).unwrap();
+        }
+        let mut reedline = Reedline::create()
+            .with_history(Box::new(history))
+            .with_cwd_scoped_history(true);
+        reedline.save_to_history("here");
+
+        reedline.previous_history();
+        assert_eq!(reedline.current_buffer_contents(), "here");
+        reedline.previous_history();
+        assert_eq!(reedline.current_buffer_contents(), "below");
+        reedline.previous_history();
+        assert_eq!(reedline.current_buffer_contents(), "below");
+    }
+
+    #[rstest]
+    #[case(None, "vi", &[])]
+    #[case(Some("nano"), "nano", &[])]
+    #[case(Some("code --wait -n"), "code", &["--wait", "-n"])]
+    fn editor_command_has_the_arguments(
+        #[case] editor: Option<&str>,
+        #[case] program: &str,
+        #[case] args: &[&str],
+    ) {
+        let command = editor_command(editor);
+
+        assert_eq!(command.get_program(), program);
+        assert_eq!(command.get_args().collect::<Vec<_>>(), args);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn temp_file_is_private_and_not_created_through_a_symlink() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("buffer.txt");
+        create_temp_file(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let target = tmp.path().join("target");
+        std::fs::write(&target, "kept").unwrap();
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert!(create_temp_file(&link).is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "kept");
+    }
+
+    #[rstest]
+    #[case(EnterBehavior::Validate, "(", "(\n")]
+    #[case(EnterBehavior::Newline, "ls", "ls\n")]
+    fn enter_inserts_a_newline(
+        #[case] enter_behavior: EnterBehavior,
+        #[case] buffer: &str,
+        #[case] expected: &str,
+    ) {
+        let mut reedline = Reedline::create()
+            .with_validator(Box::new(DefaultValidator))
+            .with_enter_behavior(enter_behavior);
+
+        handle(&mut reedline, vec![insert(buffer), ReedlineEvent::Enter]);
+
+        assert_eq!(reedline.current_buffer_contents(), expected);
+    }
+
+    #[test]
+    fn insert_newline_ignores_the_validator() {
+        let mut reedline = Reedline::create().with_validator(Box::new(DefaultValidator));
+
+        handle(
+            &mut reedline,
+            vec![insert("ls"), ReedlineEvent::InsertNewline],
+        );
+
+        assert_eq!(reedline.current_buffer_contents(), "ls\n");
+    }
+
+    #[test]
+    fn command_edits_are_undone_at_once() {
+        let mut reedline = Reedline::create().with_command(
+            "wrap",
+            Box::new(|line_buffer| {
+                line_buffer.move_to_start();
+                line_buffer.insert_char('(');
+                line_buffer.move_to_end();
+                line_buffer.insert_char(')');
+            }),
+        );
+
+        handle(
+            &mut reedline,
+            vec![
+                type_text("ls"),
+                ReedlineEvent::RunCommand("wrap".to_string()),
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "(ls)");
+
+        let prompt = DefaultPrompt::default();
+        assert!(matches!(
+            reedline.handle_event(&prompt, ReedlineEvent::RunCommand("unknown".to_string())),
+            Ok(EventStatus::Inapplicable)
+        ));
+
+        handle(
+            &mut reedline,
+            vec![ReedlineEvent::Edit(vec![EditCommand::Undo])],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "ls");
+    }
+
+    #[test]
+    fn disabled_completer_keeps_the_menus_closed() {
+        let mut reedline = Reedline::create().with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_name("completion_menu"),
+        )));
+        let open_menu = || ReedlineEvent::Menu("completion_menu".to_string());
+
+        handle(&mut reedline, vec![open_menu()]);
+        assert!(reedline.active_menu().is_some());
+
+        reedline.disable_completer();
+        assert!(reedline.active_menu().is_none());
+        handle(&mut reedline, vec![open_menu()]);
+        assert!(reedline.active_menu().is_none());
+
+        reedline.enable_completer();
+        handle(&mut reedline, vec![open_menu()]);
+        assert!(reedline.active_menu().is_some());
+    }
+
+    #[test]
+    fn clearing_the_screen_keeps_the_menu_open() {
+        let mut reedline = Reedline::create().with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_name("completion_menu"),
+        )));
+        let prompt = DefaultPrompt::default();
+
+        for clear in [ReedlineEvent::ClearScreen, ReedlineEvent::ClearScrollback] {
+            handle(
+                &mut reedline,
+                vec![ReedlineEvent::Menu("completion_menu".to_string())],
+            );
+            assert!(reedline.active_menu().is_some());
+
+            // Clearing, then repainting, fails without a terminal, which doesn't matter here
+            let _ = reedline.handle_event(&prompt, clear);
+            assert!(reedline.active_menu().is_some());
+
+            handle(&mut reedline, vec![ReedlineEvent::Esc]);
+        }
+    }
+
+    #[test]
+    fn common_prefix_is_completed_before_opening_the_menu() {
+        let completer = DefaultCompleter::new(vec!["format".into(), "forward".into()]);
+        let mut reedline = Reedline::create()
+            .with_completer(Box::new(completer))
+            .with_completion_strategy(CompletionStrategy::LongestCommonPrefix)
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        let open_menu = || ReedlineEvent::Menu("completion_menu".to_string());
+
+        handle(&mut reedline, vec![insert("fo"), open_menu()]);
+        assert_eq!(reedline.current_buffer_contents(), "for");
+        assert!(reedline.active_menu().is_none());
+
+        handle(&mut reedline, vec![open_menu()]);
+        assert_eq!(reedline.current_buffer_contents(), "for");
+        assert!(reedline.active_menu().is_some());
+
+        // A single suggestion is accepted
+        handle(
+            &mut reedline,
+            vec![ReedlineEvent::Esc, insert("w"), open_menu()],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "forward");
+        assert!(reedline.active_menu().is_none());
+    }
+
+    #[test]
+    fn last_completion_is_repeated_where_its_text_still_is() {
+        let completer = DefaultCompleter::new(vec!["format".into()]);
+        let mut reedline = Reedline::create()
+            .with_quick_completions(true)
+            .with_completer(Box::new(completer))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        let prompt = DefaultPrompt::default();
+
+        // The single suggestion is accepted right away, then undone
+        handle(
+            &mut reedline,
+            vec![
+                insert("fo"),
+                ReedlineEvent::Menu("completion_menu".to_string()),
+                ReedlineEvent::Edit(vec![EditCommand::Undo]),
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "fo");
+        handle(&mut reedline, vec![ReedlineEvent::RepeatLastCompletion]);
+        assert_eq!(reedline.current_buffer_contents(), "format");
+
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::Edit(vec![EditCommand::Clear]),
+                insert("cat fo"),
+                ReedlineEvent::RepeatLastCompletion,
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "cat format");
+
+        // The replaced text isn't before the cursor anymore
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::Edit(vec![EditCommand::Clear]),
+                insert("cat f"),
+            ],
+        );
+        assert!(matches!(
+            reedline.handle_event(&prompt, ReedlineEvent::RepeatLastCompletion),
+            Ok(EventStatus::Inapplicable)
+        ));
+        assert_eq!(reedline.current_buffer_contents(), "cat f");
+    }
+
+    /// Completes the whole word under the cursor, wherever it is in the buffer
+    struct WordCompleter;
+
+    impl Completer for WordCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+            let end = line[pos..]
+                .find(char::is_whitespace)
+                .map_or(line.len(), |i| pos + i);
+            vec![Suggestion {
+                value: format!("<{}>", &line[start..end]),
+                span: Span::new(start, end),
+                ..Suggestion::default()
+            }]
+        }
+    }
+
+    #[test]
+    fn completers_are_given_the_whole_buffer() {
+        let mut reedline = Reedline::create()
+            .with_quick_completions(true)
+            .with_completer(Box::new(WordCompleter))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+
+        handle(
+            &mut reedline,
+            vec![
+                insert("ls\ncd\ncat form x"),
+                ReedlineEvent::Edit(vec![EditCommand::MoveLeft { select: false }; 4]),
+                ReedlineEvent::Menu("completion_menu".to_string()),
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "ls\ncd\ncat <form> x");
+    }
+
+    #[test]
+    fn top_completion_is_accepted_without_the_menu() {
+        let completer = DefaultCompleter::new(vec!["format".into(), "forward".into()]);
+        let mut reedline = Reedline::create()
+            .with_completer(Box::new(completer))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        let accept = || ReedlineEvent::AcceptTopCompletion("completion_menu".to_string());
+
+        // A single suggestion is inserted right away
+        handle(&mut reedline, vec![insert("forw"), accept()]);
+        assert_eq!(reedline.current_buffer_contents(), "forward");
+        assert!(reedline.active_menu().is_none());
+
+        // Several suggestions open the menu
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::Edit(vec![EditCommand::Clear]),
+                insert("fo"),
+                accept(),
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "fo");
+        assert_eq!(
+            reedline.active_menu().map(|menu| menu.get_values().len()),
+            Some(2)
+        );
+
+        // Without any suggestion, the menu shows that nothing matches
+        handle(
+            &mut reedline,
+            vec![ReedlineEvent::Esc, insert("x"), accept()],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "fox");
+        assert!(reedline.active_menu().is_some());
+    }
+
+    #[test]
+    fn best_completion_is_accepted_among_several() {
+        let completer = DefaultCompleter::new(vec!["format".into(), "forward".into()]);
+        let mut reedline = Reedline::create()
+            .with_completer(Box::new(completer))
+            .with_accept_best_completion(true)
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+
+        handle(
+            &mut reedline,
+            vec![
+                insert("fo"),
+                ReedlineEvent::AcceptTopCompletion("completion_menu".to_string()),
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "format");
+        assert!(reedline.active_menu().is_none());
+    }
+
+    /// Completes the paths of a small tree, the directories continuing the completion
+    struct TreeCompleter;
+
+    impl Completer for TreeCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            let start = line[..pos].rfind(' ').map_or(0, |index| index + 1);
+            let typed = &line[start..pos];
+            [
+                "src/",
+                "setup.sh",
+                "src/engine/",
+                "src/lib.rs",
+                "src/engine/mod.rs",
+                "xa.txt",
+                "xb.txt",
+            ]
+            .iter()
+            .filter(|path| {
+                path.len() > typed.len()
+                    && path.starts_with(typed)
+                    && !path[typed.len()..path.len() - 1].contains('/')
+            })
+            .map(|path| Suggestion {
+                value: path.to_string(),
+                span: Span::new(start, pos),
+                append_whitespace: !path.ends_with('/'),
+                continue_completion: path.ends_with('/'),
+                ..Suggestion::default()
+            })
+            .collect()
+        }
+    }
+
+    #[test]
+    fn accepted_directories_continue_the_completion() {
+        let mut reedline = Reedline::create()
+            .with_quick_completions(true)
+            .with_completer(Box::new(TreeCompleter))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        let values = |reedline: &mut Reedline| {
+            reedline.active_menu().map(|menu| {
+                menu.get_values()
+                    .iter()
+                    .map(|suggestion| suggestion.value.clone())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        handle(
+            &mut reedline,
+            vec![
+                insert("cat s"),
+                ReedlineEvent::Menu("completion_menu".to_string()),
+                ReedlineEvent::AcceptAndContinue,
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "cat src/");
+        assert_eq!(
+            values(&mut reedline),
+            Some(vec!["src/engine/".to_string(), "src/lib.rs".to_string()])
+        );
+
+        // The only entry of the next directory is accepted right away
+        handle(&mut reedline, vec![ReedlineEvent::AcceptAndContinue]);
+        assert_eq!(reedline.current_buffer_contents(), "cat src/engine/mod.rs ");
+        assert!(reedline.active_menu().is_none());
+
+        // Other suggestions close the menu
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::Edit(vec![EditCommand::Clear]),
+                insert("x"),
+                ReedlineEvent::Menu("completion_menu".to_string()),
+                ReedlineEvent::AcceptAndContinue,
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "xa.txt ");
+        assert!(reedline.active_menu().is_none());
+
+        // Without a menu, the next event of `UntilFound` is tried
+        let prompt = DefaultPrompt::default();
+        assert!(matches!(
+            reedline.handle_event(&prompt, ReedlineEvent::AcceptAndContinue),
+            Ok(EventStatus::Inapplicable)
+        ));
+    }
+
+    #[test]
+    fn prompt_context_describes_the_engine() {
+        let mut reedline = Reedline::create().with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_name("completion_menu"),
+        )));
+        reedline.set_last_command_status(1);
+
+        handle(
+            &mut reedline,
+            vec![
+                insert("a\nb\nc"),
+                ReedlineEvent::Menu("completion_menu".to_string()),
+            ],
+        );
+
+        let context = reedline.current_prompt_context(None);
+        assert_eq!(context.last_command_status, Some(1));
+        assert_eq!(context.edit_mode, PromptEditMode::Emacs);
+        assert_eq!(context.active_menu.as_deref(), Some("completion_menu"));
+        assert_eq!(context.pending_command, None);
+        assert_eq!(context.multiline_depth, 2);
+    }
+
+    #[test]
+    fn disabled_hinter_is_kept() {
+        let mut reedline = Reedline::create().with_hinter(Box::new(DefaultHinter::default()));
+
+        reedline.disable_hinter();
+        assert!(!reedline.hints_active());
+        assert!(reedline.hinter.is_some());
+
+        reedline.enable_hinter();
+        assert!(reedline.hints_active());
+    }
+
+    #[test]
+    fn placeholders_are_not_accepted() {
+        let hinter = PlaceholderHinter::new(|line, _pos| Some(format!("{}hint", line.len())));
+        let mut reedline = Reedline::create().with_hinter(Box::new(hinter));
+        let prompt = DefaultPrompt::default();
+        let hint = |reedline: &mut Reedline| {
+            let line = reedline.editor.get_buffer().to_string();
+            if let Some(hinter) = reedline.hinter.as_mut() {
+                hinter.handle(&line, line.len(), reedline.history.as_ref(), false);
+            }
+        };
+
+        hint(&mut reedline);
+        assert!(matches!(
+            reedline.handle_event(&prompt, ReedlineEvent::HistoryHintComplete),
+            Ok(EventStatus::Inapplicable)
+        ));
+        assert_eq!(reedline.current_buffer_contents(), "");
+
+        handle(&mut reedline, vec![insert("ls ")]);
+        hint(&mut reedline);
+        handle(&mut reedline, vec![ReedlineEvent::HistoryHintComplete]);
+        assert_eq!(reedline.current_buffer_contents(), "ls 3hint");
+    }
+
+    #[test]
+    fn esc_collapses_the_cursors() {
+        let mut reedline = Reedline::create();
+        handle(
+            &mut reedline,
+            vec![
+                insert("mv a a"),
+                ReedlineEvent::Edit(vec![EditCommand::AddCursorsAtWord]),
+                type_text("b"),
+                ReedlineEvent::Esc,
+                type_text("c"),
+            ],
+        );
+
+        assert_eq!(reedline.current_buffer_contents(), "mv ab abc");
+    }
+
+    #[test]
+    fn terminal_events_end_the_line_with_a_signal() {
+        let mut reedline = Reedline::create();
+        let prompt = DefaultPrompt::default();
+        let keys = |keys: &[(KeyModifiers, KeyCode)]| {
+            keys.iter()
+                .filter_map(|(modifiers, code)| {
+                    ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(*code, *modifiers)))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let signal = reedline
+            .handle_terminal_events(
+                &prompt,
+                vec![],
+                keys(&[
+                    (KeyModifiers::NONE, KeyCode::Char('l')),
+                    (KeyModifiers::NONE, KeyCode::Char('s')),
+                ]),
+                false,
+            )
+            .unwrap();
+        assert!(signal.is_none());
+        assert_eq!(reedline.current_buffer_contents(), "ls");
+
+        let signal = reedline
+            .handle_terminal_events(
+                &prompt,
+                vec![],
+                keys(&[(KeyModifiers::CONTROL, KeyCode::Char('c'))]),
+                false,
+            )
+            .unwrap();
+        assert!(matches!(signal, Some(Signal::CtrlC)));
+    }
+
+    #[test]
+    fn reverse_search_goes_around_and_keeps_the_last_match() {
+        let mut reedline = reedline_with_history(&["cargo test", "ls", "cargo build"]);
+        handle(
+            &mut reedline,
+            vec![
+                insert("original"),
+                ReedlineEvent::SearchHistory,
+                type_text("cargo"),
+            ],
+        );
+        assert_eq!(
+            reedline.history_search_match.as_deref(),
+            Some("cargo build")
+        );
+
+        handle(&mut reedline, vec![ReedlineEvent::SearchHistory]);
+        assert_eq!(reedline.history_search_match.as_deref(), Some("cargo test"));
+        handle(&mut reedline, vec![ReedlineEvent::SearchHistory]);
+        assert_eq!(
+            reedline.history_search_match.as_deref(),
+            Some("cargo build")
+        );
+        handle(&mut reedline, vec![ReedlineEvent::SearchHistoryForward]);
+        assert_eq!(reedline.history_search_match.as_deref(), Some("cargo test"));
+
+        // Nothing matches anymore
+        handle(&mut reedline, vec![type_text(" z")]);
+        assert_eq!(reedline.history_cursor.string_at_cursor(), None);
+        assert_eq!(reedline.history_search_match.as_deref(), Some("cargo test"));
+        assert_eq!(reedline.current_buffer_contents(), "original");
+
+        handle(&mut reedline, vec![ReedlineEvent::Enter]);
+        assert_eq!(reedline.input_mode, InputMode::Regular);
+        assert_eq!(reedline.current_buffer_contents(), "cargo test");
+    }
+
+    #[test]
+    fn cancelled_reverse_search_keeps_the_line() {
+        let mut reedline = reedline_with_history(&["cargo test"]);
+        handle(
+            &mut reedline,
+            vec![
+                insert("original"),
+                ReedlineEvent::SearchHistory,
+                type_text("cargo"),
+                ReedlineEvent::Esc,
+            ],
+        );
+
+        assert_eq!(reedline.input_mode, InputMode::Regular);
+        assert_eq!(reedline.current_buffer_contents(), "original");
+    }
+
+    #[test]
+    fn paste_is_inserted_as_a_single_undo_step() {
+        let mut reedline = Reedline::create().use_auto_pairs(true);
+        handle(
+            &mut reedline,
+            vec![
+                insert("echo "),
+                ReedlineEvent::Paste("f(\"a\r\nb\rc\r\n".to_string()),
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "echo f(\"a\nb\nc");
+
+        handle(
+            &mut reedline,
+            vec![ReedlineEvent::Edit(vec![EditCommand::Undo])],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "echo ");
+    }
+
+    #[test]
+    fn inserted_text_closes_the_menu_and_is_undone_at_once() {
+        let completer = DefaultCompleter::new(vec!["format".into(), "forward".into()]);
+        let mut reedline = Reedline::create()
+            .use_auto_pairs(true)
+            .with_completer(Box::new(completer))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                ColumnarMenu::default().with_name("completion_menu"),
+            )));
+        handle(
+            &mut reedline,
+            vec![
+                insert("cp fo"),
+                ReedlineEvent::Menu("completion_menu".to_string()),
+            ],
+        );
+        assert!(reedline.active_menu().is_some());
+
+        reedline.insert_at_cursor(" \"/tmp/a b\"\r\n");
+        assert!(reedline.active_menu().is_none());
+        assert_eq!(reedline.current_buffer_contents(), "cp fo \"/tmp/a b\"");
+
+        handle(
+            &mut reedline,
+            vec![ReedlineEvent::Edit(vec![EditCommand::Undo])],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "cp fo");
+    }
+
+    #[rstest]
+    #[case(KeyCode::Esc, "ab", "aXb")]
+    #[case(KeyCode::Char('v'), "ab", "aXb")]
+    #[case(KeyCode::Char('i'), "ab", "Xab")]
+    fn text_is_inserted_after_the_vi_cursor(
+        #[case] key: KeyCode,
+        #[case] line: &str,
+        #[case] expected: &str,
+    ) {
+        let mut reedline = Reedline::create().with_edit_mode(Box::new(Vi::default()));
+        let prompt = DefaultPrompt::default();
+        let mut keys = vec![KeyCode::Esc, KeyCode::Char('0')];
+        if key != KeyCode::Esc {
+            keys.push(key);
+        }
+        handle(&mut reedline, vec![insert(line)]);
+        for key in keys {
+            let event =
+                ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(key, KeyModifiers::NONE)))
+                    .unwrap();
+            let event = reedline.edit_mode.parse_event(event);
+            reedline.handle_event(&prompt, event).unwrap();
+        }
+
+        reedline.insert_at_cursor("X");
+        assert_eq!(reedline.current_buffer_contents(), expected);
+        assert_eq!(
+            reedline.edit_mode.edit_mode(),
+            PromptEditMode::Vi(PromptViMode::Insert)
+        );
+    }
+
+    #[test]
+    fn edit_modes_are_switched_mid_session() {
+        let mut reedline = Reedline::create().with_edit_mode(Box::new(Vi::default()));
+        let press = |reedline: &mut Reedline, key: KeyCode| {
+            let event =
+                ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(key, KeyModifiers::NONE)))
+                    .unwrap();
+            let event = reedline.edit_mode.parse_event(event);
+            reedline
+                .handle_event(&DefaultPrompt::default(), event)
+                .unwrap();
+        };
+        handle(&mut reedline, vec![insert("abc")]);
+        press(&mut reedline, KeyCode::Esc);
+        press(&mut reedline, KeyCode::Char('0'));
+        assert_eq!(reedline.current_edit_mode_name(), "vi");
+
+        // `x` deletes in Vi normal mode, `d` waits for a motion
+        press(&mut reedline, KeyCode::Char('x'));
+        press(&mut reedline, KeyCode::Char('d'));
+        assert_eq!(reedline.current_buffer_contents(), "bc");
+        assert_eq!(reedline.edit_mode.pending_command(), Some("d".to_string()));
+
+        let vi = reedline.set_edit_mode(Box::new(Emacs::default()));
+        assert_eq!(reedline.current_edit_mode_name(), "emacs");
+        press(&mut reedline, KeyCode::Char('x'));
+        assert_eq!(reedline.current_buffer_contents(), "xbc");
+
+        // Back in Vi, the `d` typed before is forgotten
+        let emacs = reedline.set_edit_mode(vi);
+        assert_eq!(emacs.edit_mode(), PromptEditMode::Emacs);
+        assert_eq!(reedline.edit_mode.pending_command(), None);
+        press(&mut reedline, KeyCode::Char('x'));
+        assert_eq!(reedline.current_buffer_contents(), "xc");
+    }
+
+    #[test]
+    fn records_and_plays_macros() {
+        let mut reedline = Reedline::create();
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::RecordMacro('a'),
+                insert("ab"),
+                ReedlineEvent::StopMacro,
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "ab");
+
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::PlayMacro('a'),
+                ReedlineEvent::Multiple(vec![ReedlineEvent::PlayLastMacro; 2]),
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "abababab");
+
+        // Unknown registers do nothing
+        handle(&mut reedline, vec![ReedlineEvent::PlayMacro('b')]);
+        assert_eq!(reedline.current_buffer_contents(), "abababab");
+    }
+
+    #[test]
+    fn macro_replaying_itself_is_not_replayed_again() {
+        let mut reedline = Reedline::create();
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::RecordMacro('a'),
+                insert("a"),
+                ReedlineEvent::PlayMacro('b'),
+                ReedlineEvent::StopMacro,
+                ReedlineEvent::RecordMacro('b'),
+                insert("b"),
+                ReedlineEvent::PlayMacro('a'),
+                ReedlineEvent::StopMacro,
+            ],
+        );
+        assert_eq!(reedline.current_buffer_contents(), "aba");
+
+        // a, then b from a, the a from b being skipped
+        handle(&mut reedline, vec![ReedlineEvent::PlayMacro('a')]);
+        assert_eq!(reedline.current_buffer_contents(), "abaab");
+    }
+
+    #[test]
+    fn played_events_are_not_recorded_twice() {
+        let mut reedline = Reedline::create();
+        handle(
+            &mut reedline,
+            vec![
+                ReedlineEvent::RecordMacro('a'),
+                insert("a"),
+                ReedlineEvent::StopMacro,
+                ReedlineEvent::RecordMacro('b'),
+                ReedlineEvent::PlayMacro('a'),
+                insert("b"),
+                ReedlineEvent::StopMacro,
+            ],
+        );
+
+        assert_eq!(
+            reedline.macros.get(&'b'),
+            Some(&vec![ReedlineEvent::PlayMacro('a'), insert("b")])
+        );
+    }
+}
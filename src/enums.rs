@@ -1,14 +1,31 @@
-use crossterm::event::{Event, KeyEvent, KeyEventKind};
+use crossterm::event::{Event, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind};
 use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
 use strum_macros::EnumIter;
 
+/// The event of the mouse clicks and drags with the left button, which reedline handles
+pub(crate) fn mouse_event(event: MouseEvent) -> ReedlineEvent {
+    let MouseEvent {
+        kind, column, row, ..
+    } = event;
+    match kind {
+        MouseEventKind::Down(MouseButton::Left) => ReedlineEvent::MouseClick { column, row },
+        MouseEventKind::Drag(MouseButton::Left) => ReedlineEvent::MouseDrag { column, row },
+        _ => ReedlineEvent::Mouse,
+    }
+}
+
 /// Valid ways how `Reedline::read_line()` can return
 #[derive(Debug)]
 pub enum Signal {
     /// Entry succeeded with the provided content
     Success(String),
     /// Entry was aborted with `Ctrl+C`
+    ///
+    /// The aborted text is returned by [`crate::Reedline::last_interrupted_buffer`].
     CtrlC, // Interrupt current editing
     /// Abort with `Ctrl+D` signalling `EOF` or abort of a whole interactive session
     CtrlD, // End terminal session
@@ -163,6 +180,14 @@ pub enum EditCommand {
     /// Cut from the insertion point to the end of the current line
     CutToLineEnd,
 
+    /// Cut from the insertion point to the end of the current row of the screen, when the
+    /// current line wraps
+    ///
+    /// Uses the screen width and the prompt of the last paint of the buffer: before that, or
+    /// with [`LineOverflow::Scroll`], it cuts to the end of the line like
+    /// [`EditCommand::CutToLineEnd`].
+    CutToVisualLineEnd,
+
     /// Cut the word left of the insertion point
     CutWordLeft,
 
@@ -187,6 +212,11 @@ pub enum EditCommand {
     /// Paste the cut buffer in front of the insertion point (vi `p`)
     PasteCutBufferAfter,
 
+    /// Replace the text that was just pasted from the cut buffer with the previous cut (Emacs `M-y`)
+    ///
+    /// Does nothing unless it right follows a paste or another yank-pop.
+    YankPop,
+
     /// Upper case the current word
     UppercaseWord,
 
@@ -196,19 +226,28 @@ pub enum EditCommand {
     /// Capitalize the current character
     CapitalizeChar,
 
+    /// Capitalize the word from the cursor, lowercasing its other characters, and move
+    /// behind it
+    CapitalizeWord,
+
     /// Switch the case of the current character
     SwitchcaseChar,
 
-    /// Swap the current word with the word to the right
+    /// Swap the current word with the word to the right, and move behind them
     SwapWords,
 
     /// Swap the current grapheme/character with the one to the right
     SwapGraphemes,
 
     /// Undo the previous edit command
+    ///
+    /// Successive insertions or deletions are undone together as configured with the
+    /// [`UndoGranularity`], cursor movements are never undone on their own
     Undo,
 
     /// Redo an edit command from the undo history
+    ///
+    /// Only available until the next edit after an [`EditCommand::Undo`]
     Redo,
 
     /// CutUntil right until char
@@ -255,6 +294,22 @@ pub enum EditCommand {
         select: bool,
     },
 
+    /// Move right before char, skipping one directly to the right of the cursor
+    MoveRightBeforeNext {
+        /// Char to move towards
+        c: char,
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
+    /// Move left before char, skipping one directly to the left of the cursor
+    MoveLeftBeforeNext {
+        /// Char to move towards
+        c: char,
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
     /// Select whole input buffer
     SelectAll,
 
@@ -267,6 +322,78 @@ pub enum EditCommand {
     /// Paste content from local buffer at the current cursor position
     Paste,
 
+    /// Copy the current line to the cut buffer
+    CopyCurrentLine,
+
+    /// Copy from the start of the current line to the insertion point
+    CopyFromLineStart,
+
+    /// Copy from the insertion point to the end of the current line
+    CopyToLineEnd,
+
+    /// Copy the word left of the insertion point
+    CopyWordLeft,
+
+    /// Copy the WORD left of the insertion point
+    CopyBigWordLeft,
+
+    /// Copy the word right of the insertion point
+    CopyWordRight,
+
+    /// Copy the WORD right of the insertion point
+    CopyBigWordRight,
+
+    /// Copy the word right of the insertion point and any following space
+    CopyWordRightToNext,
+
+    /// Copy the WORD right of the insertion point and any following space
+    CopyBigWordRightToNext,
+
+    /// Copy the grapheme left of the insertion point
+    CopyLeft,
+
+    /// Copy the grapheme right of the insertion point
+    CopyRight,
+
+    /// Copy up to and including the char right of the insertion point
+    CopyRightUntil(char),
+
+    /// Copy up to but excluding the char right of the insertion point
+    CopyRightBefore(char),
+
+    /// Copy up to and including the char left of the insertion point
+    CopyLeftUntil(char),
+
+    /// Copy up to but excluding the char left of the insertion point
+    CopyLeftBefore(char),
+
+    /// Use the vi register `char` instead of the cut buffer for the next cut, copy or paste
+    ///
+    /// Uppercase letters append to the register of the lowercase letter.
+    SelectRegister(char),
+
+    /// Start a selection at the insertion point, or change the mode of the current one
+    StartSelection(SelectionMode),
+
+    /// Swap the insertion point with the other end of the selection
+    SwapSelectionEnds,
+
+    /// Stop selecting without moving the insertion point
+    ClearSelection,
+
+    /// Indent the lines touched by the selection, or the current line without one
+    IndentSelection,
+
+    /// Add a cursor at the same place in every other occurrence of the word under the
+    /// insertion point
+    ///
+    /// The characters inserted and deleted with [`EditCommand::InsertChar`],
+    /// [`EditCommand::InsertString`], [`EditCommand::Backspace`] and [`EditCommand::Delete`]
+    /// are then inserted and deleted at every cursor, as a single undo step for each command.
+    /// Any other command, or [`crate::ReedlineEvent::Esc`], goes back to the insertion point
+    /// alone.
+    AddCursorsAtWord,
+
     /// Cut selection to system clipboard
     #[cfg(feature = "system_clipboard")]
     CutSelectionSystem,
@@ -321,6 +448,18 @@ impl Display for EditCommand {
             EditCommand::MoveLeftBefore { .. } => {
                 write!(f, "MoveLeftBefore Value: <char>, Optional[select: <bool>]")
             }
+            EditCommand::MoveRightBeforeNext { .. } => {
+                write!(
+                    f,
+                    "MoveRightBeforeNext Value: <char>, Optional[select: <bool>]"
+                )
+            }
+            EditCommand::MoveLeftBeforeNext { .. } => {
+                write!(
+                    f,
+                    "MoveLeftBeforeNext Value: <char>, Optional[select: <bool>]"
+                )
+            }
             EditCommand::InsertChar(_) => write!(f, "InsertChar  Value: <char>"),
             EditCommand::InsertString(_) => write!(f, "InsertString Value: <string>"),
             EditCommand::InsertNewline => write!(f, "InsertNewline"),
@@ -339,6 +478,7 @@ impl Display for EditCommand {
             EditCommand::CutFromLineStart => write!(f, "CutFromLineStart"),
             EditCommand::CutToEnd => write!(f, "CutToEnd"),
             EditCommand::CutToLineEnd => write!(f, "CutToLineEnd"),
+            EditCommand::CutToVisualLineEnd => write!(f, "CutToVisualLineEnd"),
             EditCommand::CutWordLeft => write!(f, "CutWordLeft"),
             EditCommand::CutBigWordLeft => write!(f, "CutBigWordLeft"),
             EditCommand::CutWordRight => write!(f, "CutWordRight"),
@@ -347,10 +487,12 @@ impl Display for EditCommand {
             EditCommand::CutBigWordRightToNext => write!(f, "CutBigWordRightToNext"),
             EditCommand::PasteCutBufferBefore => write!(f, "PasteCutBufferBefore"),
             EditCommand::PasteCutBufferAfter => write!(f, "PasteCutBufferAfter"),
+            EditCommand::YankPop => write!(f, "YankPop"),
             EditCommand::UppercaseWord => write!(f, "UppercaseWord"),
             EditCommand::LowercaseWord => write!(f, "LowercaseWord"),
             EditCommand::SwitchcaseChar => write!(f, "SwitchcaseChar"),
             EditCommand::CapitalizeChar => write!(f, "CapitalizeChar"),
+            EditCommand::CapitalizeWord => write!(f, "CapitalizeWord"),
             EditCommand::SwapWords => write!(f, "SwapWords"),
             EditCommand::SwapGraphemes => write!(f, "SwapGraphemes"),
             EditCommand::Undo => write!(f, "Undo"),
@@ -364,6 +506,27 @@ impl Display for EditCommand {
             EditCommand::SelectAll => write!(f, "SelectAll"),
             EditCommand::CutSelection => write!(f, "CutSelection"),
             EditCommand::CopySelection => write!(f, "CopySelection"),
+            EditCommand::CopyCurrentLine => write!(f, "CopyCurrentLine"),
+            EditCommand::CopyFromLineStart => write!(f, "CopyFromLineStart"),
+            EditCommand::CopyToLineEnd => write!(f, "CopyToLineEnd"),
+            EditCommand::CopyWordLeft => write!(f, "CopyWordLeft"),
+            EditCommand::CopyBigWordLeft => write!(f, "CopyBigWordLeft"),
+            EditCommand::CopyWordRight => write!(f, "CopyWordRight"),
+            EditCommand::CopyBigWordRight => write!(f, "CopyBigWordRight"),
+            EditCommand::CopyWordRightToNext => write!(f, "CopyWordRightToNext"),
+            EditCommand::CopyBigWordRightToNext => write!(f, "CopyBigWordRightToNext"),
+            EditCommand::CopyLeft => write!(f, "CopyLeft"),
+            EditCommand::CopyRight => write!(f, "CopyRight"),
+            EditCommand::CopyRightUntil(_) => write!(f, "CopyRightUntil Value: <char>"),
+            EditCommand::CopyRightBefore(_) => write!(f, "CopyRightBefore Value: <char>"),
+            EditCommand::CopyLeftUntil(_) => write!(f, "CopyLeftUntil Value: <char>"),
+            EditCommand::CopyLeftBefore(_) => write!(f, "CopyLeftBefore Value: <char>"),
+            EditCommand::SelectRegister(_) => write!(f, "SelectRegister Value: <char>"),
+            EditCommand::StartSelection(_) => write!(f, "StartSelection Value: <SelectionMode>"),
+            EditCommand::SwapSelectionEnds => write!(f, "SwapSelectionEnds"),
+            EditCommand::ClearSelection => write!(f, "ClearSelection"),
+            EditCommand::IndentSelection => write!(f, "IndentSelection"),
+            EditCommand::AddCursorsAtWord => write!(f, "AddCursorsAtWord"),
             EditCommand::Paste => write!(f, "Paste"),
             #[cfg(feature = "system_clipboard")]
             EditCommand::CutSelectionSystem => write!(f, "CutSelectionSystem"),
@@ -398,11 +561,18 @@ impl EditCommand {
             | EditCommand::MoveRightUntil { select, .. }
             | EditCommand::MoveRightBefore { select, .. }
             | EditCommand::MoveLeftUntil { select, .. }
-            | EditCommand::MoveLeftBefore { select, .. } => {
+            | EditCommand::MoveLeftBefore { select, .. }
+            | EditCommand::MoveRightBeforeNext { select, .. }
+            | EditCommand::MoveLeftBeforeNext { select, .. } => {
                 EditType::MoveCursor { select: *select }
             }
 
-            EditCommand::SelectAll => EditType::MoveCursor { select: true },
+            EditCommand::SelectAll
+            | EditCommand::StartSelection(_)
+            | EditCommand::SwapSelectionEnds => EditType::MoveCursor { select: true },
+            EditCommand::ClearSelection | EditCommand::AddCursorsAtWord => {
+                EditType::MoveCursor { select: false }
+            }
             // Text edits
             EditCommand::InsertChar(_)
             | EditCommand::Backspace
@@ -422,6 +592,7 @@ impl EditCommand {
             | EditCommand::CutFromLineStart
             | EditCommand::CutToLineEnd
             | EditCommand::CutToEnd
+            | EditCommand::CutToVisualLineEnd
             | EditCommand::CutWordLeft
             | EditCommand::CutBigWordLeft
             | EditCommand::CutWordRight
@@ -430,10 +601,12 @@ impl EditCommand {
             | EditCommand::CutBigWordRightToNext
             | EditCommand::PasteCutBufferBefore
             | EditCommand::PasteCutBufferAfter
+            | EditCommand::YankPop
             | EditCommand::UppercaseWord
             | EditCommand::LowercaseWord
             | EditCommand::SwitchcaseChar
             | EditCommand::CapitalizeChar
+            | EditCommand::CapitalizeWord
             | EditCommand::SwapWords
             | EditCommand::SwapGraphemes
             | EditCommand::CutRightUntil(_)
@@ -441,6 +614,7 @@ impl EditCommand {
             | EditCommand::CutLeftUntil(_)
             | EditCommand::CutLeftBefore(_)
             | EditCommand::CutSelection
+            | EditCommand::IndentSelection
             | EditCommand::Paste => EditType::EditText,
 
             #[cfg(feature = "system_clipboard")] // Sadly cfg attributes in patterns don't work
@@ -448,7 +622,23 @@ impl EditCommand {
 
             EditCommand::Undo | EditCommand::Redo => EditType::UndoRedo,
 
-            EditCommand::CopySelection => EditType::NoOp,
+            EditCommand::CopySelection
+            | EditCommand::CopyCurrentLine
+            | EditCommand::CopyFromLineStart
+            | EditCommand::CopyToLineEnd
+            | EditCommand::CopyWordLeft
+            | EditCommand::CopyBigWordLeft
+            | EditCommand::CopyWordRight
+            | EditCommand::CopyBigWordRight
+            | EditCommand::CopyWordRightToNext
+            | EditCommand::CopyBigWordRightToNext
+            | EditCommand::CopyLeft
+            | EditCommand::CopyRight
+            | EditCommand::CopyRightUntil(_)
+            | EditCommand::CopyRightBefore(_)
+            | EditCommand::CopyLeftUntil(_)
+            | EditCommand::CopyLeftBefore(_)
+            | EditCommand::SelectRegister(_) => EditType::NoOp,
             #[cfg(feature = "system_clipboard")]
             EditCommand::CopySelectionSystem => EditType::NoOp,
         }
@@ -469,6 +659,18 @@ pub enum EditType {
     NoOp,
 }
 
+/// Extent of the selection between its anchor and the insertion point
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelectionMode {
+    /// Up to the insertion point
+    #[default]
+    Exclusive,
+    /// Including the grapheme right of the insertion point, like the vi visual mode
+    Inclusive,
+    /// The whole lines of both ends, like the vi visual line mode
+    Lines,
+}
+
 /// Every line change should come with an `UndoBehavior` tag, which can be used to
 /// calculate how the change should be reflected on the undo stack
 #[derive(Debug)]
@@ -492,7 +694,78 @@ pub enum UndoBehavior {
     UndoRedo,
 }
 
+/// Whether [`ReedlineEvent::Enter`] submits the buffer or inserts a newline
+///
+/// Only `Enter` follows it: [`ReedlineEvent::Submit`] always submits and
+/// [`ReedlineEvent::InsertNewline`] always inserts a newline, so binding them to other keys
+/// (e.g. `Alt-Enter`) gives the other choice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnterBehavior {
+    /// Submits the buffer, or inserts a newline when the [`crate::Validator`] finds it
+    /// incomplete
+    #[default]
+    Validate,
+    /// Always submits the buffer, without running the validator
+    Submit,
+    /// Always inserts a newline, without running the validator
+    Newline,
+}
+
+/// How the lines of the buffer wider than the terminal are painted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineOverflow {
+    /// The terminal wraps them on the next rows
+    #[default]
+    Wrap,
+    /// They stay on a single row, scrolled horizontally to keep the cursor visible like in a
+    /// text field
+    ///
+    /// All the lines are scrolled together, the prompt and the multiline indicators staying
+    /// in place. The explicit new lines of the buffer are still painted on separate rows.
+    Scroll,
+}
+
+/// What opening a completion menu (e.g. with `Tab`) does first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompletionStrategy {
+    /// Opens the menu at once, first completing the common prefix of the suggestions if
+    /// [`crate::Reedline::with_partial_completions`] is on
+    #[default]
+    MenuFirst,
+    /// Like bash, completes the longest common prefix of the suggestions without opening the
+    /// menu, and only opens it once no more characters can be added (e.g. on the next `Tab`)
+    ///
+    /// A single suggestion is accepted at once. The menus that can't partially complete,
+    /// such as the history menu, are opened as usual.
+    LongestCommonPrefix,
+}
+
+/// How successive insertions, or deletions, of characters are grouped into a single undo step
+///
+/// Insertions and deletions are never grouped together, and moving the cursor or any
+/// other edit always ends a group.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UndoGranularity {
+    /// Every inserted or deleted character is undone on its own
+    Character,
+    /// Characters are undone a word and its adjacent whitespace at a time
+    #[default]
+    Word,
+    /// Like [`UndoGranularity::Word`], also ending a group when editing resumes after a
+    /// pause of at least the given duration
+    WordOrPause(Duration),
+}
+
 impl UndoBehavior {
+    /// Whether this is the insertion or deletion of a character, grouped as set by the
+    /// [`UndoGranularity`]
+    pub(crate) const fn is_character_edit(&self) -> bool {
+        matches!(
+            self,
+            UndoBehavior::InsertCharacter(_) | UndoBehavior::Backspace(_) | UndoBehavior::Delete(_)
+        )
+    }
+
     /// Return if the current operation should start a new undo set, or be
     /// combined with the previous operation
     pub fn create_undo_point_after(&self, previous: &UndoBehavior) -> bool {
@@ -553,29 +826,62 @@ pub enum ReedlineEvent {
     CtrlC,
 
     /// Clears the screen and sets prompt to first line
+    ///
+    /// The current input and an active menu are kept and repainted below the prompt
     ClearScreen,
 
     /// Clears the screen and the scrollback buffer
     ///
-    /// Sets the prompt back to the first line
+    /// Sets the prompt back to the first line, keeping the current input and an active menu
     ClearScrollback,
 
     /// Handle enter event
+    ///
+    /// Submits the buffer or inserts a newline as set with
+    /// [`crate::Reedline::with_enter_behavior`], by default depending on the validator
     Enter,
 
     /// Handle unconditional submit event
     Submit,
 
     /// Submit at the end of the *complete* text, otherwise newline
+    ///
+    /// The text is complete if the validator finds it so, whatever the [`EnterBehavior`]
     SubmitOrNewline,
 
+    /// Insert a newline, whatever the validator and the [`EnterBehavior`]
+    InsertNewline,
+
     /// Esc event
     Esc,
 
     /// Mouse
     Mouse, // Fill in details later
 
+    /// Left mouse button pressed at `column` and `row` of the screen
+    ///
+    /// Moves the cursor to the clicked character of the buffer, or selects the clicked value of
+    /// the active menu. Only sent with [`crate::Reedline::use_mouse_capture`].
+    MouseClick {
+        /// Column of the screen, from 0
+        column: u16,
+        /// Row of the screen, from 0
+        row: u16,
+    },
+
+    /// Mouse moved to `column` and `row` of the screen with the left button held
+    ///
+    /// Selects the buffer from the last click to the character under the mouse.
+    MouseDrag {
+        /// Column of the screen, from 0
+        column: u16,
+        /// Row of the screen, from 0
+        row: u16,
+    },
+
     /// trigger terminal resize
+    ///
+    /// Repaints the prompt, the buffer and the active menu wrapped to the new width
     Resize(u16, u16),
 
     /// Run these commands in the editor
@@ -602,9 +908,30 @@ pub enum ReedlineEvent {
     /// Navigate to the next historic buffer
     NextHistory,
 
+    /// Navigate to the previous historic buffer starting with the text before the cursor, like
+    /// the `history-search-backward` of bash
+    ///
+    /// The text is the prefix of the whole browsing, the cursor staying at its end. Once text is
+    /// typed, the next browsing starts with the longer prefix.
+    HistoryPrefixUp,
+
+    /// Navigate to the next historic buffer starting with the text before the cursor, like the
+    /// `history-search-forward` of bash
+    ///
+    /// Past the newest one, the line edited before the browsing is back.
+    HistoryPrefixDown,
+
     /// Search the history for a string
+    ///
+    /// Repeating it during the search moves to the previous match, going around to the most
+    /// recent one after the oldest
     SearchHistory,
 
+    /// Search the history for a string, moving to the next match when searching
+    ///
+    /// Goes around to the oldest match after the most recent one
+    SearchHistoryForward,
+
     /// In vi mode multiple reedline events can be chained while parsing the
     /// command or movement characters
     Multiple(Vec<ReedlineEvent>),
@@ -615,6 +942,31 @@ pub enum ReedlineEvent {
     /// Trigger a menu event. It activates a menu with the event name
     Menu(String),
 
+    /// Accept the suggestion of the menu with the event name without showing it, if the
+    /// completer returns a single one, or else open the menu
+    ///
+    /// The first suggestion is accepted among several with
+    /// [`crate::Reedline::with_accept_best_completion`]. It replaces the same span of the buffer
+    /// as the menu would. Not bound by default, e.g. bind it to `Alt-/` and keep `Tab` opening
+    /// the menu
+    AcceptTopCompletion(String),
+
+    /// Accept the suggestion selected in the active menu, then complete the text after it
+    /// right away if the suggestion sets [`crate::Suggestion::continue_completion`]
+    ///
+    /// Lets a deep path be completed one directory after the other, the menu reopening with
+    /// the entries of each accepted directory. The other suggestions are accepted and close the
+    /// menu, like with [`ReedlineEvent::Enter`]. Not bound by default, e.g. bind `Enter` to
+    /// `UntilFound(vec![AcceptAndContinue, Enter])`
+    AcceptAndContinue,
+
+    /// Accept again the last suggestion accepted from a completion menu, at the cursor
+    ///
+    /// The suggestion is only inserted if the text it replaced is still around the cursor,
+    /// e.g. once the completion is undone. Otherwise the event is inapplicable, and the next
+    /// event of an `UntilFound` is tried. Not bound by default
+    RepeatLastCompletion,
+
     /// Next element in the menu
     MenuNext,
 
@@ -642,8 +994,62 @@ pub enum ReedlineEvent {
     /// Way to bind the execution of a whole command (directly returning from [`crate::Reedline::read_line()`]) to a keybinding
     ExecuteHostCommand(String),
 
-    /// Open text editor
+    /// Edit the buffer in a text editor, replacing it when the editor exits successfully
+    ///
+    /// The editor is set with [`crate::Reedline::with_buffer_editor`], or else is `$EDITOR`,
+    /// `$VISUAL`, then `vi`. Bash's `Ctrl-x Ctrl-e`
     OpenEditor,
+
+    /// Stop the process like `Ctrl-z` does in the cooked mode, painting the prompt again once
+    /// it is resumed (e.g. with `fg`)
+    ///
+    /// The terminal is restored while the process is stopped. Not bound by default, only
+    /// handled on unix with the `suspend` feature (see
+    /// [`crate::Reedline::use_suspend_handling`])
+    Suspend,
+
+    /// Run the command registered under the given name with [`crate::Reedline::with_command`]
+    ///
+    /// Its edits of the buffer are undone in a single step.
+    RunCommand(String),
+
+    /// Insert text pasted in the terminal with bracketed paste
+    ///
+    /// The text is inserted as is in a single undo step, without completions nor auto-pairs.
+    /// Line endings are inserted as newlines, except a trailing one that would submit the
+    /// line.
+    Paste(String),
+
+    /// Start recording the next events as a macro in the given register
+    ///
+    /// Vi's `q<register>`, Emacs' `Ctrl-x (`
+    RecordMacro(char),
+
+    /// Stop recording the macro
+    ///
+    /// Vi's `q`, Emacs' `Ctrl-x )`
+    StopMacro,
+
+    /// Replay the events of the macro recorded in the given register
+    ///
+    /// A macro replaying itself, directly or from another macro, is not replayed again.
+    /// Vi's `@<register>`
+    PlayMacro(char),
+
+    /// Replay the last played, or else last recorded, macro
+    ///
+    /// Vi's `@@`, Emacs' `Ctrl-x e`
+    PlayLastMacro,
+
+    /// Start the numeric argument repeating the next event, or multiply it by four
+    ///
+    /// Handled by the Emacs edit mode, Emacs' `Ctrl-u`
+    UniversalArgument,
+
+    /// Append a digit to the numeric argument repeating the next event
+    ///
+    /// Handled by the Emacs edit mode, Emacs' `Alt-<digit>`
+    DigitArgument(u8),
 }
 
 impl Display for ReedlineEvent {
@@ -659,8 +1065,11 @@ impl Display for ReedlineEvent {
             ReedlineEvent::Enter => write!(f, "Enter"),
             ReedlineEvent::Submit => write!(f, "Submit"),
             ReedlineEvent::SubmitOrNewline => write!(f, "SubmitOrNewline"),
+            ReedlineEvent::InsertNewline => write!(f, "InsertNewline"),
             ReedlineEvent::Esc => write!(f, "Esc"),
             ReedlineEvent::Mouse => write!(f, "Mouse"),
+            ReedlineEvent::MouseClick { .. } => write!(f, "MouseClick <int> <int>"),
+            ReedlineEvent::MouseDrag { .. } => write!(f, "MouseDrag <int> <int>"),
             ReedlineEvent::Resize(_, _) => write!(f, "Resize <int> <int>"),
             ReedlineEvent::Edit(_) => write!(
                 f,
@@ -673,10 +1082,18 @@ impl Display for ReedlineEvent {
             ReedlineEvent::Right => write!(f, "Right"),
             ReedlineEvent::Left => write!(f, "Left"),
             ReedlineEvent::NextHistory => write!(f, "NextHistory"),
+            ReedlineEvent::HistoryPrefixUp => write!(f, "HistoryPrefixUp"),
+            ReedlineEvent::HistoryPrefixDown => write!(f, "HistoryPrefixDown"),
             ReedlineEvent::SearchHistory => write!(f, "SearchHistory"),
+            ReedlineEvent::SearchHistoryForward => write!(f, "SearchHistoryForward"),
             ReedlineEvent::Multiple(_) => write!(f, "Multiple[ {{ ReedLineEvents, }} ]"),
             ReedlineEvent::UntilFound(_) => write!(f, "UntilFound [ {{ ReedLineEvents, }} ]"),
             ReedlineEvent::Menu(_) => write!(f, "Menu Name: <string>"),
+            ReedlineEvent::AcceptTopCompletion(_) => {
+                write!(f, "AcceptTopCompletion Name: <string>")
+            }
+            ReedlineEvent::AcceptAndContinue => write!(f, "AcceptAndContinue"),
+            ReedlineEvent::RepeatLastCompletion => write!(f, "RepeatLastCompletion"),
             ReedlineEvent::MenuNext => write!(f, "MenuNext"),
             ReedlineEvent::MenuPrevious => write!(f, "MenuPrevious"),
             ReedlineEvent::MenuUp => write!(f, "MenuUp"),
@@ -687,6 +1104,15 @@ impl Display for ReedlineEvent {
             ReedlineEvent::MenuPagePrevious => write!(f, "MenuPagePrevious"),
             ReedlineEvent::ExecuteHostCommand(_) => write!(f, "ExecuteHostCommand"),
             ReedlineEvent::OpenEditor => write!(f, "OpenEditor"),
+            ReedlineEvent::Suspend => write!(f, "Suspend"),
+            ReedlineEvent::RunCommand(_) => write!(f, "RunCommand <name>"),
+            ReedlineEvent::Paste(_) => write!(f, "Paste Value: <string>"),
+            ReedlineEvent::RecordMacro(_) => write!(f, "RecordMacro <char>"),
+            ReedlineEvent::StopMacro => write!(f, "StopMacro"),
+            ReedlineEvent::PlayMacro(_) => write!(f, "PlayMacro <char>"),
+            ReedlineEvent::PlayLastMacro => write!(f, "PlayLastMacro"),
+            ReedlineEvent::UniversalArgument => write!(f, "UniversalArgument"),
+            ReedlineEvent::DigitArgument(_) => write!(f, "DigitArgument <digit>"),
         }
     }
 }
@@ -0,0 +1,263 @@
+use crate::highlighter::Highlighter;
+use crate::StyledText;
+use nu_ansi_term::Style;
+
+/// How a [`ChainedHighlighter`] combines the style of a highlighter with the style given to
+/// the same text by the highlighters before it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StyleMerge {
+    /// The later style replaces the earlier one
+    #[default]
+    Override,
+    /// The later colors replace the earlier ones when they are set, and the attributes (bold,
+    /// underline...) of both styles are kept
+    Blend,
+}
+
+impl StyleMerge {
+    fn merge(self, earlier: Style, later: Style) -> Style {
+        if later == Style::default() {
+            return earlier;
+        }
+        match self {
+            StyleMerge::Override => later,
+            StyleMerge::Blend => Style {
+                foreground: later.foreground.or(earlier.foreground),
+                background: later.background.or(earlier.background),
+                is_bold: earlier.is_bold || later.is_bold,
+                is_dimmed: earlier.is_dimmed || later.is_dimmed,
+                is_italic: earlier.is_italic || later.is_italic,
+                is_underline: earlier.is_underline || later.is_underline,
+                is_blink: earlier.is_blink || later.is_blink,
+                is_reverse: earlier.is_reverse || later.is_reverse,
+                is_hidden: earlier.is_hidden || later.is_hidden,
+                is_strikethrough: earlier.is_strikethrough || later.is_strikethrough,
+                prefix_with_reset: earlier.prefix_with_reset || later.prefix_with_reset,
+            },
+        }
+    }
+}
+
+/// A highlighter running several highlighters in order, stacking their styles
+///
+/// Each highlighter styles the whole line, then its styles are merged byte range by byte range
+/// into the ones of the highlighters before it with the [`StyleMerge`] policy:
+///
+/// - Text left in the default style by a highlighter keeps its earlier style, so a highlighter
+///   only styling a few ranges (like [`crate::SimpleMatchHighlighter`]) doesn't erase the
+///   others.
+/// - Otherwise the last highlighter wins with [`StyleMerge::Override`] (the default), while
+///   [`StyleMerge::Blend`] only replaces the colors it sets and adds up the attributes.
+///
+/// A highlighter whose text isn't the line it was given is ignored. The chained highlighters
+/// always style the whole line: their [`Highlighter::highlight_changes`] isn't used.
+///
+/// # Example
+///
+/// ```rust
+/// use reedline::{
+///     ChainedHighlighter, ExampleHighlighter, Reedline, SimpleMatchHighlighter, StyleMerge,
+/// };
+///
+/// let highlighter = ChainedHighlighter::new()
+///     .with_highlighter(Box::new(ExampleHighlighter::new(vec!["test".into()])))
+///     .with_highlighter(Box::new(SimpleMatchHighlighter::new("query".into())))
+///     .with_merge(StyleMerge::Blend);
+///
+/// let mut line_editor = Reedline::create().with_highlighter(Box::new(highlighter));
+/// ```
+#[derive(Default)]
+pub struct ChainedHighlighter {
+    highlighters: Vec<Box<dyn Highlighter>>,
+    merge: StyleMerge,
+}
+
+impl ChainedHighlighter {
+    /// Creates a highlighter without any sub-highlighter, leaving the line unstyled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder appending a highlighter, whose styles are merged over the earlier ones
+    #[must_use]
+    pub fn with_highlighter(mut self, highlighter: Box<dyn Highlighter>) -> Self {
+        self.highlighters.push(highlighter);
+        self
+    }
+
+    /// A builder setting how the styles on the same text are merged
+    #[must_use]
+    pub fn with_merge(mut self, merge: StyleMerge) -> Self {
+        self.merge = merge;
+        self
+    }
+}
+
+impl Highlighter for ChainedHighlighter {
+    fn highlight(&self, line: &str, cursor: usize) -> StyledText {
+        let mut styles = vec![(line.len(), Style::default())];
+        for highlighter in &self.highlighters {
+            let styled_text = highlighter.highlight(line, cursor);
+            if styled_text.raw_string() == line {
+                styles = self.merge_styles(&styles, &styled_text);
+            }
+        }
+
+        let mut merged = StyledText::new();
+        let mut start = 0;
+        for (end, style) in styles {
+            if start < end {
+                merged.push((style, line[start..end].to_string()));
+            }
+            start = end;
+        }
+        merged
+    }
+
+    /// Only if every highlighter is fine with a window of the buffer
+    fn visible_window_only(&self) -> bool {
+        !self.highlighters.is_empty()
+            && self
+                .highlighters
+                .iter()
+                .all(|highlighter| highlighter.visible_window_only())
+    }
+}
+
+impl ChainedHighlighter {
+    /// Merges the styles of `styled_text` into `styles`, both given as the end of each of
+    /// their ranges
+    fn merge_styles(
+        &self,
+        styles: &[(usize, Style)],
+        styled_text: &StyledText,
+    ) -> Vec<(usize, Style)> {
+        let mut later = Vec::with_capacity(styled_text.buffer.len());
+        let mut end = 0;
+        for (style, text) in &styled_text.buffer {
+            end += text.len();
+            later.push((end, *style));
+        }
+
+        let mut merged: Vec<(usize, Style)> = Vec::new();
+        let (mut earlier, mut later) = (styles.iter().peekable(), later.iter().peekable());
+        while let (Some(&&(earlier_end, earlier_style)), Some(&&(later_end, later_style))) =
+            (earlier.peek(), later.peek())
+        {
+            let end = earlier_end.min(later_end);
+            let style = self.merge.merge(earlier_style, later_style);
+            match merged.last_mut() {
+                Some((last_end, last_style)) if *last_style == style => *last_end = end,
+                _ => merged.push((end, style)),
+            }
+            if earlier_end == end {
+                earlier.next();
+            }
+            if later_end == end {
+                later.next();
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RangeHighlighter;
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::ops::Range;
+
+    fn ranges(ranges: Vec<(Range<usize>, Style)>) -> Box<dyn Highlighter> {
+        Box::new(RangeHighlighter::new(move |_| ranges.clone()))
+    }
+
+    fn keywords() -> Box<dyn Highlighter> {
+        ranges(vec![
+            (0..3, Style::new().fg(Color::Green)),
+            (8..10, Style::new().fg(Color::Green)),
+        ])
+    }
+
+    fn emphasis() -> Box<dyn Highlighter> {
+        ranges(vec![
+            (4..5, Style::new().bold().fg(Color::Blue)),
+            (8..9, Style::new().bold()),
+        ])
+    }
+
+    #[rstest]
+    #[case(StyleMerge::Override, vec![
+        (Style::new().fg(Color::Green), "let"),
+        (Style::default(), " "),
+        (Style::new().bold().fg(Color::Blue), "("),
+        (Style::default(), "x) "),
+        (Style::new().bold(), "i"),
+        (Style::new().fg(Color::Green), "f"),
+    ])]
+    #[case(StyleMerge::Blend, vec![
+        (Style::new().fg(Color::Green), "let"),
+        (Style::default(), " "),
+        (Style::new().bold().fg(Color::Blue), "("),
+        (Style::default(), "x) "),
+        (Style::new().bold().fg(Color::Green), "i"),
+        (Style::new().fg(Color::Green), "f"),
+    ])]
+    fn test_merged_styles(#[case] merge: StyleMerge, #[case] expected: Vec<(Style, &str)>) {
+        let highlighter = ChainedHighlighter::new()
+            .with_highlighter(keywords())
+            .with_highlighter(emphasis())
+            .with_merge(merge);
+
+        assert_eq!(
+            highlighter.highlight("let (x) if", 0).buffer,
+            expected
+                .into_iter()
+                .map(|(style, text)| (style, text.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn later_styles_override_the_earlier_ones() {
+        let red = Style::new().fg(Color::Red);
+        let highlighter = ChainedHighlighter::new()
+            .with_highlighter(keywords())
+            .with_highlighter(ranges(vec![(1..9, red)]));
+
+        assert_eq!(
+            highlighter.highlight("let (x) if", 0).buffer,
+            vec![
+                (Style::new().fg(Color::Green), "l".to_string()),
+                (red, "et (x) i".to_string()),
+                (Style::new().fg(Color::Green), "f".to_string()),
+            ]
+        );
+    }
+
+    /// Styles another text than the line
+    struct WrongHighlighter;
+
+    impl Highlighter for WrongHighlighter {
+        fn highlight(&self, _line: &str, _cursor: usize) -> StyledText {
+            StyledText {
+                buffer: vec![(Style::new().fg(Color::Red), "other".to_string())],
+            }
+        }
+    }
+
+    #[test]
+    fn highlighters_changing_the_text_are_ignored() {
+        let highlighter = ChainedHighlighter::new()
+            .with_highlighter(keywords())
+            .with_highlighter(Box::new(WrongHighlighter));
+
+        assert_eq!(
+            highlighter.highlight("let", 0).buffer,
+            vec![(Style::new().fg(Color::Green), "let".to_string())]
+        );
+        assert!(ChainedHighlighter::new().highlight("", 0).buffer.is_empty());
+    }
+}
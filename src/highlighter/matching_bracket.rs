@@ -0,0 +1,193 @@
+use crate::highlighter::Highlighter;
+use crate::StyledText;
+use nu_ansi_term::{Color, Style};
+
+/// Opening and closing brackets matched against each other
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Delimiters of the string literals in which brackets are ignored
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+
+/// Highlight the bracket under or right before the cursor together with its matching bracket
+///
+/// Wraps another [`Highlighter`] and restyles the brackets over its output. Nesting is
+/// respected and brackets inside string literals are ignored. A bracket without a match is
+/// highlighted alone in the error style.
+///
+/// Default style:
+///
+/// - matching brackets: Bold cyan foreground color
+/// - unbalanced bracket: Bold red foreground color
+pub struct MatchingBracketHighlighter {
+    inner: Box<dyn Highlighter>,
+    match_style: Style,
+    error_style: Style,
+}
+
+impl Highlighter for MatchingBracketHighlighter {
+    fn highlight(&self, line: &str, cursor: usize) -> StyledText {
+        let mut styled_text = self.inner.highlight(line, cursor);
+
+        match find_matching_bracket(line, cursor) {
+            Some(BracketMatch::Balanced(bracket, partner)) => {
+                styled_text.style_range(bracket, bracket + 1, self.match_style);
+                styled_text.style_range(partner, partner + 1, self.match_style);
+            }
+            Some(BracketMatch::Unbalanced(bracket)) => {
+                styled_text.style_range(bracket, bracket + 1, self.error_style);
+            }
+            None => {}
+        }
+
+        styled_text
+    }
+}
+
+impl MatchingBracketHighlighter {
+    /// Create a highlighter adding the bracket matching on top of `inner`
+    pub fn new(inner: Box<dyn Highlighter>) -> Self {
+        Self {
+            inner,
+            match_style: Style::new().bold().fg(Color::Cyan),
+            error_style: Style::new().bold().fg(Color::Red),
+        }
+    }
+
+    /// Set style for the bracket at the cursor and its matching bracket
+    #[must_use]
+    pub fn with_match_style(mut self, match_style: Style) -> Self {
+        self.match_style = match_style;
+        self
+    }
+
+    /// Set style for the bracket at the cursor when it has no matching bracket
+    #[must_use]
+    pub fn with_error_style(mut self, error_style: Style) -> Self {
+        self.error_style = error_style;
+        self
+    }
+}
+
+/// Byte offsets of the bracket at the cursor and of its partner if any
+#[derive(Debug, PartialEq, Eq)]
+enum BracketMatch {
+    Balanced(usize, usize),
+    Unbalanced(usize),
+}
+
+/// Match the bracket at `cursor`, or else the one right before it, against the other
+/// brackets of the line outside of string literals
+fn find_matching_bracket(line: &str, cursor: usize) -> Option<BracketMatch> {
+    let brackets = brackets_outside_strings(line);
+
+    let at_cursor = brackets.iter().position(|(index, _)| *index == cursor);
+    let before_cursor = || {
+        let before = line.get(..cursor)?.chars().next_back()?;
+        brackets
+            .iter()
+            .position(|(index, _)| *index == cursor - before.len_utf8())
+    };
+    let bracket = at_cursor.or_else(before_cursor)?;
+
+    // Pair the brackets like a parser would, a closing bracket not matching the innermost
+    // open one being left unbalanced
+    let mut partners = vec![None; brackets.len()];
+    let mut open = Vec::new();
+    for (position, (_, c)) in brackets.iter().enumerate() {
+        if BRACKETS.iter().any(|(opening, _)| opening == c) {
+            open.push(position);
+        } else if let Some(&last) = open.last() {
+            if BRACKETS.contains(&(brackets[last].1, *c)) {
+                open.pop();
+                partners[last] = Some(position);
+                partners[position] = Some(last);
+            }
+        }
+    }
+
+    let index = brackets[bracket].0;
+    Some(match partners[bracket] {
+        Some(partner) => BracketMatch::Balanced(index, brackets[partner].0),
+        None => BracketMatch::Unbalanced(index),
+    })
+}
+
+/// Byte offsets and characters of the brackets not inside a string literal
+fn brackets_outside_strings(line: &str) -> Vec<(usize, char)> {
+    let mut brackets = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if QUOTES.contains(&c) => quote = Some(c),
+            None if BRACKETS
+                .iter()
+                .any(|(opening, closing)| *opening == c || *closing == c) =>
+            {
+                brackets.push((index, c))
+            }
+            None => {}
+        }
+    }
+
+    brackets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("(a)", 0, Some(BracketMatch::Balanced(0, 2)))]
+    #[case("(a)", 3, Some(BracketMatch::Balanced(2, 0)))]
+    #[case("(a)", 1, Some(BracketMatch::Balanced(0, 2)))]
+    #[case("a(b)", 0, None)]
+    #[case("f(a[0], {b: (c)})", 1, Some(BracketMatch::Balanced(1, 16)))]
+    #[case("f(a[0], {b: (c)})", 8, Some(BracketMatch::Balanced(8, 15)))]
+    #[case("f(a[0], {b: (c)})", 6, Some(BracketMatch::Balanced(5, 3)))]
+    #[case("(\")\" )", 0, Some(BracketMatch::Balanced(0, 5)))]
+    #[case("(\"\\\")\" )", 0, Some(BracketMatch::Balanced(0, 7)))]
+    #[case("echo '(' )", 6, None)]
+    #[case("echo '(' )", 9, Some(BracketMatch::Unbalanced(9)))]
+    #[case("(a", 0, Some(BracketMatch::Unbalanced(0)))]
+    #[case("(]", 0, Some(BracketMatch::Unbalanced(0)))]
+    #[case("(]", 1, Some(BracketMatch::Unbalanced(1)))]
+    #[case("(é)", 3, Some(BracketMatch::Balanced(3, 0)))]
+    #[case("(é)", 4, Some(BracketMatch::Balanced(3, 0)))]
+    fn test_find_matching_bracket(
+        #[case] line: &str,
+        #[case] cursor: usize,
+        #[case] expected: Option<BracketMatch>,
+    ) {
+        assert_eq!(find_matching_bracket(line, cursor), expected);
+    }
+
+    #[test]
+    fn test_highlight_wraps_inner_highlighter() {
+        let neutral_style = Style::new().fg(Color::White);
+        let match_style = Style::new().underline();
+        let highlighter = MatchingBracketHighlighter::new(Box::new(
+            crate::SimpleMatchHighlighter::default().with_neutral_style(neutral_style),
+        ))
+        .with_match_style(match_style);
+
+        let styled_text = highlighter.highlight("f(x)", 1);
+
+        assert_eq!(
+            styled_text.buffer,
+            vec![
+                (neutral_style, "f".to_string()),
+                (match_style, "(".to_string()),
+                (neutral_style, "x".to_string()),
+                (match_style, ")".to_string()),
+            ]
+        );
+    }
+}
@@ -1,9 +1,17 @@
+mod chained;
 mod example;
+mod matching_bracket;
+mod range;
 mod simple_match;
 
 use crate::StyledText;
+use nu_ansi_term::Style;
+use std::ops::Range;
 
+pub use chained::{ChainedHighlighter, StyleMerge};
 pub use example::ExampleHighlighter;
+pub use matching_bracket::MatchingBracketHighlighter;
+pub use range::RangeHighlighter;
 pub use simple_match::SimpleMatchHighlighter;
 /// The syntax highlighting trait. Implementers of this trait will take in the current string and then
 /// return a `StyledText` object, which represents the contents of the original line as styled strings
@@ -12,4 +20,431 @@ pub trait Highlighter: Send {
     ///
     /// Cursor position as byte offsets in the string
     fn highlight(&self, line: &str, cursor: usize) -> StyledText;
+
+    /// Whether the highlighter only needs the lines of the buffer that can be on the screen
+    ///
+    /// If so, [`Highlighter::highlight`] is given the lines around the cursor, at most as many
+    /// above and below it as the screen has rows, and the rest of the buffer is left unstyled.
+    /// This keeps an expensive highlighter fast on huge buffers, at the cost of highlighting
+    /// the lines without the context of the ones before them.
+    ///
+    /// Defaults to `false`, the whole buffer being highlighted.
+    fn visible_window_only(&self) -> bool {
+        false
+    }
+
+    /// Restyles only the part of the buffer edited since the last highlighting
+    ///
+    /// `changed` is the range of `line` differing from the buffer of the last call, empty if
+    /// only the cursor moved. The highlighter returns the styles of a part of `line` covering
+    /// it, and the rest of the buffer keeps its previous styles. The [`HighlightExtent`] of the
+    /// patch says how far the edit restyled the buffer, e.g. to the end of the buffer for an
+    /// opened string quote.
+    ///
+    /// Defaults to `None`, the whole buffer being highlighted again with
+    /// [`Highlighter::highlight`], which is also done when the patch doesn't match `line`. Not
+    /// used with [`Highlighter::visible_window_only`], nor by a [`ChainedHighlighter`], which
+    /// highlights the whole buffer again even if its highlighters implement this.
+    fn highlight_changes(
+        &self,
+        line: &str,
+        cursor: usize,
+        changed: Range<usize>,
+    ) -> Option<HighlightPatch> {
+        let _ = (line, cursor, changed);
+        None
+    }
+}
+
+/// How far the styles of a [`HighlightPatch`] reach in the buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HighlightExtent {
+    /// Only the text of the patch is restyled
+    #[default]
+    Patch,
+    /// The patch goes on to the end of the line the edit ends on
+    EndOfLine,
+    /// The patch goes on to the end of the buffer
+    EndOfBuffer,
+}
+
+/// The styles of a part of the buffer, returned by [`Highlighter::highlight_changes`]
+#[derive(Clone)]
+pub struct HighlightPatch {
+    /// Where the patch starts in the buffer, at the latest where the edit starts
+    pub start: usize,
+    /// The styled text from `start`, which must cover the edit and match the buffer up to
+    /// the end of the `extent`
+    pub styled_text: StyledText,
+    /// How far the patch reaches
+    pub extent: HighlightExtent,
+}
+
+/// The styles of the last highlighted buffer, restyled with the patches of
+/// [`Highlighter::highlight_changes`]
+#[derive(Default)]
+pub(crate) struct HighlightCache {
+    previous: Option<(String, StyledText)>,
+}
+
+impl HighlightCache {
+    /// Forgets the previous styles, e.g. when the highlighter is replaced
+    pub fn clear(&mut self) {
+        self.previous = None;
+    }
+
+    /// Highlights the buffer like [`highlight_visible`], only its edited part if the
+    /// highlighter supports it
+    pub fn highlight(
+        &mut self,
+        highlighter: &dyn Highlighter,
+        line: &str,
+        cursor: usize,
+        screen_height: usize,
+    ) -> StyledText {
+        if highlighter.visible_window_only() {
+            self.previous = None;
+            return highlight_visible(highlighter, line, cursor, screen_height);
+        }
+
+        // Without previous styles, the whole buffer changed
+        let (previous_line, previous_styles) = self.previous.take().unwrap_or_default();
+        let (changed, previous_end) = changed_range(&previous_line, line);
+        let Some(patch) = highlighter.highlight_changes(line, cursor, changed.clone()) else {
+            return highlighter.highlight(line, cursor);
+        };
+
+        let styled_text = splice(&previous_styles, line, &changed, previous_end, patch)
+            .unwrap_or_else(|| highlighter.highlight(line, cursor));
+        if styled_text.raw_string() == line {
+            self.previous = Some((line.to_string(), styled_text.clone()));
+        }
+        styled_text
+    }
+}
+
+/// The range of `line` differing from `previous`, and the end of the matching range of
+/// `previous`
+fn changed_range(previous: &str, line: &str) -> (Range<usize>, usize) {
+    let mut prefix = previous
+        .bytes()
+        .zip(line.bytes())
+        .take_while(|(previous, current)| previous == current)
+        .count();
+    while !line.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let mut suffix = previous
+        .bytes()
+        .rev()
+        .zip(line.bytes().rev())
+        .take(previous.len().min(line.len()) - prefix)
+        .take_while(|(previous, current)| previous == current)
+        .count();
+    while !line.is_char_boundary(line.len() - suffix) {
+        suffix -= 1;
+    }
+    (prefix..line.len() - suffix, previous.len() - suffix)
+}
+
+/// The previous styles with the patch, `None` if it doesn't cover the edit or match `line`
+fn splice(
+    previous: &StyledText,
+    line: &str,
+    changed: &Range<usize>,
+    previous_end: usize,
+    patch: HighlightPatch,
+) -> Option<StyledText> {
+    let patched = patch.styled_text.raw_string();
+    let end = match patch.extent {
+        HighlightExtent::Patch => patch.start + patched.len(),
+        HighlightExtent::EndOfLine => line[changed.end..]
+            .find('\n')
+            .map_or(line.len(), |index| changed.end + index),
+        HighlightExtent::EndOfBuffer => line.len(),
+    };
+    if patch.start > changed.start || end < changed.end || line.get(patch.start..end)? != patched {
+        return None;
+    }
+
+    let mut styled_text = StyledText::new();
+    push_range(&mut styled_text, previous, 0..patch.start);
+    styled_text.buffer.extend(patch.styled_text.buffer);
+    push_range(
+        &mut styled_text,
+        previous,
+        end - changed.end + previous_end..usize::MAX,
+    );
+    Some(styled_text)
+}
+
+/// Pushes the styled parts of `styled_text` in `range`
+fn push_range(to: &mut StyledText, styled_text: &StyledText, range: Range<usize>) {
+    let mut start = 0;
+    for (style, text) in &styled_text.buffer {
+        let end = start + text.len();
+        let (from, until) = (range.start.max(start), range.end.min(end));
+        if from < until {
+            to.push((*style, text[from - start..until - start].to_string()));
+        }
+        start = end;
+    }
+}
+
+/// Highlights the buffer, or only its lines that can be on a screen of `screen_height` rows if
+/// the highlighter asks for it with [`Highlighter::visible_window_only`]
+fn highlight_visible(
+    highlighter: &dyn Highlighter,
+    line: &str,
+    cursor: usize,
+    screen_height: usize,
+) -> StyledText {
+    if !highlighter.visible_window_only() {
+        return highlighter.highlight(line, cursor);
+    }
+
+    // A wrapped line takes several rows, so no more lines than rows are visible on either side
+    let start = line[..cursor]
+        .rmatch_indices('\n')
+        .nth(screen_height)
+        .map_or(0, |(index, _)| index + 1);
+    let end = line[cursor..]
+        .match_indices('\n')
+        .nth(screen_height)
+        .map_or(line.len(), |(index, _)| cursor + index);
+
+    let mut styled_text = StyledText::new();
+    if start > 0 {
+        styled_text.push((Style::new(), line[..start].to_string()));
+    }
+    styled_text.buffer.extend(
+        highlighter
+            .highlight(&line[start..end], cursor - start)
+            .buffer,
+    );
+    if end < line.len() {
+        styled_text.push((Style::new(), line[end..].to_string()));
+    }
+    styled_text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExampleHighlighter;
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    /// Styles everything it is given
+    struct WindowHighlighter;
+
+    impl Highlighter for WindowHighlighter {
+        fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+            let mut styled_text = StyledText::new();
+            styled_text.push((Style::new().bold(), line.to_string()));
+            styled_text
+        }
+
+        fn visible_window_only(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn only_the_visible_window_is_highlighted() {
+        let line = "1\n2\n3\n4\n5\n6\n7";
+        // On the line "4"
+        let styled_text = highlight_visible(&WindowHighlighter, line, 6, 2);
+
+        assert_eq!(
+            styled_text.buffer,
+            vec![
+                (Style::new(), "1\n".to_string()),
+                (Style::new().bold(), "2\n3\n4\n5\n6".to_string()),
+                (Style::new(), "\n7".to_string()),
+            ]
+        );
+        assert_eq!(styled_text.raw_string(), line);
+    }
+
+    /// Styles every word on its own, for the whole buffer or only its visible window
+    struct WordHighlighter {
+        window_only: bool,
+    }
+
+    impl Highlighter for WordHighlighter {
+        fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+            let mut styled_text = StyledText::new();
+            for (index, word) in line.split_inclusive(char::is_whitespace).enumerate() {
+                let style = Style::new().fg(Color::Fixed((index % 256) as u8));
+                styled_text.push((style, word.to_string()));
+            }
+            styled_text
+        }
+
+        fn visible_window_only(&self) -> bool {
+            self.window_only
+        }
+    }
+
+    /// Time taken to highlight a pasted buffer of 5000 lines 100 times, the cursor in the middle
+    fn time_large_paste(window_only: bool) -> Duration {
+        let highlighter = WordHighlighter { window_only };
+        let line = (0..5000)
+            .map(|i| format!("echo \"line {i}\" | cargo run --release -- --count {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let cursor = line.len() / 2;
+
+        let start = Instant::now();
+        for _ in 0..100 {
+            let styled_text = highlight_visible(&highlighter, &line, cursor, 24);
+            assert_eq!(styled_text.raw_string().len(), line.len());
+        }
+        start.elapsed()
+    }
+
+    // Run with `cargo test --release -- --ignored large_paste`
+    #[test]
+    #[ignore = "Timing comparison, run on demand"]
+    fn large_paste_highlighting_is_faster_in_the_visible_window() {
+        let whole_buffer = time_large_paste(false);
+        let visible_window = time_large_paste(true);
+
+        assert!(
+            visible_window < whole_buffer,
+            "whole buffer: {whole_buffer:?}, visible window only: {visible_window:?}"
+        );
+    }
+
+    /// Styles the edited text with the number of the call, up to the end of the `extent`
+    #[derive(Default)]
+    struct PatchHighlighter {
+        calls: Cell<u8>,
+        extent: Cell<HighlightExtent>,
+    }
+
+    impl Highlighter for PatchHighlighter {
+        fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+            let mut styled_text = StyledText::new();
+            styled_text.push((Style::new(), line.to_string()));
+            styled_text
+        }
+
+        fn highlight_changes(
+            &self,
+            line: &str,
+            _cursor: usize,
+            changed: Range<usize>,
+        ) -> Option<HighlightPatch> {
+            self.calls.set(self.calls.get() + 1);
+            let end = match self.extent.get() {
+                HighlightExtent::Patch => changed.end,
+                HighlightExtent::EndOfLine => line[changed.end..]
+                    .find('\n')
+                    .map_or(line.len(), |index| changed.end + index),
+                HighlightExtent::EndOfBuffer => line.len(),
+            };
+            let mut styled_text = StyledText::new();
+            if changed.start < end {
+                let style = Style::new().fg(Color::Fixed(self.calls.get()));
+                styled_text.push((style, line[changed.start..end].to_string()));
+            }
+            Some(HighlightPatch {
+                start: changed.start,
+                styled_text,
+                extent: self.extent.get(),
+            })
+        }
+    }
+
+    fn styles(styled_text: StyledText) -> Vec<(u8, String)> {
+        styled_text
+            .buffer
+            .into_iter()
+            .map(|(style, text)| match style.foreground {
+                Some(Color::Fixed(call)) => (call, text),
+                _ => (0, text),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_the_edited_text_is_restyled() {
+        let highlighter = PatchHighlighter::default();
+        let mut cache = HighlightCache::default();
+        let mut highlight = |line: &str, extent| {
+            highlighter.extent.set(extent);
+            styles(cache.highlight(&highlighter, line, 0, 24))
+        };
+        let text = |parts: &[(u8, &str)]| -> Vec<(u8, String)> {
+            parts
+                .iter()
+                .map(|&(call, text)| (call, text.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            highlight("ab\ncd", HighlightExtent::Patch),
+            text(&[(1, "ab\ncd")])
+        );
+        assert_eq!(
+            highlight("aXb\ncd", HighlightExtent::EndOfLine),
+            text(&[(1, "a"), (2, "Xb"), (1, "\ncd")])
+        );
+        // Only the cursor moved
+        assert_eq!(
+            highlight("aXb\ncd", HighlightExtent::Patch),
+            text(&[(1, "a"), (2, "Xb"), (1, "\ncd")])
+        );
+        assert_eq!(
+            highlight("aXYb\ncd", HighlightExtent::EndOfBuffer),
+            text(&[(1, "a"), (2, "X"), (4, "Yb\ncd")])
+        );
+        assert_eq!(
+            highlight("aXY", HighlightExtent::Patch),
+            text(&[(1, "a"), (2, "X"), (4, "Y")])
+        );
+    }
+
+    #[test]
+    fn highlighters_without_patches_restyle_everything() {
+        let highlighter = ExampleHighlighter::new(vec!["ls".into()]);
+        let mut cache = HighlightCache::default();
+
+        for line in ["ls", "ls -a", "cd"] {
+            assert_eq!(
+                cache.highlight(&highlighter, line, 0, 24).buffer,
+                highlighter.highlight(line, 0).buffer
+            );
+        }
+    }
+
+    #[rstest]
+    #[case("", "abc", 0..3, 0)]
+    #[case("abc", "abc", 3..3, 3)]
+    #[case("hello world", "hello big world", 6..10, 6)]
+    #[case("aaa", "aa", 2..2, 3)]
+    #[case("é", "è", 0..2, 2)]
+    fn test_changed_range(
+        #[case] previous: &str,
+        #[case] line: &str,
+        #[case] changed: Range<usize>,
+        #[case] previous_end: usize,
+    ) {
+        assert_eq!(changed_range(previous, line), (changed, previous_end));
+    }
+
+    #[test]
+    fn short_buffers_are_fully_highlighted() {
+        let styled_text = highlight_visible(&WindowHighlighter, "1\n2", 0, 2);
+
+        assert_eq!(
+            styled_text.buffer,
+            vec![(Style::new().bold(), "1\n2".to_string())]
+        );
+    }
 }
@@ -0,0 +1,143 @@
+use crate::highlighter::Highlighter;
+use crate::StyledText;
+use nu_ansi_term::Style;
+use std::ops::Range;
+
+/// Tokenizer returning the byte ranges of the line to style
+type TokenRanges = dyn Fn(&str) -> Vec<(Range<usize>, Style)> + Send;
+
+/// Highlight a line with the styled byte ranges returned by a user-supplied tokenizer
+///
+/// Lets an external lexer drive the syntax highlighting. The ranges are applied in the
+/// order of their start. Ranges that are empty, go past the end of the line, do not fall on
+/// char boundaries or overlap a previous range are ignored. The rest of the line gets the
+/// default style.
+///
+/// Default style:
+///
+/// - text outside of the ranges: Default style
+pub struct RangeHighlighter {
+    tokenizer: Box<TokenRanges>,
+    default_style: Style,
+}
+
+impl Highlighter for RangeHighlighter {
+    fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+        let mut ranges = (self.tokenizer)(line);
+        ranges.sort_by_key(|(range, _)| range.start);
+
+        let mut styled_text = StyledText::new();
+        let mut next_idx = 0;
+        for (range, style) in ranges {
+            let valid = next_idx <= range.start
+                && range.start < range.end
+                && line.is_char_boundary(range.start)
+                && line.is_char_boundary(range.end);
+            if !valid {
+                continue;
+            }
+
+            if range.start != next_idx {
+                styled_text.push((self.default_style, line[next_idx..range.start].to_owned()));
+            }
+            styled_text.push((style, line[range.clone()].to_owned()));
+            next_idx = range.end;
+        }
+        if next_idx != line.len() {
+            styled_text.push((self.default_style, line[next_idx..].to_owned()));
+        }
+
+        styled_text
+    }
+}
+
+impl RangeHighlighter {
+    /// Create a highlighter styling the byte ranges returned by `tokenizer` for the line
+    pub fn new<F>(tokenizer: F) -> Self
+    where
+        F: Fn(&str) -> Vec<(Range<usize>, Style)> + Send + 'static,
+    {
+        Self {
+            tokenizer: Box::new(tokenizer),
+            default_style: Style::default(),
+        }
+    }
+
+    /// Set style for the text not covered by any range
+    #[must_use]
+    pub fn with_default_style(mut self, default_style: Style) -> Self {
+        self.default_style = default_style;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+
+    fn segments(line: &str, ranges: Vec<(Range<usize>, Style)>) -> Vec<(Style, String)> {
+        RangeHighlighter::new(move |_| ranges.clone())
+            .highlight(line, 0)
+            .buffer
+    }
+
+    #[test]
+    fn fills_the_gaps_with_the_default_style() {
+        let keyword = Style::new().fg(Color::Purple);
+        let number = Style::new().fg(Color::Yellow);
+
+        assert_eq!(
+            segments("let x = 42", vec![(8..10, number), (0..3, keyword)]),
+            vec![
+                (keyword, "let".to_string()),
+                (Style::default(), " x = ".to_string()),
+                (number, "42".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_invalid_ranges() {
+        let style = Style::new().fg(Color::Green);
+        let line = "é = 'ü'";
+
+        assert_eq!(
+            segments(
+                line,
+                vec![
+                    // inside of 'é'
+                    (1..2, style),
+                    (5..9, style),
+                    // overlaps the previous range
+                    (7..8, style),
+                    (3..3, style),
+                    // past the end of the line
+                    (6..100, style),
+                ]
+            ),
+            vec![
+                (Style::default(), "é = ".to_string()),
+                (style, "'ü'".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_the_tokenizer_on_the_line() {
+        let style = Style::new().bold();
+        let highlighter = RangeHighlighter::new(move |line: &str| {
+            line.match_indices("ls")
+                .map(|(idx, m)| (idx..idx + m.len(), style))
+                .collect()
+        })
+        .with_default_style(Style::new().fg(Color::White));
+
+        assert_eq!(highlighter.highlight("ls | ls", 0).raw_string(), "ls | ls");
+        assert_eq!(
+            highlighter.highlight("ls | ls", 0).buffer[2],
+            (style, "ls".to_string())
+        );
+    }
+}
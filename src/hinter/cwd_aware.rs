@@ -1,5 +1,5 @@
 use crate::{
-    hinter::get_first_token,
+    hinter::{get_first_token, history_hint},
     history::SearchQuery,
     result::{ReedlineError, ReedlineErrorVariants::HistoryFeatureUnsupported},
     Hinter, History,
@@ -66,11 +66,7 @@ impl Hinter for CwdAwareHinter {
             String::new()
         };
 
-        if use_ansi_coloring && !self.current_hint.is_empty() {
-            self.style.paint(&self.current_hint).to_string()
-        } else {
-            self.current_hint.clone()
-        }
+        history_hint(self.style, &self.current_hint, use_ansi_coloring)
     }
 
     fn complete_hint(&self) -> String {
@@ -1,4 +1,8 @@
-use crate::{hinter::get_first_token, history::SearchQuery, Hinter, History};
+use crate::{
+    hinter::{get_first_token, history_hint},
+    history::SearchQuery,
+    Hinter, History,
+};
 use nu_ansi_term::{Color, Style};
 
 /// A hinter that uses the completions or the history to show a hint to the user
@@ -35,11 +39,7 @@ impl Hinter for DefaultHinter {
             String::new()
         };
 
-        if use_ansi_coloring && !self.current_hint.is_empty() {
-            self.style.paint(&self.current_hint).to_string()
-        } else {
-            self.current_hint.clone()
-        }
+        history_hint(self.style, &self.current_hint, use_ansi_coloring)
     }
 
     fn complete_hint(&self) -> String {
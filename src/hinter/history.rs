@@ -0,0 +1,246 @@
+use crate::{
+    hinter::{get_first_token, history_hint},
+    history::{SearchDirection, SearchFilter, SearchQuery},
+    CommandLineSearch, Hinter, History,
+};
+use nu_ansi_term::{Color, Style};
+use std::{cmp::Ordering, collections::HashMap};
+
+/// How [`HistoryHinter`] picks its hint among the history entries starting with the line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HintScoring {
+    /// The most recently used entry, like [`DefaultHinter`](crate::DefaultHinter)
+    #[default]
+    MostRecent,
+    /// The most often used entry
+    MostFrequent,
+    /// The number of uses of an entry divided by its position when the entries are
+    /// ordered by their last use, so that a frequent entry beats a rare but more recent one
+    Hybrid,
+}
+
+/// A hinter that ranks the history entries starting with the line by frequency and/or recency
+///
+/// Ties are always broken in favor of the most recently used entry.
+pub struct HistoryHinter {
+    style: Style,
+    current_hint: String,
+    min_chars: usize,
+    scoring: HintScoring,
+}
+
+/// An entry of the history with the statistics used for the scoring
+struct Candidate<'a> {
+    command_line: &'a str,
+    uses: usize,
+    /// Position in the entries ordered from the most recently used one
+    recency: usize,
+}
+
+impl HintScoring {
+    /// Ordering of `a` relative to `b`, the best candidate being the greatest
+    fn compare(self, a: &Candidate, b: &Candidate) -> Ordering {
+        let by_recency = b.recency.cmp(&a.recency);
+        match self {
+            HintScoring::MostRecent => by_recency,
+            HintScoring::MostFrequent => a.uses.cmp(&b.uses).then(by_recency),
+            HintScoring::Hybrid => (a.uses * (b.recency + 1))
+                .cmp(&(b.uses * (a.recency + 1)))
+                .then(by_recency),
+        }
+    }
+}
+
+impl Hinter for HistoryHinter {
+    fn handle(
+        &mut self,
+        line: &str,
+        #[allow(unused_variables)] pos: usize,
+        history: &dyn History,
+        use_ansi_coloring: bool,
+    ) -> String {
+        self.current_hint = if line.chars().count() >= self.min_chars {
+            let query = match self.scoring {
+                HintScoring::MostRecent => {
+                    SearchQuery::last_with_prefix(line.to_string(), history.session())
+                }
+                // The frequencies need all the matching entries
+                HintScoring::MostFrequent | HintScoring::Hybrid => SearchQuery {
+                    filter: SearchFilter::from_text_search(
+                        CommandLineSearch::Prefix(line.to_string()),
+                        history.session(),
+                    ),
+                    ..SearchQuery::everything(SearchDirection::Backward, None)
+                },
+            };
+            let entries = history.search(query).unwrap_or_default();
+
+            let mut candidates: Vec<Candidate> = Vec::new();
+            let mut positions = HashMap::new();
+            for entry in &entries {
+                // An entry equal to the line has nothing left to hint
+                if entry.command_line.len() <= line.len() {
+                    continue;
+                }
+
+                let position = *positions
+                    .entry(entry.command_line.as_str())
+                    .or_insert_with(|| {
+                        candidates.push(Candidate {
+                            command_line: &entry.command_line,
+                            uses: 0,
+                            recency: candidates.len(),
+                        });
+                        candidates.len() - 1
+                    });
+                candidates[position].uses += 1;
+            }
+
+            candidates
+                .iter()
+                .max_by(|a, b| self.scoring.compare(a, b))
+                .map_or_else(String::new, |candidate| {
+                    candidate
+                        .command_line
+                        .get(line.len()..)
+                        .unwrap_or_default()
+                        .to_string()
+                })
+        } else {
+            String::new()
+        };
+
+        history_hint(self.style, &self.current_hint, use_ansi_coloring)
+    }
+
+    fn complete_hint(&self) -> String {
+        self.current_hint.clone()
+    }
+
+    fn next_hint_token(&self) -> String {
+        get_first_token(&self.current_hint)
+    }
+}
+
+impl Default for HistoryHinter {
+    fn default() -> Self {
+        HistoryHinter {
+            style: Style::new().fg(Color::LightGray),
+            current_hint: String::new(),
+            min_chars: 1,
+            scoring: HintScoring::default(),
+        }
+    }
+}
+
+impl HistoryHinter {
+    /// A builder that sets the style applied to the hint as part of the buffer
+    #[must_use]
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// A builder that sets the number of characters that have to be present to enable history hints
+    #[must_use]
+    pub fn with_min_chars(mut self, min_chars: usize) -> Self {
+        self.min_chars = min_chars;
+        self
+    }
+
+    /// A builder that sets how the hint is picked among the matching history entries
+    #[must_use]
+    pub fn with_scoring(mut self, scoring: HintScoring) -> Self {
+        self.scoring = scoring;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileBackedHistory, HistoryItem};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn history_of(command_lines: &[&str]) -> FileBackedHistory {
+        let mut history = FileBackedHistory::default();
+        for command_line in command_lines {
+            let id = history.generate_id();
+            history
+                .save(&HistoryItem::from_command_line(*command_line, id))
+                .unwrap();
+        }
+        history
+    }
+
+    #[rstest]
+    #[case(HintScoring::MostRecent, "cargo", " check")]
+    #[case(HintScoring::MostFrequent, "cargo", " test")]
+    #[case(HintScoring::Hybrid, "cargo", " test")]
+    #[case(HintScoring::MostFrequent, "cargo c", "heck")]
+    #[case(HintScoring::MostFrequent, "git", "")]
+    fn test_scoring(#[case] scoring: HintScoring, #[case] line: &str, #[case] expected: &str) {
+        let history = history_of(&[
+            "cargo test",
+            "cargo build",
+            "cargo test",
+            "ls",
+            "cargo test",
+            "cargo clippy",
+            "cargo test",
+            "cargo fmt",
+            "cargo check",
+        ]);
+        let mut hinter = HistoryHinter::default().with_scoring(scoring);
+
+        assert_eq!(hinter.handle(line, line.len(), &history, false), expected);
+        assert_eq!(hinter.complete_hint(), expected);
+    }
+
+    #[rstest]
+    #[case(HintScoring::MostFrequent, " test")]
+    #[case(HintScoring::Hybrid, " check")]
+    fn test_hybrid_favors_recent_entries(#[case] scoring: HintScoring, #[case] expected: &str) {
+        let history = history_of(&[
+            "cargo test",
+            "ls",
+            "cargo test",
+            "cargo build",
+            "cargo check",
+        ]);
+        let mut hinter = HistoryHinter::default().with_scoring(scoring);
+
+        assert_eq!(hinter.handle("cargo", 5, &history, false), expected);
+    }
+
+    #[test]
+    fn test_frequency_tie_broken_by_recency() {
+        let history = history_of(&["cargo check", "cargo build", "cargo check", "cargo build"]);
+        let mut hinter = HistoryHinter::default().with_scoring(HintScoring::MostFrequent);
+
+        assert_eq!(hinter.handle("cargo", 5, &history, false), " build");
+    }
+
+    #[test]
+    fn test_entry_equal_to_line_is_skipped() {
+        let history = history_of(&["cargo test", "cargo", "ls", "cargo"]);
+        let mut hinter = HistoryHinter::default().with_scoring(HintScoring::MostFrequent);
+
+        assert_eq!(hinter.handle("cargo", 5, &history, false), " test");
+    }
+
+    #[test]
+    fn escape_sequences_of_the_entry_are_not_painted() {
+        let history = history_of(&["echo \x1b[31mred"]);
+        let mut hinter = HistoryHinter::default().with_style(Style::new().dimmed());
+
+        assert_eq!(
+            hinter.handle("echo", 4, &history, true),
+            Style::new().dimmed().paint(" red").to_string()
+        );
+        assert_eq!(hinter.handle("echo", 4, &history, false), " red");
+        // The entry is still accepted as it is
+        assert_eq!(hinter.complete_hint(), " \x1b[31mred");
+    }
+}
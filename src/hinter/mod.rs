@@ -1,8 +1,14 @@
 mod cwd_aware;
 mod default;
+mod history;
+mod placeholder;
 pub use cwd_aware::CwdAwareHinter;
 pub use default::DefaultHinter;
+pub use history::{HintScoring, HistoryHinter};
+pub use placeholder::PlaceholderHinter;
 
+use crate::painting::strip_ansi;
+use nu_ansi_term::Style;
 use unicode_segmentation::UnicodeSegmentation;
 
 pub fn is_whitespace_str(s: &str) -> bool {
@@ -27,6 +33,19 @@ pub fn get_first_token(string: &str) -> String {
     result
 }
 
+/// A `hint` taken from a history entry, painted with `style`
+///
+/// The escape sequences the entry may contain are removed, for the style to apply to the whole
+/// hint and nothing else.
+pub(crate) fn history_hint(style: Style, hint: &str, use_ansi_coloring: bool) -> String {
+    let hint = strip_ansi(hint);
+    if use_ansi_coloring && !hint.is_empty() {
+        style.paint(&hint).to_string()
+    } else {
+        hint
+    }
+}
+
 use crate::History;
 /// A trait that's responsible for returning the hint for the current line and position
 /// Hints are often shown in-line as part of the buffer, showing the user text they can accept or ignore
@@ -48,4 +67,37 @@ pub trait Hinter: Send {
     /// Return the first semantic token of the hint
     /// for incremental completion
     fn next_hint_token(&self) -> String;
+
+    /// Whether the current hint can be accepted with [`crate::ReedlineEvent::HistoryHintComplete`]
+    /// or [`crate::ReedlineEvent::HistoryHintWordComplete`], rather than only being shown
+    /// (e.g. a placeholder of the empty line)
+    fn is_hint_acceptable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LineBuffer;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(" test --all", " test")]
+    #[case("test --all", "test")]
+    #[case("--all", "-")]
+    #[case("/path/to/file", "/")]
+    #[case("  héllo wörld", "  héllo")]
+    #[case("e\u{301}tude later", "e\u{301}tude")]
+    #[case("   ", "   ")]
+    #[case("", "")]
+    fn first_token_matches_word_motion(#[case] hint: &str, #[case] expected: &str) {
+        assert_eq!(get_first_token(hint), expected);
+
+        // Accepting a word of the hint inserts what moving a word right would skip over
+        let mut line_buffer = LineBuffer::from(hint);
+        line_buffer.set_insertion_point(0);
+        assert_eq!(&hint[..line_buffer.word_right_index()], expected);
+    }
 }
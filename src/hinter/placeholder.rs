@@ -0,0 +1,138 @@
+use crate::{hinter::get_first_token, Hinter, History};
+use nu_ansi_term::{Color, Style};
+
+/// Placeholder returning the hint for the line and the cursor position
+type Placeholder = dyn Fn(&str, usize) -> Option<String> + Send;
+
+/// A hinter showing the hints returned by a user-supplied closure, even on an empty line
+///
+/// Lets the line show a placeholder while it is empty (e.g. `type a command…`), or a
+/// contextual hint for the arguments of a command (e.g. the last target after `cd `). The
+/// hint is shown after the line, like a history hint.
+///
+/// The hints of a non-empty line are accepted like the history hints, e.g. with `Right` or
+/// `End`. The placeholder of the empty line is only shown, unless
+/// [`PlaceholderHinter::with_accept_on_empty_line`] is enabled.
+///
+/// # Example
+///
+/// ```rust
+/// use reedline::{PlaceholderHinter, Reedline};
+///
+/// let hinter = PlaceholderHinter::new(|line, _pos| match line {
+///     "" => Some("type a command…".to_string()),
+///     "cd " => Some("~/projects".to_string()),
+///     _ => None,
+/// });
+///
+/// let mut line_editor = Reedline::create().with_hinter(Box::new(hinter));
+/// ```
+pub struct PlaceholderHinter {
+    placeholder: Box<Placeholder>,
+    style: Style,
+    accept_on_empty_line: bool,
+    current_hint: String,
+    acceptable: bool,
+}
+
+impl Hinter for PlaceholderHinter {
+    fn handle(
+        &mut self,
+        line: &str,
+        pos: usize,
+        _history: &dyn History,
+        use_ansi_coloring: bool,
+    ) -> String {
+        self.current_hint = (self.placeholder)(line, pos).unwrap_or_default();
+        self.acceptable = self.accept_on_empty_line || !line.is_empty();
+
+        if use_ansi_coloring && !self.current_hint.is_empty() {
+            self.style.paint(&self.current_hint).to_string()
+        } else {
+            self.current_hint.clone()
+        }
+    }
+
+    fn complete_hint(&self) -> String {
+        self.current_hint.clone()
+    }
+
+    fn next_hint_token(&self) -> String {
+        get_first_token(&self.current_hint)
+    }
+
+    fn is_hint_acceptable(&self) -> bool {
+        self.acceptable
+    }
+}
+
+impl PlaceholderHinter {
+    /// Create a hinter showing the hint returned by `placeholder` for the line and the cursor
+    /// position, `None` showing no hint
+    pub fn new<F>(placeholder: F) -> Self
+    where
+        F: Fn(&str, usize) -> Option<String> + Send + 'static,
+    {
+        Self {
+            placeholder: Box::new(placeholder),
+            style: Style::new().fg(Color::DarkGray).italic(),
+            accept_on_empty_line: false,
+            current_hint: String::new(),
+            acceptable: false,
+        }
+    }
+
+    /// A builder that sets the style applied to the hint as part of the buffer
+    #[must_use]
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// A builder that sets whether the hint of the empty line can be accepted like the other
+    /// hints, rather than being a placeholder only shown
+    #[must_use]
+    pub fn with_accept_on_empty_line(mut self, accept_on_empty_line: bool) -> Self {
+        self.accept_on_empty_line = accept_on_empty_line;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileBackedHistory;
+    use pretty_assertions::assert_eq;
+
+    fn hinter() -> PlaceholderHinter {
+        PlaceholderHinter::new(|line, _pos| match line {
+            "" => Some("type a command".to_string()),
+            "cd " => Some("projects/reedline".to_string()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn placeholders_of_the_empty_line_are_not_acceptable() {
+        let history = FileBackedHistory::default();
+        let mut hinter = hinter();
+
+        assert_eq!(hinter.handle("", 0, &history, false), "type a command");
+        assert!(!hinter.is_hint_acceptable());
+
+        assert_eq!(
+            hinter.handle("cd ", 3, &history, false),
+            "projects/reedline"
+        );
+        assert!(hinter.is_hint_acceptable());
+        assert_eq!(hinter.complete_hint(), "projects/reedline");
+        assert_eq!(hinter.next_hint_token(), "projects");
+
+        assert_eq!(hinter.handle("ls", 2, &history, false), "");
+        assert_eq!(hinter.complete_hint(), "");
+
+        let mut hinter = hinter.with_accept_on_empty_line(true);
+        hinter.handle("", 0, &history, false);
+        assert!(hinter.is_hint_acceptable());
+    }
+}
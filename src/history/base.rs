@@ -1,6 +1,12 @@
 use super::HistoryItemId;
-use crate::{core_editor::LineBuffer, HistoryItem, HistorySessionId, Result};
+use crate::{
+    core_editor::LineBuffer,
+    result::{ReedlineError, ReedlineErrorVariants},
+    HistoryItem, HistorySessionId, Result,
+};
 use chrono::Utc;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
 
 /// Browsing modes for a [`History`]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -26,6 +32,82 @@ pub enum CommandLineSearch {
     ///
     /// Useful to gather statistics
     Exact(String),
+    /// Command line matches the regular expression
+    Regex(String),
+    /// Command line matches the glob pattern as a whole
+    ///
+    /// `*` matches any sequence of characters (spaces included), `?` exactly one character
+    /// and `\` escapes the next character, e.g. `git * --force`
+    Glob(String),
+}
+
+/// Part of a [`CommandLineSearch::Glob`] pattern
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum GlobToken {
+    /// A character matching itself
+    Literal(char),
+    /// `*`
+    AnyChars,
+    /// `?`
+    AnyChar,
+}
+
+/// Split a glob pattern into its tokens
+pub(crate) fn parse_glob(glob: &str) -> Result<Vec<GlobToken>> {
+    let mut tokens = Vec::new();
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' => GlobToken::AnyChars,
+            '?' => GlobToken::AnyChar,
+            '\\' => GlobToken::Literal(chars.next().ok_or_else(|| {
+                ReedlineError(ReedlineErrorVariants::InvalidHistoryGlob(format!(
+                    "{glob:?} ends with an escape character"
+                )))
+            })?),
+            c => GlobToken::Literal(c),
+        });
+    }
+    Ok(tokens)
+}
+
+impl CommandLineSearch {
+    /// Regular expression pattern equivalent to this search
+    pub(crate) fn regex_pattern(&self, case_sensitive: bool) -> Result<String> {
+        let pattern = match self {
+            CommandLineSearch::Prefix(prefix) => format!("^{}", regex::escape(prefix)),
+            CommandLineSearch::Substring(substring) => regex::escape(substring),
+            CommandLineSearch::Exact(exact) => format!("^{}$", regex::escape(exact)),
+            CommandLineSearch::Regex(regex) => regex.clone(),
+            CommandLineSearch::Glob(glob) => {
+                let pattern: String = parse_glob(glob)?
+                    .into_iter()
+                    .map(|token| match token {
+                        GlobToken::Literal(c) => regex::escape(c.encode_utf8(&mut [0; 4])),
+                        GlobToken::AnyChars => ".*".to_string(),
+                        GlobToken::AnyChar => ".".to_string(),
+                    })
+                    .collect();
+                // The wildcards match the newlines of multiline command lines too
+                format!("(?s)^{pattern}$")
+            }
+        };
+
+        if case_sensitive {
+            Ok(pattern)
+        } else {
+            Ok(format!("(?i){pattern}"))
+        }
+    }
+
+    /// Compile the regular expression equivalent to this search
+    pub(crate) fn to_regex(&self, case_sensitive: bool) -> Result<Regex> {
+        RegexBuilder::new(&self.regex_pattern(case_sensitive)?)
+            .build()
+            .map_err(|err| {
+                ReedlineError(ReedlineErrorVariants::InvalidHistoryRegex(err.to_string()))
+            })
+    }
 }
 
 /// Defines how to traverse the history when executing a [`SearchQuery`]
@@ -41,18 +123,30 @@ pub enum SearchDirection {
 pub struct SearchFilter {
     /// Query for the command line content
     pub command_line: Option<CommandLineSearch>,
+    /// Whether [`SearchFilter::command_line`] is matched case-sensitively (the default)
+    ///
+    /// The SQLite history used to ignore the case of ASCII characters with its `LIKE`
+    /// searches: it now respects this setting like the other histories.
+    pub case_sensitive: bool,
     /// Considered implementation detail for now
     pub not_command_line: Option<String>, // to skip the currently shown value in up-arrow navigation
     /// Filter based on the executing systems hostname
     pub hostname: Option<String>,
     /// Exact filter for the working directory
     pub cwd_exact: Option<String>,
-    /// Prefix filter for the working directory
+    /// Filter for the working directory or the directories below it
+    ///
+    /// Only whole path components are matched: `/home/me` doesn't match `/home/meow`. The
+    /// entries saved without a working directory don't match any directory filter.
     pub cwd_prefix: Option<String>,
     /// Filter whether the command completed
     pub exit_successful: Option<bool>,
     /// Filter on the session id
     pub session: Option<HistorySessionId>,
+    /// Filter out the items of the session
+    pub not_session: Option<HistorySessionId>,
+    /// Only keep the items having each of these tags set to the given value
+    pub tags: HashMap<String, String>,
 }
 
 impl SearchFilter {
@@ -82,12 +176,15 @@ impl SearchFilter {
     pub fn anything(session: Option<HistorySessionId>) -> SearchFilter {
         SearchFilter {
             command_line: None,
+            case_sensitive: true,
             not_command_line: None,
             hostname: None,
             cwd_exact: None,
             cwd_prefix: None,
             exit_successful: None,
             session,
+            not_session: None,
+            tags: HashMap::new(),
         }
     }
 }
@@ -206,9 +303,39 @@ pub trait History: Send {
         self.count(SearchQuery::everything(SearchDirection::Forward, None))
     }
 
+    /// return the number of distinct command lines
+    ///
+    /// By default every item is iterated over, the command lines being tallied in memory
+    fn unique_count(&self) -> Result<u64> {
+        Ok(tally_command_lines(all_command_lines(self)?).len() as u64)
+    }
+
+    /// return the `n` most frequent command lines with their number of items, the most frequent
+    /// first
+    ///
+    /// Command lines as frequent are sorted by their last use, the most recent first. By default
+    /// every item is iterated over, the command lines being tallied in memory
+    fn top_commands(&self, n: usize) -> Result<Vec<(String, u64)>> {
+        Ok(top_command_lines(all_command_lines(self)?, n))
+    }
+
     /// return the results of a query
     fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>>;
 
+    /// lazily return the results of a query, the search stopping once the iterator is dropped
+    ///
+    /// An error preventing the whole search (e.g. an invalid regex) is returned as the only item.
+    /// By default the results of [`History::search`] are iterated over, which doesn't save anything.
+    fn search_iter(
+        &self,
+        query: SearchQuery,
+    ) -> Box<dyn Iterator<Item = Result<HistoryItem>> + '_> {
+        match self.search(query) {
+            Ok(items) => Box::new(items.into_iter().map(Ok)),
+            Err(err) => Box::new(std::iter::once(Err(err))),
+        }
+    }
+
     /// update an item atomically
     fn update(
         &mut self,
@@ -229,6 +356,84 @@ pub trait History: Send {
     fn session(&self) -> Option<HistorySessionId>;
 }
 
+/// The command lines of every item, from the oldest to the most recent
+fn all_command_lines<H: History + ?Sized>(history: &H) -> Result<Vec<String>> {
+    history
+        .search_iter(SearchQuery::everything(SearchDirection::Forward, None))
+        .map(|item| item.map(|item| item.command_line))
+        .collect()
+}
+
+/// Number of uses and position of the last use of each command line, from oldest to most recent
+fn tally_command_lines<S: AsRef<str>>(
+    command_lines: impl IntoIterator<Item = S>,
+) -> HashMap<String, (u64, usize)> {
+    let mut tally: HashMap<String, (u64, usize)> = HashMap::new();
+    for (position, command_line) in command_lines.into_iter().enumerate() {
+        let command_line = command_line.as_ref();
+        match tally.get_mut(command_line) {
+            Some((count, last_use)) => {
+                *count += 1;
+                *last_use = position;
+            }
+            None => {
+                tally.insert(command_line.to_string(), (1, position));
+            }
+        }
+    }
+    tally
+}
+
+/// The `n` most frequent command lines, see [`History::top_commands`]
+pub(crate) fn top_command_lines<S: AsRef<str>>(
+    command_lines: impl IntoIterator<Item = S>,
+    n: usize,
+) -> Vec<(String, u64)> {
+    let mut tally: Vec<_> = tally_command_lines(command_lines).into_iter().collect();
+    tally.sort_unstable_by(
+        |(_, (count, last_use)), (_, (other_count, other_last_use))| {
+            other_count
+                .cmp(count)
+                .then_with(|| other_last_use.cmp(last_use))
+        },
+    );
+    tally
+        .into_iter()
+        .take(n)
+        .map(|(command_line, (count, _))| (command_line, count))
+        .collect()
+}
+
+/// The start of the paths of the directories below `dir`, with a single trailing separator
+pub(crate) fn subdirectory_prefix(dir: &str) -> String {
+    let separator = std::path::MAIN_SEPARATOR;
+    format!("{}{separator}", dir.trim_end_matches(separator))
+}
+
+/// Copy every item of the `src` [`History`] into the `dst` one, from the oldest to the most recent
+///
+/// Item ids are preserved if the destination supports it, so migrating twice doesn't duplicate entries
+/// in the [`SqliteBackedHistory`](crate::SqliteBackedHistory).
+///
+/// As the [`FileBackedHistory`](crate::FileBackedHistory) only stores the command line, the start timestamp,
+/// the session id (with [`FileBackedHistory::with_session()`](crate::FileBackedHistory::with_session))
+/// and the cwd (with [`FileBackedHistory::with_cwd()`](crate::FileBackedHistory::with_cwd)),
+/// every other field (hostname, duration, exit status, tags and additional info) is dropped
+/// when migrating to it. The start timestamp is only persisted to its file if
+/// [`FileBackedHistory::with_timestamps()`](crate::FileBackedHistory::with_timestamps) is enabled.
+/// Nothing is dropped when migrating to the [`SqliteBackedHistory`](crate::SqliteBackedHistory).
+///
+/// The destination's own rules still apply (e.g. deduplication or capacity of the [`FileBackedHistory`](crate::FileBackedHistory)).
+pub fn migrate_history(src: &dyn History, dst: &mut dyn History) -> Result<()> {
+    for item in src.search(SearchQuery::everything(SearchDirection::Forward, None))? {
+        dst.save(&item)?;
+    }
+
+    dst.sync()?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use std::time::Duration;
@@ -254,6 +459,7 @@ mod test {
             duration: Some(Duration::from_millis(1000)),
             exit_status: Some(exit_status),
             more_info: None,
+            tags: HashMap::new(),
         };
 
         hist.save(&item)?;
@@ -337,6 +543,88 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn command_stats() -> Result<()> {
+        let mut history = create_filled_example_history()?;
+        create_item(&mut *history, 1, "/etc/nginx", "ls -l", 0)?;
+        create_item(&mut *history, 1, "/etc/nginx", "ls", 0)?;
+        create_item(&mut *history, 1, "/etc/nginx", "vim nginx.conf", 0)?;
+
+        assert_eq!(history.unique_count()?, 12);
+        // The most recently used first among as frequent command lines
+        assert_eq!(
+            history.top_commands(4)?,
+            vec![
+                ("vim nginx.conf".to_string(), 2),
+                ("ls".to_string(), 2),
+                ("ls -l".to_string(), 2),
+                ("cat nginx.conf".to_string(), 1),
+            ]
+        );
+        assert_eq!(history.top_commands(0)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn default_command_stats_tally_the_items() -> Result<()> {
+        /// Only implements the required methods
+        struct Items(Vec<HistoryItem>);
+
+        impl History for Items {
+            fn generate_id(&mut self) -> HistoryItemId {
+                HistoryItemId::new(self.0.len() as i64)
+            }
+            fn save(&mut self, h: &HistoryItem) -> Result<()> {
+                self.0.push(h.clone());
+                Ok(())
+            }
+            fn replace(&mut self, _h: &HistoryItem) -> Result<()> {
+                unimplemented!()
+            }
+            fn load(&self, _id: HistoryItemId) -> Result<HistoryItem> {
+                unimplemented!()
+            }
+            fn count(&self, _query: SearchQuery) -> Result<u64> {
+                Ok(self.0.len() as u64)
+            }
+            fn search(&self, _query: SearchQuery) -> Result<Vec<HistoryItem>> {
+                Ok(self.0.clone())
+            }
+            fn update(
+                &mut self,
+                _id: HistoryItemId,
+                _updater: &dyn Fn(HistoryItem) -> HistoryItem,
+            ) -> Result<()> {
+                unimplemented!()
+            }
+            fn clear(&mut self) -> Result<()> {
+                unimplemented!()
+            }
+            fn delete(&mut self, _h: HistoryItemId) -> Result<()> {
+                unimplemented!()
+            }
+            fn sync(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+            fn session(&self) -> Option<HistorySessionId> {
+                None
+            }
+        }
+
+        let mut history = Items(Vec::new());
+        for command_line in ["b", "a", "b", "a", "c"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(command_line, id))?;
+        }
+
+        assert_eq!(history.unique_count()?, 3);
+        assert_eq!(
+            history.top_commands(2)?,
+            vec![("a".to_string(), 2), ("b".to_string(), 2)]
+        );
+        Ok(())
+    }
+
     #[test]
     fn get_latest() -> Result<()> {
         let history = create_filled_example_history()?;
@@ -390,6 +678,162 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn search_case_insensitive() -> Result<()> {
+        let history = create_filled_example_history()?;
+        let res = history.search(SearchQuery {
+            filter: SearchFilter {
+                case_sensitive: false,
+                ..SearchFilter::from_text_search(CommandLineSearch::Prefix("LS ".to_string()), None)
+            },
+            ..SearchQuery::everything(SearchDirection::Backward, None)
+        })?;
+        search_returned(&*history, res, vec![9, 6])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_regex() -> Result<()> {
+        let history = create_filled_example_history()?;
+        let res = history.search(SearchQuery {
+            filter: SearchFilter::from_text_search(
+                CommandLineSearch::Regex(r"^(vim|cat) .*\.conf$".to_string()),
+                None,
+            ),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        })?;
+        search_returned(&*history, res, vec![10, 12])?;
+
+        let res = history.search(SearchQuery {
+            filter: SearchFilter {
+                case_sensitive: false,
+                ..SearchFilter::from_text_search(
+                    CommandLineSearch::Regex("^UNZIP".to_string()),
+                    None,
+                )
+            },
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        })?;
+        search_returned(&*history, res, vec![3])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_invalid_regex() -> Result<()> {
+        let history = create_filled_example_history()?;
+        let res = history.search(SearchQuery {
+            filter: SearchFilter::from_text_search(
+                CommandLineSearch::Regex("(ls".to_string()),
+                None,
+            ),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        });
+        assert!(matches!(
+            res,
+            Err(ReedlineError(ReedlineErrorVariants::InvalidHistoryRegex(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_glob() -> Result<()> {
+        let history = create_filled_example_history()?;
+        let search = |glob: &str, case_sensitive: bool| {
+            history.search(SearchQuery {
+                filter: SearchFilter {
+                    case_sensitive,
+                    ..SearchFilter::from_text_search(
+                        CommandLineSearch::Glob(glob.to_string()),
+                        None,
+                    )
+                },
+                ..SearchQuery::everything(SearchDirection::Forward, None)
+            })
+        };
+
+        // `*` spans spaces and can match nothing
+        search_returned(&*history, search("cd*nginx", true)?, vec![8])?;
+        search_returned(&*history, search("* *.conf", true)?, vec![10, 12])?;
+        search_returned(&*history, search("ls*", true)?, vec![5, 6, 9])?;
+        // `?` matches exactly one character
+        search_returned(&*history, search("unz?p *", true)?, vec![3])?;
+        search_returned(&*history, search("ls -???", true)?, vec![6])?;
+        // The pattern matches the whole command line
+        search_returned(&*history, search("nginx", true)?, vec![])?;
+        search_returned(&*history, search("UNZ?P *", false)?, vec![3])?;
+        search_returned(&*history, search("UNZ?P *", true)?, vec![])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn glob_escapes_the_wildcards() -> Result<()> {
+        let regex = CommandLineSearch::Glob(r"echo \* [a]?".to_string()).to_regex(true)?;
+
+        assert!(regex.is_match("echo * [a]!"));
+        assert!(!regex.is_match("echo foo [a]!"));
+        assert!(!regex.is_match("echo * a!"));
+        assert!(regex.is_match("echo * [a]\n"));
+
+        let history = create_filled_example_history()?;
+        let res = history.search(SearchQuery {
+            filter: SearchFilter::from_text_search(
+                CommandLineSearch::Glob(r"ls \".to_string()),
+                None,
+            ),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        });
+        assert!(matches!(
+            res,
+            Err(ReedlineError(ReedlineErrorVariants::InvalidHistoryGlob(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_iter_yields_the_search_results() -> Result<()> {
+        let history = create_filled_example_history()?;
+        let query = || SearchQuery {
+            filter: SearchFilter::from_text_search(
+                CommandLineSearch::Prefix("cd".to_string()),
+                None,
+            ),
+            ..SearchQuery::everything(SearchDirection::Backward, None)
+        };
+
+        let res = history
+            .search_iter(query())
+            .take(2)
+            .collect::<Result<Vec<_>>>()?;
+        search_returned(&*history, res, vec![8, 4])?;
+
+        assert_eq!(
+            history.search_iter(query()).collect::<Result<Vec<_>>>()?,
+            history.search(query())?
+        );
+
+        let mut invalid = history.search_iter(SearchQuery {
+            filter: SearchFilter::from_text_search(
+                CommandLineSearch::Regex("(ls".to_string()),
+                None,
+            ),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        });
+        assert!(matches!(
+            invalid.next(),
+            Some(Err(ReedlineError(
+                ReedlineErrorVariants::InvalidHistoryRegex(_)
+            )))
+        ));
+        assert!(invalid.next().is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn search_includes() -> Result<()> {
         let history = create_filled_example_history()?;
@@ -482,6 +926,38 @@ mod test {
         Ok(())
     }
 
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[test]
+    fn migrate_between_backends() -> Result<()> {
+        let mut file_history = crate::FileBackedHistory::default();
+        for cmd in ["cd ~/Downloads", "unzip foo.zip", "ls"] {
+            let id = file_history.generate_id();
+            file_history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+
+        let mut sqlite_history = crate::SqliteBackedHistory::in_memory()?;
+        migrate_history(&file_history, &mut sqlite_history)?;
+
+        let everything = || SearchQuery::everything(SearchDirection::Forward, None);
+        assert_eq!(
+            sqlite_history.search(everything())?,
+            file_history.search(everything())?
+        );
+
+        // Ids are preserved, so migrating again doesn't duplicate anything
+        migrate_history(&file_history, &mut sqlite_history)?;
+        assert_eq!(sqlite_history.count_all()?, 3);
+
+        let mut round_trip = crate::FileBackedHistory::default();
+        migrate_history(&sqlite_history, &mut round_trip)?;
+        assert_eq!(
+            round_trip.search(everything())?,
+            file_history.search(everything())?
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn create_file_backed_history() {
         use crate::HISTORY_SIZE;
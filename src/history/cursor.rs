@@ -14,6 +14,7 @@ pub struct HistoryCursor {
     current: Option<HistoryItem>,
     skip_dupes: bool,
     session: Option<HistorySessionId>,
+    cwd_prefix: Option<String>,
 }
 
 impl HistoryCursor {
@@ -23,9 +24,18 @@ impl HistoryCursor {
             current: None,
             skip_dupes: true,
             session,
+            cwd_prefix: None,
         }
     }
 
+    /// A builder restricting the navigation to the items run in a working directory starting
+    /// with `cwd_prefix` (see [`SearchFilter::cwd_prefix`])
+    #[must_use]
+    pub fn with_cwd_prefix(mut self, cwd_prefix: Option<String>) -> Self {
+        self.cwd_prefix = cwd_prefix;
+        self
+    }
+
     /// This moves the cursor backwards respecting the navigation query that is set
     /// - Results in a no-op if the cursor is at the initial point
     pub fn back(&mut self, history: &dyn History) -> Result<()> {
@@ -38,8 +48,36 @@ impl HistoryCursor {
         self.navigate_in_direction(history, SearchDirection::Forward)
     }
 
+    /// This moves the cursor backwards like [`HistoryCursor::back`], going around to the latest
+    /// match once past the earliest one
+    pub fn back_wrapping(&mut self, history: &dyn History) -> Result<()> {
+        let previous = self.current.as_ref().map(|e| e.id);
+        self.back(history)?;
+        if previous.is_some() && self.current.as_ref().map(|e| e.id) == previous {
+            self.current = None;
+            self.back(history)?;
+        }
+        Ok(())
+    }
+
+    /// This moves the cursor forwards like [`HistoryCursor::forward`], going around to the
+    /// earliest match once past the latest one
+    pub fn forward_wrapping(&mut self, history: &dyn History) -> Result<()> {
+        let previous = self.current.clone();
+        self.forward(history)?;
+        if self.current.is_none() {
+            let mut earliest = history.search(SearchQuery {
+                limit: Some(1),
+                filter: self.get_search_filter(),
+                ..SearchQuery::everything(SearchDirection::Forward, self.session)
+            })?;
+            self.current = earliest.pop().or(previous);
+        }
+        Ok(())
+    }
+
     fn get_search_filter(&self) -> SearchFilter {
-        let filter = match self.query.clone() {
+        let mut filter = match self.query.clone() {
             HistoryNavigationQuery::Normal(_) => SearchFilter::anything(self.session),
             HistoryNavigationQuery::PrefixSearch(prefix) => {
                 SearchFilter::from_text_search(CommandLineSearch::Prefix(prefix), self.session)
@@ -49,6 +87,7 @@ impl HistoryCursor {
                 self.session,
             ),
         };
+        filter.cwd_prefix = self.cwd_prefix.clone();
         if let (true, Some(current)) = (self.skip_dupes, &self.current) {
             SearchFilter {
                 not_command_line: Some(current.command_line.clone()),
@@ -194,6 +233,54 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn wrapping_goes_around_the_matches() -> Result<()> {
+        let (mut hist, _) = create_history();
+        add_text_entries(
+            hist.as_mut(),
+            &["cargo test", "ls", "cargo build", "cargo check"],
+        );
+        let mut cursor = HistoryCursor::new(
+            HistoryNavigationQuery::SubstringSearch("cargo".to_string()),
+            None,
+        );
+
+        let mut backwards = vec![];
+        for _ in 0..4 {
+            cursor.back_wrapping(&*hist)?;
+            backwards.push(cursor.string_at_cursor().unwrap());
+        }
+        assert_eq!(
+            backwards,
+            vec!["cargo check", "cargo build", "cargo test", "cargo check"]
+        );
+
+        let mut forwards = vec![];
+        for _ in 0..2 {
+            cursor.forward_wrapping(&*hist)?;
+            forwards.push(cursor.string_at_cursor().unwrap());
+        }
+        assert_eq!(forwards, vec!["cargo test", "cargo build"]);
+        Ok(())
+    }
+
+    #[test]
+    fn wrapping_stays_on_a_single_match() -> Result<()> {
+        let (mut hist, _) = create_history();
+        add_text_entries(hist.as_mut(), &["cargo test", "ls"]);
+        let mut cursor = HistoryCursor::new(
+            HistoryNavigationQuery::SubstringSearch("cargo".to_string()),
+            None,
+        );
+
+        cursor.back_wrapping(&*hist)?;
+        cursor.back_wrapping(&*hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("cargo test".to_string()));
+        cursor.forward_wrapping(&*hist)?;
+        assert_eq!(cursor.string_at_cursor(), Some("cargo test".to_string()));
+        Ok(())
+    }
+
     #[test]
     fn going_forwards_bottoms_out() -> Result<()> {
         let (mut hist, mut cursor) = create_history();
@@ -1,8 +1,10 @@
+use chrono::{DateTime, TimeZone, Utc};
 use indexmap::IndexMap;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
 use super::{
-    base::CommandLineSearch, History, HistoryItem, HistoryItemId, SearchDirection, SearchQuery,
+    base::{subdirectory_prefix, top_command_lines, CommandLineSearch},
+    History, HistoryIgnoreFilter, HistoryItem, HistoryItemId, SearchDirection, SearchQuery,
 };
 use crate::{
     result::{ReedlineError, ReedlineErrorVariants},
@@ -10,31 +12,115 @@ use crate::{
 };
 
 use std::{
-    fs::OpenOptions,
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
     hash::{DefaultHasher, Hash, Hasher},
-    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
-    ops::{Deref, DerefMut},
-    path::PathBuf,
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
 };
 
 /// Default size of the [`FileBackedHistory`] used when calling [`FileBackedHistory::default()`]
 pub const HISTORY_SIZE: usize = 1000;
 pub const NEWLINE_ESCAPE: &str = "<\\n>";
 
+/// Defines which entries are considered as duplicates when saving a command to the [`FileBackedHistory`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryDedup {
+    /// Keep every command, even if it is identical to the previous one
+    None,
+    /// Don't save a command if it is identical to the previous one
+    #[default]
+    Consecutive,
+    /// Remove any earlier identical command before saving, effectively moving it to the end of the history
+    ///
+    /// This requires scanning every entry of the history each time a command is saved
+    All,
+}
+
+/// How the [`FileBackedHistory`] writes the commands spanning several lines to its file
+///
+/// Both formats are read back whichever one is used to write the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultilineFormat {
+    /// Write each command on a single line, its newlines being replaced with [`NEWLINE_ESCAPE`]
+    #[default]
+    Escaped,
+    /// Write the lines of a command as they are, between a `<<entry` line and an `entry` line
+    ///
+    /// Like a here-document, the delimiter gets a number (e.g. `<<entry1` and `entry1`) when a
+    /// line of the command is `entry`. A command with a carriage return is still escaped.
+    Block,
+}
+
 /// Stateful history that allows up/down-arrow browsing with an internal cursor.
 ///
 /// Can optionally be associated with a newline separated history file using the [`FileBackedHistory::with_file()`] constructor.
 /// Similar to bash's behavior without HISTTIMEFORMAT.
 /// (See <https://www.gnu.org/software/bash/manual/html_node/Bash-History-Facilities.html>)
 /// If the history is associated to a file all new changes within a given history capacity will be written to disk when History is dropped.
-#[derive(Debug)]
+///
+/// Timestamps can be persisted alongside the commands by opting in with [`FileBackedHistory::with_timestamps()`],
+/// and working directories with [`FileBackedHistory::with_cwd()`].
+/// Commands spanning several lines are escaped on a single line, unless they are written as blocks of lines with
+/// [`FileBackedHistory::with_multiline_format()`].
+///
+/// By default, a command identical to the previous one is not saved again (see [`HistoryDedup`]).
+/// Commands can also be kept out of the history entirely with [`FileBackedHistory::with_ignore_filter()`].
 pub struct FileBackedHistory {
     capacity: usize,
-    entries: IndexMap<HistoryItemId, String>,
+    max_bytes: Option<usize>,
+    entries: IndexMap<HistoryItemId, FileBackedEntry>,
     file: Option<PathBuf>,
     last_on_disk: Option<HistoryItemId>,
+    /// Changes to entries that were already written to disk, applied to the file on the next sync
+    disk_changes: HashMap<HistoryItemId, DiskChange>,
     session: Option<HistorySessionId>,
+    timestamps: bool,
+    cwd: bool,
+    multiline: MultilineFormat,
+    dedup: HistoryDedup,
+    ignore: Option<HistoryIgnoreFilter>,
     rng: SmallRng,
+    read_only: bool,
+}
+
+impl std::fmt::Debug for FileBackedHistory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileBackedHistory")
+            .field("capacity", &self.capacity)
+            .field("max_bytes", &self.max_bytes)
+            .field("entries", &self.entries)
+            .field("file", &self.file)
+            .field("last_on_disk", &self.last_on_disk)
+            .field("disk_changes", &self.disk_changes)
+            .field("session", &self.session)
+            .field("timestamps", &self.timestamps)
+            .field("cwd", &self.cwd)
+            .field("multiline", &self.multiline)
+            .field("dedup", &self.dedup)
+            .field("ignore", &self.ignore.as_ref().map(|_| "<filter>"))
+            .field("read_only", &self.read_only)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The information [`FileBackedHistory`] keeps about a single command
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileBackedEntry {
+    command_line: String,
+    start_timestamp: Option<DateTime<Utc>>,
+    session: Option<HistorySessionId>,
+    cwd: Option<String>,
+}
+
+/// A pending change to an entry that was already written to disk
+#[derive(Debug)]
+struct DiskChange {
+    /// Id of the entry when read back from the file, which differs from the in-memory one
+    /// for entries written using the legacy format
+    disk_id: HistoryItemId,
+    /// New content of the entry, or `None` if it was deleted
+    entry: Option<FileBackedEntry>,
 }
 
 impl Default for FileBackedHistory {
@@ -53,27 +139,432 @@ impl Default for FileBackedHistory {
     }
 }
 
-fn encode_entry(s: &str) -> String {
-    s.replace('\n', NEWLINE_ESCAPE)
+const ID_MARKER: &str = "<id>";
+
+/// Prefix of the command lines written with backslash escapes
+const ESCAPED_MARKER: &str = "<esc>";
+
+/// Separates the session of an entry from the rest of its metadata
+const SESSION_MARKER: char = '#';
+
+/// Separates the working directory of an entry, at the end of its metadata
+const CWD_MARKER: &str = "<cwd>";
+
+/// Prefix of the command lines written as a block, followed by the delimiter ending the block
+const BLOCK_MARKER: &str = "<<";
+
+/// Delimiter of the blocks, numbered when a line of the command is the delimiter itself
+const BLOCK_DELIMITER: &str = "entry";
+
+/// Optional first line of the file, followed by the version of its format
+const FORMAT_HEADER: &str = "#reedline-history v";
+
+/// Latest version of the format that can be read, which is also the version of the files
+/// without a header
+///
+/// A version of the format that can't be read by the running reedline anymore (i.e. whose
+/// lines would be misparsed as legacy lines) increments it, and is written with a header.
+const FORMAT_VERSION: u32 = 1;
+
+/// Encode an entry
+///
+/// Without timestamps, a session nor a working directory, the legacy format is used.
+/// Otherwise the item's id is written as well, followed by the timestamp if timestamps are
+/// enabled and there is one, then by the session and the working directory if there are some
+/// (see [`decode_entry`] for the different formats).
+fn encode_entry(
+    id: HistoryItemId,
+    entry: &FileBackedEntry,
+    timestamps: bool,
+    multiline: MultilineFormat,
+) -> String {
+    let legacy = !timestamps && entry.session.is_none() && entry.cwd.is_none();
+    let command_line = encode_command_line(&entry.command_line, legacy, multiline);
+
+    if legacy {
+        return command_line;
+    }
+
+    let mut metadata = String::new();
+    if let (true, Some(timestamp)) = (timestamps, entry.start_timestamp) {
+        metadata = format!("@{}", timestamp.timestamp_millis());
+    }
+    if let Some(session) = entry.session {
+        metadata = format!("{metadata}{SESSION_MARKER}{session}");
+    }
+    if let Some(cwd) = &entry.cwd {
+        metadata = format!("{metadata}{CWD_MARKER}{}", encode_cwd(cwd));
+    }
+
+    format!("{id}{ID_MARKER}{metadata}:{command_line}")
+}
+
+/// Encode a command line so that it fits on a single line of the file, or in a block of lines
+/// with [`MultilineFormat::Block`]
+///
+/// Newlines are replaced with [`NEWLINE_ESCAPE`], unless the result couldn't be decoded back
+/// to the same command line (e.g. the command line contains [`NEWLINE_ESCAPE`] itself). The
+/// command line is then prefixed with `<esc>` and escaped with backslashes instead: `\n` for a
+/// newline, `\r` for a carriage return and `\\` for a backslash.
+fn encode_command_line(command_line: &str, legacy: bool, multiline: MultilineFormat) -> String {
+    // Carriage returns are stripped from the line endings when reading the file
+    if multiline == MultilineFormat::Block
+        && command_line.contains('\n')
+        && !command_line.contains('\r')
+    {
+        let mut delimiter = BLOCK_DELIMITER.to_string();
+        let mut number = 0;
+        while command_line.split('\n').any(|line| line == delimiter) {
+            number += 1;
+            delimiter = format!("{BLOCK_DELIMITER}{number}");
+        }
+        return format!("{BLOCK_MARKER}{delimiter}\n{command_line}\n{delimiter}");
+    }
+
+    let encoded = command_line.replace('\n', NEWLINE_ESCAPE);
+
+    let ambiguous = command_line.contains(NEWLINE_ESCAPE)
+        // A trailing carriage return is stripped from the line when reading the file
+        || command_line.ends_with('\r')
+        || encoded.starts_with(ESCAPED_MARKER)
+        || block_delimiter(&encoded).is_some()
+        || (legacy && parse_id_format(&encoded).is_some())
+        || (legacy && format_version(&encoded).is_some());
+
+    if !ambiguous {
+        return encoded;
+    }
+
+    let mut escaped = ESCAPED_MARKER.to_string();
+    for c in command_line.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The delimiter ending the block started by the first line of `command_line`, if it starts one
+fn block_delimiter(command_line: &str) -> Option<&str> {
+    let first_line = command_line.split('\n').next()?;
+    let delimiter = first_line.strip_prefix(BLOCK_MARKER)?;
+    let number = delimiter.strip_prefix(BLOCK_DELIMITER)?;
+    number
+        .chars()
+        .all(|c| c.is_ascii_digit())
+        .then_some(delimiter)
+}
+
+/// Percent-encode the characters of a working directory that would end the metadata or the line
+fn encode_cwd(cwd: &str) -> String {
+    let mut encoded = String::with_capacity(cwd.len());
+    for c in cwd.chars() {
+        match c {
+            '%' => encoded.push_str("%25"),
+            ':' => encoded.push_str("%3A"),
+            '\n' => encoded.push_str("%0A"),
+            '\r' => encoded.push_str("%0D"),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+/// Decode a working directory encoded by [`encode_cwd`]
+fn decode_cwd(s: &str) -> String {
+    let mut cwd = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(index) = rest.find('%') {
+        cwd.push_str(&rest[..index]);
+        let (decoded, len) = match rest.get(index..index + 3) {
+            Some("%25") => ('%', 3),
+            Some("%3A") => (':', 3),
+            Some("%0A") => ('\n', 3),
+            Some("%0D") => ('\r', 3),
+            _ => ('%', 1),
+        };
+        cwd.push(decoded);
+        rest = &rest[index + len..];
+    }
+    cwd.push_str(rest);
+    cwd
+}
+
+/// The version of the format given by a header line
+fn format_version(line: &str) -> Option<u32> {
+    line.strip_prefix(FORMAT_HEADER)?.parse().ok()
+}
+
+/// Decode a command line encoded by [`encode_command_line`]
+///
+/// A block missing its last line, e.g. when the file was cut, ends with the file.
+fn decode_command_line(s: &str) -> String {
+    if let Some(delimiter) = block_delimiter(s) {
+        let lines = s.split_once('\n').map_or("", |(_, lines)| lines);
+        return match lines.strip_suffix(delimiter) {
+            Some(lines) if lines.ends_with('\n') => lines[..lines.len() - 1].to_string(),
+            _ if lines == delimiter => String::new(),
+            _ => lines.to_string(),
+        };
+    }
+
+    let Some(escaped) = s.strip_prefix(ESCAPED_MARKER) else {
+        return s.replace(NEWLINE_ESCAPE, "\n");
+    };
+
+    let mut command_line = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            command_line.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => command_line.push('\n'),
+            Some('r') => command_line.push('\r'),
+            Some(c) => command_line.push(c),
+            None => command_line.push('\\'),
+        }
+    }
+    command_line
 }
 
 /// Decode an entry
 ///
-/// Legacy format: ls /
-/// New format   : 182535<id>:ls /
+/// Legacy format   : ls /
+/// New format      : 182535<id>:ls /
+/// Timestamp format: 182535<id>@1700000000000:ls /
+/// Session format  : 182535<id>#42:ls / or 182535<id>@1700000000000#42:ls /
+/// Cwd format      : 182535<id><cwd>/home/me:ls / or 182535<id>@1700000000000#42<cwd>/home/me:ls /
+///
+/// The timestamp is the number of milliseconds since the Unix epoch, the session is the
+/// [`HistorySessionId`] of the entry, and the working directory has its `%`, `:` and line
+/// endings percent-encoded.
+///
+/// In every format, the command line may start with `<esc>` when it is escaped with
+/// backslashes, or be a block of lines (see [`encode_command_line`]):
 ///
-/// If a line can't be parsed using the new format, it will fallback to the legacy one.
+/// Block format    : 182535<id>:<<entry
+///                   echo a
+///                   echo b
+///                   entry
+///
+/// If a line can't be parsed using the new formats, it will fallback to the legacy one.
 ///
 /// This allows this function to support decoding for both legacy and new histories,
 /// as well as mixing both of them.
-fn decode_entry(s: &str, counter: &mut i64) -> (HistoryItemId, String) {
+fn decode_entry(s: &str, counter: &mut i64) -> (HistoryItemId, FileBackedEntry) {
+    if let Some((id, start_timestamp, session, cwd, command_line)) = parse_id_format(s) {
+        return (
+            id,
+            FileBackedEntry {
+                command_line: decode_command_line(command_line),
+                start_timestamp,
+                session,
+                cwd,
+            },
+        );
+    }
+
     let mut hasher = DefaultHasher::new();
     counter.hash(&mut hasher);
     s.hash(&mut hasher);
 
     let id = hasher.finish() as i64;
 
-    (HistoryItemId(id), s.replace(NEWLINE_ESCAPE, "\n"))
+    (
+        HistoryItemId(id),
+        FileBackedEntry {
+            command_line: decode_command_line(s),
+            start_timestamp: None,
+            session: None,
+            cwd: None,
+        },
+    )
+}
+
+/// The id, timestamp, session, working directory and command line of a line in the new formats
+type IdFormatLine<'a> = (
+    HistoryItemId,
+    Option<DateTime<Utc>>,
+    Option<HistorySessionId>,
+    Option<String>,
+    &'a str,
+);
+
+/// Parse a line using the new, timestamp, session or cwd format
+fn parse_id_format(s: &str) -> Option<IdFormatLine<'_>> {
+    let (id, rest) = s.split_once(ID_MARKER)?;
+    let id = id.parse::<i64>().ok()?;
+    // The metadata has no `:`, unlike the command line
+    let (metadata, command_line) = rest.split_once(':')?;
+
+    let (metadata, cwd) = match metadata.split_once(CWD_MARKER) {
+        Some((metadata, cwd)) => (metadata, Some(decode_cwd(cwd))),
+        None => (metadata, None),
+    };
+    let (timestamp, session) = match metadata.split_once(SESSION_MARKER) {
+        Some((timestamp, session)) => (timestamp, Some(session.parse::<i64>().ok()?)),
+        None => (metadata, None),
+    };
+
+    let timestamp = match timestamp {
+        "" => None,
+        timestamp => {
+            let millis = timestamp.strip_prefix('@')?.parse::<i64>().ok()?;
+            match Utc.timestamp_millis_opt(millis) {
+                chrono::LocalResult::Single(timestamp) => Some(timestamp),
+                _ => return None,
+            }
+        }
+    };
+
+    Some((
+        HistoryItemId(id),
+        timestamp,
+        session.map(HistorySessionId::new),
+        cwd,
+        command_line,
+    ))
+}
+
+/// Read the entries of the file, which is considered empty if it doesn't exist
+///
+/// An id appearing on several lines (e.g. after a sync failed once the entries were written)
+/// is only kept once, at the position of its first line and with the content of its last one.
+/// The boolean tells whether there were such duplicated lines. Repeated legacy lines share an id
+/// without being duplicates, so they don't count.
+fn read_entries(fname: &Path) -> std::io::Result<(IndexMap<HistoryItemId, FileBackedEntry>, bool)> {
+    let file = match File::open(fname) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok((IndexMap::new(), false)),
+        Err(err) => return Err(err),
+    };
+
+    let mut counter = 0;
+    let mut entries = IndexMap::new();
+    let mut duplicates = false;
+
+    for encoded in encoded_entries(BufReader::new(file))? {
+        let legacy = parse_id_format(&encoded).is_none();
+        let (id, entry) = decode_entry(&encoded, &mut counter);
+        duplicates |= entries.insert(id, entry).is_some() && !legacy;
+    }
+
+    Ok((entries, duplicates))
+}
+
+/// Split the lines of a file into encoded entries, the lines of a block being kept together
+///
+/// Fails with a [`ReedlineErrorVariants::HistoryFormatVersionMismatch`] if the header of the
+/// file gives a newer version of the format than [`FORMAT_VERSION`], rather than misparsing it.
+fn encoded_entries(reader: impl BufRead) -> std::io::Result<Vec<String>> {
+    let mut encoded_entries = Vec::new();
+    let mut lines = reader.lines().peekable();
+    if let Some(Ok(header)) = lines.peek() {
+        if let Some(found) = format_version(header) {
+            if found > FORMAT_VERSION {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    ReedlineError(ReedlineErrorVariants::HistoryFormatVersionMismatch {
+                        found,
+                        supported: FORMAT_VERSION,
+                    }),
+                ));
+            }
+            lines.next();
+        }
+    }
+
+    while let Some(line) = lines.next() {
+        let mut encoded = line?;
+
+        let command_line = parse_id_format(&encoded).map_or(encoded.as_str(), |line| line.4);
+        if let Some(delimiter) = block_delimiter(command_line).map(str::to_string) {
+            for line in lines.by_ref() {
+                let line = line?;
+                encoded.push('\n');
+                encoded.push_str(&line);
+                if line == delimiter {
+                    break;
+                }
+            }
+        }
+
+        encoded_entries.push(encoded);
+    }
+    Ok(encoded_entries)
+}
+
+/// Write the encoded entries to the file, and wait for them to reach the disk
+fn write_entries<'a>(
+    file: File,
+    entries: impl Iterator<Item = (&'a HistoryItemId, &'a FileBackedEntry)>,
+    timestamps: bool,
+    multiline: MultilineFormat,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(file);
+
+    for (id, entry) in entries {
+        writer.write_all(encode_entry(*id, entry, timestamps, multiline).as_bytes())?;
+        writer.write_all("\n".as_bytes())?;
+    }
+
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+/// The file next to `fname` named after it, with `.{suffix}` appended to its whole name
+///
+/// Unlike [`Path::with_extension`], history files differing only by their extension (e.g.
+/// `history.txt` and `history.hist`) get different files.
+fn sibling_file(fname: &Path, suffix: &str) -> PathBuf {
+    let mut name = fname.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    fname.with_file_name(name)
+}
+
+/// The error with a description of the failed operation
+///
+/// An error wrapping a [`ReedlineError`] is left as is, to be converted back to it.
+fn io_context(err: std::io::Error, context: String) -> std::io::Error {
+    if err
+        .get_ref()
+        .map_or(false, |inner| inner.is::<ReedlineError>())
+    {
+        return err;
+    }
+    std::io::Error::new(err.kind(), format!("{context}: {err}"))
+}
+
+/// The file a chain of symlinks points to, which may not exist yet
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    // Bounded like the resolution of the OS, in case of a cycle
+    for _ in 0..40 {
+        let Ok(target) = std::fs::read_link(&path) else {
+            break;
+        };
+        path = match path.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+    path
+}
+
+fn check_capacity(capacity: usize) -> Result<()> {
+    if capacity == usize::MAX {
+        return Err(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
+            "History capacity too large to be addressed safely",
+        )));
+    }
+    Ok(())
 }
 
 impl History for FileBackedHistory {
@@ -81,29 +572,68 @@ impl History for FileBackedHistory {
         HistoryItemId(self.rng.gen())
     }
 
-    /// only saves a value if it's different than the last value
+    /// only saves a value if it's not a duplicate according to the configured [`HistoryDedup`]
     fn save(&mut self, h: &HistoryItem) -> Result<()> {
         let entry = h.command_line.clone();
 
-        // Don't append if the preceding value is identical or the string empty
-        if self
-            .entries
-            .last()
-            .map_or(true, |(_, previous)| previous != &entry)
-            && !entry.is_empty()
-            && self.capacity > 0
-        {
-            if self.entries.len() >= self.capacity {
-                // History is "full", so we delete the oldest entry first,
-                // before adding a new one.
-                let first_id = *(self.entries.first().unwrap().0);
-                let prev = self.entries.shift_remove(&first_id);
-                assert!(prev.is_some());
+        // Don't append if the string is empty
+        if entry.is_empty() || self.capacity == 0 {
+            return Ok(());
+        }
+
+        if self.ignore.as_ref().map_or(false, |ignore| ignore(&entry)) {
+            return Ok(());
+        }
+
+        match self.dedup {
+            HistoryDedup::None => {}
+            HistoryDedup::Consecutive => {
+                // Don't append if the preceding value is identical
+                if self
+                    .entries
+                    .last()
+                    .map_or(false, |(_, previous)| previous.command_line == entry)
+                {
+                    return Ok(());
+                }
+            }
+            HistoryDedup::All => {
+                let duplicates = self
+                    .entries
+                    .iter()
+                    .filter(|(_, previous)| previous.command_line == entry)
+                    .map(|(id, _)| *id)
+                    .collect::<Vec<_>>();
+
+                for id in duplicates {
+                    self.delete(id)?;
+                }
             }
+        }
+
+        if self.entries.len() >= self.capacity {
+            // History is "full", so we delete the oldest entry first,
+            // before adding a new one.
+            let first_id = *(self.entries.first().unwrap().0);
+            let prev = self.entries.shift_remove(&first_id);
+            assert!(prev.is_some());
 
-            self.entries.insert(h.id, entry.to_string());
+            // Every remaining entry is newer than the evicted one, and so not written yet
+            if self.last_on_disk == Some(first_id) {
+                self.last_on_disk = None;
+            }
         }
 
+        self.entries.insert(
+            h.id,
+            FileBackedEntry {
+                command_line: entry,
+                start_timestamp: h.start_timestamp,
+                session: self.session.and(h.session_id.or(self.session)),
+                cwd: h.cwd.clone().filter(|_| self.cwd),
+            },
+        );
+
         Ok(())
     }
 
@@ -113,139 +643,99 @@ impl History for FileBackedHistory {
     }
 
     fn load(&self, id: HistoryItemId) -> Result<HistoryItem> {
-        println!("{:?}", self.entries);
-
         Ok(FileBackedHistory::construct_entry(
             id,
-            self.entries
-                .get(&id)
-                .ok_or(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
-                    "Item does not exist",
-                )))?
-                .clone(),
+            self.entries.get(&id).ok_or(ReedlineError(
+                ReedlineErrorVariants::HistoryItemNotFound(id),
+            ))?,
         ))
     }
 
+    /// The matching entries are counted without being cloned
     fn count(&self, query: SearchQuery) -> Result<u64> {
-        // todo: this could be done cheaper
-        Ok(self.search(query)?.len() as u64)
+        Ok(self.matching_entries(query)?.count() as u64)
     }
 
-    fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
-        // Destructure the query - this ensures that if another element is added to this type later on,
-        // we won't forget to update this function as the destructuring will then be incomplete.
-        let SearchQuery {
-            direction,
-            start_time,
-            end_time,
-            start_id,
-            end_id,
-            limit,
-            filter,
-        } = query;
+    fn unique_count(&self) -> Result<u64> {
+        let command_lines: HashSet<&str> = self
+            .entries
+            .values()
+            .map(|entry| entry.command_line.as_str())
+            .collect();
+        Ok(command_lines.len() as u64)
+    }
 
-        if start_time.is_some() || end_time.is_some() {
-            return Err(ReedlineError(
-                ReedlineErrorVariants::HistoryFeatureUnsupported {
-                    history: "FileBackedHistory",
-                    feature: "filtering by time",
-                },
-            ));
-        }
+    /// The entries are tallied without being cloned
+    fn top_commands(&self, n: usize) -> Result<Vec<(String, u64)>> {
+        Ok(top_command_lines(
+            self.entries.values().map(|entry| &entry.command_line),
+            n,
+        ))
+    }
 
-        if filter.hostname.is_some()
-            || filter.cwd_exact.is_some()
-            || filter.cwd_prefix.is_some()
-            || filter.exit_successful.is_some()
-        {
-            return Err(ReedlineError(
-                ReedlineErrorVariants::HistoryFeatureUnsupported {
-                    history: "FileBackedHistory",
-                    feature: "filtering by extra info",
-                },
-            ));
+    fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
+        self.search_iter(query).collect()
+    }
+
+    /// The entries are only cloned as they are iterated over
+    fn search_iter(
+        &self,
+        query: SearchQuery,
+    ) -> Box<dyn Iterator<Item = Result<HistoryItem>> + '_> {
+        match self.matching_entries(query) {
+            Ok(entries) => Box::new(
+                entries.map(|(id, entry)| Ok(FileBackedHistory::construct_entry(*id, entry))),
+            ),
+            Err(err) => Box::new(std::iter::once(Err(err))),
         }
+    }
 
-        let (start_id, end_id) = {
-            if let SearchDirection::Backward = direction {
-                (end_id, start_id)
-            } else {
-                (start_id, end_id)
-            }
-        };
+    /// The command line, start timestamp, session id and working directory of the updated item
+    /// are stored, the last two following the same rules as [`History::save`].
+    ///
+    /// The item keeps its position in the history, and entries which were already written to disk
+    /// are updated in the file on the next [`History::sync`]
+    ///
+    /// A missing id is reported with [`ReedlineErrorVariants::HistoryItemNotFound`], like by
+    /// [`History::load`] and [`History::delete`], rather than with a generic
+    /// [`ReedlineErrorVariants::OtherHistoryError`].
+    fn update(
+        &mut self,
+        id: super::HistoryItemId,
+        updater: &dyn Fn(super::HistoryItem) -> super::HistoryItem,
+    ) -> Result<()> {
+        let index = self.entries.get_index_of(&id).ok_or(ReedlineError(
+            ReedlineErrorVariants::HistoryItemNotFound(id),
+        ))?;
 
-        let start_idx = match start_id {
-            Some(from_id) => self.entries.get_index_of(&from_id).ok_or(ReedlineError(
-                ReedlineErrorVariants::OtherHistoryError("provided 'start_id' item was not found"),
-            ))?,
-            None => 0,
-        };
+        let updated = updater(FileBackedHistory::construct_entry(id, &self.entries[index]));
 
-        let end_idx = match end_id {
-            Some(to_id) => self.entries.get_index_of(&to_id).ok_or(ReedlineError(
-                ReedlineErrorVariants::OtherHistoryError("provided 'end_id' item was not found"),
-            ))?,
-            None => self.entries.len().saturating_sub(1),
+        let entry = FileBackedEntry {
+            command_line: updated.command_line,
+            start_timestamp: updated.start_timestamp,
+            session: self.session.and(updated.session_id.or(self.session)),
+            cwd: updated.cwd.filter(|_| self.cwd),
         };
 
-        assert!(start_idx <= end_idx);
-
-        let iter = self
-            .entries
-            .iter()
-            .skip(start_idx)
-            .take(1 + end_idx - start_idx);
-
-        let limit = limit
-            .and_then(|limit| usize::try_from(limit).ok())
-            .unwrap_or(usize::MAX);
-
-        let filter = |(id, cmd): (&HistoryItemId, &String)| {
-            let str_matches = match &filter.command_line {
-                Some(CommandLineSearch::Prefix(p)) => cmd.starts_with(p),
-                Some(CommandLineSearch::Substring(p)) => cmd.contains(p),
-                Some(CommandLineSearch::Exact(p)) => cmd == p,
-                None => true,
-            };
-
-            if !str_matches {
-                return None;
-            }
-
-            if let Some(str) = &filter.not_command_line {
-                if cmd == str {
-                    return None;
-                }
-            }
-
-            Some(FileBackedHistory::construct_entry(
-                *id,
-                cmd.clone(), // todo: this cloning might be a perf bottleneck
-            ))
-        };
+        if let Some(disk_id) = self.disk_id(id, index) {
+            self.disk_changes.insert(
+                id,
+                DiskChange {
+                    disk_id,
+                    entry: Some(entry.clone()),
+                },
+            );
+        }
 
-        Ok(match query.direction {
-            SearchDirection::Backward => iter.rev().filter_map(filter).take(limit).collect(),
-            SearchDirection::Forward => iter.filter_map(filter).take(limit).collect(),
-        })
-    }
+        self.entries[index] = entry;
 
-    fn update(
-        &mut self,
-        _id: super::HistoryItemId,
-        _updater: &dyn Fn(super::HistoryItem) -> super::HistoryItem,
-    ) -> Result<()> {
-        Err(ReedlineError(
-            ReedlineErrorVariants::HistoryFeatureUnsupported {
-                history: "FileBackedHistory",
-                feature: "updating entries",
-            },
-        ))
+        Ok(())
     }
 
     fn clear(&mut self) -> Result<()> {
         self.entries.clear();
         self.last_on_disk = None;
+        self.disk_changes.clear();
 
         if let Some(file) = &self.file {
             if let Err(err) = std::fs::remove_file(file) {
@@ -256,22 +746,51 @@ impl History for FileBackedHistory {
         Ok(())
     }
 
-    fn delete(&mut self, _h: super::HistoryItemId) -> Result<()> {
-        Err(ReedlineError(
-            ReedlineErrorVariants::HistoryFeatureUnsupported {
-                history: "FileBackedHistory",
-                feature: "removing entries",
-            },
-        ))
+    /// Entries which were already written to disk are removed from the file on the next [`History::sync`]
+    fn delete(&mut self, h: super::HistoryItemId) -> Result<()> {
+        let index = self
+            .entries
+            .get_index_of(&h)
+            .ok_or(ReedlineError(ReedlineErrorVariants::HistoryItemNotFound(h)))?;
+
+        if let Some(disk_id) = self.disk_id(h, index) {
+            self.disk_changes.insert(
+                h,
+                DiskChange {
+                    disk_id,
+                    entry: None,
+                },
+            );
+        }
+
+        if self.last_on_disk == Some(h) {
+            self.last_on_disk = index
+                .checked_sub(1)
+                .map(|prev| *self.entries.get_index(prev).unwrap().0);
+        }
+
+        self.entries.shift_remove(&h);
+
+        Ok(())
     }
 
     /// Writes unwritten history contents to disk.
     ///
     /// If file would exceed `capacity` truncates the oldest entries.
+    /// The same goes if the file would exceed `max_bytes` (see [`FileBackedHistory::with_max_bytes()`]).
+    /// Entries deleted or updated since the last sync are changed accordingly in the file.
+    /// Errors describe the failed operation: creating the directory of the file, opening or
+    /// locking it, or reading or writing it. A read-only history never writes anything.
     fn sync(&mut self) -> std::io::Result<()> {
         let Some(fname) = &self.file else {
             return Ok(());
         };
+        if self.read_only {
+            return Ok(());
+        }
+
+        // The rewritten file replaces the target of a symlink, not the symlink itself
+        let fname = &resolve_symlinks(fname);
 
         // The unwritten entries
         let last_index_on_disk = self
@@ -283,87 +802,190 @@ impl History for FileBackedHistory {
             None => 0,
         };
 
-        let own_entries = self.entries.get_range(range_start..).unwrap();
-
         if let Some(base_dir) = fname.parent() {
-            std::fs::create_dir_all(base_dir)?;
+            std::fs::create_dir_all(base_dir).map_err(|err| {
+                io_context(
+                    err,
+                    format!("cannot create the history directory {}", base_dir.display()),
+                )
+            })?;
         }
 
+        // The lock is taken on a separate file, as the history file itself gets replaced when rewritten
+        let lock_fname = sibling_file(fname, "lock");
         let mut f_lock = fd_lock::RwLock::new(
             OpenOptions::new()
                 .create(true)
                 .truncate(false)
                 .write(true)
-                .read(true)
-                .open(fname)?,
+                .open(&lock_fname)
+                .map_err(|err| {
+                    io_context(
+                        err,
+                        format!(
+                            "cannot open the history lock file {} for writing",
+                            lock_fname.display()
+                        ),
+                    )
+                })?,
         );
 
-        let mut writer_guard = f_lock.write()?;
+        let _lock_guard = f_lock.write().map_err(|err| {
+            io_context(
+                err,
+                format!("cannot acquire the lock {}", lock_fname.display()),
+            )
+        })?;
+
+        let (mut from_file, duplicates) = read_entries(fname).map_err(|err| {
+            io_context(
+                err,
+                format!("cannot read the history file {}", fname.display()),
+            )
+        })?;
+
+        // Entries written by a sync which failed before recording it must not be written again
+        let already_written = self.entries[range_start..]
+            .keys()
+            .filter(|id| from_file.contains_key(*id))
+            .copied()
+            .collect::<Vec<_>>();
+        for id in already_written {
+            self.entries.shift_remove(&id);
+        }
+
+        let mut own_entries = self.entries.get_range(range_start..).unwrap();
 
-        let (mut foreign_entries, truncate) = {
-            let reader = BufReader::new(writer_guard.deref());
+        let (mut foreign_entries, rewrite) = {
+            // Entries deleted or updated in this history must not be re-introduced from the file
+            let disk_changes = self
+                .disk_changes
+                .iter()
+                .flat_map(|(id, change)| [(*id, change), (change.disk_id, change)])
+                .collect::<HashMap<_, _>>();
 
-            let mut counter = 0;
+            // Duplicated lines are dropped from the file as well
+            let mut changed = duplicates;
 
-            let mut from_file = reader
-                .lines()
-                .map(|o| o.map(|i| decode_entry(&i, &mut counter)))
-                .collect::<std::io::Result<IndexMap<_, _>>>()?;
+            from_file.retain(|id, entry| match disk_changes.get(id) {
+                Some(DiskChange { entry: None, .. }) => {
+                    changed = true;
+                    false
+                }
+                Some(DiskChange {
+                    entry: Some(updated),
+                    ..
+                }) => {
+                    changed = true;
+                    *entry = updated.clone();
+                    true
+                }
+                None => true,
+            });
 
             if from_file.len() + own_entries.len() > self.capacity {
                 let start = from_file.len() + own_entries.len() - self.capacity;
 
                 (from_file.split_off(start), true)
             } else {
-                (from_file, false)
+                (from_file, changed)
             }
         };
 
-        {
-            let mut writer = BufWriter::new(writer_guard.deref_mut());
+        // Number of own entries which are too old to fit in the file
+        let mut own_skip = 0;
 
-            // In case of truncation, we first write every foreign entry (replacing existing content)
-            if truncate {
-                writer.rewind()?;
+        let rewrite = match self.max_bytes {
+            Some(max_bytes) => {
+                // Size of each line in the file, including the trailing newline
+                let line_size = |(id, entry): (&HistoryItemId, &FileBackedEntry)| {
+                    encode_entry(*id, entry, self.timestamps, self.multiline).len() + 1
+                };
 
-                for line in foreign_entries.values() {
-                    writer.write_all(encode_entry(line).as_bytes())?;
-                    writer.write_all("\n".as_bytes())?;
-                }
-            } else {
-                // Otherwise we directly jump at the end of the file
-                writer.seek(SeekFrom::End(0))?;
-            }
+                let mut size = foreign_entries
+                    .iter()
+                    .chain(own_entries)
+                    .map(line_size)
+                    .sum::<usize>();
 
-            // Then we write new entries (that haven't been synced to the file yet)
-            for line in own_entries.values() {
-                writer.write_all(encode_entry(line).as_bytes())?;
-                writer.write_all("\n".as_bytes())?;
-            }
+                let mut foreign_skip = 0;
 
-            writer.flush()?;
-        }
+                for line in foreign_entries.iter().chain(own_entries) {
+                    if size <= max_bytes {
+                        break;
+                    }
 
-        // If truncation is needed, we then remove everything after the cursor's current location
-        if truncate {
-            let file = writer_guard.deref_mut();
-            let file_len = file.stream_position()?;
-            file.set_len(file_len)?;
-        }
+                    size -= line_size(line);
 
-        match last_index_on_disk {
-            Some(last_index_on_disk) => {
-                if last_index_on_disk + 1 < self.entries.len() {
-                    foreign_entries.extend(self.entries.drain(last_index_on_disk + 1..));
+                    if foreign_skip < foreign_entries.len() {
+                        foreign_skip += 1;
+                    } else {
+                        own_skip += 1;
+                    }
                 }
+
+                if foreign_skip > 0 {
+                    foreign_entries = foreign_entries.split_off(foreign_skip);
+                }
+
+                own_entries = own_entries.get_range(own_skip..).unwrap();
+
+                rewrite || foreign_skip > 0
             }
 
-            None => {
-                foreign_entries.extend(self.entries.drain(..));
+            None => rewrite,
+        };
+
+        let cannot_write = |err| {
+            io_context(
+                err,
+                format!(
+                    "cannot open the history file {} for writing",
+                    fname.display()
+                ),
+            )
+        };
+
+        if rewrite {
+            // In case of truncation or deletion, every foreign entry followed by the new entries
+            // is written to a temporary file, which then replaces the history file.
+            // This ensures the history file is never left partially written.
+            let tmp_fname = sibling_file(fname, "tmp");
+
+            let written = File::create(&tmp_fname)
+                .map_err(cannot_write)
+                .and_then(|tmp_file| {
+                    if let Ok(metadata) = std::fs::metadata(fname) {
+                        tmp_file.set_permissions(metadata.permissions())?;
+                    }
+
+                    write_entries(
+                        tmp_file,
+                        foreign_entries.iter().chain(own_entries),
+                        self.timestamps,
+                        self.multiline,
+                    )?;
+                    std::fs::rename(&tmp_fname, fname)
+                });
+
+            if let Err(err) = written {
+                let _ = std::fs::remove_file(&tmp_fname);
+                return Err(err);
             }
+        } else {
+            // Otherwise new entries (that haven't been synced to the file yet) are directly appended
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(fname)
+                .map_err(cannot_write)?;
+            write_entries(file, own_entries.iter(), self.timestamps, self.multiline)?;
         }
 
+        foreign_entries.extend(self.entries.drain(range_start..).skip(own_skip));
+
         self.entries = foreign_entries;
+        self.disk_changes.clear();
 
         self.last_on_disk = self.entries.last().map(|(id, _)| *id);
 
@@ -379,25 +1001,44 @@ impl FileBackedHistory {
     /// Creates a new in-memory history that remembers `n <= capacity` elements
     ///
     pub fn new(capacity: usize) -> Result<Self> {
-        if capacity == usize::MAX {
-            return Err(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
-                "History capacity too large to be addressed safely",
-            )));
-        }
+        check_capacity(capacity)?;
 
         Ok(FileBackedHistory {
             capacity,
+            max_bytes: None,
             entries: IndexMap::new(),
             file: None,
             last_on_disk: None,
+            disk_changes: HashMap::new(),
             session: None,
+            timestamps: false,
+            cwd: false,
+            multiline: MultilineFormat::default(),
+            dedup: HistoryDedup::default(),
+            ignore: None,
             rng: SmallRng::from_entropy(),
+            read_only: false,
         })
     }
 
     /// Creates a new history with an associated history file.
     ///
     /// History file format: commands separated by new lines.
+    /// Newlines inside of a command are written as `<\n>`, or as `\n` behind an `<esc>` prefix
+    /// when the command can't be written unambiguously otherwise (e.g. it contains `<\n>`).
+    /// Concurrent accesses are synchronized using a lock file next to the history file, named after
+    /// it with `.lock` appended, e.g. `history.txt.lock`. Older versions of reedline locked the
+    /// history file itself: they don't exclude the current ones when syncing the same file.
+    /// When timestamps are enabled with [`FileBackedHistory::with_timestamps()`], each line is
+    /// prefixed with the item's id and start timestamp in milliseconds, e.g. `182535<id>@1700000000000:ls /`.
+    /// The lines of entries with a session (see [`FileBackedHistory::with_session()`]) end their
+    /// prefix with it, e.g. `182535<id>#42:ls /`, while files without sessions are left unchanged.
+    /// The working directories recorded with [`FileBackedHistory::with_cwd()`] come last, e.g.
+    /// `182535<id><cwd>/home/me:ls /`.
+    /// A file starting with a header like `#reedline-history v2`, written by a newer version of
+    /// reedline in a format it can't read, fails with a
+    /// [`ReedlineErrorVariants::HistoryFormatVersionMismatch`] instead of being misparsed and
+    /// rewritten. Files without a header are read as before.
     /// If file exists file will be read otherwise empty file will be created.
     ///
     ///
@@ -408,6 +1049,12 @@ impl FileBackedHistory {
 
         if let Some(base_dir) = file.parent() {
             std::fs::create_dir_all(base_dir)
+                .map_err(|err| {
+                    io_context(
+                        err,
+                        format!("cannot create the history directory {}", base_dir.display()),
+                    )
+                })
                 .map_err(ReedlineErrorVariants::IOError)
                 .map_err(ReedlineError)?;
         }
@@ -418,18 +1065,354 @@ impl FileBackedHistory {
         Ok(hist)
     }
 
-    // this history doesn't store any info except command line
-    fn construct_entry(id: HistoryItemId, command_line: String) -> HistoryItem {
+    /// Creates a new history with the entries of a history file, which is never written to.
+    ///
+    /// Nothing is created, a missing file being read as an empty history, and no lock is taken.
+    /// Saved, updated or deleted entries are only changed in memory: neither [`History::sync`]
+    /// nor dropping the history write them. Useful for a history on a read-only filesystem.
+    pub fn with_file_read_only(capacity: usize, file: PathBuf) -> Result<Self> {
+        let mut hist = Self::new(capacity)?;
+
+        let (entries, _) = read_entries(&file)
+            .map_err(|err| {
+                io_context(
+                    err,
+                    format!("cannot read the history file {}", file.display()),
+                )
+            })
+            .map_err(ReedlineError::from)?;
+
+        // The most recent entries are kept
+        let skipped = entries.len().saturating_sub(capacity);
+        hist.entries = entries.into_iter().skip(skipped).collect();
+        hist.last_on_disk = hist.entries.last().map(|(id, _)| *id);
+        hist.file = Some(file);
+        hist.read_only = true;
+
+        Ok(hist)
+    }
+
+    /// Persist the start timestamp of each command to the history file.
+    ///
+    /// Entries are then written using the timestamp format (see [`FileBackedHistory::with_file()`])
+    /// and searches can be filtered by time using [`SearchQuery::start_time`] and [`SearchQuery::end_time`].
+    ///
+    /// Files written without timestamps can still be read, and entries without a timestamp never match a time filter.
+    #[must_use]
+    pub fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Persist the working directory of each command to the history file.
+    ///
+    /// The [`HistoryItem::cwd`] of the saved entries is kept, and written using the cwd format
+    /// (see [`FileBackedHistory::with_file()`]). Searches can then be filtered by directory with
+    /// [`SearchFilter::cwd_exact`](crate::SearchFilter::cwd_exact) and
+    /// [`SearchFilter::cwd_prefix`](crate::SearchFilter::cwd_prefix).
+    ///
+    /// Entries without a working directory, e.g. saved before it was recorded, don't match
+    /// any directory filter. Without this, filtering by directory is unsupported.
+    #[must_use]
+    pub fn with_cwd(mut self, cwd: bool) -> Self {
+        self.cwd = cwd;
+        self
+    }
+
+    /// Write the commands spanning several lines with `multiline` (see [`MultilineFormat`]),
+    /// instead of escaping their newlines
+    #[must_use]
+    pub fn with_multiline_format(mut self, multiline: MultilineFormat) -> Self {
+        self.multiline = multiline;
+        self
+    }
+
+    /// Limit the size of the history file to `max_bytes`, in addition to the entry capacity.
+    ///
+    /// When syncing, the oldest entries are dropped until every encoded line (including its
+    /// id prefix and newline) fits in `max_bytes`.
+    #[must_use]
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Tag the saved entries with a session, persisted in the history file
+    ///
+    /// Each saved entry gets the [`HistoryItem::session_id`] of the item, or else `session`,
+    /// and its line of the file is written using the session format (see
+    /// [`FileBackedHistory::with_file()`]). Without a session, entries are saved without any.
+    ///
+    /// A search filtered on a session with [`SearchFilter::session`](crate::SearchFilter::session) only returns its entries and
+    /// the entries without a session, which were saved before sessions were used. The entries of
+    /// the other sessions are returned by a search filtered with [`SearchFilter::not_session`](crate::SearchFilter::not_session).
+    #[must_use]
+    pub fn with_session(mut self, session: HistorySessionId) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Configure which commands are considered as duplicates when saving (see [`HistoryDedup`])
+    #[must_use]
+    pub fn with_dedup(mut self, dedup: HistoryDedup) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Silently drop the commands for which `ignore` returns `true` instead of saving them
+    ///
+    /// See [`ignore_leading_space()`](crate::ignore_leading_space) and the other helpers for common filters
+    #[must_use]
+    pub fn with_ignore_filter(mut self, ignore: HistoryIgnoreFilter) -> Self {
+        self.ignore = Some(ignore);
+        self
+    }
+
+    /// Change the number of remembered elements
+    ///
+    /// When shrinking, the oldest entries are dropped right away, and the history file is
+    /// truncated to the new capacity on the next [`History::sync`].
+    pub fn set_capacity(&mut self, capacity: usize) -> Result<()> {
+        check_capacity(capacity)?;
+
+        if self.entries.len() > capacity {
+            let evicted = self.entries.len() - capacity;
+            // The entries on disk are the oldest ones: if the last one is evicted, all of them are
+            if self.last_on_disk.map_or(false, |id| {
+                self.entries.get_index_of(&id).unwrap() < evicted
+            }) {
+                self.last_on_disk = None;
+            }
+            self.entries.drain(..evicted);
+        }
+        self.capacity = capacity;
+
+        Ok(())
+    }
+
+    /// Like [`History::search`], only borrowing the id and command line of the matching entries
+    ///
+    /// Nothing is cloned, for callers only reading the command lines.
+    pub fn search_refs(&self, query: SearchQuery) -> Result<Vec<(HistoryItemId, &str)>> {
+        Ok(self
+            .matching_entries(query)?
+            .map(|(id, entry)| (*id, entry.command_line.as_str()))
+            .collect())
+    }
+
+    /// Lazily find the entries matching the query
+    fn matching_entries<'a>(
+        &'a self,
+        query: SearchQuery,
+    ) -> Result<Box<dyn Iterator<Item = (&'a HistoryItemId, &'a FileBackedEntry)> + 'a>> {
+        // Destructure the query - this ensures that if another element is added to this type later on,
+        // we won't forget to update this function as the destructuring will then be incomplete.
+        let SearchQuery {
+            direction,
+            start_time,
+            end_time,
+            start_id,
+            end_id,
+            limit,
+            filter,
+        } = query;
+
+        if !self.timestamps && (start_time.is_some() || end_time.is_some()) {
+            return Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported {
+                    history: "FileBackedHistory",
+                    feature: "filtering by time",
+                },
+            ));
+        }
+
+        if filter.hostname.is_some() || filter.exit_successful.is_some() || !filter.tags.is_empty()
+        {
+            return Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported {
+                    history: "FileBackedHistory",
+                    feature: "filtering by extra info",
+                },
+            ));
+        }
+
+        if !self.cwd && (filter.cwd_exact.is_some() || filter.cwd_prefix.is_some()) {
+            return Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported {
+                    history: "FileBackedHistory",
+                    feature: "filtering by directory",
+                },
+            ));
+        }
+
+        let (start_id, end_id) = {
+            if let SearchDirection::Backward = direction {
+                (end_id, start_id)
+            } else {
+                (start_id, end_id)
+            }
+        };
+
+        let start_idx = match start_id {
+            Some(from_id) => self.entries.get_index_of(&from_id).ok_or(ReedlineError(
+                ReedlineErrorVariants::OtherHistoryError("provided 'start_id' item was not found"),
+            ))?,
+            None => 0,
+        };
+
+        let end_idx = match end_id {
+            Some(to_id) => self.entries.get_index_of(&to_id).ok_or(ReedlineError(
+                ReedlineErrorVariants::OtherHistoryError("provided 'end_id' item was not found"),
+            ))?,
+            None => self.entries.len().saturating_sub(1),
+        };
+
+        assert!(start_idx <= end_idx);
+
+        let iter = self
+            .entries
+            .iter()
+            .skip(start_idx)
+            .take(1 + end_idx - start_idx);
+
+        let limit = limit
+            .and_then(|limit| usize::try_from(limit).ok())
+            .unwrap_or(usize::MAX);
+
+        let is_asc = direction == SearchDirection::Forward;
+
+        // Case-sensitive plain text searches don't need a regular expression
+        let command_line_regex = match &filter.command_line {
+            Some(search @ (CommandLineSearch::Regex(_) | CommandLineSearch::Glob(_))) => {
+                Some(search.to_regex(filter.case_sensitive)?)
+            }
+            Some(search) if !filter.case_sensitive => Some(search.to_regex(false)?),
+            _ => None,
+        };
+
+        let subdirectories = filter.cwd_prefix.as_deref().map(subdirectory_prefix);
+
+        let matches = move |(id, entry): (&'a HistoryItemId, &'a FileBackedEntry)| {
+            if start_time.is_some() || end_time.is_some() {
+                let timestamp = entry.start_timestamp?;
+
+                if let Some(start_time) = start_time {
+                    let in_range = if is_asc {
+                        timestamp > start_time
+                    } else {
+                        timestamp < start_time
+                    };
+
+                    if !in_range {
+                        return None;
+                    }
+                }
+
+                if let Some(end_time) = end_time {
+                    let in_range = if is_asc {
+                        timestamp <= end_time
+                    } else {
+                        timestamp >= end_time
+                    };
+
+                    if !in_range {
+                        return None;
+                    }
+                }
+            }
+
+            // Entries without a session were saved before sessions were used
+            if let (Some(session), Some(entry_session)) = (filter.session, entry.session) {
+                if entry_session != session {
+                    return None;
+                }
+            }
+
+            if filter.not_session.is_some() && entry.session == filter.not_session {
+                return None;
+            }
+
+            if let Some(exact) = &filter.cwd_exact {
+                if entry.cwd.as_ref() != Some(exact) {
+                    return None;
+                }
+            }
+
+            if let (Some(prefix), Some(subdirectories)) = (&filter.cwd_prefix, &subdirectories) {
+                let cwd = entry.cwd.as_deref()?;
+                if cwd != prefix && !cwd.starts_with(subdirectories.as_str()) {
+                    return None;
+                }
+            }
+
+            let cmd = &entry.command_line;
+
+            let str_matches = match (&command_line_regex, &filter.command_line) {
+                (Some(regex), _) => regex.is_match(cmd),
+                (None, Some(CommandLineSearch::Prefix(p))) => cmd.starts_with(p),
+                (None, Some(CommandLineSearch::Substring(p))) => cmd.contains(p),
+                (None, Some(CommandLineSearch::Exact(p))) => cmd == p,
+                (None, Some(CommandLineSearch::Regex(_) | CommandLineSearch::Glob(_))) => {
+                    unreachable!()
+                }
+                (None, None) => true,
+            };
+
+            if !str_matches {
+                return None;
+            }
+
+            if let Some(str) = &filter.not_command_line {
+                if cmd == str {
+                    return None;
+                }
+            }
+
+            Some((id, entry))
+        };
+
+        Ok(match direction {
+            SearchDirection::Backward => Box::new(iter.rev().filter_map(matches).take(limit)),
+            SearchDirection::Forward => Box::new(iter.filter_map(matches).take(limit)),
+        })
+    }
+
+    /// Id under which the entry at `index` can be found in the file, if it was already written to it
+    fn disk_id(&self, id: HistoryItemId, index: usize) -> Option<HistoryItemId> {
+        let last_index_on_disk = self
+            .last_on_disk
+            .map(|id| self.entries.get_index_of(&id).unwrap())?;
+
+        if index > last_index_on_disk {
+            return None;
+        }
+
+        // The entry may have been updated since it was written
+        if let Some(change) = self.disk_changes.get(&id) {
+            return Some(change.disk_id);
+        }
+
+        let (disk_id, _) = decode_entry(
+            &encode_entry(id, &self.entries[index], self.timestamps, self.multiline),
+            &mut 0,
+        );
+
+        Some(disk_id)
+    }
+
+    // this history doesn't store any info except command line, start timestamp, session and cwd
+    fn construct_entry(id: HistoryItemId, entry: &FileBackedEntry) -> HistoryItem {
         HistoryItem {
             id,
-            start_timestamp: None,
-            command_line,
-            session_id: None,
+            start_timestamp: entry.start_timestamp,
+            command_line: entry.command_line.clone(),
+            session_id: entry.session,
             hostname: None,
-            cwd: None,
+            cwd: entry.cwd.clone(),
             duration: None,
             exit_status: None,
             more_info: None,
+            tags: HashMap::new(),
         }
     }
 }
@@ -440,3 +1423,1212 @@ impl Drop for FileBackedHistory {
         let _res = self.sync();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchFilter;
+
+    #[test]
+    fn load_missing_item_reports_its_id() {
+        let mut history = FileBackedHistory::default();
+        let id = history.generate_id();
+        history
+            .save(&HistoryItem::from_command_line("ls", id))
+            .unwrap();
+
+        let missing = HistoryItemId::new(id.0.wrapping_add(1));
+
+        match history.load(missing) {
+            Err(ReedlineError(ReedlineErrorVariants::HistoryItemNotFound(err_id))) => {
+                assert_eq!(err_id, missing);
+            }
+            other => panic!("expected HistoryItemNotFound, got {other:?}"),
+        }
+
+        assert_eq!(history.load(id).unwrap().command_line, "ls");
+    }
+
+    #[test]
+    fn search_refs_match_search() -> Result<()> {
+        let mut history = FileBackedHistory::default();
+        for cmd in ["git status", "ls", "git push"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+        let query = || SearchQuery::all_that_contain_rev("git".to_string());
+
+        let items = history.search(query())?;
+        assert_eq!(
+            history.search_refs(query())?,
+            items
+                .iter()
+                .map(|item| (item.id, item.command_line.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(history.count(query())?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn decodes_every_format() {
+        let mut counter = 0;
+
+        let (_, legacy) = decode_entry("ls /", &mut counter);
+        assert_eq!(legacy.command_line, "ls /");
+        assert_eq!(legacy.start_timestamp, None);
+
+        let (id, new) = decode_entry("-182535<id>:echo a<\\n>b", &mut counter);
+        assert_eq!(id, HistoryItemId::new(-182535));
+        assert_eq!(new.command_line, "echo a\nb");
+        assert_eq!(new.start_timestamp, None);
+
+        let (id, timestamped) = decode_entry("182535<id>@1700000000000:ls /", &mut counter);
+        assert_eq!(id, HistoryItemId::new(182535));
+        assert_eq!(timestamped.command_line, "ls /");
+        assert_eq!(
+            timestamped.start_timestamp,
+            Some(Utc.timestamp_millis_opt(1700000000000).unwrap())
+        );
+
+        let (id, session) = decode_entry("182535<id>@1700000000000#42:ls /", &mut counter);
+        assert_eq!(id, HistoryItemId::new(182535));
+        assert_eq!(session.command_line, "ls /");
+        assert_eq!(session.session, Some(HistorySessionId::new(42)));
+        assert!(session.start_timestamp.is_some());
+
+        let (_, session) = decode_entry("182535<id>#-42:cd #1", &mut counter);
+        assert_eq!(session.command_line, "cd #1");
+        assert_eq!(session.session, Some(HistorySessionId::new(-42)));
+        assert_eq!(session.start_timestamp, None);
+
+        let (_, cwd) = decode_entry("182535<id>#42<cwd>/home/a%3Ab%25:ls /", &mut counter);
+        assert_eq!(cwd.command_line, "ls /");
+        assert_eq!(cwd.session, Some(HistorySessionId::new(42)));
+        assert_eq!(cwd.cwd.as_deref(), Some("/home/a:b%"));
+
+        for not_an_id in ["echo <id>:", "1<id>#:ls", "1<id>#a:ls", "1<id>#1@1:ls"] {
+            let (_, entry) = decode_entry(not_an_id, &mut counter);
+            assert_eq!(entry.command_line, not_an_id);
+        }
+    }
+
+    #[test]
+    fn any_command_line_survives_encoding() {
+        let fragments = [
+            "a",
+            " ",
+            "\n",
+            "\r",
+            "\\",
+            "\\n",
+            "<\\n>",
+            "<",
+            ">",
+            "n",
+            "<id>",
+            "<esc>",
+            "42",
+            ":",
+            "@",
+            "#",
+            "é",
+            "<<",
+            "entry",
+            "1",
+            "#reedline-history v",
+            "%",
+            "%3A",
+        ];
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        for _ in 0..2000 {
+            let mut fragments_line = || {
+                let len = rng.gen_range(0..10);
+                (0..len)
+                    .map(|_| fragments[rng.gen_range(0..fragments.len())])
+                    .collect::<String>()
+            };
+            let command_line = fragments_line();
+            let cwd = fragments_line();
+            let session = rng.gen_bool(0.5).then_some(HistorySessionId::new(3));
+            let cwd = rng.gen_bool(0.5).then_some(cwd);
+            let entry = FileBackedEntry {
+                command_line,
+                start_timestamp: None,
+                session,
+                cwd,
+            };
+
+            for (timestamps, multiline) in [
+                (false, MultilineFormat::Escaped),
+                (true, MultilineFormat::Escaped),
+                (false, MultilineFormat::Block),
+                (true, MultilineFormat::Block),
+            ] {
+                let encoded = encode_entry(HistoryItemId::new(7), &entry, timestamps, multiline);
+                // Lines are read back without their line ending
+                let file = format!("{encoded}\n");
+                let entries = encoded_entries(file.as_bytes()).unwrap();
+                assert_eq!(entries.len(), 1, "{encoded:?}");
+
+                let (id, decoded) = decode_entry(&entries[0], &mut 0);
+                assert_eq!(decoded, entry, "{encoded:?}");
+                if timestamps || session.is_some() || entry.cwd.is_some() {
+                    assert_eq!(id, HistoryItemId::new(7));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multiline_commands_are_written_as_blocks() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        // An existing file with an escaped command
+        std::fs::write(&histfile, "echo a<\\n>b\n").unwrap();
+
+        let commands = [
+            "for x in a b\ndo echo $x\ndone",
+            "cat <<entry\nhi\nentry",
+            "ls",
+        ];
+        {
+            let mut history = FileBackedHistory::with_file(20, histfile.clone())?
+                .with_multiline_format(MultilineFormat::Block);
+            for cmd in commands {
+                let id = history.generate_id();
+                history.save(&HistoryItem::from_command_line(cmd, id))?;
+            }
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "echo a<\\n>b\n\
+             <<entry\nfor x in a b\ndo echo $x\ndone\nentry\n\
+             <<entry1\ncat <<entry\nhi\nentry\nentry1\n\
+             ls\n"
+        );
+
+        // Both formats are read back, whichever is used to write
+        let history = FileBackedHistory::with_file(20, histfile.clone())?;
+        let found = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))?
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect::<Vec<_>>();
+        assert_eq!(found, ["echo a\nb", commands[0], commands[1], commands[2]]);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn cut_blocks_end_with_the_file() {
+        let entries = encoded_entries("ls\n<<entry\necho a\necho b\n".as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let (_, entry) = decode_entry(&entries[1], &mut 0);
+        assert_eq!(entry.command_line, "echo a\necho b");
+    }
+
+    #[test]
+    fn newer_format_versions_are_not_read() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        // The supported version is read like a file without a header
+        std::fs::write(&histfile, "#reedline-history v1\nls\n").unwrap();
+        let history = FileBackedHistory::with_file(20, histfile.clone())?;
+        let found = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))?
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect::<Vec<_>>();
+        assert_eq!(found, ["ls"]);
+
+        let newer = "#reedline-history v2\n1<id>:ls\n";
+        std::fs::write(&histfile, newer).unwrap();
+        for opened in [
+            FileBackedHistory::with_file(20, histfile.clone()),
+            FileBackedHistory::with_file_read_only(20, histfile.clone()),
+        ] {
+            assert!(matches!(
+                opened,
+                Err(ReedlineError(
+                    ReedlineErrorVariants::HistoryFormatVersionMismatch {
+                        found: 2,
+                        supported: 1
+                    }
+                ))
+            ));
+        }
+
+        // The new entries of an open history aren't written over the file
+        let mut history = FileBackedHistory::new(20)?;
+        history.file = Some(histfile.clone());
+        let id = history.generate_id();
+        history.save(&HistoryItem::from_command_line("cd", id))?;
+        let err = history.sync().unwrap_err();
+        assert!(matches!(
+            ReedlineError::from(err),
+            ReedlineError(ReedlineErrorVariants::HistoryFormatVersionMismatch { .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&histfile).unwrap(), newer);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn escape_literals_survive_reload() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let commands = [
+            "echo '<\\n>'",
+            "printf 'a\\nb'\n",
+            "1<id>:ls",
+            "<esc>\\",
+            "cat\r",
+        ];
+
+        // An existing file written with the former scheme
+        std::fs::write(&histfile, "echo a<\\n>b\n").unwrap();
+
+        for timestamps in [false, true] {
+            {
+                let mut history =
+                    FileBackedHistory::with_file(20, histfile.clone())?.with_timestamps(timestamps);
+                for cmd in commands {
+                    let id = history.generate_id();
+                    history.save(&HistoryItem::from_command_line(cmd, id))?;
+                }
+            }
+
+            let history = FileBackedHistory::with_file(20, histfile.clone())?;
+            let saved = history
+                .search(SearchQuery::everything(SearchDirection::Forward, None))?
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect::<Vec<_>>();
+            assert_eq!(saved[0], "echo a\nb");
+            assert_eq!(saved[saved.len() - commands.len()..], commands);
+        }
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn persists_timestamps() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let timestamp = Utc.timestamp_millis_opt(1700000000123).unwrap();
+
+        let (with_timestamp, without_timestamp) = {
+            let mut history =
+                FileBackedHistory::with_file(10, histfile.clone())?.with_timestamps(true);
+
+            let mut item = HistoryItem::from_command_line("ls /", history.generate_id());
+            item.start_timestamp = Some(timestamp);
+            history.save(&item)?;
+
+            let other = HistoryItem::from_command_line("cd", history.generate_id());
+            history.save(&other)?;
+
+            (item, other)
+        };
+
+        let history = FileBackedHistory::with_file(10, histfile)?;
+        assert_eq!(history.load(with_timestamp.id)?, with_timestamp);
+        assert_eq!(history.load(without_timestamp.id)?, without_timestamp);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn filters_by_time_when_timestamps_are_enabled() -> Result<()> {
+        let timestamp = |secs| Some(Utc.timestamp_opt(secs, 0).unwrap());
+
+        let mut history = FileBackedHistory::default();
+        assert!(history
+            .search(SearchQuery {
+                start_time: timestamp(0),
+                ..SearchQuery::everything(SearchDirection::Forward, None)
+            })
+            .is_err());
+
+        history = history.with_timestamps(true);
+
+        for (cmd, start_timestamp) in [
+            ("a", timestamp(10)),
+            ("b", None),
+            ("c", timestamp(20)),
+            ("d", timestamp(30)),
+        ] {
+            let mut item = HistoryItem::from_command_line(cmd, history.generate_id());
+            item.start_timestamp = start_timestamp;
+            history.save(&item)?;
+        }
+
+        let commands = |items: Vec<HistoryItem>| {
+            items
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect::<Vec<_>>()
+        };
+
+        let forward = history.search(SearchQuery {
+            start_time: timestamp(10),
+            end_time: timestamp(20),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        })?;
+        assert_eq!(commands(forward), vec!["c"]);
+
+        let backward = history.search(SearchQuery {
+            start_time: timestamp(30),
+            ..SearchQuery::everything(SearchDirection::Backward, None)
+        })?;
+        assert_eq!(commands(backward), vec!["c", "a"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deleted_entries_are_removed_from_file() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let commands = |history: &FileBackedHistory| {
+            history
+                .search(SearchQuery::everything(SearchDirection::Forward, None))
+                .unwrap()
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect::<Vec<_>>()
+        };
+
+        {
+            let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+            for cmd in ["first", "secret", "last"] {
+                let id = history.generate_id();
+                history.save(&HistoryItem::from_command_line(cmd, id))?;
+            }
+        }
+
+        {
+            let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+            let secret = history
+                .search(SearchQuery::all_that_contain_rev("secret".to_string()))?
+                .remove(0);
+
+            history.delete(secret.id)?;
+            assert!(history.load(secret.id).is_err());
+            assert_eq!(commands(&history), vec!["first", "last"]);
+
+            history.sync().unwrap();
+            assert_eq!(commands(&history), vec!["first", "last"]);
+        }
+
+        let history = FileBackedHistory::with_file(10, histfile)?;
+        assert_eq!(commands(&history), vec!["first", "last"]);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn deleting_last_synced_entry_keeps_new_entries() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        {
+            let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+            let synced = HistoryItem::from_command_line("synced", history.generate_id());
+            history.save(&synced)?;
+            history.sync().unwrap();
+
+            let unsynced = HistoryItem::from_command_line("unsynced", history.generate_id());
+            history.save(&unsynced)?;
+
+            history.delete(synced.id)?;
+            assert!(history.delete(synced.id).is_err());
+        }
+
+        let history = FileBackedHistory::with_file(10, histfile)?;
+        let all = history.search(SearchQuery::everything(SearchDirection::Forward, None))?;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].command_line, "unsynced");
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn updated_entries_follow_the_session_of_the_history() -> Result<()> {
+        let session = HistorySessionId::new(1);
+        let update_session = |history: &mut FileBackedHistory, session_id| -> Result<_> {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line("ls", id))?;
+            history.update(id, &|mut item| {
+                item.session_id = session_id;
+                item
+            })?;
+            Ok(history.load(id)?.session_id)
+        };
+
+        let mut history = FileBackedHistory::default();
+        assert_eq!(update_session(&mut history, Some(session))?, None);
+
+        let mut history = FileBackedHistory::default().with_session(session);
+        assert_eq!(update_session(&mut history, None)?, Some(session));
+
+        Ok(())
+    }
+
+    #[test]
+    fn updated_entries_keep_their_position() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let commands = |history: &FileBackedHistory| {
+            history
+                .search(SearchQuery::everything(SearchDirection::Forward, None))
+                .unwrap()
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect::<Vec<_>>()
+        };
+
+        {
+            let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+            for cmd in ["cd /", "gti status", "ls"] {
+                let id = history.generate_id();
+                history.save(&HistoryItem::from_command_line(cmd, id))?;
+            }
+            history.sync().unwrap();
+
+            let typo = history
+                .search(SearchQuery::all_that_contain_rev("gti".to_string()))?
+                .remove(0);
+
+            history.update(typo.id, &|mut item| {
+                item.command_line = "git status".to_string();
+                item
+            })?;
+            assert_eq!(history.load(typo.id)?.command_line, "git status");
+
+            let unsynced = history.generate_id();
+            history.save(&HistoryItem::from_command_line("pwdd", unsynced))?;
+            history.update(unsynced, &|mut item| {
+                item.command_line = "pwd".to_string();
+                item
+            })?;
+
+            assert!(history
+                .update(HistoryItemId::new(typo.id.0.wrapping_add(1)), &|item| item)
+                .is_err());
+        }
+
+        let history = FileBackedHistory::with_file(10, histfile.clone())?;
+        assert_eq!(commands(&history), vec!["cd /", "git status", "ls", "pwd"]);
+        drop(history);
+
+        // Updates must survive the truncation path as well
+        {
+            let mut history = FileBackedHistory::with_file(4, histfile.clone())?;
+            let git = history
+                .search(SearchQuery::all_that_contain_rev("git".to_string()))?
+                .remove(0);
+            history.update(git.id, &|mut item| {
+                item.command_line = "git log".to_string();
+                item
+            })?;
+
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line("exit", id))?;
+        }
+
+        let history = FileBackedHistory::with_file(4, histfile)?;
+        assert_eq!(commands(&history), vec!["git log", "ls", "pwd", "exit"]);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_strategies() -> Result<()> {
+        let commands = ["git status", "ls", "ls", "git status", "ls"];
+
+        let saved = |dedup| -> Result<Vec<String>> {
+            let mut history = FileBackedHistory::default().with_dedup(dedup);
+            for cmd in commands {
+                let id = history.generate_id();
+                history.save(&HistoryItem::from_command_line(cmd, id))?;
+            }
+
+            Ok(history
+                .search(SearchQuery::everything(SearchDirection::Forward, None))?
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect())
+        };
+
+        assert_eq!(saved(HistoryDedup::None)?, commands);
+        assert_eq!(
+            saved(HistoryDedup::Consecutive)?,
+            vec!["git status", "ls", "git status", "ls"]
+        );
+        assert_eq!(saved(HistoryDedup::All)?, vec!["git status", "ls"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_all_removes_duplicates_from_file() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        for cmd in ["ls", "cd /", "ls"] {
+            let mut history =
+                FileBackedHistory::with_file(10, histfile.clone())?.with_dedup(HistoryDedup::All);
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+
+        let history = FileBackedHistory::with_file(10, histfile)?;
+        let all = history.search(SearchQuery::everything(SearchDirection::Forward, None))?;
+        assert_eq!(
+            all.into_iter()
+                .map(|item| item.command_line)
+                .collect::<Vec<_>>(),
+            vec!["cd /", "ls"]
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn max_bytes_drops_oldest_entries() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        {
+            // "first\n" and "second\n" already take 13 bytes
+            let mut history =
+                FileBackedHistory::with_file(10, histfile.clone())?.with_max_bytes(13);
+            for cmd in ["first", "second", "third"] {
+                let id = history.generate_id();
+                history.save(&HistoryItem::from_command_line(cmd, id))?;
+            }
+            history.sync()?;
+
+            let all = history.search(SearchQuery::everything(SearchDirection::Forward, None))?;
+            assert_eq!(
+                all.into_iter()
+                    .map(|item| item.command_line)
+                    .collect::<Vec<_>>(),
+                vec!["second", "third"]
+            );
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "second\nthird\n"
+        );
+
+        // Entries already in the file are dropped first
+        {
+            let mut history =
+                FileBackedHistory::with_file(10, histfile.clone())?.with_max_bytes(13);
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line("fourth", id))?;
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "third\nfourth\n"
+        );
+
+        // Without limit, nothing is dropped
+        {
+            let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line("fifth", id))?;
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "third\nfourth\nfifth\n"
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn shrinking_capacity_truncates_file_on_sync() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+        for cmd in ["first", "second", "third"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+        history.sync()?;
+        for cmd in ["fourth", "fifth"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+
+        // Drops every synced entry and one of the new ones
+        history.set_capacity(1)?;
+        assert_eq!(history.count_all()?, 1);
+        history.sync()?;
+
+        assert_eq!(std::fs::read_to_string(&histfile).unwrap(), "fifth\n");
+
+        // The entries written by another history are dropped too
+        let mut other = FileBackedHistory::with_file(10, histfile.clone())?;
+        history.set_capacity(3)?;
+        for cmd in ["sixth", "seventh"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+        history.sync()?;
+        other.set_capacity(2)?;
+        other.sync()?;
+
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "sixth\nseventh\n"
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn sessions_are_persisted_and_filtered() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "legacy\n").unwrap();
+
+        for (session, cmd) in [(1, "first"), (2, "second")] {
+            let mut history = FileBackedHistory::with_file(10, histfile.clone())?
+                .with_session(HistorySessionId::new(session));
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+
+        let lines = std::fs::read_to_string(&histfile).unwrap();
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "legacy");
+        assert!(lines[1].ends_with("<id>#1:first"), "{lines:?}");
+        assert!(lines[2].ends_with("<id>#2:second"), "{lines:?}");
+
+        let history = FileBackedHistory::with_file(10, histfile.clone())?;
+        let search = |filter: SearchFilter| -> Result<Vec<String>> {
+            Ok(history
+                .search(SearchQuery {
+                    filter,
+                    ..SearchQuery::everything(SearchDirection::Forward, None)
+                })?
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect())
+        };
+
+        assert_eq!(
+            search(SearchFilter::anything(Some(HistorySessionId::new(1))))?,
+            vec!["legacy", "first"]
+        );
+        assert_eq!(
+            search(SearchFilter {
+                not_session: Some(HistorySessionId::new(1)),
+                ..SearchFilter::anything(None)
+            })?,
+            vec!["legacy", "second"]
+        );
+        assert_eq!(
+            history
+                .search(SearchQuery::everything(SearchDirection::Forward, None))?
+                .into_iter()
+                .map(|item| item.session_id)
+                .collect::<Vec<_>>(),
+            vec![
+                None,
+                Some(HistorySessionId::new(1)),
+                Some(HistorySessionId::new(2))
+            ]
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn cwds_are_persisted_and_filtered() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "legacy\n").unwrap();
+
+        let mut history = FileBackedHistory::with_file(10, histfile.clone())?.with_cwd(true);
+        for (cwd, cmd) in [
+            ("/home/me", "first"),
+            ("/home/me/a:b\n", "second"),
+            ("/tmp", "third"),
+        ] {
+            let mut item = HistoryItem::from_command_line(cmd, history.generate_id());
+            item.cwd = Some(cwd.to_string());
+            history.save(&item)?;
+        }
+        history.sync()?;
+
+        let lines = std::fs::read_to_string(&histfile).unwrap();
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4, "{lines:?}");
+        assert!(
+            lines[2].ends_with("<id><cwd>/home/me/a%3Ab%0A:second"),
+            "{lines:?}"
+        );
+
+        let history = FileBackedHistory::with_file(10, histfile.clone())?.with_cwd(true);
+        let search = |filter: SearchFilter| -> Result<Vec<String>> {
+            Ok(history
+                .search(SearchQuery {
+                    filter,
+                    ..SearchQuery::everything(SearchDirection::Forward, None)
+                })?
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect())
+        };
+
+        assert_eq!(
+            search(SearchFilter {
+                cwd_prefix: Some("/home/me".to_string()),
+                ..SearchFilter::anything(None)
+            })?,
+            vec!["first", "second"]
+        );
+        assert_eq!(
+            search(SearchFilter {
+                cwd_prefix: Some("/home/m".to_string()),
+                ..SearchFilter::anything(None)
+            })?,
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            search(SearchFilter {
+                cwd_exact: Some("/home/me/a:b\n".to_string()),
+                ..SearchFilter::anything(None)
+            })?,
+            vec!["second"]
+        );
+
+        // Filtering by directory needs the directories
+        let history = FileBackedHistory::with_file(10, histfile.clone())?;
+        assert!(matches!(
+            history.search(SearchQuery {
+                filter: SearchFilter {
+                    cwd_prefix: Some("/home/me".to_string()),
+                    ..SearchFilter::anything(None)
+                },
+                ..SearchQuery::everything(SearchDirection::Forward, None)
+            }),
+            Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported { .. }
+            ))
+        ));
+
+        // Without recording them, the working directories are dropped
+        let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+        let mut item = HistoryItem::from_command_line("fourth", history.generate_id());
+        item.cwd = Some("/tmp".to_string());
+        history.save(&item)?;
+        history.sync()?;
+        let lines = std::fs::read_to_string(&histfile).unwrap();
+        assert!(lines.ends_with(":third\nfourth\n"), "{lines:?}");
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn filtering_by_tags_is_unsupported() {
+        let history = FileBackedHistory::default();
+        let result = history.search(SearchQuery {
+            filter: SearchFilter {
+                tags: HashMap::from([("branch".to_string(), "main".to_string())]),
+                ..SearchFilter::anything(None)
+            },
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        });
+
+        assert!(matches!(
+            result,
+            Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn read_only_history_never_writes() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "first\nsecond\nthird\n").unwrap();
+        let mut permissions = std::fs::metadata(tmp.path()).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(tmp.path(), permissions.clone()).unwrap();
+
+        {
+            let mut history = FileBackedHistory::with_file_read_only(2, histfile.clone())?;
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line("fourth", id))?;
+            history.sync()?;
+
+            assert_eq!(
+                history
+                    .search_refs(SearchQuery::everything(SearchDirection::Forward, None))?
+                    .into_iter()
+                    .map(|(_, command_line)| command_line)
+                    .collect::<Vec<_>>(),
+                vec!["third", "fourth"]
+            );
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "first\nsecond\nthird\n"
+        );
+        // No lock file either
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+
+        // A missing file is an empty history, nothing is created
+        let missing = tmp.path().join("missing").join(".history");
+        let history = FileBackedHistory::with_file_read_only(2, missing)?;
+        assert_eq!(history.count_all()?, 0);
+        assert!(!tmp.path().join("missing").exists());
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(tmp.path(), permissions).unwrap();
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn sync_errors_describe_the_operation() {
+        let tmp = tempfile::tempdir().unwrap();
+        // The directory can't be created below a file
+        let not_a_dir = tmp.path().join("file");
+        std::fs::write(&not_a_dir, "").unwrap();
+
+        let err = FileBackedHistory::with_file(10, not_a_dir.join("dir").join(".history"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cannot create the history directory"), "{err}");
+
+        // The history file can't be opened as it's a directory
+        let dir = tmp.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        let err = FileBackedHistory::with_file(10, dir)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cannot read the history file"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rewriting_through_a_symlink_keeps_it() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().join("target");
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink("target", &link).unwrap();
+
+        let mut history = FileBackedHistory::with_file(10, link.clone())?;
+        for cmd in ["first", "second"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+        history.sync()?;
+        // Deleting a written entry rewrites the file
+        let first =
+            history.search_refs(SearchQuery::everything(SearchDirection::Forward, None))?[0].0;
+        history.delete(first)?;
+        history.sync()?;
+
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "second\n");
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn set_capacity_rejects_too_large_capacity() -> Result<()> {
+        let mut history = FileBackedHistory::new(10)?;
+        assert!(history.set_capacity(usize::MAX).is_err());
+        history.set_capacity(usize::MAX - 1)?;
+        Ok(())
+    }
+
+    #[test]
+    fn max_bytes_accounts_for_id_prefix() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let mut history = FileBackedHistory::with_file(10, histfile.clone())?
+            .with_timestamps(true)
+            .with_max_bytes(20);
+
+        // Each "1000<id>:a\n" line takes 11 bytes, so only the newest one fits
+        for (id, cmd) in [(1000, "a"), (1001, "b")] {
+            history.save(&HistoryItem::from_command_line(cmd, HistoryItemId::new(id)))?;
+        }
+        history.sync()?;
+
+        assert_eq!(std::fs::read_to_string(&histfile).unwrap(), "1001<id>:b\n");
+        assert_eq!(history.count_all()?, 1);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn histories_differing_by_extension_have_their_own_lock() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+
+        for name in ["history.txt", "history.hist"] {
+            let mut history = FileBackedHistory::with_file(10, tmp.path().join(name))?;
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(name, id))?;
+            history.sync().unwrap();
+        }
+
+        assert!(tmp.path().join("history.txt.lock").exists());
+        assert!(tmp.path().join("history.hist.lock").exists());
+        assert!(!tmp.path().join("history.lock").exists());
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn failed_rewrite_leaves_file_untouched() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join("history.txt");
+
+        {
+            let mut history = FileBackedHistory::with_file(2, histfile.clone())?;
+            for cmd in ["first", "second"] {
+                let id = history.generate_id();
+                history.save(&HistoryItem::from_command_line(cmd, id))?;
+            }
+        }
+
+        let original = std::fs::read_to_string(&histfile).unwrap();
+
+        // Occupy the temporary file's path so that the rewrite fails before the rename
+        std::fs::create_dir(tmp.path().join("history.txt.tmp")).unwrap();
+
+        let mut history = FileBackedHistory::with_file(2, histfile.clone())?;
+        let id = history.generate_id();
+        history.save(&HistoryItem::from_command_line("third", id))?;
+        assert!(history.sync().is_err());
+
+        assert_eq!(std::fs::read_to_string(&histfile).unwrap(), original);
+
+        std::fs::remove_dir(tmp.path().join("history.txt.tmp")).unwrap();
+        history.sync().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "second\nthird\n"
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_filter_runs_before_dedup() -> Result<()> {
+        let mut history = FileBackedHistory::default()
+            .with_dedup(HistoryDedup::All)
+            .with_ignore_filter(crate::ignore_leading_space());
+
+        for cmd in ["ls", " ls", " secret"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+
+        let saved = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))?
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect::<Vec<_>>();
+        assert_eq!(saved, vec!["ls"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_syncs_keep_every_entry_once() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let writers = ["a", "b"].map(|name| {
+            let histfile = histfile.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let mut history =
+                    FileBackedHistory::with_file(1000, histfile)?.with_timestamps(true);
+                for i in 0..50 {
+                    let id = history.generate_id();
+                    history.save(&HistoryItem::from_command_line(format!("{name} {i}"), id))?;
+                    if i % 3 == 0 {
+                        history.sync().unwrap();
+                    }
+                }
+                Ok(())
+            })
+        });
+        for writer in writers {
+            writer.join().unwrap()?;
+        }
+
+        let history = FileBackedHistory::with_file(1000, histfile)?;
+        let mut saved = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))?
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect::<Vec<_>>();
+        saved.sort();
+
+        let mut expected = ["a", "b"]
+            .iter()
+            .flat_map(|name| (0..50).map(move |i| format!("{name} {i}")))
+            .collect::<Vec<_>>();
+        expected.sort();
+
+        assert_eq!(saved, expected);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn interleaved_syncs_merge_foreign_entries() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let open = || -> Result<FileBackedHistory> {
+            Ok(FileBackedHistory::with_file(100, histfile.clone())?.with_timestamps(true))
+        };
+        let commands = |history: &FileBackedHistory| {
+            history
+                .search(SearchQuery::everything(SearchDirection::Forward, None))
+                .unwrap()
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect::<Vec<_>>()
+        };
+
+        let mut a = open()?;
+        let mut b = open()?;
+        let save = |history: &mut FileBackedHistory, cmd: &str| -> Result<()> {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+            history.sync().unwrap();
+            Ok(())
+        };
+
+        save(&mut a, "a1")?;
+        save(&mut b, "b1")?;
+        save(&mut a, "a2")?;
+        save(&mut b, "b2")?;
+        save(&mut a, "a3")?;
+        b.sync().unwrap();
+
+        let expected = vec!["a1", "b1", "a2", "b2", "a3"];
+        assert_eq!(commands(&a), expected);
+        assert_eq!(commands(&b), expected);
+        assert_eq!(commands(&open()?), expected);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn entries_appearing_twice_in_file_are_read_once() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let mut history = FileBackedHistory::with_file(10, histfile.clone())?.with_timestamps(true);
+        let unsynced = HistoryItem::from_command_line("unsynced", HistoryItemId::new(2));
+        history.save(&unsynced)?;
+
+        // As left by a sync interrupted between writing the entries and recording it
+        std::fs::write(&histfile, "1<id>:ls\n1<id>:ls\n2<id>:unsynced\n").unwrap();
+
+        history.sync().unwrap();
+        assert_eq!(history.count_all()?, 2);
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "1<id>:ls\n2<id>:unsynced\n"
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_legacy_lines_are_not_removed_from_file() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        std::fs::write(&histfile, "ls\ncd /\nls\n").unwrap();
+
+        let mut history = FileBackedHistory::with_file(10, histfile.clone())?;
+        history.sync().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "ls\ncd /\nls\n"
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn evicting_synced_entries_keeps_unsynced_ones() -> Result<()> {
+        let tmp = tempfile::tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+
+        let mut history = FileBackedHistory::with_file(2, histfile.clone())?;
+        for cmd in ["first", "second", "third", "fourth"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+            if cmd == "first" {
+                history.sync().unwrap();
+            }
+        }
+        history.sync().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&histfile).unwrap(),
+            "third\nfourth\n"
+        );
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use super::{History, HistoryItem, HistoryItemId, HistorySessionId, SearchQuery};
+use crate::Result;
+
+/// Predicate deciding if a command line must be kept out of the [`History`]
+///
+/// Returning `true` means the command is ignored, it will silently not be saved.
+pub type HistoryIgnoreFilter = Box<dyn Fn(&str) -> bool + Send>;
+
+/// Ignore commands starting with a space, like bash's `HISTCONTROL=ignorespace`
+pub fn ignore_leading_space() -> HistoryIgnoreFilter {
+    Box::new(|command_line| command_line.starts_with(' '))
+}
+
+/// Ignore commands exactly matching one of the given commands, like bash's `HISTIGNORE`
+pub fn ignore_commands(commands: Vec<String>) -> HistoryIgnoreFilter {
+    Box::new(move |command_line| commands.iter().any(|command| command == command_line))
+}
+
+/// Ignore commands containing the given string (e.g. a secret token)
+pub fn ignore_containing(needle: String) -> HistoryIgnoreFilter {
+    Box::new(move |command_line| command_line.contains(&needle))
+}
+
+/// Wrapper around any [`History`] which drops the commands matching an ignore filter
+///
+/// [`FileBackedHistory`](crate::FileBackedHistory) can be configured directly with
+/// [`FileBackedHistory::with_ignore_filter()`](crate::FileBackedHistory::with_ignore_filter).
+///
+/// # Example
+/// ```rust
+/// use reedline::{ignore_leading_space, FilteredHistory, Reedline, FileBackedHistory};
+///
+/// let history = FilteredHistory::new(
+///     Box::<FileBackedHistory>::default(),
+///     ignore_leading_space(),
+/// );
+///
+/// let mut line_editor = Reedline::create().with_history(Box::new(history));
+/// ```
+pub struct FilteredHistory {
+    history: Box<dyn History>,
+    ignore: HistoryIgnoreFilter,
+}
+
+impl FilteredHistory {
+    /// Wrap a [`History`], ignoring the commands for which `ignore` returns `true`
+    pub fn new(history: Box<dyn History>, ignore: HistoryIgnoreFilter) -> Self {
+        Self { history, ignore }
+    }
+
+    /// Unwrap the underlying [`History`]
+    pub fn into_inner(self) -> Box<dyn History> {
+        self.history
+    }
+}
+
+impl History for FilteredHistory {
+    fn generate_id(&mut self) -> HistoryItemId {
+        self.history.generate_id()
+    }
+
+    fn save(&mut self, h: &HistoryItem) -> Result<()> {
+        if (self.ignore)(&h.command_line) {
+            return Ok(());
+        }
+
+        self.history.save(h)
+    }
+
+    fn replace(&mut self, h: &HistoryItem) -> Result<()> {
+        if (self.ignore)(&h.command_line) {
+            return Ok(());
+        }
+
+        self.history.replace(h)
+    }
+
+    fn load(&self, id: HistoryItemId) -> Result<HistoryItem> {
+        self.history.load(id)
+    }
+
+    fn count(&self, query: SearchQuery) -> Result<u64> {
+        self.history.count(query)
+    }
+
+    fn count_all(&self) -> Result<u64> {
+        self.history.count_all()
+    }
+
+    fn unique_count(&self) -> Result<u64> {
+        self.history.unique_count()
+    }
+
+    fn top_commands(&self, n: usize) -> Result<Vec<(String, u64)>> {
+        self.history.top_commands(n)
+    }
+
+    fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
+        self.history.search(query)
+    }
+
+    fn search_iter(
+        &self,
+        query: SearchQuery,
+    ) -> Box<dyn Iterator<Item = Result<HistoryItem>> + '_> {
+        self.history.search_iter(query)
+    }
+
+    fn update(
+        &mut self,
+        id: HistoryItemId,
+        updater: &dyn Fn(HistoryItem) -> HistoryItem,
+    ) -> Result<()> {
+        self.history.update(id, updater)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.history.clear()
+    }
+
+    fn delete(&mut self, h: HistoryItemId) -> Result<()> {
+        self.history.delete(h)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.history.sync()
+    }
+
+    fn session(&self) -> Option<HistorySessionId> {
+        self.history.session()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileBackedHistory, SearchDirection};
+
+    #[test]
+    fn filtered_history_drops_ignored_commands() -> Result<()> {
+        let mut history = FilteredHistory::new(
+            Box::<FileBackedHistory>::default(),
+            ignore_commands(vec!["clear".to_string()]),
+        );
+
+        for cmd in ["ls", "clear", "clear -x"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+
+        let saved = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))?
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect::<Vec<_>>();
+        assert_eq!(saved, vec!["ls", "clear -x"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn statistics_come_from_the_inner_history() -> Result<()> {
+        let mut history =
+            FilteredHistory::new(Box::<FileBackedHistory>::default(), ignore_leading_space());
+
+        for cmd in ["ls", " secret", "cd", "ls"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(cmd, id))?;
+        }
+
+        assert_eq!(history.count_all()?, 3);
+        assert_eq!(history.unique_count()?, 2);
+        assert_eq!(history.top_commands(1)?, vec![("ls".to_string(), 2)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn helpers() {
+        assert!(ignore_leading_space()(" secret"));
+        assert!(!ignore_leading_space()("ls "));
+
+        assert!(ignore_containing("TOKEN".to_string())("curl -H TOKEN=1"));
+        assert!(!ignore_containing("TOKEN".to_string())("curl"));
+    }
+}
@@ -2,7 +2,7 @@ use chrono::Utc;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 use rusqlite::ToSql;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{fmt::Display, time::Duration};
+use std::{collections::HashMap, fmt::Display, time::Duration};
 
 /// Unique ID for the [`HistoryItem`].
 /// These are generated pseudo-randomly.
@@ -107,6 +107,11 @@ pub struct HistoryItem<ExtraInfo: HistoryItemExtraInfo = IgnoreAllExtraInfo> {
     ///       (see <https://github.com/serde-rs/serde/issues/1296#issuecomment-394056188> for the fix)
     #[serde(deserialize_with = "Option::<ExtraInfo>::deserialize")]
     pub more_info: Option<ExtraInfo>,
+    /// user-defined key/value metadata, e.g. the project or the git branch the command was run in.
+    /// The sqlite history stores and filters them (see [`crate::SearchFilter::tags`]),
+    /// the file history drops them
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
 }
 
 impl HistoryItem {
@@ -122,6 +127,7 @@ impl HistoryItem {
             duration: None,
             exit_status: None,
             more_info: None,
+            tags: HashMap::new(),
         }
     }
 }
@@ -1,6 +1,7 @@
 mod base;
 mod cursor;
 mod file_backed;
+mod ignore;
 mod item;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 mod sqlite_backed;
@@ -8,9 +9,13 @@ mod sqlite_backed;
 pub use sqlite_backed::SqliteBackedHistory;
 
 pub use base::{
-    CommandLineSearch, History, HistoryNavigationQuery, SearchDirection, SearchFilter, SearchQuery,
+    migrate_history, CommandLineSearch, History, HistoryNavigationQuery, SearchDirection,
+    SearchFilter, SearchQuery,
 };
 pub use cursor::HistoryCursor;
 pub use item::{HistoryItem, HistoryItemId, HistorySessionId};
 
-pub use file_backed::{FileBackedHistory, HISTORY_SIZE};
+pub use file_backed::{FileBackedHistory, HistoryDedup, MultilineFormat, HISTORY_SIZE};
+pub use ignore::{
+    ignore_commands, ignore_containing, ignore_leading_space, FilteredHistory, HistoryIgnoreFilter,
+};
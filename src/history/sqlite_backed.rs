@@ -1,5 +1,7 @@
 use super::{
-    base::{CommandLineSearch, SearchDirection, SearchQuery},
+    base::{
+        parse_glob, subdirectory_prefix, CommandLineSearch, GlobToken, SearchDirection, SearchQuery,
+    },
     History, HistoryItem, HistoryItemId, HistorySessionId,
 };
 use crate::{
@@ -8,10 +10,18 @@ use crate::{
 };
 use chrono::{TimeZone, Utc};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use rusqlite::{named_params, params, Connection, ToSql};
-use std::{path::PathBuf, time::Duration};
+use regex::Regex;
+use rusqlite::{functions::FunctionFlags, named_params, params, Connection, ToSql};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 const SQLITE_APPLICATION_ID: i32 = 1151497937;
 
+/// The columns of a history row, along with its tags gathered as a JSON object
+const HISTORY_ITEM_COLUMNS: &str = "*, (select json_group_object(key, value) from history_tag \
+     where history_tag.history_id = history.id) as tags";
+
+/// The number of rows fetched at once by [`SqliteBackedHistory`]'s [`History::search_iter`]
+const SEARCH_ITER_PAGE_SIZE: i64 = 100;
+
 /// A history that stores the values to an SQLite database.
 /// In addition to storing the command, the history can store an additional arbitrary HistoryEntryContext,
 /// to add information such as a timestamp, running directory, result...
@@ -27,6 +37,7 @@ pub struct SqliteBackedHistory {
 
 fn deserialize_history_item(row: &rusqlite::Row) -> rusqlite::Result<HistoryItem> {
     let x: Option<String> = row.get("more_info")?;
+    let tags: String = row.get("tags")?;
 
     Ok(HistoryItem {
         id: HistoryItemId::new(row.get("id")?),
@@ -58,17 +69,75 @@ fn deserialize_history_item(row: &rusqlite::Row) -> rusqlite::Result<HistoryItem
                 })
             })
             .transpose()?,
+        tags: serde_json::from_str::<HashMap<String, String>>(&tags).map_err(|e| {
+            rusqlite::Error::InvalidColumnType(
+                0,
+                format!("could not deserialize tags: {e}"),
+                rusqlite::types::Type::Text,
+            )
+        })?,
     })
 }
 
+/// The results of a search, fetched a page at a time so that no more rows are read once the
+/// iterator is dropped
+///
+/// Each page starts after the last item of the previous one, the query being run again with
+/// this item as its [`SearchQuery::start_id`].
+struct SearchPages<'a> {
+    history: &'a SqliteBackedHistory,
+    query: SearchQuery,
+    /// The number of items left to return, if the query is limited
+    remaining: Option<i64>,
+    page: std::vec::IntoIter<HistoryItem>,
+    /// Whether the current page is the last one
+    done: bool,
+}
+
+impl Iterator for SearchPages<'_> {
+    type Item = Result<HistoryItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.page.next() {
+            return Some(Ok(item));
+        }
+        if self.done {
+            return None;
+        }
+
+        let page_size = self.remaining.map_or(SEARCH_ITER_PAGE_SIZE, |remaining| {
+            remaining.min(SEARCH_ITER_PAGE_SIZE)
+        });
+        self.query.limit = Some(page_size);
+        let page = match self.history.search_rows(&self.query) {
+            Ok(page) => page,
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+
+        let fetched = page.len() as i64;
+        self.remaining = self.remaining.map(|remaining| remaining - fetched);
+        self.done = fetched < page_size || self.remaining == Some(0);
+        if let Some(last) = page.last() {
+            self.query.start_id = Some(last.id);
+        }
+
+        self.page = page.into_iter();
+        self.page.next().map(Ok)
+    }
+}
+
 impl History for SqliteBackedHistory {
     fn generate_id(&mut self) -> HistoryItemId {
         HistoryItemId(self.rng.gen())
     }
 
     fn save(&mut self, entry: &HistoryItem) -> Result<()> {
-        self
-            .db
+        // The item and its tags are replaced together
+        let transaction = self.db.transaction().map_err(map_sqlite_err)?;
+        transaction
             .prepare(
                 "insert into history
                                (id, start_timestamp,  command_line,  session_id,  hostname,  cwd,  duration_ms,  exit_status,  more_info)
@@ -97,8 +166,26 @@ impl History for SqliteBackedHistory {
                     ":more_info": entry.more_info.as_ref().map(|e| serde_json::to_string(e).unwrap())
                 },
             )
-            .map(|_| ())
-            .map_err(map_sqlite_err)
+            .map_err(map_sqlite_err)?;
+
+        transaction
+            .execute(
+                "delete from history_tag where history_id = ?",
+                params![entry.id.0],
+            )
+            .map_err(map_sqlite_err)?;
+        {
+            let mut insert_tag = transaction
+                .prepare("insert into history_tag (history_id, key, value) values (?, ?, ?)")
+                .map_err(map_sqlite_err)?;
+            for (key, value) in &entry.tags {
+                insert_tag
+                    .execute(params![entry.id.0, key, value])
+                    .map_err(map_sqlite_err)?;
+            }
+        }
+
+        transaction.commit().map_err(map_sqlite_err)
     }
 
     /// this history doesn't replace entries
@@ -109,7 +196,9 @@ impl History for SqliteBackedHistory {
     fn load(&self, id: HistoryItemId) -> Result<HistoryItem> {
         let entry = self
             .db
-            .prepare("select * from history where id = :id")
+            .prepare(&format!(
+                "select {HISTORY_ITEM_COLUMNS} from history where id = :id"
+            ))
             .map_err(map_sqlite_err)?
             .query_row(named_params! { ":id": id.0 }, deserialize_history_item)
             .map_err(map_sqlite_err)?;
@@ -118,34 +207,65 @@ impl History for SqliteBackedHistory {
     }
 
     fn count(&self, query: SearchQuery) -> Result<u64> {
-        let (query, params) = self.construct_query(&query, "coalesce(count(*), 0)");
+        let (query, params) = self.construct_query(&query, "coalesce(count(*), 0)")?;
 
         let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
 
         let result: u64 = self
             .db
             .prepare(&query)
-            .unwrap()
+            .map_err(map_sqlite_err)?
             .query_row(&params_borrow[..], |r| r.get(0))
             .map_err(map_sqlite_err)?;
 
         Ok(result)
     }
 
-    fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
-        let (query, params) = self.construct_query(&query, "*");
-
-        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+    fn unique_count(&self) -> Result<u64> {
+        self.db
+            .query_row(
+                "select count(distinct command_line) from history",
+                params![],
+                |r| r.get(0),
+            )
+            .map_err(map_sqlite_err)
+    }
 
-        let results: Vec<HistoryItem> = self
+    fn top_commands(&self, n: usize) -> Result<Vec<(String, u64)>> {
+        let mut statement = self
             .db
-            .prepare(&query)
-            .unwrap()
-            .query_map(&params_borrow[..], deserialize_history_item)
+            .prepare(
+                "select command_line, count(*) from history group by command_line
+                order by count(*) desc, max(idx) desc limit ?",
+            )
+            .map_err(map_sqlite_err)?;
+        let limit = i64::try_from(n).unwrap_or(i64::MAX);
+        let top_commands = statement
+            .query_map(params![limit], |r| Ok((r.get(0)?, r.get(1)?)))
             .map_err(map_sqlite_err)?
-            .collect::<rusqlite::Result<Vec<HistoryItem>>>()
+            .collect::<rusqlite::Result<_>>()
             .map_err(map_sqlite_err)?;
-        Ok(results)
+        Ok(top_commands)
+    }
+
+    fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
+        self.search_rows(&query)
+    }
+
+    /// The results are fetched a hundred rows at a time, by running the query again from the last
+    /// fetched item
+    fn search_iter(
+        &self,
+        query: SearchQuery,
+    ) -> Box<dyn Iterator<Item = Result<HistoryItem>> + '_> {
+        let remaining = query.limit.filter(|limit| *limit >= 0);
+        Box::new(SearchPages {
+            history: self,
+            query,
+            remaining,
+            page: Vec::new().into_iter(),
+            done: false,
+        })
     }
 
     fn update(
@@ -204,6 +324,30 @@ fn map_sqlite_err(err: rusqlite::Error) -> ReedlineError {
 
 type BoxedNamedParams<'a> = Vec<(&'static str, Box<dyn ToSql + 'a>)>;
 
+/// Escape the special characters of an SQL LIKE pattern, to match `text` literally
+/// (!) Requires the LIKE clause to specify an `ESCAPE '\\'` clause
+fn escape_like(text: &str) -> String {
+    let mut text = text.replace(ESCAPE_CHAR, &format!("{ESCAPE_CHAR}{ESCAPE_CHAR}"));
+
+    for special in ['%', '_'] {
+        text = text.replace(special, &format!("{ESCAPE_CHAR}{special}"));
+    }
+
+    text
+}
+
+static ESCAPE_CHAR: char = '\\';
+
+/// Escape the special characters of SQLite's GLOB in brackets, to match `text` literally
+fn escape_glob(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '*' | '?' | '[' => format!("[{c}]"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
 impl SqliteBackedHistory {
     /// Creates a new history with an associated history file.
     ///
@@ -231,6 +375,51 @@ impl SqliteBackedHistory {
             None,
         )
     }
+
+    /// Compact the database file, first deleting the entries started before `older_than`
+    /// and the entries beyond the `max_entries` most recent ones
+    ///
+    /// The deletions happen in a single transaction, entries without a start timestamp
+    /// being kept by `older_than`. Returns the number of deleted entries.
+    ///
+    /// **Performance:** the final `VACUUM` rewrites the whole file, which takes time
+    /// proportional to the size of the database and up to twice that size in temporary
+    /// disk space, and blocks the writes of other connections while it runs. Call this
+    /// occasionally (e.g. at startup or on shell exit) rather than after every command.
+    pub fn compact(
+        &mut self,
+        older_than: Option<chrono::DateTime<Utc>>,
+        max_entries: Option<usize>,
+    ) -> Result<usize> {
+        let mut inner = || -> rusqlite::Result<usize> {
+            let transaction = self.db.transaction()?;
+            let mut deleted = 0;
+            if let Some(cutoff) = older_than {
+                deleted += transaction.execute(
+                    "delete from history where start_timestamp < ?",
+                    params![cutoff.timestamp_millis()],
+                )?;
+            }
+            if let Some(max_entries) = max_entries {
+                deleted += transaction.execute(
+                    "delete from history where idx not in
+                        (select idx from history order by idx desc limit ?)",
+                    params![i64::try_from(max_entries).unwrap_or(i64::MAX)],
+                )?;
+            }
+            transaction.commit()?;
+            Ok(deleted)
+        };
+        let deleted = inner().map_err(map_sqlite_err)?;
+
+        // VACUUM can't run inside of a transaction
+        self.db
+            .execute("VACUUM", params![])
+            .map_err(map_sqlite_err)?;
+
+        Ok(deleted)
+    }
+
     /// initialize a new database / migrate an existing one
     fn from_connection(
         mut db: Connection,
@@ -245,6 +434,24 @@ impl SqliteBackedHistory {
             db.pragma_update(None, "foreign_keys", "on")?;
             db.pragma_update(None, "application_id", SQLITE_APPLICATION_ID)?;
 
+            // Used for regular expression and case-insensitive command line searches
+            db.create_scalar_function(
+                "regexp",
+                2,
+                FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+                |ctx| {
+                    // The compiled regular expression is cached by SQLite for the whole statement
+                    let regex = ctx.get_or_create_aux(0, |value| -> rusqlite::Result<Regex> {
+                        Regex::new(value.as_str()?)
+                            .map_err(|err| rusqlite::Error::UserFunctionError(Box::new(err)))
+                    })?;
+
+                    let text = ctx.get_raw(1).as_str().map_err(rusqlite::Error::from)?;
+
+                    Ok(regex.is_match(text))
+                },
+            )?;
+
             // Get the user version
             // By default, it is set to 0
             let mut db_version: i32 = db.query_row(
@@ -253,7 +460,7 @@ impl SqliteBackedHistory {
                 |r| r.get(0),
             )?;
 
-            // An up-to-date database should have its version set to the latest number (currently 1)
+            // An up-to-date database should have its version set to the latest number (currently 3)
             // 0 means the database is either uninitialized or it is using the old history format
             if db_version == 0 {
                 // Check if an history already exists
@@ -327,6 +534,43 @@ impl SqliteBackedHistory {
                 transaction.commit()?;
             }
 
+            // Version 2 adds the user-defined tags of the items
+            if db_version == 1 {
+                let transaction = db.transaction()?;
+                transaction.execute_batch(
+                    "
+                    create table history_tag (
+                        history_id integer not null references history(id) on delete cascade,
+                        key text not null,
+                        value text not null,
+                        primary key (history_id, key)
+                    ) strict;
+
+                    create index if not exists idx_history_tag on history_tag(key, value);
+
+                    pragma user_version = 2;
+                    ",
+                )?;
+                transaction.commit()?;
+                db_version = 2;
+            }
+
+            // Version 3 indexes the working directory along with the exit status, both being
+            // filtered on together. The index on the working directory alone is superseded
+            if db_version == 2 {
+                let transaction = db.transaction()?;
+                transaction.execute_batch(
+                    "
+                    create index if not exists idx_history_cwd_exit_status on history(cwd, exit_status);
+                    drop index if exists idx_history_cwd;
+
+                    pragma user_version = 3;
+                    ",
+                )?;
+                transaction.commit()?;
+                db_version = 3;
+            }
+
             Ok((
                 db_version,
                 SqliteBackedHistory {
@@ -345,7 +589,7 @@ impl SqliteBackedHistory {
         // If this isn't the case, then something is wrong
         // (either the previous versions migration is buggy, or the database is using a format deployed on a
         //  later reedline version than this one)
-        if db_version != 1 {
+        if db_version != 3 {
             return Err(ReedlineError(ReedlineErrorVariants::HistoryDatabaseError(
                 format!("Unknown database version {db_version}"),
             )));
@@ -354,11 +598,28 @@ impl SqliteBackedHistory {
         Ok(history)
     }
 
+    /// The results of `query`, all fetched at once
+    fn search_rows(&self, query: &SearchQuery) -> Result<Vec<HistoryItem>> {
+        let (query, params) = self.construct_query(query, HISTORY_ITEM_COLUMNS)?;
+
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+
+        let results: Vec<HistoryItem> = self
+            .db
+            .prepare(&query)
+            .map_err(map_sqlite_err)?
+            .query_map(&params_borrow[..], deserialize_history_item)
+            .map_err(map_sqlite_err)?
+            .collect::<rusqlite::Result<Vec<HistoryItem>>>()
+            .map_err(map_sqlite_err)?;
+        Ok(results)
+    }
+
     fn construct_query<'a>(
         &self,
         query: &'a SearchQuery,
         select_expression: &str,
-    ) -> (String, BoxedNamedParams<'a>) {
+    ) -> Result<(String, BoxedNamedParams<'a>)> {
         // Destructure the query - this ensures that if another element is added to this type later on,
         // we won't forget to update this function as the destructuring will then be incomplete.
         let SearchQuery {
@@ -384,13 +645,13 @@ impl SqliteBackedHistory {
 
         if let Some(start) = start_time {
             let cmp_op = if is_asc { '>' } else { '<' };
-            wheres.push(format!("timestamp_start {cmp_op} :start_time"));
+            wheres.push(format!("start_timestamp {cmp_op} :start_time"));
             params.push((":start_time", Box::new(start.timestamp_millis())));
         }
 
         if let Some(end) = end_time {
             let cmp_op = if is_asc { ">=" } else { "<=" };
-            wheres.push(format!(":end_time {cmp_op} timestamp_start"));
+            wheres.push(format!(":end_time {cmp_op} start_timestamp"));
             params.push((":end_time", Box::new(end.timestamp_millis())));
         }
 
@@ -405,7 +666,7 @@ impl SqliteBackedHistory {
         if let Some(end) = end_id {
             let cmp_op = if is_asc { ">=" } else { "<=" };
             wheres.push(format!(
-                "idx {cmp_op} (SELECT idx FROM history WHERE id = :start_id)"
+                "(SELECT idx FROM history WHERE id = :end_id) {cmp_op} idx"
             ));
             params.push((":end_id", Box::new(end.0)));
         }
@@ -419,20 +680,61 @@ impl SqliteBackedHistory {
         };
 
         if let Some(command_line) = &filter.command_line {
-            let command_line_like = match command_line {
-                CommandLineSearch::Exact(e) => escape_like_with_backslashes(e, ESCAPE_CHAR),
-                CommandLineSearch::Prefix(prefix) => {
-                    format!("{}%", escape_like_with_backslashes(prefix, ESCAPE_CHAR))
-                }
-                CommandLineSearch::Substring(cont) => {
-                    format!("%{}%", escape_like_with_backslashes(cont, ESCAPE_CHAR))
+            // LIKE narrows the rows down the way it always did, ignoring the case of ASCII
+            // characters: `=` and `instr` only make the case of the kept ones count
+            let like_search = match command_line {
+                CommandLineSearch::Exact(e) => {
+                    Some((escape_like(e), e, "command_line = :command_line"))
                 }
+                CommandLineSearch::Prefix(prefix) => Some((
+                    format!("{}%", escape_like(prefix)),
+                    prefix,
+                    "instr(command_line, :command_line) = 1",
+                )),
+                CommandLineSearch::Substring(cont) => Some((
+                    format!("%{}%", escape_like(cont)),
+                    cont,
+                    "instr(command_line, :command_line) > 0",
+                )),
+                _ => None,
             };
 
-            wheres.push(format!(
-                "command_line like :command_line escape '{ESCAPE_CHAR}'"
-            ));
-            params.push((":command_line", Box::new(command_line_like)));
+            match (command_line, filter.case_sensitive, like_search) {
+                (_, true, Some((command_line_like, text, case_check))) => {
+                    wheres.push(format!(
+                        "command_line like :command_line_like escape '{ESCAPE_CHAR}' \
+                         and {case_check}"
+                    ));
+                    params.push((":command_line_like", Box::new(command_line_like)));
+                    params.push((":command_line", Box::new(text)));
+                }
+                // SQLite's GLOB matches case-sensitively with the same wildcards,
+                // the special characters being escaped in brackets
+                (CommandLineSearch::Glob(glob), true, _) => {
+                    let pattern: String = parse_glob(glob)?
+                        .into_iter()
+                        .map(|token| match token {
+                            GlobToken::Literal(c) => escape_glob(&c.to_string()),
+                            GlobToken::AnyChars => "*".to_string(),
+                            GlobToken::AnyChar => "?".to_string(),
+                        })
+                        .collect();
+                    wheres.push("command_line glob :command_line".to_owned());
+                    params.push((":command_line", Box::new(pattern)));
+                }
+                // SQLite's LIKE only ignores the case of ASCII characters,
+                // so regular expressions are used for case-insensitive searches
+                (search, case_sensitive, _) => {
+                    // Ensure an invalid regular expression is reported before running the query
+                    search.to_regex(case_sensitive)?;
+
+                    wheres.push("command_line regexp :command_line".to_owned());
+                    params.push((
+                        ":command_line",
+                        Box::new(search.regex_pattern(case_sensitive)?),
+                    ));
+                }
+            }
         }
 
         if let Some(str) = &filter.not_command_line {
@@ -450,10 +752,13 @@ impl SqliteBackedHistory {
             params.push((":cwd", Box::new(cwd_exact)));
         }
 
+        // GLOB matches case-sensitively, and can use the index of the directory for a pattern
+        // starting with the literal prefix
         if let Some(cwd_prefix) = &filter.cwd_prefix {
-            wheres.push("cwd like :cwd_like".to_owned());
-            let cwd_like = format!("{cwd_prefix}%");
-            params.push((":cwd_like", Box::new(cwd_like)));
+            wheres.push("(cwd = :cwd_prefix or cwd glob :cwd_glob)".to_owned());
+            let cwd_glob = format!("{}*", escape_glob(&subdirectory_prefix(cwd_prefix)));
+            params.push((":cwd_prefix", Box::new(cwd_prefix)));
+            params.push((":cwd_glob", Box::new(cwd_glob)));
         }
 
         if let Some(exit_successful) = filter.exit_successful {
@@ -477,6 +782,25 @@ impl SqliteBackedHistory {
             ));
         }
 
+        if let Some(not_session) = filter.not_session {
+            wheres.push("(session_id IS NULL OR session_id != :not_session_id)".to_owned());
+            params.push((":not_session_id", Box::new(not_session)));
+        }
+
+        if !filter.tags.is_empty() {
+            // Every wanted tag must be set on the item
+            wheres.push(
+                "not exists (select 1 from json_each(:tags) as tag where not exists \
+                 (select 1 from history_tag where history_tag.history_id = history.id \
+                 and history_tag.key = tag.key and history_tag.value = tag.value))"
+                    .to_owned(),
+            );
+            params.push((
+                ":tags",
+                Box::new(serde_json::to_string(&filter.tags).unwrap()),
+            ));
+        }
+
         let mut wheres = wheres.join(" and ");
 
         if wheres.is_empty() {
@@ -491,20 +815,428 @@ impl SqliteBackedHistory {
              {limit}"
         );
 
-        (query, params)
+        Ok((query, params))
     }
 }
 
-/// Escape special symbols for an SQL LIKE clause
-/// (!) Requires LIKE clause to specify an `ESCAPE '<char>'` clause
-fn escape_like_with_backslashes(str: &str, escape_char: char) -> String {
-    let mut str = str.replace(escape_char, &format!("{escape_char}{escape_char}"));
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CommandLineSearch, SearchFilter};
+    use pretty_assertions::assert_eq;
+
+    fn history_with_entries(count: i64) -> Result<SqliteBackedHistory> {
+        let mut history = SqliteBackedHistory::in_memory()?;
+        for i in 0..count {
+            let id = history.generate_id();
+            history.save(&HistoryItem {
+                start_timestamp: Utc.timestamp_millis_opt(i * 1000).single(),
+                ..HistoryItem::from_command_line(format!("echo {i}"), id)
+            })?;
+        }
+        Ok(history)
+    }
+
+    #[test]
+    fn compact_keeps_the_most_recent_entries() -> Result<()> {
+        let mut history = history_with_entries(200)?;
+        let oldest = history.search(SearchQuery::everything(SearchDirection::Forward, None))?;
+        for entry in oldest.iter().step_by(10) {
+            history.delete(entry.id)?;
+        }
+        assert_eq!(history.count_all()?, 180);
+
+        assert_eq!(history.compact(None, Some(50))?, 130);
+        assert_eq!(history.count_all()?, 50);
+
+        let found = history.search(SearchQuery::last_with_prefix("echo 19".to_string(), None))?;
+        assert_eq!(found[0].command_line, "echo 199");
+        assert!(history
+            .search(SearchQuery::last_with_prefix("echo 1".to_string(), None))?
+            .iter()
+            .all(|entry| entry.command_line != "echo 100"));
 
-    for forbidden in ['%', '\'', '\n'] {
-        str = str.replace(forbidden, &format!("{escape_char}{forbidden}"));
+        Ok(())
     }
 
-    str
-}
+    #[test]
+    fn compact_deletes_older_entries() -> Result<()> {
+        let mut history = history_with_entries(100)?;
 
-static ESCAPE_CHAR: char = '\\';
+        let cutoff = Utc.timestamp_millis_opt(40_000).unwrap();
+        assert_eq!(history.compact(Some(cutoff), Some(100))?, 40);
+        assert_eq!(history.count_all()?, 60);
+        assert_eq!(history.compact(None, None)?, 0);
+
+        let oldest = history.search(SearchQuery {
+            limit: Some(1),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        })?;
+        assert_eq!(oldest[0].command_line, "echo 40");
+
+        Ok(())
+    }
+
+    #[test]
+    fn text_searches_match_the_like_wildcards_literally() -> Result<()> {
+        let mut history = SqliteBackedHistory::in_memory()?;
+        for command_line in ["echo 100%", "echo 1000", "echo a_b", "echo axb", "ECHO a_b"] {
+            let id = history.generate_id();
+            history.save(&HistoryItem::from_command_line(command_line, id))?;
+        }
+
+        let found = |search: CommandLineSearch| -> Result<Vec<String>> {
+            let mut query = SearchQuery::everything(SearchDirection::Forward, None);
+            query.filter.command_line = Some(search);
+            Ok(history
+                .search(query)?
+                .into_iter()
+                .map(|entry| entry.command_line)
+                .collect())
+        };
+
+        assert_eq!(
+            found(CommandLineSearch::Substring("100%".to_string()))?,
+            ["echo 100%"]
+        );
+        assert_eq!(
+            found(CommandLineSearch::Prefix("echo a_".to_string()))?,
+            ["echo a_b"]
+        );
+        assert_eq!(
+            found(CommandLineSearch::Exact("ECHO a_b".to_string()))?,
+            ["ECHO a_b"]
+        );
+
+        Ok(())
+    }
+
+    fn tags(tags: &[(&str, &str)]) -> HashMap<String, String> {
+        tags.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn items_are_filtered_by_tags() -> Result<()> {
+        let mut history = SqliteBackedHistory::in_memory()?;
+        for (command_line, item_tags) in [
+            (
+                "cargo build",
+                tags(&[("project", "reedline"), ("branch", "main")]),
+            ),
+            (
+                "cargo test",
+                tags(&[("project", "reedline"), ("branch", "tags")]),
+            ),
+            (
+                "npm test",
+                tags(&[("project", "website"), ("branch", "main")]),
+            ),
+            ("ls", tags(&[])),
+        ] {
+            let id = history.generate_id();
+            history.save(&HistoryItem {
+                tags: item_tags,
+                ..HistoryItem::from_command_line(command_line, id)
+            })?;
+        }
+
+        let search = |wanted: &[(&str, &str)]| -> Result<Vec<String>> {
+            Ok(history
+                .search(SearchQuery {
+                    filter: SearchFilter {
+                        tags: tags(wanted),
+                        ..SearchFilter::anything(None)
+                    },
+                    ..SearchQuery::everything(SearchDirection::Forward, None)
+                })?
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect())
+        };
+
+        assert_eq!(
+            search(&[("branch", "main")])?,
+            vec!["cargo build", "npm test"]
+        );
+        assert_eq!(
+            search(&[("project", "reedline"), ("branch", "main")])?,
+            vec!["cargo build"]
+        );
+        assert_eq!(search(&[("project", "other")])?, Vec::<String>::new());
+        assert_eq!(search(&[])?.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags_are_loaded_and_replaced() -> Result<()> {
+        let mut history = SqliteBackedHistory::in_memory()?;
+        let id = history.generate_id();
+        history.save(&HistoryItem {
+            tags: tags(&[("project", "reedline"), ("branch", "main")]),
+            ..HistoryItem::from_command_line("cargo build", id)
+        })?;
+        assert_eq!(
+            history.load(id)?.tags,
+            tags(&[("project", "reedline"), ("branch", "main")])
+        );
+
+        history.update(id, &|mut item| {
+            item.tags.remove("branch");
+            item
+        })?;
+        assert_eq!(history.load(id)?.tags, tags(&[("project", "reedline")]));
+
+        history.delete(id)?;
+        let remaining: i64 = history
+            .db
+            .query_row("select count(*) from history_tag", params![], |r| r.get(0))
+            .map_err(map_sqlite_err)?;
+        assert_eq!(remaining, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn version_1_databases_are_migrated() -> Result<()> {
+        let db = Connection::open_in_memory().map_err(map_sqlite_err)?;
+        db.execute_batch(
+            "
+            create table history (
+                idx integer primary key autoincrement,
+                id integer unique not null,
+                command_line text not null,
+                start_timestamp integer,
+                session_id integer,
+                hostname text,
+                cwd text,
+                duration_ms integer,
+                exit_status integer,
+                more_info text
+            ) strict;
+
+            insert into history (id, command_line) values (1, 'cargo build');
+
+            pragma user_version = 1;
+            ",
+        )
+        .map_err(map_sqlite_err)?;
+
+        let mut history = SqliteBackedHistory::from_connection(db, None, None)?;
+        assert_eq!(history.load(HistoryItemId::new(1))?.tags, HashMap::new());
+
+        history.update(HistoryItemId::new(1), &|mut item| {
+            item.tags = tags(&[("branch", "main")]);
+            item
+        })?;
+        assert_eq!(
+            history.load(HistoryItemId::new(1))?.tags,
+            tags(&[("branch", "main")])
+        );
+
+        Ok(())
+    }
+
+    /// The details of the query plan of `query`, one step per line
+    fn query_plan(history: &SqliteBackedHistory, query: &SearchQuery) -> Result<String> {
+        let (query, params) = history.construct_query(query, HISTORY_ITEM_COLUMNS)?;
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+        let mut statement = history
+            .db
+            .prepare(&format!("explain query plan {query}"))
+            .map_err(map_sqlite_err)?;
+        let steps = statement
+            .query_map(&params_borrow[..], |r| r.get::<_, String>("detail"))
+            .map_err(map_sqlite_err)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(map_sqlite_err)?;
+        Ok(steps.join("\n"))
+    }
+
+    #[test]
+    fn combined_filters_return_the_intersection() -> Result<()> {
+        let mut history = SqliteBackedHistory::in_memory()?;
+        for (command_line, cwd, exit_status) in [
+            ("cargo build", "/home/user/reedline", 0),
+            ("cargo test", "/home/user/reedline", 1),
+            ("cargo build", "/home/user/reedline/src", 0),
+            ("cargo build", "/home/user/nushell", 0),
+            ("cargo build", "/home/User/reedline", 0),
+            ("cargo build", "/home/user_reedline", 0),
+            ("cargo build", "/home/user/reedline-old", 0),
+            ("cargo run", "/home/user/reedline", 0),
+            ("make build", "/home/user/reedline", 0),
+        ] {
+            let id = history.generate_id();
+            history.save(&HistoryItem {
+                cwd: Some(cwd.to_string()),
+                exit_status: Some(exit_status),
+                ..HistoryItem::from_command_line(command_line, id)
+            })?;
+        }
+
+        let found = history.search(SearchQuery {
+            filter: SearchFilter {
+                command_line: Some(CommandLineSearch::Substring("cargo".to_string())),
+                cwd_prefix: Some("/home/user/reedline".to_string()),
+                exit_successful: Some(true),
+                ..SearchFilter::anything(None)
+            },
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        })?;
+        assert_eq!(
+            found
+                .iter()
+                .map(|item| (item.command_line.as_str(), item.cwd.as_deref().unwrap()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("cargo build", "/home/user/reedline"),
+                ("cargo build", "/home/user/reedline/src"),
+                ("cargo run", "/home/user/reedline"),
+            ]
+        );
+
+        // The wildcards of the prefix are matched literally
+        let found = history.search(SearchQuery {
+            filter: SearchFilter {
+                cwd_prefix: Some("/home/user?reedline".to_string()),
+                ..SearchFilter::anything(None)
+            },
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        })?;
+        assert!(found.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn combined_filters_use_an_index() -> Result<()> {
+        let history = history_with_entries(10)?;
+
+        let plan = query_plan(
+            &history,
+            &SearchQuery {
+                limit: Some(1),
+                filter: SearchFilter {
+                    command_line: Some(CommandLineSearch::Prefix("cargo".to_string())),
+                    cwd_exact: Some("/home/user/reedline".to_string()),
+                    exit_successful: Some(true),
+                    ..SearchFilter::anything(None)
+                },
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            },
+        )?;
+        // The matching rows are found in the order of the history, without sorting them
+        assert!(
+            plan.contains("USING INDEX idx_history_cwd_exit_status (cwd=? AND exit_status=?)"),
+            "{plan}"
+        );
+        assert!(!plan.contains("TEMP B-TREE"), "{plan}");
+
+        let plan = query_plan(
+            &history,
+            &SearchQuery {
+                filter: SearchFilter {
+                    cwd_prefix: Some("/home/user".to_string()),
+                    exit_successful: Some(false),
+                    ..SearchFilter::anything(None)
+                },
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            },
+        )?;
+        assert!(
+            plan.contains("USING INDEX idx_history_cwd_exit_status (cwd>? AND cwd<?)"),
+            "{plan}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn time_and_id_bounds_combine_with_the_filters() -> Result<()> {
+        let history = history_with_entries(10)?;
+        let ids = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))?
+            .into_iter()
+            .map(|item| item.id)
+            .collect::<Vec<_>>();
+        let echo = || SearchFilter {
+            command_line: Some(CommandLineSearch::Prefix("echo".to_string())),
+            ..SearchFilter::anything(None)
+        };
+        let commands = |query| -> Result<Vec<String>> {
+            Ok(history
+                .search(query)?
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect())
+        };
+
+        let forward = || SearchQuery {
+            start_time: Utc.timestamp_millis_opt(2000).single(),
+            end_id: Some(ids[7]),
+            filter: echo(),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        };
+        assert_eq!(
+            commands(forward())?,
+            vec!["echo 3", "echo 4", "echo 5", "echo 6", "echo 7"]
+        );
+        assert_eq!(history.count(forward())?, 5);
+        let limited = SearchQuery {
+            limit: Some(2),
+            ..forward()
+        };
+        assert_eq!(commands(limited)?, vec!["echo 3", "echo 4"]);
+
+        let backward = SearchQuery {
+            start_id: Some(ids[8]),
+            end_time: Utc.timestamp_millis_opt(5000).single(),
+            filter: echo(),
+            ..SearchQuery::everything(SearchDirection::Backward, None)
+        };
+        assert_eq!(commands(backward)?, vec!["echo 7", "echo 6", "echo 5"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_iter_fetches_every_page() -> Result<()> {
+        let history = history_with_entries(2 * SEARCH_ITER_PAGE_SIZE + 10)?;
+        let query = |direction, limit| SearchQuery {
+            limit,
+            filter: SearchFilter::from_text_search(
+                CommandLineSearch::Substring("1".to_string()),
+                None,
+            ),
+            ..SearchQuery::everything(direction, None)
+        };
+
+        for direction in [SearchDirection::Forward, SearchDirection::Backward] {
+            for limit in [
+                None,
+                Some(0),
+                Some(SEARCH_ITER_PAGE_SIZE),
+                Some(150),
+                Some(1000),
+            ] {
+                let lazy = history
+                    .search_iter(query(direction, limit))
+                    .collect::<Result<Vec<_>>>()?;
+                assert_eq!(lazy, history.search(query(direction, limit))?);
+            }
+        }
+
+        // An invalid query is reported once
+        let mut invalid = history.search_iter(SearchQuery {
+            filter: SearchFilter::from_text_search(CommandLineSearch::Regex("(".to_string()), None),
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        });
+        assert!(invalid.next().unwrap().is_err());
+        assert!(invalid.next().is_none());
+
+        Ok(())
+    }
+}
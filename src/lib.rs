@@ -182,6 +182,7 @@
 //! - `sqlite`: Provides the `SqliteBackedHistory` to store richer information in the history. Statically links the required sqlite version.
 //! - `sqlite-dynlib`: Alternative to the feature `sqlite`. Will not statically link. Requires `sqlite >= 3.38` to link dynamically!
 //! - `external_printer`: **Experimental:** Thread-safe `ExternalPrinter` handle to print lines from concurrently running threads.
+//! - `suspend`: Restore the terminal when the process is stopped and repaint the prompt once resumed, see `Reedline::use_suspend_handling` and `ReedlineEvent::Suspend`. Only on unix.
 //!
 //! ## Are we prompt yet? (Development status)
 //!
@@ -228,15 +229,19 @@
 mod core_editor;
 pub use core_editor::Editor;
 pub use core_editor::LineBuffer;
+pub use core_editor::WordChars;
 
 mod enums;
-pub use enums::{EditCommand, ReedlineEvent, ReedlineRawEvent, Signal, UndoBehavior};
+pub use enums::{
+    CompletionStrategy, EditCommand, EnterBehavior, LineOverflow, ReedlineEvent, ReedlineRawEvent,
+    SelectionMode, Signal, UndoBehavior, UndoGranularity,
+};
 
 mod painting;
 pub use painting::{Painter, StyledText};
 
 mod engine;
-pub use engine::Reedline;
+pub use engine::{Bell, CustomCommand, HistoryRedactor, Reedline};
 
 mod result;
 pub use result::{ReedlineError, ReedlineErrorVariants, Result};
@@ -245,40 +250,48 @@ mod history;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 pub use history::SqliteBackedHistory;
 pub use history::{
-    CommandLineSearch, FileBackedHistory, History, HistoryItem, HistoryItemId,
-    HistoryNavigationQuery, HistorySessionId, SearchDirection, SearchFilter, SearchQuery,
-    HISTORY_SIZE,
+    ignore_commands, ignore_containing, ignore_leading_space, migrate_history, CommandLineSearch,
+    FileBackedHistory, FilteredHistory, History, HistoryDedup, HistoryIgnoreFilter, HistoryItem,
+    HistoryItemId, HistoryNavigationQuery, HistorySessionId, MultilineFormat, SearchDirection,
+    SearchFilter, SearchQuery, HISTORY_SIZE,
 };
 
 mod prompt;
 pub use prompt::{
-    DefaultPrompt, DefaultPromptSegment, Prompt, PromptEditMode, PromptHistorySearch,
-    PromptHistorySearchStatus, PromptViMode,
+    DefaultPrompt, DefaultPromptSegment, Prompt, PromptContext, PromptEditMode,
+    PromptHistorySearch, PromptHistorySearchStatus, PromptViMode,
 };
 
 mod edit_mode;
 pub use edit_mode::{
     default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings,
-    CursorConfig, EditMode, Emacs, Keybindings, Vi,
+    CursorConfig, EditMode, Emacs, KeyCombination, Keybindings, ParseKeyError, SequenceMatch, Vi,
 };
 
 mod highlighter;
-pub use highlighter::{ExampleHighlighter, Highlighter, SimpleMatchHighlighter};
+pub use highlighter::{
+    ChainedHighlighter, ExampleHighlighter, HighlightExtent, HighlightPatch, Highlighter,
+    MatchingBracketHighlighter, RangeHighlighter, SimpleMatchHighlighter, StyleMerge,
+};
 
 mod completion;
-pub use completion::{Completer, DefaultCompleter, Span, Suggestion};
+pub use completion::{
+    AsyncCompleter, AsyncCompleterAdapter, Completer, CompletionFuture, CompletionTransformer,
+    CurrentLineCompleter, DefaultCompleter, FuzzyCompleter, MultiCompleter, PathCompleter,
+    QuoteContext, Span, Suggestion,
+};
 
 mod hinter;
 pub use hinter::CwdAwareHinter;
-pub use hinter::{DefaultHinter, Hinter};
+pub use hinter::{DefaultHinter, HintScoring, Hinter, HistoryHinter, PlaceholderHinter};
 
 mod validator;
 pub use validator::{DefaultValidator, ValidationResult, Validator};
 
 mod menu;
 pub use menu::{
-    menu_functions, ColumnarMenu, DescriptionMenu, DescriptionMode, IdeMenu, ListMenu, Menu,
-    MenuBuilder, MenuEvent, MenuTextStyle, ReedlineMenu,
+    menu_functions, Alignment, ColumnarMenu, DescriptionMenu, DescriptionMode, IdeMenu, ListMenu,
+    Menu, MenuBuilder, MenuEvent, MenuTextStyle, ReedlineMenu,
 };
 
 mod terminal_extensions;
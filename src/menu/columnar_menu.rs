@@ -1,13 +1,27 @@
 use super::{Menu, MenuBuilder, MenuEvent, MenuSettings};
 use crate::{
     core_editor::Editor,
-    menu_functions::{can_partially_complete, completer_input, replace_in_buffer},
+    menu_functions::{
+        can_partially_complete, completer_input, replace_in_buffer, style_match_indices,
+        truncate_with_ellipsis, IncrementalFilter,
+    },
     painting::Painter,
     Completer, Suggestion,
 };
-use nu_ansi_term::ansi::RESET;
+use nu_ansi_term::{ansi::RESET, Style};
+use std::borrow::Cow;
 use unicode_width::UnicodeWidthStr;
 
+/// Alignment of the values in the columns of the menu
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// Values start at the left of their column
+    #[default]
+    Left,
+    /// Values end at the right of their column, before the column padding
+    Right,
+}
+
 /// Default values used as reference for the menu. These values are set during
 /// the initial declaration of the menu and are always kept as reference for the
 /// changeable [`ColumnDetails`]
@@ -18,6 +32,12 @@ struct DefaultColumnDetails {
     pub col_width: Option<usize>,
     /// Column padding
     pub col_padding: usize,
+    /// Minimum column width, including the padding
+    pub min_col_width: Option<usize>,
+    /// Maximum column width, including the padding
+    pub max_col_width: Option<usize>,
+    /// Alignment of the values in their column
+    pub alignment: Alignment,
 }
 
 impl Default for DefaultColumnDetails {
@@ -26,6 +46,9 @@ impl Default for DefaultColumnDetails {
             columns: 4,
             col_width: None,
             col_padding: 2,
+            min_col_width: None,
+            max_col_width: None,
+            alignment: Alignment::Left,
         }
     }
 }
@@ -38,6 +61,8 @@ struct ColumnDetails {
     pub columns: u16,
     /// Column width
     pub col_width: usize,
+    /// Whether the values are shown with their description, in a single column
+    pub descriptions: bool,
     /// The shortest of the strings, which the suggestions are based on
     pub shortest_base_string: String,
 }
@@ -69,6 +94,8 @@ pub struct ColumnarMenu {
     longest_suggestion: usize,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Suggestions filtered as the user types, with the incremental filter enabled
+    filter: IncrementalFilter,
 }
 
 impl Default for ColumnarMenu {
@@ -85,6 +112,7 @@ impl Default for ColumnarMenu {
             event: None,
             longest_suggestion: 0,
             input: None,
+            filter: IncrementalFilter::default(),
         }
     }
 }
@@ -112,6 +140,27 @@ impl ColumnarMenu {
         self
     }
 
+    /// Menu builder with minimum and maximum column widths, including the column padding
+    ///
+    /// The columns are still sized from the longest value, within these bounds:
+    /// values too long to fit are truncated with an ellipsis.
+    /// These bounds don't apply to the single column used for suggestions with a description.
+    #[must_use]
+    pub fn with_column_width_bounds(mut self, min: usize, max: usize) -> Self {
+        self.default_details.min_col_width = Some(min);
+        self.default_details.max_col_width = Some(max);
+        self
+    }
+
+    /// Menu builder with new alignment of the values in their column
+    ///
+    /// The alignment doesn't apply to the single column used for suggestions with a description.
+    #[must_use]
+    pub fn with_column_alignment(mut self, alignment: Alignment) -> Self {
+        self.default_details.alignment = alignment;
+        self
+    }
+
     /// Menu builder with new column width value
     #[must_use]
     pub fn with_column_padding(mut self, col_padding: usize) -> Self {
@@ -124,6 +173,9 @@ impl ColumnarMenu {
 impl ColumnarMenu {
     /// Move menu cursor to the next element
     fn move_next(&mut self) {
+        if !self.settings.wrap && self.is_last_selected() {
+            return;
+        }
         let mut new_col = self.col_pos + 1;
         let mut new_row = self.row_pos;
 
@@ -148,6 +200,9 @@ impl ColumnarMenu {
 
     /// Move menu cursor to the previous element
     fn move_previous(&mut self) {
+        if !self.settings.wrap && self.index() == 0 {
+            return;
+        }
         let new_col = self.col_pos.checked_sub(1);
 
         let (new_col, new_row) = match new_col {
@@ -228,6 +283,11 @@ impl ColumnarMenu {
         index as usize
     }
 
+    /// Whether the last value is selected
+    fn is_last_selected(&self) -> bool {
+        self.index() + 1 >= self.get_values().len()
+    }
+
     /// Get selected value from the menu
     fn get_value(&self) -> Option<Suggestion> {
         self.get_values().get(self.index()).cloned()
@@ -289,6 +349,104 @@ impl ColumnarMenu {
         }
     }
 
+    /// Value of the suggestion as shown in its column, truncated to the column width,
+    /// along with the spaces to print before and after it
+    fn column_value<'a>(
+        &self,
+        suggestion: &'a Suggestion,
+        empty_space: usize,
+    ) -> (Cow<'a, str>, usize, usize) {
+        if self.working_details.descriptions {
+            return (Cow::Borrowed(&suggestion.value), 0, empty_space);
+        }
+
+        let col_padding = self.default_details.col_padding;
+        let max_width = self.get_width().saturating_sub(col_padding).max(1);
+
+        let (value, empty_space) = if suggestion.value.width() > max_width {
+            let value = truncate_with_ellipsis(&suggestion.value, max_width);
+            let empty_space = self.get_width().saturating_sub(value.width());
+            (Cow::Owned(value), empty_space)
+        } else {
+            (Cow::Borrowed(suggestion.value.as_str()), empty_space)
+        };
+
+        match self.default_details.alignment {
+            Alignment::Left => (value, 0, empty_space),
+            Alignment::Right => {
+                let leading_space = empty_space.saturating_sub(col_padding);
+                (value, leading_space, empty_space - leading_space)
+            }
+        }
+    }
+
+    /// Creates the string of a suggestion whose matched characters are given by its `match_indices`
+    ///
+    /// `spacing` holds the leading space and the empty space after the value, without description
+    fn create_match_indices_string(
+        &self,
+        suggestion: &Suggestion,
+        value: &str,
+        match_indices: &[usize],
+        selected: bool,
+        column: u16,
+        spacing: (String, usize),
+    ) -> String {
+        let suggestion_style_prefix = suggestion
+            .style
+            .unwrap_or(self.settings.color.text_style)
+            .prefix();
+
+        let (match_style, text_style) = if selected {
+            (
+                self.settings.color.selected_match_style,
+                self.settings.color.selected_text_style,
+            )
+        } else {
+            (self.settings.color.match_style, Style::new())
+        };
+
+        let styled_value = style_match_indices(
+            value,
+            match_indices,
+            &format!("{}{}", suggestion_style_prefix, match_style.prefix()),
+            &format!("{}{}", suggestion_style_prefix, text_style.prefix()),
+        );
+
+        if let Some(description) = &suggestion.description {
+            let left_text_size = self.longest_suggestion + self.default_details.col_padding;
+            let right_text_size = self.get_width().saturating_sub(left_text_size);
+            let description_style = if selected {
+                self.settings.color.selected_text_style
+            } else {
+                Style::new()
+            };
+
+            format!(
+                "{}{:padding$}{}{}{}{}{}",
+                styled_value,
+                "",
+                self.settings.color.description_style.prefix(),
+                description_style.prefix(),
+                truncate_with_ellipsis(description, right_text_size),
+                RESET,
+                self.end_of_line(column),
+                padding = left_text_size.saturating_sub(value.width()),
+            )
+        } else {
+            let (leading_space, empty_space) = spacing;
+
+            format!(
+                "{}{}{:>empty$}{}",
+                leading_space,
+                styled_value,
+                "",
+                self.end_of_line(column),
+                empty = empty_space,
+            )
+        }
+    }
+
     /// Creates default string that represents one suggestion from the menu
     fn create_string(
         &self,
@@ -298,11 +456,32 @@ impl ColumnarMenu {
         empty_space: usize,
         use_ansi_coloring: bool,
     ) -> String {
+        let (value, leading_space, empty_space) = self.column_value(suggestion, empty_space);
+        let leading_space = " ".repeat(leading_space);
+
         if use_ansi_coloring {
-            let match_len = self.working_details.shortest_base_string.len();
+            if let Some(match_indices) = &suggestion.match_indices {
+                return self.create_match_indices_string(
+                    suggestion,
+                    &value,
+                    match_indices,
+                    index == self.index(),
+                    column,
+                    (leading_space, empty_space),
+                );
+            }
+
+            let mut match_len = self
+                .working_details
+                .shortest_base_string
+                .len()
+                .min(value.len());
+            while !value.is_char_boundary(match_len) {
+                match_len -= 1;
+            }
 
             // Split string so the match text can be styled
-            let (match_str, remaining_str) = suggestion.value.split_at(match_len);
+            let (match_str, remaining_str) = value.split_at(match_len);
 
             let suggestion_style_prefix = suggestion
                 .style
@@ -329,17 +508,14 @@ impl ColumnarMenu {
                         RESET,
                         self.settings.color.description_style.prefix(),
                         self.settings.color.selected_text_style.prefix(),
-                        description
-                            .chars()
-                            .take(right_text_size)
-                            .collect::<String>()
-                            .replace('\n', " "),
+                        truncate_with_ellipsis(description, right_text_size),
                         RESET,
                         self.end_of_line(column),
                     )
                 } else {
                     format!(
-                        "{}{}{}{}{}{}{}{}{:>empty$}{}",
+                        "{}{}{}{}{}{}{}{}{}{:>empty$}{}",
+                        leading_space,
                         suggestion_style_prefix,
                         self.settings.color.selected_match_style.prefix(),
                         match_str,
@@ -364,17 +540,14 @@ impl ColumnarMenu {
                     remaining_str,
                     RESET,
                     self.settings.color.description_style.prefix(),
-                    description
-                        .chars()
-                        .take(right_text_size)
-                        .collect::<String>()
-                        .replace('\n', " "),
+                    truncate_with_ellipsis(description, right_text_size),
                     RESET,
                     self.end_of_line(column),
                 )
             } else {
                 format!(
-                    "{}{}{}{}{}{}{}{}{:>empty$}{}{}",
+                    "{}{}{}{}{}{}{}{}{}{:>empty$}{}{}",
+                    leading_space,
                     suggestion_style_prefix,
                     self.settings.color.match_style.prefix(),
                     match_str,
@@ -394,15 +567,14 @@ impl ColumnarMenu {
             let marker = if index == self.index() { ">" } else { "" };
 
             let line = if let Some(description) = &suggestion.description {
+                let left_text_size = self.longest_suggestion + self.default_details.col_padding;
+                let right_text_size = self.get_width().saturating_sub(left_text_size);
+
                 format!(
                     "{}{:max$}{}{}",
                     marker,
                     &suggestion.value,
-                    description
-                        .chars()
-                        .take(empty_space)
-                        .collect::<String>()
-                        .replace('\n', " "),
+                    truncate_with_ellipsis(description, right_text_size),
                     self.end_of_line(column),
                     max = self.longest_suggestion
                         + self
@@ -412,9 +584,10 @@ impl ColumnarMenu {
                 )
             } else {
                 format!(
-                    "{}{}{:>empty$}{}",
+                    "{}{}{}{:>empty$}{}",
                     marker,
-                    &suggestion.value,
+                    leading_space,
+                    value,
                     "",
                     self.end_of_line(column),
                     empty = empty_space.saturating_sub(marker.len()),
@@ -478,6 +651,7 @@ impl Menu for ColumnarMenu {
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
+                self.filter.clear();
             }
             _ => {}
         }
@@ -487,6 +661,18 @@ impl Menu for ColumnarMenu {
 
     /// Updates menu values
     fn update_values(&mut self, editor: &mut Editor, completer: &mut dyn Completer) {
+        if self.settings.incremental_filter {
+            let filtered = self
+                .filter
+                .filter(editor.get_buffer(), editor.insertion_point());
+
+            if let Some(values) = filtered {
+                self.values = values;
+                self.reset_position();
+                return;
+            }
+        }
+
         let (input, pos) = completer_input(
             editor.get_buffer(),
             editor.insertion_point(),
@@ -497,6 +683,10 @@ impl Menu for ColumnarMenu {
         let (values, base_ranges) = completer.complete_with_base_ranges(&input, pos);
 
         self.values = values;
+        if self.settings.incremental_filter {
+            self.filter
+                .store(editor.get_buffer(), editor.insertion_point(), &self.values);
+        }
         self.working_details.shortest_base_string = base_ranges
             .iter()
             .map(|range| editor.get_buffer()[range.clone()].to_string())
@@ -525,6 +715,8 @@ impl Menu for ColumnarMenu {
                 .iter()
                 .any(|suggestion| suggestion.description.is_some());
 
+            self.working_details.descriptions = exist_description;
+
             if exist_description {
                 self.working_details.columns = 1;
                 self.working_details.col_width = painter.screen_width() as usize;
@@ -563,6 +755,19 @@ impl Menu for ColumnarMenu {
                     self.working_details.col_width = default_width;
                 };
 
+                // The bounds are applied last, so that a long value doesn't stretch every column
+                if let Some(min_col_width) = self.default_details.min_col_width {
+                    self.working_details.col_width =
+                        self.working_details.col_width.max(min_col_width);
+                }
+                if let Some(max_col_width) = self.default_details.max_col_width {
+                    self.working_details.col_width = self
+                        .working_details
+                        .col_width
+                        .min(max_col_width)
+                        .max(self.default_details.col_padding + 1);
+                }
+
                 // The working columns is adjusted based on possible number of columns
                 // that could be fitted in the screen with the calculated column width
                 let possible_cols = painter.screen_width() / self.working_details.col_width as u16;
@@ -624,6 +829,33 @@ impl Menu for ColumnarMenu {
         &self.values
     }
 
+    fn get_selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
+    fn accepts_on_next(&self) -> bool {
+        self.settings.tab_accepts_on_end && !self.get_values().is_empty() && self.is_last_selected()
+    }
+
+    /// The rows skipped by [`Menu::menu_string`] to show the selected one are counted in
+    fn select_at(&mut self, row: u16, column: u16, available_lines: u16) -> bool {
+        let skip_rows = if self.row_pos >= available_lines {
+            self.row_pos.saturating_sub(available_lines) + 1
+        } else {
+            0
+        };
+        let row = skip_rows + row;
+        let col = (column as usize / self.get_width().max(1)) as u16;
+        let index = (row * self.get_cols() + col) as usize;
+        if col >= self.get_cols() || index >= self.get_values().len() {
+            return false;
+        }
+
+        self.row_pos = row;
+        self.col_pos = col;
+        true
+    }
+
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
         self.get_rows()
     }
@@ -750,6 +982,12 @@ mod tests {
             extra: None,
             span: Span { start: 0, end: pos },
             append_whitespace: false,
+            match_indices: None,
+            score: None,
+            preview: None,
+            cursor_offset: None,
+            source: None,
+            continue_completion: false,
         }
     }
 
@@ -773,4 +1011,169 @@ mod tests {
             "cursor should be at the end after completion"
         );
     }
+
+    fn render(menu: ColumnarMenu, values: &[&str], use_ansi_coloring: bool) -> String {
+        let mut menu = menu;
+        let mut completer = FakeCompleter::new(values);
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+
+        menu.update_values(&mut editor, &mut completer);
+        menu.menu_event(MenuEvent::Activate(true));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        menu.menu_string(10, use_ansi_coloring)
+    }
+
+    #[test]
+    fn test_column_width_bounds() {
+        let values = ["12", "345", "a-very-long-suggestion-name"];
+
+        assert_eq!(
+            render(
+                ColumnarMenu::default().with_column_width_bounds(6, 10),
+                &values,
+                false
+            ),
+            ">12       345       a-very-…  "
+        );
+
+        // The minimum width applies to short values
+        assert_eq!(
+            render(
+                ColumnarMenu::default()
+                    .with_column_width(Some(3))
+                    .with_column_width_bounds(6, 10),
+                &["a", "b"],
+                false
+            ),
+            ">A    b     "
+        );
+
+        assert!(render(
+            ColumnarMenu::default().with_column_width_bounds(6, 10),
+            &values,
+            true
+        )
+        .contains("a-very-…"));
+    }
+
+    #[test]
+    fn test_column_alignment() {
+        assert_eq!(
+            render(
+                ColumnarMenu::default()
+                    .with_column_width(Some(6))
+                    .with_column_alignment(Alignment::Right),
+                &["1", "22", "333"],
+                false
+            ),
+            ">   1   22   333  "
+        );
+    }
+
+    #[test]
+    fn test_incremental_filter() {
+        let mut completer = FakeCompleter::new(&["checkout", "cherry-pick", "commit"]);
+        let mut menu = ColumnarMenu::default().with_incremental_filter(true);
+        let mut editor = Editor::default();
+
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_values(&mut editor, &mut completer);
+        assert_eq!(menu.get_values().len(), 3);
+
+        // The kept suggestions are filtered, the completer isn't called again
+        completer.completions.clear();
+        editor.set_buffer("cmt".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.update_values(&mut editor, &mut completer);
+        assert_eq!(
+            menu.get_values()
+                .iter()
+                .map(|suggestion| (suggestion.value.as_str(), suggestion.span))
+                .collect::<Vec<_>>(),
+            vec![("commit", Span::new(0, 3))]
+        );
+
+        editor.set_buffer("c".to_string(), UndoBehavior::CreateUndoPoint);
+        menu.update_values(&mut editor, &mut completer);
+        assert_eq!(menu.get_values().len(), 3);
+
+        // Once deactivated, the completer is called again
+        menu.menu_event(MenuEvent::Deactivate);
+        menu.update_values(&mut editor, &mut completer);
+        assert_eq!(menu.get_values().len(), 0);
+    }
+
+    #[test]
+    fn test_select_at() {
+        let mut completer = FakeCompleter::new(&["a", "b", "c", "d", "e", "f"]);
+        let mut menu = ColumnarMenu::default().with_column_width(Some(6));
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        assert!(menu.select_at(1, 7, 10));
+        assert_eq!(
+            menu.get_value().map(|suggestion| suggestion.value),
+            Some("f".into())
+        );
+
+        // Past the last value, or on the right of the last column
+        assert!(!menu.select_at(1, 13, 10));
+        assert!(!menu.select_at(0, 30, 10));
+
+        // The first row is skipped to show the selected one on a single line
+        assert!(menu.select_at(0, 0, 1));
+        assert_eq!(
+            menu.get_value().map(|suggestion| suggestion.value),
+            Some("e".into())
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(true, MenuEvent::NextElement, &["b", "c", "a", "b"])]
+    #[case(false, MenuEvent::NextElement, &["b", "c", "c", "c"])]
+    #[case(true, MenuEvent::PreviousElement, &["c", "b", "a", "c"])]
+    #[case(false, MenuEvent::PreviousElement, &["a", "a", "a", "a"])]
+    fn test_wrap(#[case] wrap: bool, #[case] event: MenuEvent, #[case] expected: &[&str]) {
+        let mut completer = FakeCompleter::new(&["a", "b", "c"]);
+        let mut menu = ColumnarMenu::default().with_wrap(wrap);
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        let mut selected = Vec::new();
+        for _ in expected {
+            menu.menu_event(event.clone());
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+            selected.push(menu.get_value().map(|suggestion| suggestion.value).unwrap());
+        }
+        assert_eq!(selected, expected);
+        assert!(!menu.accepts_on_next());
+    }
+
+    #[test]
+    fn test_tab_accepts_on_end() {
+        let mut completer = FakeCompleter::new(&["a", "b", "c"]);
+        let mut menu = ColumnarMenu::default().with_tab_accepts_on_end(true);
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        let mut accepts = Vec::new();
+        for _ in 0..3 {
+            accepts.push(menu.accepts_on_next());
+            menu.menu_event(MenuEvent::NextElement);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+        assert_eq!(accepts, vec![false, false, true]);
+    }
 }
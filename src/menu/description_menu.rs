@@ -1,7 +1,7 @@
 use {
     super::MenuSettings,
     crate::{
-        menu_functions::{completer_input, replace_in_buffer},
+        menu_functions::{completer_input, replace_in_buffer, IncrementalFilter},
         Completer, Editor, Menu, MenuBuilder, MenuEvent, Painter, Suggestion,
     },
     nu_ansi_term::ansi::RESET,
@@ -71,6 +71,8 @@ pub struct DescriptionMenu {
     event: Option<MenuEvent>,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Suggestions filtered as the user types, with the incremental filter enabled
+    filter: IncrementalFilter,
     /// Examples to select
     examples: Vec<String>,
     /// Example index
@@ -97,6 +99,7 @@ impl Default for DescriptionMenu {
             row_pos: 0,
             event: None,
             input: None,
+            filter: IncrementalFilter::default(),
             examples: Vec::new(),
             example_index: None,
             show_examples: true,
@@ -149,6 +152,9 @@ impl DescriptionMenu {
 impl DescriptionMenu {
     /// Move menu cursor to the next element
     fn move_next(&mut self) {
+        if !self.settings.wrap && self.is_last_selected() {
+            return;
+        }
         let mut new_col = self.col_pos + 1;
         let mut new_row = self.row_pos;
 
@@ -173,6 +179,9 @@ impl DescriptionMenu {
 
     /// Move menu cursor to the previous element
     fn move_previous(&mut self) {
+        if !self.settings.wrap && self.index() == 0 {
+            return;
+        }
         let new_col = self.col_pos.checked_sub(1);
 
         let (new_col, new_row) = match new_col {
@@ -202,6 +211,11 @@ impl DescriptionMenu {
         index as usize
     }
 
+    /// Whether the last value is selected
+    fn is_last_selected(&self) -> bool {
+        self.index() + 1 >= self.get_values().len()
+    }
+
     /// Get selected value from the menu
     fn get_value(&self) -> Option<Suggestion> {
         self.get_values().get(self.index()).cloned()
@@ -432,6 +446,7 @@ impl Menu for DescriptionMenu {
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
+                self.filter.clear();
                 self.values = Vec::new();
             }
             _ => {}
@@ -442,6 +457,18 @@ impl Menu for DescriptionMenu {
 
     /// Updates menu values
     fn update_values(&mut self, editor: &mut Editor, completer: &mut dyn Completer) {
+        if self.settings.incremental_filter {
+            let filtered = self
+                .filter
+                .filter(editor.get_buffer(), editor.insertion_point());
+
+            if let Some(values) = filtered {
+                self.values = values;
+                self.reset_position();
+                return;
+            }
+        }
+
         let (input, pos) = completer_input(
             editor.get_buffer(),
             editor.insertion_point(),
@@ -449,6 +476,10 @@ impl Menu for DescriptionMenu {
             self.settings.only_buffer_difference,
         );
         self.values = completer.complete(&input, pos);
+        if self.settings.incremental_filter {
+            self.filter
+                .store(editor.get_buffer(), editor.insertion_point(), &self.values);
+        }
 
         self.reset_position();
     }
@@ -605,6 +636,14 @@ impl Menu for DescriptionMenu {
         &self.values
     }
 
+    fn get_selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
+    fn accepts_on_next(&self) -> bool {
+        self.settings.tab_accepts_on_end && !self.get_values().is_empty() && self.is_last_selected()
+    }
+
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
         let example_lines = self
             .examples
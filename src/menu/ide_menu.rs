@@ -1,7 +1,10 @@
 use super::{Menu, MenuBuilder, MenuEvent, MenuSettings};
 use crate::{
     core_editor::Editor,
-    menu_functions::{can_partially_complete, completer_input, replace_in_buffer},
+    menu_functions::{
+        can_partially_complete, completer_input, replace_in_buffer, style_match_indices,
+        truncate_with_ellipsis, IncrementalFilter,
+    },
     painting::Painter,
     Completer, Suggestion,
 };
@@ -57,11 +60,14 @@ struct DefaultIdeMenuDetails {
     pub max_completion_width: u16,
     /// Max height of the completion box, including the border
     /// this will be capped by the lines available in the terminal
+    /// The suggestions that don't fit are scrolled through
     pub max_completion_height: u16,
     /// Padding to the left and right of the suggestions
     pub padding: u16,
     /// Whether the menu has a border or not
     pub border: Option<BorderSymbols>,
+    /// Whether a scrollbar is shown on the right edge of the completion box
+    pub scrollbar: bool,
     /// Horizontal offset from the cursor.
     /// 0 means the top left corner of the menu is below the cursor
     pub cursor_offset: i16,
@@ -77,6 +83,10 @@ struct DefaultIdeMenuDetails {
     pub max_description_height: u16,
     /// Offset from the suggestion box to the description box
     pub description_offset: u16,
+    /// Whether the preview of the selected suggestion is shown below the menu
+    pub preview: bool,
+    /// Max height of the preview box, including the border
+    pub max_preview_height: u16,
     /// If true, the cursor pos will be corrected, so the suggestions match up with the typed text
     /// ```text
     /// C:\> str
@@ -95,12 +105,15 @@ impl Default for DefaultIdeMenuDetails {
             max_completion_height: u16::MAX, // will be limited by the available lines
             padding: 0,
             border: None,
+            scrollbar: false,
             cursor_offset: 0,
             description_mode: DescriptionMode::PreferRight,
             min_description_width: 0,
             max_description_width: 50,
             max_description_height: 10,
             description_offset: 1,
+            preview: false,
+            max_preview_height: 10,
             correct_cursor_pos: false,
         }
     }
@@ -127,6 +140,10 @@ struct IdeMenuDetails {
     pub description_offset: u16,
     /// The shortest of the strings, which the suggestions are based on
     pub shortest_base_string: String,
+    /// Index of the first suggestion shown, the selected suggestion is always visible
+    pub scroll_offset: u16,
+    /// Width of the preview box, including the border
+    pub preview_width: u16,
 }
 
 /// Menu to present suggestions like similar to Ide completion menus
@@ -150,6 +167,8 @@ pub struct IdeMenu {
     longest_suggestion: usize,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Suggestions filtered as the user types, with the incremental filter enabled
+    filter: IncrementalFilter,
 }
 
 impl Default for IdeMenu {
@@ -164,6 +183,7 @@ impl Default for IdeMenu {
             event: None,
             longest_suggestion: 0,
             input: None,
+            filter: IncrementalFilter::default(),
         }
     }
 }
@@ -234,6 +254,14 @@ impl IdeMenu {
         self
     }
 
+    /// Menu builder showing a scrollbar on the right edge of the completion box,
+    /// indicating which part of the suggestions is shown
+    #[must_use]
+    pub fn with_scrollbar(mut self, scrollbar: bool) -> Self {
+        self.default_details.scrollbar = scrollbar;
+        self
+    }
+
     /// Menu builder with new value for cursor offset
     #[must_use]
     pub fn with_cursor_offset(mut self, cursor_offset: i16) -> Self {
@@ -276,6 +304,22 @@ impl IdeMenu {
         self
     }
 
+    /// Menu builder showing the [`Suggestion::preview`] of the selected suggestion
+    /// in a bordered box below the menu
+    #[must_use]
+    pub fn with_preview(mut self, preview: bool) -> Self {
+        self.default_details.preview = preview;
+        self
+    }
+
+    /// Menu builder with new max preview height.
+    /// A preview taller than this, or than the available lines, is clipped
+    #[must_use]
+    pub fn with_max_preview_height(mut self, max_preview_height: u16) -> Self {
+        self.default_details.max_preview_height = max_preview_height;
+        self
+    }
+
     /// Menu builder with new correct cursor pos
     #[must_use]
     pub fn with_correct_cursor_pos(mut self, correct_cursor_pos: bool) -> Self {
@@ -287,6 +331,9 @@ impl IdeMenu {
 // Menu functionality
 impl IdeMenu {
     fn move_next(&mut self) {
+        if !self.settings.wrap && self.is_last_selected() {
+            return;
+        }
         if self.selected < (self.values.len() as u16).saturating_sub(1) {
             self.selected += 1;
         } else {
@@ -295,6 +342,9 @@ impl IdeMenu {
     }
 
     fn move_previous(&mut self) {
+        if !self.settings.wrap && self.selected == 0 {
+            return;
+        }
         if self.selected > 0 {
             self.selected -= 1;
         } else {
@@ -306,6 +356,11 @@ impl IdeMenu {
         self.selected as usize
     }
 
+    /// Whether the last value is selected
+    fn is_last_selected(&self) -> bool {
+        self.index() + 1 >= self.values.len()
+    }
+
     fn get_value(&self) -> Option<Suggestion> {
         self.values.get(self.index()).cloned()
     }
@@ -349,6 +404,130 @@ impl IdeMenu {
 
     fn reset_position(&mut self) {
         self.selected = 0;
+        self.working_details.scroll_offset = 0;
+    }
+
+    /// Moves the selection by a full viewport of `rows` suggestions
+    fn move_page(&mut self, rows: u16, forward: bool) {
+        let last = (self.values.len() as u16).saturating_sub(1);
+
+        self.selected = if forward {
+            self.selected.saturating_add(rows).min(last)
+        } else {
+            self.selected.saturating_sub(rows)
+        };
+    }
+
+    /// Width of the scrollbar column added to the completion box.
+    /// With a border, the scrollbar replaces the right border instead
+    fn scrollbar_width(&self) -> u16 {
+        if self.default_details.scrollbar && self.default_details.border.is_none() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// First suggestion to show so that the selected one is visible among `rows` suggestions,
+    /// scrolling as little as possible from `scroll_offset`
+    fn scroll_offset(&self, scroll_offset: u16, rows: u16) -> u16 {
+        let max_offset = (self.values.len() as u16).saturating_sub(rows.max(1));
+
+        if self.selected < scroll_offset {
+            self.selected
+        } else if self.selected >= scroll_offset.saturating_add(rows) {
+            (self.selected + 1).saturating_sub(rows)
+        } else {
+            scroll_offset.min(max_offset)
+        }
+    }
+
+    /// Preview of the selected suggestion, if the preview box is enabled
+    fn selected_preview(&self) -> Option<String> {
+        if !self.default_details.preview {
+            return None;
+        }
+
+        self.get_value()
+            .and_then(|value| value.preview)
+            .filter(|preview| !preview.is_empty())
+    }
+
+    /// Lines reserved below the suggestions for the preview box, including its border
+    fn preview_height(&self) -> u16 {
+        self.selected_preview().map_or(0, |preview| {
+            (preview.lines().count() as u16)
+                .saturating_add(2)
+                .min(self.default_details.max_preview_height)
+        })
+    }
+
+    fn create_preview(
+        &self,
+        preview: &str,
+        use_ansi_coloring: bool,
+        available_width: u16,
+        available_height: u16,
+    ) -> Vec<String> {
+        // Below that, not even one line would fit inside the border
+        if available_width < 3 || available_height < 3 {
+            return Vec::new();
+        }
+
+        let default_border = BorderSymbols::default();
+        let border = self
+            .default_details
+            .border
+            .as_ref()
+            .unwrap_or(&default_border);
+
+        let content_width = available_width.saturating_sub(2) as usize;
+        let content_height = available_height.saturating_sub(2) as usize;
+
+        let mut preview_lines = preview
+            .lines()
+            .map(|line| truncate_with_ellipsis(line, content_width))
+            .collect::<Vec<_>>();
+
+        // The last line that fits indicates that the preview was clipped
+        if preview_lines.len() > content_height {
+            preview_lines.truncate(content_height - 1);
+            preview_lines.push(truncate_with_ellipsis("...", content_width));
+        }
+
+        let horizontal_border = border.horizontal.to_string().repeat(content_width);
+
+        for line in &mut preview_lines {
+            let padding = " ".repeat(content_width.saturating_sub(line.width()));
+
+            if use_ansi_coloring {
+                *line = format!(
+                    "{}{}{}{}{}{}",
+                    border.vertical,
+                    self.settings.color.description_style.prefix(),
+                    line,
+                    padding,
+                    RESET,
+                    border.vertical
+                );
+            } else {
+                *line = format!("{}{}{}{}", border.vertical, line, padding, border.vertical);
+            }
+        }
+
+        preview_lines.insert(
+            0,
+            format!(
+                "{}{}{}",
+                border.top_left, horizontal_border, border.top_right
+            ),
+        );
+        preview_lines.push(format!(
+            "{}{}{}",
+            border.bottom_left, horizontal_border, border.bottom_right
+        ));
+
+        preview_lines
     }
 
     fn no_records_msg(&self, use_ansi_coloring: bool) -> String {
@@ -493,6 +672,8 @@ impl IdeMenu {
             .map(|border| border.vertical)
             .unwrap_or_default();
 
+        let border_width = border_width + self.scrollbar_width() as usize;
+
         let padding_right = (self.working_details.completion_width as usize)
             .saturating_sub(suggestion.value.chars().count() + border_width + padding);
 
@@ -512,48 +693,60 @@ impl IdeMenu {
         };
 
         if use_ansi_coloring {
-            let match_len = self.working_details.shortest_base_string.len();
-
-            // Split string so the match text can be styled
-            let (match_str, remaining_str) = string.split_at(match_len);
-
             let suggestion_style_prefix = suggestion
                 .style
                 .unwrap_or(self.settings.color.text_style)
                 .prefix();
 
-            if index == self.index() {
-                format!(
-                    "{}{}{}{}{}{}{}{}{}{}{}{}",
-                    vertical_border,
-                    suggestion_style_prefix,
-                    " ".repeat(padding),
+            let (match_style_prefix, text_style_prefix) = if index == self.index() {
+                (
                     self.settings.color.selected_match_style.prefix(),
-                    match_str,
-                    RESET,
-                    suggestion_style_prefix,
-                    self.settings.color.selected_text_style.prefix(),
-                    remaining_str,
-                    " ".repeat(padding_right),
-                    RESET,
-                    vertical_border,
+                    format!(
+                        "{}{}",
+                        suggestion_style_prefix,
+                        self.settings.color.selected_text_style.prefix()
+                    ),
                 )
             } else {
-                format!(
-                    "{}{}{}{}{}{}{}{}{}{}{}",
-                    vertical_border,
-                    suggestion_style_prefix,
-                    " ".repeat(padding),
+                (
                     self.settings.color.match_style.prefix(),
-                    match_str,
-                    RESET,
-                    suggestion_style_prefix,
-                    remaining_str,
-                    " ".repeat(padding_right),
-                    RESET,
-                    vertical_border,
+                    suggestion_style_prefix.to_string(),
                 )
-            }
+            };
+
+            let styled_string = if let Some(match_indices) = &suggestion.match_indices {
+                format!(
+                    "{}{}",
+                    style_match_indices(
+                        &string,
+                        match_indices,
+                        &format!("{}{}", suggestion_style_prefix, match_style_prefix),
+                        &text_style_prefix,
+                    ),
+                    text_style_prefix,
+                )
+            } else {
+                let match_len = self.working_details.shortest_base_string.len();
+
+                // Split string so the match text can be styled
+                let (match_str, remaining_str) = string.split_at(match_len);
+
+                format!(
+                    "{}{}{}{}{}",
+                    match_style_prefix, match_str, RESET, text_style_prefix, remaining_str,
+                )
+            };
+
+            format!(
+                "{}{}{}{}{}{}{}",
+                vertical_border,
+                suggestion_style_prefix,
+                " ".repeat(padding),
+                styled_string,
+                " ".repeat(padding_right),
+                RESET,
+                vertical_border,
+            )
         } else {
             let marker = if index == self.index() { ">" } else { "" };
 
@@ -616,6 +809,7 @@ impl Menu for IdeMenu {
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
+                self.filter.clear();
             }
             _ => {}
         }
@@ -625,6 +819,18 @@ impl Menu for IdeMenu {
 
     /// Update menu values
     fn update_values(&mut self, editor: &mut Editor, completer: &mut dyn Completer) {
+        if self.settings.incremental_filter {
+            let filtered = self
+                .filter
+                .filter(editor.get_buffer(), editor.insertion_point());
+
+            if let Some(values) = filtered {
+                self.values = values;
+                self.reset_position();
+                return;
+            }
+        }
+
         let (input, pos) = completer_input(
             editor.get_buffer(),
             editor.insertion_point(),
@@ -634,6 +840,10 @@ impl Menu for IdeMenu {
         let (values, base_ranges) = completer.complete_with_base_ranges(&input, pos);
 
         self.values = values;
+        if self.settings.incremental_filter {
+            self.filter
+                .store(editor.get_buffer(), editor.insertion_point(), &self.values);
+        }
         self.working_details.shortest_base_string = base_ranges
             .iter()
             .map(|range| editor.get_buffer()[range.clone()].to_string())
@@ -678,12 +888,16 @@ impl Menu for IdeMenu {
                 }
                 MenuEvent::NextElement | MenuEvent::MoveDown => self.move_next(),
                 MenuEvent::PreviousElement | MenuEvent::MoveUp => self.move_previous(),
-                MenuEvent::MoveLeft
-                | MenuEvent::MoveRight
-                | MenuEvent::PreviousPage
-                | MenuEvent::NextPage => {}
+                MenuEvent::NextPage => self.move_page(self.visible_rows(painter), true),
+                MenuEvent::PreviousPage => self.move_page(self.visible_rows(painter), false),
+                MenuEvent::MoveLeft | MenuEvent::MoveRight => {}
             }
 
+            self.working_details.scroll_offset = self.scroll_offset(
+                self.working_details.scroll_offset,
+                self.visible_rows(painter),
+            );
+
             self.longest_suggestion = self.get_values().iter().fold(0, |prev, suggestion| {
                 if prev >= suggestion.value.len() {
                     prev
@@ -729,11 +943,12 @@ impl Menu for IdeMenu {
 
             let completion_width = ((self.longest_suggestion.min(u16::MAX as usize) as u16)
                 + 2 * self.default_details.padding
-                + border_width)
-                .min(self.default_details.max_completion_width)
-                .max(self.default_details.min_completion_width)
-                .min(terminal_width.saturating_sub(min_description_width))
-                .max(3 + border_width); // Big enough to show "..."
+                + border_width
+                + self.scrollbar_width())
+            .min(self.default_details.max_completion_width)
+            .max(self.default_details.min_completion_width)
+            .min(terminal_width.saturating_sub(min_description_width))
+            .max(3 + border_width + self.scrollbar_width()); // Big enough to show "..."
 
             let available_description_width = terminal_width
                 .saturating_sub(completion_width)
@@ -800,6 +1015,16 @@ impl Menu for IdeMenu {
 
             self.working_details.space_left = space_left;
             self.working_details.space_right = space_right;
+
+            // The preview box is at least as wide as the menu, and grows up to the right edge
+            self.working_details.preview_width = self.selected_preview().map_or(0, |preview| {
+                let longest_line = preview.lines().map(|line| line.width()).max();
+
+                (longest_line.unwrap_or_default().min(u16::MAX as usize) as u16)
+                    .saturating_add(2)
+                    .max(self.get_width())
+                    .min(terminal_width.saturating_sub(space_left))
+            });
         }
     }
 
@@ -810,16 +1035,25 @@ impl Menu for IdeMenu {
 
     /// Minimum rows that should be displayed by the menu
     fn min_rows(&self) -> u16 {
-        self.get_rows()
+        self.get_rows() + self.preview_height()
     }
 
     fn get_values(&self) -> &[Suggestion] {
         &self.values
     }
 
+    fn get_selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
+    fn accepts_on_next(&self) -> bool {
+        self.settings.tab_accepts_on_end && !self.values.is_empty() && self.is_last_selected()
+    }
+
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
         self.get_rows()
             .min(self.default_details.max_completion_height)
+            + self.preview_height()
     }
 
     fn menu_string(&self, available_lines: u16, use_ansi_coloring: bool) -> String {
@@ -832,23 +1066,26 @@ impl Menu for IdeMenu {
                 0
             };
 
-            let available_lines = available_lines.min(self.default_details.max_completion_height);
+            // The lines for the preview are reserved first, the suggestions still show at least one value
+            let total_lines = available_lines;
+            let available_lines = available_lines
+                .saturating_sub(self.preview_height())
+                .max(border_width + 1)
+                .min(total_lines)
+                .min(self.default_details.max_completion_height);
+            let available_values = available_lines.saturating_sub(border_width);
+
             // The skip values represent the number of lines that should be skipped
             // while printing the menu
-            let skip_values = if self.selected >= available_lines.saturating_sub(border_width) {
-                let skip_lines = self
-                    .selected
-                    .saturating_sub(available_lines.saturating_sub(border_width))
-                    + 1;
-                skip_lines as usize
-            } else {
-                0
-            };
+            let skip_values =
+                self.scroll_offset(self.working_details.scroll_offset, available_values) as usize;
 
-            let available_values = available_lines.saturating_sub(border_width) as usize;
+            let available_values = available_values as usize;
 
             let max_padding = self.working_details.completion_width.saturating_sub(
-                self.longest_suggestion.min(u16::MAX as usize) as u16 + border_width,
+                self.longest_suggestion.min(u16::MAX as usize) as u16
+                    + border_width
+                    + self.scrollbar_width(),
             ) / 2;
 
             let corrected_padding = self.default_details.padding.min(max_padding) as usize;
@@ -872,6 +1109,25 @@ impl Menu for IdeMenu {
                 })
                 .collect::<Vec<String>>();
 
+            if self.default_details.scrollbar {
+                match scrollbar(self.get_values().len(), skip_values, strings.len()) {
+                    Some(scrollbar) => {
+                        for (line, scrollbar_char) in strings.iter_mut().zip(scrollbar) {
+                            if self.default_details.border.is_some() {
+                                // The scrollbar replaces the right border
+                                line.pop();
+                            }
+                            line.push(scrollbar_char);
+                        }
+                    }
+                    // Every value is shown, only the space of the scrollbar is kept
+                    None if self.default_details.border.is_none() => {
+                        strings.iter_mut().for_each(|line| line.push(' '));
+                    }
+                    None => {}
+                }
+            }
+
             // Add top and bottom border
             if let Some(border) = &self.default_details.border {
                 let inner_width = self.working_details.completion_width.saturating_sub(2) as usize;
@@ -979,6 +1235,21 @@ impl Menu for IdeMenu {
                 }
             }
 
+            if let Some(preview) = self.selected_preview() {
+                let preview_lines = self.create_preview(
+                    &preview,
+                    use_ansi_coloring,
+                    self.working_details.preview_width,
+                    total_lines.saturating_sub(strings.len() as u16),
+                );
+
+                strings.extend(
+                    preview_lines
+                        .into_iter()
+                        .map(|line| format!("{}{}", distance_left, line)),
+                );
+            }
+
             strings.join("\r\n")
         }
     }
@@ -988,6 +1259,57 @@ impl Menu for IdeMenu {
     }
 }
 
+impl IdeMenu {
+    /// Number of suggestions expected to be visible at once, as the menu is limited
+    /// by its max height and the lines available below the prompt
+    fn visible_rows(&self, painter: &Painter) -> u16 {
+        let border_width = if self.default_details.border.is_some() {
+            2
+        } else {
+            0
+        };
+
+        self.default_details
+            .max_completion_height
+            .min(
+                painter
+                    .remaining_lines()
+                    .saturating_sub(1)
+                    .saturating_sub(self.preview_height()),
+            )
+            .saturating_sub(border_width)
+            .max(1)
+    }
+}
+
+/// Characters of a scrollbar of `rows` lines, for `total` values of which the ones from `offset` are shown
+///
+/// The thumb (`█`) is drawn on a track of `│`, or `None` is returned if every value is shown
+fn scrollbar(total: usize, offset: usize, rows: usize) -> Option<Vec<char>> {
+    if rows == 0 || total <= rows {
+        return None;
+    }
+
+    let thumb_size = (rows * rows / total).clamp(1, rows);
+    let thumb_start = if offset + rows >= total {
+        rows - thumb_size
+    } else {
+        (offset * rows / total).min(rows - thumb_size)
+    };
+
+    let scrollbar = (0..rows)
+        .map(|row| {
+            if (thumb_start..thumb_start + thumb_size).contains(&row) {
+                '█'
+            } else {
+                '│'
+            }
+        })
+        .collect();
+
+    Some(scrollbar)
+}
+
 /// Split the input into strings that are at most `max_length` (in columns, not in chars) long
 /// The split is done at whitespace if possible
 fn split_string(input_str: &str, max_length: usize) -> Vec<String> {
@@ -1382,6 +1704,12 @@ mod tests {
             extra: None,
             span: Span { start: 0, end: pos },
             append_whitespace: false,
+            match_indices: None,
+            score: None,
+            preview: None,
+            cursor_offset: None,
+            source: None,
+            continue_completion: false,
         }
     }
 
@@ -1405,4 +1733,127 @@ mod tests {
             "cursor should be at the end after completion"
         );
     }
+
+    #[rstest]
+    #[case(3, 0, 5, None)]
+    #[case(10, 0, 3, Some(vec!['█', '│', '│']))]
+    #[case(10, 5, 3, Some(vec!['│', '█', '│']))]
+    #[case(10, 7, 3, Some(vec!['│', '│', '█']))]
+    #[case(4, 2, 2, Some(vec!['│', '█']))]
+    #[case(4, 0, 2, Some(vec!['█', '│']))]
+    fn test_scrollbar(
+        #[case] total: usize,
+        #[case] offset: usize,
+        #[case] rows: usize,
+        #[case] expected: Option<Vec<char>>,
+    ) {
+        assert_eq!(scrollbar(total, offset, rows), expected);
+    }
+
+    #[test]
+    fn test_menu_scrolls_to_selection() {
+        let values = (0..10).map(|i| format!("value{i}")).collect::<Vec<_>>();
+        let mut completer =
+            FakeCompleter::new(&values.iter().map(String::as_str).collect::<Vec<_>>());
+        let mut menu = IdeMenu::default()
+            .with_scrollbar(true)
+            .with_max_completion_height(3);
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+
+        let mut send = |menu: &mut IdeMenu, event: MenuEvent| {
+            menu.menu_event(event);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        };
+
+        send(&mut menu, MenuEvent::Activate(false));
+        for _ in 0..4 {
+            send(&mut menu, MenuEvent::MoveDown);
+        }
+
+        let shown = |menu: &IdeMenu| {
+            menu.menu_string(10, false)
+                .split("\r\n")
+                .map(|line| {
+                    let value = values
+                        .iter()
+                        .position(|value| line.contains(value.as_str()));
+                    (value.unwrap(), line.chars().last().unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(shown(&menu), vec![(2, '█'), (3, '│'), (4, '│')]);
+
+        // A page down jumps a full viewport
+        send(&mut menu, MenuEvent::NextPage);
+        assert_eq!(menu.index(), 7);
+        assert_eq!(shown(&menu), vec![(5, '│'), (6, '█'), (7, '│')]);
+
+        // Moving inside the viewport doesn't scroll
+        send(&mut menu, MenuEvent::MoveUp);
+        assert_eq!(menu.index(), 6);
+        assert_eq!(shown(&menu), vec![(5, '│'), (6, '█'), (7, '│')]);
+
+        send(&mut menu, MenuEvent::PreviousPage);
+        assert_eq!(menu.index(), 3);
+        assert_eq!(shown(&menu), vec![(3, '█'), (4, '│'), (5, '│')]);
+    }
+
+    #[test]
+    fn test_preview() {
+        let mut menu = IdeMenu::default().with_preview(true);
+        let mut completer = FakeCompleter::new(&[]);
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+
+        menu.values = vec![
+            Suggestion {
+                value: "ls".to_string(),
+                preview: Some("List the files\nls -la".to_string()),
+                ..Suggestion::default()
+            },
+            Suggestion {
+                value: "cd".to_string(),
+                ..Suggestion::default()
+            },
+        ];
+        menu.menu_event(MenuEvent::Activate(true));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        assert_eq!(menu.menu_required_lines(80), 6);
+        assert_eq!(
+            menu.menu_string(10, false)
+                .split("\r\n")
+                .collect::<Vec<_>>(),
+            vec![
+                "\0>ls \0",
+                "\0cd \0",
+                "╭──────────────╮",
+                "│List the files│",
+                "│ls -la        │",
+                "╰──────────────╯",
+            ]
+        );
+
+        // A preview taller than the available lines is clipped
+        assert_eq!(
+            menu.menu_string(4, false).split("\r\n").collect::<Vec<_>>(),
+            vec![
+                "\0>ls \0",
+                "╭──────────────╮",
+                "│...           │",
+                "╰──────────────╯",
+            ]
+        );
+
+        // The box follows the selection
+        menu.menu_event(MenuEvent::NextElement);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        assert_eq!(menu.menu_required_lines(80), 2);
+        assert_eq!(menu.menu_string(10, false).split("\r\n").count(), 2);
+    }
 }
@@ -2,7 +2,7 @@ use {
     super::{menu_functions::parse_selection_char, Menu, MenuBuilder, MenuEvent, MenuSettings},
     crate::{
         core_editor::Editor,
-        menu_functions::{completer_input, replace_in_buffer},
+        menu_functions::{completer_input, replace_in_buffer, style_match_indices},
         painting::{estimate_single_line_wraps, Painter},
         Completer, Suggestion,
     },
@@ -15,7 +15,6 @@ const SELECTION_CHAR: char = '!';
 
 struct Page {
     size: usize,
-    full: bool,
 }
 
 impl<'a> Sum<&'a Page> for Page {
@@ -23,16 +22,9 @@ impl<'a> Sum<&'a Page> for Page {
     where
         I: Iterator<Item = &'a Page>,
     {
-        iter.fold(
-            Page {
-                size: 0,
-                full: false,
-            },
-            |acc, menu| Page {
-                size: acc.size + menu.size,
-                full: acc.full || menu.full,
-            },
-        )
+        iter.fold(Page { size: 0 }, |acc, menu| Page {
+            size: acc.size + menu.size,
+        })
     }
 }
 
@@ -41,8 +33,14 @@ impl<'a> Sum<&'a Page> for Page {
 pub struct ListMenu {
     /// Menu settings
     settings: MenuSettings,
-    /// Number of records pulled until page is full
+    /// Number of records shown in a page, if they fit on the screen
     page_size: usize,
+    /// Whether moving past the last (first) entry of a page goes to the next (previous) page
+    move_across_pages: bool,
+    /// Whether a header with the source of the suggestions starts each group of them
+    source_headers: bool,
+    /// Width of the terminal, updated with the working details
+    terminal_columns: u16,
     /// Menu active status
     active: bool,
     /// Cached values collected when querying the completer.
@@ -77,6 +75,9 @@ impl Default for ListMenu {
                 .with_marker("? ")
                 .with_only_buffer_difference(true),
             page_size: 10,
+            move_across_pages: true,
+            source_headers: false,
+            terminal_columns: 80,
             active: false,
             values: Vec::new(),
             row_position: 0,
@@ -113,6 +114,23 @@ impl ListMenu {
         self.max_lines = max_lines;
         self
     }
+
+    /// Menu builder for whether moving down from the last entry of a page (or up from the
+    /// first one) goes to the next (or previous) page, instead of staying on the entry
+    #[must_use]
+    pub fn with_move_across_pages(mut self, move_across_pages: bool) -> Self {
+        self.move_across_pages = move_across_pages;
+        self
+    }
+
+    /// Menu builder for whether a header line with the [`Suggestion::source`] is shown
+    /// before the suggestions coming from each source, e.g. with a
+    /// [`MultiCompleter`](crate::MultiCompleter)
+    #[must_use]
+    pub fn with_source_headers(mut self, source_headers: bool) -> Self {
+        self.source_headers = source_headers;
+        self
+    }
 }
 
 // Menu functionality
@@ -132,6 +150,55 @@ impl ListMenu {
         number_of_lines(entry, self.max_lines as usize, terminal_columns)
     }
 
+    /// The number of rows the entry at `index` in the page takes, with its row number and
+    /// source header
+    fn entry_lines(&self, index: usize, terminal_columns: u16) -> u16 {
+        let Some(suggestion) = self.get_values().get(index) else {
+            return 0;
+        };
+        //  to account for the index and the indicator e.g. 0: XXXX
+        let columns = terminal_columns
+            .saturating_sub(self.indicator().width() as u16 + count_digits(index))
+            .max(1);
+        self.number_of_lines(&suggestion.value, columns)
+            + u16::from(self.source_header(index).is_some())
+    }
+
+    /// Source of the entry at `index` in the page when it starts a group of entries with the
+    /// same source
+    fn source_header(&self, index: usize) -> Option<&str> {
+        if !self.source_headers {
+            return None;
+        }
+
+        let values = self.get_values();
+        let source = values.get(index)?.source.as_deref()?;
+        let previous_source = index
+            .checked_sub(1)
+            .and_then(|previous| values[previous].source.as_deref());
+        (previous_source != Some(source)).then_some(source)
+    }
+
+    /// Number of pages, estimating the size of the pages not computed yet with the last one
+    fn total_pages(&self) -> usize {
+        let known_values = self.pages.iter().sum::<Page>().size;
+        let page_size = self
+            .pages
+            .last()
+            .map_or(self.page_size, |page| page.size)
+            .max(1);
+        let remaining_values = self.total_values().saturating_sub(known_values);
+
+        self.pages.len().max(1) + (remaining_values + page_size - 1) / page_size
+    }
+
+    /// Keeps the selection on the page when it has less values than the previous one
+    fn clamp_row_position(&mut self) {
+        let page_size = self.pages.get(self.page).map_or(0, |page| page.size);
+        let values = self.get_values().len().min(page_size);
+        self.row_position = self.row_position.min(values.saturating_sub(1) as u16);
+    }
+
     fn total_values(&self) -> usize {
         self.query_size.unwrap_or(self.values.len())
     }
@@ -142,8 +209,7 @@ impl ListMenu {
 
     fn set_actual_page_size(&mut self, printable_entries: usize) {
         if let Some(page) = self.pages.get_mut(self.page) {
-            page.full = page.size > printable_entries || page.full;
-            page.size = printable_entries;
+            page.size = page.size.min(printable_entries);
         }
     }
 
@@ -168,31 +234,22 @@ impl ListMenu {
         // The number 2 comes from the prompt line and the banner printed at the bottom
         // of the menu
         let available_lines = painter.screen_height().saturating_sub(2);
-        let (printable_entries, _) =
-            self.get_values()
-                .iter()
-                .fold(
-                    (0, Some(0)),
-                    |(lines, total_lines), suggestion| match total_lines {
-                        None => (lines, None),
-                        Some(total_lines) => {
-                            let new_total_lines = total_lines
-                                + self.number_of_lines(
-                                    &suggestion.value,
-                                    //  to account for the index and the indicator e.g. 0: XXXX
-                                    painter.screen_width().saturating_sub(
-                                        self.indicator().width() as u16 + count_digits(lines),
-                                    ),
-                                );
-
-                            if new_total_lines < available_lines {
-                                (lines + 1, Some(new_total_lines))
-                            } else {
-                                (lines, None)
-                            }
-                        }
-                    },
-                );
+        let (printable_entries, _) = self.get_values().iter().fold(
+            (0, Some(0)),
+            |(lines, total_lines), _| match total_lines {
+                None => (lines, None),
+                Some(total_lines) => {
+                    let new_total_lines =
+                        total_lines + self.entry_lines(lines, painter.screen_width());
+
+                    if new_total_lines < available_lines {
+                        (lines + 1, Some(new_total_lines))
+                    } else {
+                        (lines, None)
+                    }
+                }
+            },
+        );
 
         printable_entries
     }
@@ -211,23 +268,18 @@ impl ListMenu {
         }
     }
 
-    fn banner_message(&self, page: &Page, use_ansi_coloring: bool) -> String {
-        let values_until = self.values_until_current_page().saturating_sub(1);
-        let value_before = if self.values.is_empty() || self.page == 0 {
-            0
-        } else {
-            let page_size = self.pages.get(self.page).map(|page| page.size).unwrap_or(0);
-            values_until.saturating_sub(page_size) + 1
-        };
-
-        let full_page = if page.full { "[FULL]" } else { "" };
+    fn banner_message(&self, use_ansi_coloring: bool) -> String {
+        let total_values = self.total_values();
         let status_bar = format!(
-            "Page {}: records {} - {}  total: {}  {}",
+            "[page {}/{}, {} {}]",
             self.page + 1,
-            value_before,
-            values_until,
-            self.total_values(),
-            full_page,
+            self.total_pages(),
+            total_values,
+            if total_values == 1 {
+                "match"
+            } else {
+                "matches"
+            },
         );
 
         if use_ansi_coloring {
@@ -256,6 +308,38 @@ impl ListMenu {
         }
     }
 
+    /// The value with its matched characters in the match style, see
+    /// [`Suggestion::match_indices`]
+    fn style_matches(&self, value: &str, match_indices: &[usize], index: usize) -> String {
+        let match_style = if index == self.index() {
+            self.settings.color.selected_match_style
+        } else {
+            self.settings.color.match_style
+        };
+
+        style_match_indices(
+            value,
+            match_indices,
+            &match_style.prefix().to_string(),
+            &self.text_style(index),
+        )
+    }
+
+    /// Line starting the group of suggestions coming from `source`
+    fn header_string(&self, source: &str, use_ansi_coloring: bool) -> String {
+        if use_ansi_coloring {
+            format!(
+                "{}[{}]{}{}",
+                self.settings.color.description_style.prefix(),
+                source,
+                RESET,
+                Self::end_of_line(),
+            )
+        } else {
+            format!("[{}]{}", source, Self::end_of_line())
+        }
+    }
+
     /// Creates default string that represents one line from a menu
     fn create_string(
         &self,
@@ -346,8 +430,9 @@ impl Menu for ListMenu {
 
     /// Collecting the value from the completer to be shown in the menu
     fn update_values(&mut self, editor: &mut Editor, completer: &mut dyn Completer) {
+        // The row selector is typed right before the cursor, the text after it is left out
         let (input, pos) = completer_input(
-            editor.get_buffer(),
+            &editor.get_buffer()[..editor.insertion_point()],
             editor.insertion_point(),
             self.input.as_deref(),
             self.settings.only_buffer_difference,
@@ -406,6 +491,10 @@ impl Menu for ListMenu {
         }
     }
 
+    fn get_selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
     /// The buffer gets cleared with the actual value
     fn replace_in_buffer(&self, editor: &mut Editor) {
         replace_in_buffer(self.get_value(), editor);
@@ -417,6 +506,7 @@ impl Menu for ListMenu {
         completer: &mut dyn Completer,
         painter: &Painter,
     ) {
+        self.terminal_columns = painter.screen_width();
         if let Some(event) = self.event.clone() {
             match event {
                 MenuEvent::Activate(_) => {
@@ -432,7 +522,6 @@ impl Menu for ListMenu {
 
                     self.pages.push(Page {
                         size: self.printable_entries(painter),
-                        full: false,
                     });
                 }
                 MenuEvent::Deactivate => {
@@ -443,25 +532,25 @@ impl Menu for ListMenu {
                     self.update_values(editor, completer);
                     self.pages.push(Page {
                         size: self.printable_entries(painter),
-                        full: false,
                     });
                 }
                 MenuEvent::NextElement | MenuEvent::MoveDown | MenuEvent::MoveRight => {
                     let new_pos = self.row_position + 1;
 
                     if let Some(page) = self.pages.get(self.page) {
-                        if new_pos >= page.size as u16 {
+                        if new_pos < page.size as u16 {
+                            self.row_position = new_pos;
+                        } else if self.move_across_pages {
                             self.event = Some(MenuEvent::NextPage);
                             self.update_working_details(editor, completer, painter);
-                        } else {
-                            self.row_position = new_pos;
+                            self.row_position = 0;
                         }
                     }
                 }
                 MenuEvent::PreviousElement | MenuEvent::MoveUp | MenuEvent::MoveLeft => {
                     if let Some(new_pos) = self.row_position.checked_sub(1) {
                         self.row_position = new_pos;
-                    } else {
+                    } else if self.move_across_pages {
                         let page = if let Some(page) = self.page.checked_sub(1) {
                             self.pages.get(page)
                         } else {
@@ -477,29 +566,21 @@ impl Menu for ListMenu {
                     }
                 }
                 MenuEvent::NextPage => {
-                    if self.values_until_current_page() <= self.total_values().saturating_sub(1) {
-                        if let Some(page) = self.pages.get_mut(self.page) {
-                            if page.full {
-                                self.row_position = 0;
-                                self.page += 1;
-                                if self.page >= self.pages.len() {
-                                    self.pages.push(Page {
-                                        size: self.page_size,
-                                        full: false,
-                                    });
-                                }
-                            } else {
-                                page.size += self.page_size;
-                            }
+                    if self.values_until_current_page() < self.total_values() {
+                        self.page += 1;
+                        if self.page >= self.pages.len() {
+                            self.pages.push(Page {
+                                size: self.page_size,
+                            });
                         }
 
                         self.update_values(editor, completer);
                         self.set_actual_page_size(self.printable_entries(painter));
                     } else {
-                        self.row_position = 0;
                         self.page = 0;
                         self.update_values(editor, completer);
                     }
+                    self.clamp_row_position();
                 }
                 MenuEvent::PreviousPage => {
                     match self.page.checked_sub(1) {
@@ -507,6 +588,7 @@ impl Menu for ListMenu {
                         None => self.page = self.pages.len().saturating_sub(1),
                     }
                     self.update_values(editor, completer);
+                    self.clamp_row_position();
                 }
             }
 
@@ -517,34 +599,44 @@ impl Menu for ListMenu {
     /// Calculates the real required lines for the menu considering how many lines
     /// wrap the terminal and if an entry is larger than the remaining lines
     fn menu_required_lines(&self, terminal_columns: u16) -> u16 {
-        let mut entry_index = 0;
-        self.get_values().iter().fold(0, |total_lines, suggestion| {
-            //  to account for the the index and the indicator e.g. 0: XXXX
-            let ret = total_lines
-                + self.number_of_lines(
-                    &suggestion.value,
-                    terminal_columns.saturating_sub(
-                        self.indicator().width() as u16 + count_digits(entry_index),
-                    ),
-                );
-            entry_index += 1;
-            ret
-        }) + 1
+        let page_size = self.pages.get(self.page).map_or(0, |page| page.size);
+        (0..self.get_values().len().min(page_size))
+            .map(|index| self.entry_lines(index, terminal_columns))
+            .sum::<u16>()
+            + 1
     }
 
     /// Creates the menu representation as a string which will be painted by the painter
-    fn menu_string(&self, _available_lines: u16, use_ansi_coloring: bool) -> String {
+    ///
+    /// The entries that don't fit in the `available_lines` with the banner are left out
+    fn menu_string(&self, available_lines: u16, use_ansi_coloring: bool) -> String {
         let values_before_page = self.pages.iter().take(self.page).sum::<Page>().size;
         match self.pages.get(self.page) {
             Some(page) => {
+                let mut lines_left = available_lines.saturating_sub(1);
                 let lines_string = self
                     .get_values()
                     .iter()
                     .take(page.size)
                     .enumerate()
+                    .take_while(|(index, _)| {
+                        let lines = self.entry_lines(*index, self.terminal_columns);
+                        match lines_left.checked_sub(lines) {
+                            Some(left) => {
+                                lines_left = left;
+                                true
+                            }
+                            None => false,
+                        }
+                    })
                     .map(|(index, suggestion)| {
                         // Final string with colors
-                        let line = &suggestion.value;
+                        let line = match &suggestion.match_indices {
+                            Some(match_indices) if use_ansi_coloring => {
+                                self.style_matches(&suggestion.value, match_indices, index)
+                            }
+                            _ => suggestion.value.clone(),
+                        };
                         let line = if line.lines().count() > self.max_lines as usize {
                             let lines = line.lines().take(self.max_lines as usize).fold(
                                 String::new(),
@@ -565,21 +657,22 @@ impl Menu for ListMenu {
 
                         let row_number = format!("{}: ", index + values_before_page);
 
-                        self.create_string(
-                            &line,
-                            suggestion.description.as_deref(),
-                            index,
-                            &row_number,
-                            use_ansi_coloring,
-                        )
+                        let header = self.source_header(index).map_or(String::new(), |source| {
+                            self.header_string(source, use_ansi_coloring)
+                        });
+
+                        header
+                            + &self.create_string(
+                                &line,
+                                suggestion.description.as_deref(),
+                                index,
+                                &row_number,
+                                use_ansi_coloring,
+                            )
                     })
                     .collect::<String>();
 
-                format!(
-                    "{}{}",
-                    lines_string,
-                    self.banner_message(page, use_ansi_coloring)
-                )
+                format!("{}{}", lines_string, self.banner_message(use_ansi_coloring))
             }
             None => self.no_page_msg(use_ansi_coloring),
         }
@@ -630,6 +723,206 @@ fn count_digits(mut n: usize) -> u16 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Span;
+    use pretty_assertions::assert_eq;
+
+    struct FakeCompleter {
+        completions: Vec<String>,
+    }
+
+    struct FixedCompleter(Vec<Suggestion>);
+
+    impl Completer for FixedCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            self.0.clone()
+        }
+    }
+
+    impl Completer for FakeCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            self.completions
+                .iter()
+                .map(|value| Suggestion {
+                    value: value.clone(),
+                    span: Span { start: 0, end: pos },
+                    ..Suggestion::default()
+                })
+                .collect()
+        }
+    }
+
+    /// Sends the events to a menu listing `values_count` values, returning the page index,
+    /// the row position and the banner after each event
+    fn send(
+        menu: &mut ListMenu,
+        values_count: usize,
+        events: Vec<MenuEvent>,
+    ) -> Vec<(usize, u16, String)> {
+        let mut completer = FakeCompleter {
+            completions: (0..values_count).map(|i| format!("value{i}")).collect(),
+        };
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+
+        events
+            .into_iter()
+            .map(|event| {
+                menu.menu_event(event);
+                menu.update_working_details(&mut editor, &mut completer, &painter);
+                (menu.page, menu.row_position, menu.banner_message(false))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn banner_shows_the_page_and_the_matches() {
+        let mut menu = ListMenu::default();
+
+        let states = send(
+            &mut menu,
+            47,
+            vec![
+                MenuEvent::Activate(false),
+                MenuEvent::NextPage,
+                MenuEvent::PreviousPage,
+            ],
+        );
+
+        assert_eq!(
+            states,
+            vec![
+                (0, 0, "[page 1/5, 47 matches]".to_string()),
+                (1, 0, "[page 2/5, 47 matches]".to_string()),
+                (0, 0, "[page 1/5, 47 matches]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn moving_past_the_page_can_be_disabled() {
+        let moves = || {
+            std::iter::once(MenuEvent::Activate(false))
+                .chain(std::iter::repeat(MenuEvent::MoveDown).take(10))
+                .collect::<Vec<_>>()
+        };
+
+        let mut menu = ListMenu::default();
+        let (page, row, _) = send(&mut menu, 47, moves()).pop().unwrap();
+        assert_eq!((page, row), (1, 0));
+
+        let mut menu = ListMenu::default().with_move_across_pages(false);
+        let (page, row, _) = send(&mut menu, 47, moves()).pop().unwrap();
+        assert_eq!((page, row), (0, 9));
+        let (page, row, _) = send(&mut menu, 47, vec![MenuEvent::MoveUp; 10])
+            .pop()
+            .unwrap();
+        assert_eq!((page, row), (0, 0));
+    }
+
+    #[test]
+    fn paging_keeps_the_selection_on_the_page() {
+        let mut menu = ListMenu::default();
+        let mut events = vec![MenuEvent::Activate(false)];
+        events.extend(std::iter::repeat(MenuEvent::MoveDown).take(8));
+        events.extend(std::iter::repeat(MenuEvent::NextPage).take(4));
+
+        let states = send(&mut menu, 47, events);
+
+        // The last page only has 7 values
+        assert_eq!(
+            states[9..]
+                .iter()
+                .map(|(page, row, _)| (*page, *row))
+                .collect::<Vec<_>>(),
+            vec![(1, 8), (2, 8), (3, 8), (4, 6)]
+        );
+        assert_eq!(menu.get_value().unwrap().value, "value46");
+    }
+
+    #[test]
+    fn menu_string_fits_in_the_available_lines() {
+        let mut menu = ListMenu::default();
+        send(&mut menu, 47, vec![MenuEvent::Activate(false)]);
+
+        assert_eq!(menu.menu_required_lines(80), 11);
+        assert_eq!(menu.menu_string(11, false).lines().count(), 11);
+        assert_eq!(
+            menu.menu_string(4, false).lines().collect::<Vec<_>>(),
+            vec![
+                "0: >VALUE0",
+                "1: value1",
+                "2: value2",
+                "[page 1/5, 47 matches]"
+            ]
+        );
+    }
+
+    #[test]
+    fn source_headers_start_the_groups() {
+        let fake = |values: &[&str]| {
+            Box::new(FakeCompleter {
+                completions: values.iter().map(|value| value.to_string()).collect(),
+            })
+        };
+        let mut completer = crate::MultiCompleter::new()
+            .with_completer("history", fake(&["git push", "git pull"]))
+            .with_completer("commands", fake(&["git"]));
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+
+        let mut menu = ListMenu::default().with_source_headers(true);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        assert_eq!(menu.menu_required_lines(80), 6);
+        assert_eq!(
+            menu.menu_string(10, false).lines().collect::<Vec<_>>(),
+            vec![
+                "[history]",
+                "0: >GIT PUSH",
+                "1: git pull",
+                "[commands]",
+                "2: git",
+                "[page 1/1, 3 matches]"
+            ]
+        );
+    }
+
+    #[test]
+    fn matched_characters_are_styled() {
+        let mut completer = FixedCompleter(vec![Suggestion {
+            value: "git status".to_string(),
+            match_indices: Some(vec![4, 5]),
+            ..Suggestion::default()
+        }]);
+        let mut editor = Editor::default();
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(80, 24);
+
+        let mut menu = ListMenu::default();
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+
+        let colors = &menu.settings.color;
+        let selected_text = colors.selected_text_style.prefix().to_string();
+        assert_eq!(
+            menu.menu_string(10, true).lines().next(),
+            Some(
+                format!(
+                    "0: {selected_text}{selected_text}git {RESET}{}st{RESET}{selected_text}atus{RESET}{RESET}",
+                    colors.selected_match_style.prefix()
+                )
+                .as_str()
+            )
+        );
+        // Without colors, the value is unchanged
+        assert_eq!(
+            menu.menu_string(10, false).lines().next(),
+            Some("0: >GIT STATUS")
+        );
+    }
 
     #[test]
     fn number_of_lines_test() {
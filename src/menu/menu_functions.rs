@@ -1,5 +1,9 @@
 //! Collection of common functions that can be used to create menus
-use crate::{Editor, Suggestion, UndoBehavior};
+use crate::{completion::fuzzy_match, Editor, Span, Suggestion, UndoBehavior};
+use nu_ansi_term::ansi::RESET;
+use std::cmp::Reverse;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 /// Index result obtained from parsing a string with an index marker
 /// For example, the next string:
@@ -270,6 +274,9 @@ pub fn string_difference<'a>(new_string: &'a str, old_string: &str) -> (usize, &
 /// Get the part of the line that should be given as input to the completer, as well
 /// as the index of the end of that piece of text
 ///
+/// Without `only_buffer_difference`, this is the whole buffer, all its lines included, with the
+/// cursor position in it, so the completers see the text after the cursor and on the other lines.
+///
 /// `prev_input` is the text in the buffer when the menu was activated. Needed if only_buffer_difference is true
 pub fn completer_input(
     buffer: &str,
@@ -289,74 +296,289 @@ pub fn completer_input(
             (String::new(), insertion_point)
         }
     } else {
-        // TODO previously, all but the list menu replaced newlines with spaces here
-        // The completers should be adapted to account for this, and tests need to be added
-        (buffer[..insertion_point].to_owned(), insertion_point)
+        (buffer.to_owned(), insertion_point)
     }
 }
 
 /// Helper to accept a completion suggestion and edit the buffer
+///
+/// The value is first transformed by the transformer set with
+/// `Reedline::with_completion_transformer`, if any.
 pub fn replace_in_buffer(value: Option<Suggestion>, editor: &mut Editor) {
     if let Some(Suggestion {
-        mut value,
+        value,
         span,
         append_whitespace,
+        cursor_offset,
         ..
     }) = value
     {
         let start = span.start.min(editor.line_buffer().len());
         let end = span.end.min(editor.line_buffer().len());
+        let mut value = editor.transform_completion(value, start);
+
+        // The span doesn't necessarily end at the cursor, which goes after the replacement
+        // unless the suggestion places it elsewhere
+        let insertion_point = match cursor_offset {
+            Some(offset) => {
+                // The cursor stays within the inserted value
+                let mut index = value.len().saturating_sub(offset.min(0).unsigned_abs());
+                while !value.is_char_boundary(index) {
+                    index -= 1;
+                }
+                start + index
+            }
+            None if append_whitespace => start + value.len() + 1,
+            None => start + value.len(),
+        };
         if append_whitespace {
             value.push(' ');
         }
 
         let mut line_buffer = editor.line_buffer().clone();
         line_buffer.replace_range(start..end, &value);
-        let mut offset = line_buffer.insertion_point();
-        offset = offset.saturating_add(value.len());
-        offset = offset.saturating_sub(end.saturating_sub(start));
-        line_buffer.set_insertion_point(offset);
+        line_buffer.set_insertion_point(insertion_point);
         editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
     }
 }
 
+/// Finds the longest prefix of the suggestions replacing the same span as the first one, if
+/// it adds characters to the `typed` text they replace
+///
+/// The characters are compared ignoring their case. Where the suggestions agree, the prefix
+/// has their case, even over the typed text. Where they only differ in case, the typed
+/// character is kept, and past the typed text the prefix stops as the case is ambiguous.
+/// Nothing is returned if the prefix doesn't extend the typed text, which is never replaced
+/// by another text.
+///
+/// ## Example usage
+/// ```
+/// use reedline::{menu_functions::longest_common_prefix, Span, Suggestion};
+///
+/// let suggestions: Vec<_> = ["Makefile", "makedirs.sh"]
+///     .into_iter()
+///     .map(|value| Suggestion {
+///         value: value.to_string(),
+///         span: Span::new(0, 2),
+///         ..Suggestion::default()
+///     })
+///     .collect();
+///
+/// assert_eq!(longest_common_prefix(&suggestions, "ma"), Some("make".to_string()));
+/// assert_eq!(longest_common_prefix(&suggestions, "make"), None);
+/// ```
+pub fn longest_common_prefix(values: &[Suggestion], typed: &str) -> Option<String> {
+    let first = values.first()?;
+    let mut values: Vec<_> = values
+        .iter()
+        .filter(|suggestion| suggestion.span == first.span)
+        .map(|suggestion| suggestion.value.chars())
+        .collect();
+    let eq_ignore_case = |lhs: char, rhs: char| lhs.to_lowercase().eq(rhs.to_lowercase());
+
+    let mut prefix = String::new();
+    let mut typed_chars = typed.chars();
+    loop {
+        let typed_char = typed_chars.next();
+        let mut chars = values.iter_mut().map(Iterator::next);
+        let Some(Some(c)) = chars.next() else {
+            break;
+        };
+        let others: Vec<_> = chars.collect();
+        if !others
+            .iter()
+            .all(|other| other.map_or(false, |other| eq_ignore_case(other, c)))
+        {
+            break;
+        }
+        let same_case = others.iter().all(|other| *other == Some(c));
+
+        match typed_char {
+            Some(typed_char) if !eq_ignore_case(typed_char, c) => return None,
+            Some(typed_char) if !same_case => prefix.push(typed_char),
+            Some(_) => prefix.push(c),
+            None if same_case => prefix.push(c),
+            None => break,
+        }
+    }
+
+    (prefix.chars().count() > typed.chars().count()).then_some(prefix)
+}
+
 /// Helper for `Menu::can_partially_complete`
+///
+/// Replaces the typed text with the [`longest_common_prefix`] of the suggestions
 pub fn can_partially_complete(values: &[Suggestion], editor: &mut Editor) -> bool {
-    if let (Some(Suggestion { value, span, .. }), Some(index)) = find_common_string(values) {
-        let index = index.min(value.len());
-        let matching = &value[0..index];
+    let Some(Suggestion { span, .. }) = values.first() else {
+        return false;
+    };
+    let end = span.end.min(editor.line_buffer().len());
+    let start = span.start.min(end);
+
+    match longest_common_prefix(values, &editor.get_buffer()[start..end]) {
+        Some(prefix) => {
+            let mut line_buffer = editor.line_buffer().clone();
+            line_buffer.replace_range(start..end, &prefix);
+            line_buffer.set_insertion_point(start + prefix.len());
+            editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+
+            true
+        }
+        None => false,
+    }
+}
 
-        // make sure that the partial completion does not overwrite user entered input
-        let extends_input = matching.starts_with(&editor.get_buffer()[span.start..span.end]);
+/// Helper to fit a text, such as a suggestion description, in a single line of `max_width` columns
+///
+/// New lines are replaced with spaces, and the end of a text too wide is replaced with an ellipsis
+pub fn truncate_with_ellipsis(text: &str, max_width: usize) -> String {
+    let text = text.replace("\r\n", " ").replace('\n', " ");
 
-        if !matching.is_empty() && extends_input {
-            let mut line_buffer = editor.line_buffer().clone();
-            line_buffer.replace_range(span.start..span.end, matching);
+    if text.width() <= max_width {
+        return text;
+    }
+
+    let Some(max_width) = max_width.checked_sub(1) else {
+        return String::new();
+    };
+
+    let mut width = 0;
+    let mut truncated = text
+        .graphemes(true)
+        .take_while(|grapheme| {
+            width += grapheme.width();
+            width <= max_width
+        })
+        .collect::<String>();
 
-            let offset = if matching.len() < (span.end - span.start) {
-                line_buffer
-                    .insertion_point()
-                    .saturating_sub((span.end - span.start) - matching.len())
+    truncated.push('…');
+    truncated
+}
+
+/// Helper to style the characters of `value` found at `match_indices`, see [`Suggestion::match_indices`]
+///
+/// Each run of matched characters is preceded by `match_prefix`, and each run of other characters
+/// by `text_prefix`. Every run is followed by a reset of the style.
+pub fn style_match_indices(
+    value: &str,
+    match_indices: &[usize],
+    match_prefix: &str,
+    text_prefix: &str,
+) -> String {
+    let mut styled = String::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+
+    for (index, c) in value.chars().enumerate() {
+        let matched = match_indices.contains(&index);
+
+        if matched != run_matched && !run.is_empty() {
+            let prefix = if run_matched {
+                match_prefix
             } else {
-                line_buffer.insertion_point() + matching.len() - (span.end - span.start)
+                text_prefix
             };
+            styled.push_str(&format!("{prefix}{run}{RESET}"));
+            run.clear();
+        }
 
-            line_buffer.set_insertion_point(offset);
-            editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        run_matched = matched;
+        run.push(c);
+    }
 
-            true
+    if !run.is_empty() {
+        let prefix = if run_matched {
+            match_prefix
         } else {
-            false
+            text_prefix
+        };
+        styled.push_str(&format!("{prefix}{run}{RESET}"));
+    }
+
+    styled
+}
+
+/// Suggestions kept by a menu while it is open, so they can be filtered as the user
+/// keeps typing instead of querying the completer again
+#[derive(Default)]
+pub(crate) struct IncrementalFilter {
+    origin: Option<FilterOrigin>,
+}
+
+/// Buffer and insertion point for which the suggestions were computed
+struct FilterOrigin {
+    buffer: String,
+    insertion_point: usize,
+    values: Vec<Suggestion>,
+}
+
+impl IncrementalFilter {
+    /// Keeps the suggestions computed for the buffer and insertion point
+    pub(crate) fn store(&mut self, buffer: &str, insertion_point: usize, values: &[Suggestion]) {
+        self.origin = Some(FilterOrigin {
+            buffer: buffer.to_string(),
+            insertion_point,
+            values: values.to_vec(),
+        });
+    }
+
+    /// Forgets the kept suggestions
+    pub(crate) fn clear(&mut self) {
+        self.origin = None;
+    }
+
+    /// Fuzzy filters the kept suggestions against the text typed after their insertion point
+    ///
+    /// The matching suggestions are sorted by score, and their span is extended over the typed text.
+    /// Deleting typed text brings back the suggestions it filtered out.
+    ///
+    /// Returns `None` if no suggestion is kept, or if the buffer was edited in another way than by
+    /// typing a word at the insertion point: the completer then has to be queried again.
+    pub(crate) fn filter(&self, buffer: &str, insertion_point: usize) -> Option<Vec<Suggestion>> {
+        let origin = self.origin.as_ref()?;
+        let typed = buffer.get(origin.insertion_point..insertion_point)?;
+
+        let unchanged = buffer[..origin.insertion_point] == origin.buffer[..origin.insertion_point]
+            && buffer[insertion_point..] == origin.buffer[origin.insertion_point..];
+
+        if !unchanged || typed.contains(char::is_whitespace) {
+            return None;
         }
-    } else {
-        false
+
+        if typed.is_empty() {
+            return Some(origin.values.clone());
+        }
+
+        let mut matches = origin
+            .values
+            .iter()
+            .filter_map(|suggestion| {
+                fuzzy_match(typed, &suggestion.value)
+                    .map(|(score, indices)| (score, indices, suggestion))
+            })
+            .collect::<Vec<_>>();
+
+        // The sort is stable, so equal scores keep the order of the completer
+        matches.sort_by_key(|(score, _, _)| Reverse(*score));
+
+        let filtered = matches
+            .into_iter()
+            .map(|(score, indices, suggestion)| Suggestion {
+                span: Span::new(suggestion.span.start, suggestion.span.end + typed.len()),
+                match_indices: Some(indices),
+                score: Some(score),
+                ..suggestion.clone()
+            })
+            .collect();
+
+        Some(filtered)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{EditCommand, LineBuffer, Span};
+    use crate::{EditCommand, LineBuffer, QuoteContext, Span};
     use rstest::rstest;
 
     #[test]
@@ -611,6 +833,12 @@ mod tests {
                 extra: None,
                 span: Span::new(0, s.len()),
                 append_whitespace: false,
+                match_indices: None,
+                score: None,
+                preview: None,
+                cursor_offset: None,
+                source: None,
+                continue_completion: false,
             })
             .collect();
         let res = find_common_string(&input);
@@ -631,6 +859,12 @@ mod tests {
                 extra: None,
                 span: Span::new(0, s.len()),
                 append_whitespace: false,
+                match_indices: None,
+                score: None,
+                preview: None,
+                cursor_offset: None,
+                source: None,
+                continue_completion: false,
             })
             .collect();
         let res = find_common_string(&input);
@@ -638,10 +872,99 @@ mod tests {
         assert!(matches!(res, (Some(elem), Some(6)) if elem == &input[0]));
     }
 
+    #[rstest]
+    #[case("cat my", "cat my\\ file ")]
+    #[case("cat 'my", "cat 'my file' ")]
+    fn replaced_value_is_transformed(#[case] buffer: &str, #[case] expected: &str) {
+        let mut editor = Editor::default();
+        editor.set_buffer(buffer.to_string(), UndoBehavior::CreateUndoPoint);
+        editor.set_completion_transformer(Some(Box::new(|value, context| match context {
+            QuoteContext::Unquoted => value.replace(' ', "\\ "),
+            QuoteContext::SingleQuoted => format!("{value}'"),
+            QuoteContext::DoubleQuoted => format!("{value}\""),
+        })));
+        let start = buffer.len() - 2;
+
+        replace_in_buffer(
+            Some(Suggestion {
+                value: "my file".to_string(),
+                span: Span::new(start, buffer.len()),
+                append_whitespace: true,
+                ..Suggestion::default()
+            }),
+            &mut editor,
+        );
+
+        assert_eq!(editor.get_buffer(), expected);
+        assert_eq!(editor.insertion_point(), expected.len());
+    }
+
+    #[rstest]
+    #[case(&["build.rs", "build-all.sh"], "", Some("build"))]
+    #[case(&["build.rs", "build-all.sh"], "bui", Some("build"))]
+    #[case(&["build.rs", "build-all.sh"], "build", None)]
+    #[case(&["build.rs", "build"], "b", Some("build"))]
+    #[case(&["build.rs", "prepare-build.sh"], "", None)]
+    #[case(&["build.rs", "build-all.sh"], "all", None)]
+    // The case of the suggestions is used where they agree
+    #[case(&["Makefile", "Makedirs"], "ma", Some("Make"))]
+    // The typed character where they don't, and not past the typed text
+    #[case(&["Makefile", "makedirs"], "ma", Some("make"))]
+    #[case(&["Makefile", "makedirs"], "", None)]
+    #[case(&["Été", "étés"], "é", Some("été"))]
+    // Only changing the case doesn't complete anything
+    #[case(&["Foo", "FOX"], "fo", None)]
+    fn test_longest_common_prefix(
+        #[case] values: &[&str],
+        #[case] typed: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let suggestions: Vec<_> = values
+            .iter()
+            .map(|value| Suggestion {
+                value: value.to_string(),
+                span: Span::new(0, typed.len()),
+                ..Suggestion::default()
+            })
+            .collect();
+
+        assert_eq!(
+            longest_common_prefix(&suggestions, typed),
+            expected.map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn longest_common_prefix_ignores_other_spans() {
+        let suggestions = vec![
+            Suggestion {
+                value: "build.rs".to_string(),
+                span: Span::new(4, 6),
+                ..Suggestion::default()
+            },
+            Suggestion {
+                value: "cargo".to_string(),
+                span: Span::new(0, 6),
+                ..Suggestion::default()
+            },
+            Suggestion {
+                value: "build-all.sh".to_string(),
+                span: Span::new(4, 6),
+                ..Suggestion::default()
+            },
+        ];
+
+        assert_eq!(
+            longest_common_prefix(&suggestions, "bu"),
+            Some("build".to_string())
+        );
+    }
+
     #[rstest]
     #[case("foobar", 6, None, false, "foobar", 6)]
-    #[case("foo\r\nbar", 5, None, false, "foo\r\n", 5)]
-    #[case("foo\nbar", 4, None, false, "foo\n", 4)]
+    #[case("foobar", 3, None, false, "foobar", 3)]
+    #[case("foo\r\nbar", 5, None, false, "foo\r\nbar", 5)]
+    #[case("foo\nbar", 4, None, false, "foo\nbar", 4)]
     #[case("foobar", 6, None, true, "", 6)]
     #[case("foobar", 3, Some("foobar"), true, "", 3)]
     #[case("foobar", 6, Some("foo"), true, "bar", 6)]
@@ -664,6 +987,8 @@ mod tests {
     #[case("foobar baz", 6, "foobleh baz", 7, "bleh", 3, 6)]
     #[case("foobar baz", 6, "foo baz", 3, "", 3, 6)]
     #[case("foobar baz", 10, "foobleh", 7, "bleh", 3, 1000)]
+    #[case("echo $VA rest", 7, "echo $VAR rest", 9, "$VAR", 5, 8)]
+    #[case("echo $VA rest", 13, "echo $VAR rest", 9, "$VAR", 5, 8)]
     fn test_replace_in_buffer(
         #[case] orig_buffer: &str,
         #[case] orig_insertion_point: usize,
@@ -686,6 +1011,12 @@ mod tests {
                 extra: None,
                 span: Span::new(start, end),
                 append_whitespace: false,
+                match_indices: None,
+                score: None,
+                preview: None,
+                cursor_offset: None,
+                source: None,
+                continue_completion: false,
             }),
             &mut editor,
         );
@@ -696,4 +1027,121 @@ mod tests {
         assert_eq!(orig_buffer, editor.get_buffer());
         assert_eq!(orig_insertion_point, editor.insertion_point());
     }
+
+    #[rstest]
+    #[case("print()", false, Some(-1), "print()", 6)]
+    #[case("print()", true, Some(-1), "print() ", 6)]
+    #[case("print()", false, Some(-100), "print()", 0)]
+    #[case("print()", false, Some(3), "print()", 7)]
+    #[case("é()", false, Some(-3), "é()", 0)]
+    #[case("print", true, None, "print ", 6)]
+    fn test_replace_in_buffer_with_cursor_offset(
+        #[case] value: &str,
+        #[case] append_whitespace: bool,
+        #[case] cursor_offset: Option<isize>,
+        #[case] new_buffer: &str,
+        #[case] new_insertion_point: usize,
+    ) {
+        let mut editor = Editor::default();
+        editor.set_buffer("pr".to_string(), UndoBehavior::CreateUndoPoint);
+        replace_in_buffer(
+            Some(Suggestion {
+                value: value.to_string(),
+                span: Span::new(0, 2),
+                append_whitespace,
+                cursor_offset,
+                ..Default::default()
+            }),
+            &mut editor,
+        );
+
+        assert_eq!(editor.get_buffer(), new_buffer);
+        assert_eq!(editor.line_buffer().insertion_point(), new_insertion_point);
+    }
+
+    #[rstest]
+    #[case("Show the working tree status", 40, "Show the working tree status")]
+    #[case("Show the working tree status", 8, "Show th…")]
+    #[case("two\nlines", 20, "two lines")]
+    #[case("ｗｉｄｅ", 5, "ｗｉ…")]
+    #[case("status", 1, "…")]
+    #[case("status", 0, "")]
+    fn test_truncate_with_ellipsis(
+        #[case] text: &str,
+        #[case] max_width: usize,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(truncate_with_ellipsis(text, max_width), expected);
+    }
+
+    #[test]
+    fn test_style_match_indices() {
+        assert_eq!(
+            style_match_indices("status", &[0, 1, 4], "<", "-"),
+            format!("<st{RESET}-at{RESET}<u{RESET}-s{RESET}")
+        );
+        assert_eq!(
+            style_match_indices("git", &[], "<", "-"),
+            format!("-git{RESET}")
+        );
+    }
+
+    fn filter_suggestion(value: &str) -> Suggestion {
+        Suggestion {
+            value: value.to_string(),
+            span: Span::new(4, 6),
+            ..Suggestion::default()
+        }
+    }
+
+    fn filtered_values(filter: &IncrementalFilter, buffer: &str) -> Option<Vec<String>> {
+        filter.filter(buffer, buffer.len()).map(|values| {
+            values
+                .into_iter()
+                .map(|suggestion| suggestion.value)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_incremental_filter() {
+        let mut filter = IncrementalFilter::default();
+        assert_eq!(filtered_values(&filter, "git ch"), None);
+
+        filter.store(
+            "git ch",
+            6,
+            &[
+                filter_suggestion("checkout"),
+                filter_suggestion("cherry-pick"),
+                filter_suggestion("cherry"),
+            ],
+        );
+
+        assert_eq!(
+            filtered_values(&filter, "git ch"),
+            Some(vec![
+                "checkout".to_string(),
+                "cherry-pick".to_string(),
+                "cherry".to_string()
+            ])
+        );
+
+        let filtered = filter.filter("git chp", 7).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].value, "cherry-pick");
+        assert_eq!(filtered[0].span, Span::new(4, 7));
+        assert_eq!(filtered[0].match_indices, Some(vec![7]));
+
+        // Deleting the typed text brings the suggestions back
+        assert_eq!(filtered_values(&filter, "git ch").map(|v| v.len()), Some(3));
+
+        // Other edits require querying the completer again
+        assert_eq!(filtered_values(&filter, "git c"), None);
+        assert_eq!(filtered_values(&filter, "gt chp"), None);
+        assert_eq!(filtered_values(&filter, "git chp "), None);
+
+        filter.clear();
+        assert_eq!(filtered_values(&filter, "git ch"), None);
+    }
 }
@@ -7,6 +7,7 @@ pub mod menu_functions;
 use crate::core_editor::Editor;
 use crate::History;
 use crate::{completion::history::HistoryCompleter, painting::Painter, Completer, Suggestion};
+pub use columnar_menu::Alignment;
 pub use columnar_menu::ColumnarMenu;
 pub use description_menu::DescriptionMenu;
 pub use ide_menu::DescriptionMode;
@@ -144,6 +145,31 @@ pub trait Menu: Send {
 
     /// Gets cached values from menu that will be displayed
     fn get_values(&self) -> &[Suggestion];
+
+    /// The selected value, which [`Menu::replace_in_buffer`] inserts
+    ///
+    /// Lets [`crate::ReedlineEvent::AcceptAndContinue`] know whether to complete again after
+    /// it. Without it, the values are accepted like with `Enter`.
+    fn get_selected_value(&self) -> Option<Suggestion> {
+        None
+    }
+
+    /// Whether [`crate::ReedlineEvent::MenuNext`] accepts the selected value rather than
+    /// selecting the next one, e.g. for the last value of a menu built with
+    /// [`MenuBuilder::with_tab_accepts_on_end`]
+    fn accepts_on_next(&self) -> bool {
+        false
+    }
+
+    /// Selects the value painted at `row` and `column` of the [`Menu::menu_string`] made for
+    /// `available_lines`, returning whether there is one
+    ///
+    /// Lets a [`crate::ReedlineEvent::MouseClick`] select a value. Without it, clicking the
+    /// menu does nothing.
+    fn select_at(&mut self, _row: u16, _column: u16, _available_lines: u16) -> bool {
+        false
+    }
+
     /// Sets the position of the cursor (currently only required by the IDE menu)
     fn set_cursor_pos(&mut self, _pos: (u16, u16)) {
         // empty implementation to make it optional
@@ -160,6 +186,12 @@ pub struct MenuSettings {
     /// Calls the completer using only the line buffer difference difference
     /// after the menu was activated
     only_buffer_difference: bool,
+    /// Filters the suggestions of the menu as the user types instead of calling the completer again
+    incremental_filter: bool,
+    /// Moves the selection from the last value to the first one and back
+    wrap: bool,
+    /// Accepts the last value when moving to the next one
+    tab_accepts_on_end: bool,
 }
 
 impl Default for MenuSettings {
@@ -169,6 +201,9 @@ impl Default for MenuSettings {
             color: MenuTextStyle::default(),
             marker: "| ".to_string(),
             only_buffer_difference: false,
+            incremental_filter: false,
+            wrap: true,
+            tab_accepts_on_end: false,
         }
     }
 }
@@ -201,6 +236,27 @@ impl MenuSettings {
         self.only_buffer_difference = only_buffer_difference;
         self
     }
+
+    /// MenuSettings builder with incremental_filter
+    #[must_use]
+    pub fn with_incremental_filter(mut self, enable_incremental_filter: bool) -> Self {
+        self.incremental_filter = enable_incremental_filter;
+        self
+    }
+
+    /// MenuSettings builder with wrap
+    #[must_use]
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// MenuSettings builder with tab_accepts_on_end
+    #[must_use]
+    pub fn with_tab_accepts_on_end(mut self, tab_accepts_on_end: bool) -> Self {
+        self.tab_accepts_on_end = tab_accepts_on_end;
+        self
+    }
 }
 
 /// Common builder for all menus
@@ -268,6 +324,46 @@ pub trait MenuBuilder: Menu + Sized {
         self.settings_mut().only_buffer_difference = only_buffer_difference;
         self
     }
+
+    /// Menu builder with new value for incremental_filter
+    ///
+    /// When enabled, the suggestions obtained when the menu is activated are kept while it is open.
+    /// The text typed afterwards fuzzy filters them, highlighting the matched characters, instead of
+    /// calling the completer again. The completer is only called again once the line is edited
+    /// another way, e.g. by typing a space or deleting text from before the menu was activated.
+    ///
+    /// The [`ListMenu`] queries its completer page per page, and doesn't support this mode.
+    #[must_use]
+    fn with_incremental_filter(mut self, enable_incremental_filter: bool) -> Self {
+        self.settings_mut().incremental_filter = enable_incremental_filter;
+        self
+    }
+
+    /// Menu builder with new value for wrap
+    ///
+    /// When enabled (the default), moving to the next element from the last one selects the
+    /// first one, e.g. with `Tab`, and moving to the previous element from the first one selects
+    /// the last one, e.g. with `Shift-Tab`. Otherwise the selection stops at either end.
+    ///
+    /// The [`ListMenu`] moves across its pages instead, and ignores this setting.
+    #[must_use]
+    fn with_wrap(mut self, wrap: bool) -> Self {
+        self.settings_mut().wrap = wrap;
+        self
+    }
+
+    /// Menu builder with new value for tab_accepts_on_end
+    ///
+    /// When enabled, moving to the next element with [`crate::ReedlineEvent::MenuNext`] (`Tab`
+    /// by default) once the last value is selected accepts it and closes the menu, like
+    /// `Enter`, instead of wrapping around or staying on it.
+    ///
+    /// The [`ListMenu`] moves across its pages instead, and ignores this setting.
+    #[must_use]
+    fn with_tab_accepts_on_end(mut self, tab_accepts_on_end: bool) -> Self {
+        self.settings_mut().tab_accepts_on_end = tab_accepts_on_end;
+        self
+    }
 }
 
 /// Allowed menus in Reedline
@@ -345,6 +441,30 @@ impl ReedlineMenu {
         }
     }
 
+    /// Whether the completer of the menu is computing completions in the background
+    pub(crate) fn completions_pending(&self, completer: &dyn Completer) -> bool {
+        match self {
+            Self::EngineCompleter(_) => completer.is_pending(),
+            Self::HistoryMenu(_) => false,
+            Self::WithCompleter {
+                completer: own_completer,
+                ..
+            } => own_completer.is_pending(),
+        }
+    }
+
+    /// Makes progress on the completions computed in the background, returning `true` once they are available
+    pub(crate) fn poll_pending_completions(&mut self, completer: &mut dyn Completer) -> bool {
+        match self {
+            Self::EngineCompleter(_) => completer.poll_pending(),
+            Self::HistoryMenu(_) => false,
+            Self::WithCompleter {
+                completer: own_completer,
+                ..
+            } => own_completer.poll_pending(),
+        }
+    }
+
     pub(crate) fn update_working_details(
         &mut self,
         editor: &mut Editor,
@@ -446,7 +566,17 @@ impl Menu for ReedlineMenu {
     }
 
     fn replace_in_buffer(&self, editor: &mut Editor) {
-        self.as_ref().replace_in_buffer(editor);
+        match self {
+            // The history entries are whole command lines, inserted as is
+            Self::HistoryMenu(menu) => {
+                let transformer = editor.take_completion_transformer();
+                menu.replace_in_buffer(editor);
+                editor.set_completion_transformer(transformer);
+            }
+            Self::EngineCompleter(menu) | Self::WithCompleter { menu, .. } => {
+                menu.replace_in_buffer(editor);
+            }
+        }
     }
 
     fn menu_required_lines(&self, terminal_columns: u16) -> u16 {
@@ -466,6 +596,18 @@ impl Menu for ReedlineMenu {
         self.as_ref().get_values()
     }
 
+    fn get_selected_value(&self) -> Option<Suggestion> {
+        self.as_ref().get_selected_value()
+    }
+
+    fn accepts_on_next(&self) -> bool {
+        self.as_ref().accepts_on_next()
+    }
+
+    fn select_at(&mut self, row: u16, column: u16, available_lines: u16) -> bool {
+        self.as_mut().select_at(row, column, available_lines)
+    }
+
     fn set_cursor_pos(&mut self, pos: (u16, u16)) {
         self.as_mut().set_cursor_pos(pos);
     }
@@ -6,4 +6,4 @@ mod utils;
 pub use painter::{Painter, PainterSuspendedState};
 pub(crate) use prompt_lines::PromptLines;
 pub use styled_text::StyledText;
-pub(crate) use utils::estimate_single_line_wraps;
+pub(crate) use utils::{estimate_single_line_wraps, strip_ansi};
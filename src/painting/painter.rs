@@ -1,20 +1,23 @@
-use crate::{CursorConfig, PromptEditMode, PromptViMode};
+use crate::{CursorConfig, LineOverflow, PromptEditMode, PromptViMode};
 
 use {
-    super::utils::{coerce_crlf, line_width},
+    super::utils::{coerce_crlf, estimate_required_lines, line_width, strip_ansi},
     crate::{
+        core_editor::VisualLayout,
         menu::{Menu, ReedlineMenu},
         painting::PromptLines,
         Prompt,
     },
     crossterm::{
-        cursor::{self, MoveTo, RestorePosition, SavePosition},
+        cursor::{self, MoveTo, RestorePosition, SavePosition, SetCursorStyle},
         style::{Attribute, Print, ResetColor, SetAttribute, SetForegroundColor},
         terminal::{self, Clear, ClearType},
         QueueableCommand,
     },
+    std::borrow::Cow,
     std::io::{Result, Write},
-    std::ops::RangeInclusive,
+    std::ops::{Range, RangeInclusive},
+    unicode_width::UnicodeWidthChar,
 };
 #[cfg(feature = "external_printer")]
 use {crate::LineBuffer, crossterm::cursor::MoveUp};
@@ -47,6 +50,106 @@ fn skip_buffer_lines(string: &str, skip: usize, offset: Option<usize>) -> &str {
     string[index..limit].trim_end_matches('\n')
 }
 
+/// Row of the prompt start for the cursor at `cursor_row` after a resize to `width` columns
+///
+/// The terminal reflows the painted lines to the new width, the prompt starting as many rows
+/// above the cursor as `text_before_cursor` needs to wrap them.
+fn prompt_start_row_after_resize(cursor_row: u16, text_before_cursor: &str, width: u16) -> u16 {
+    let distance = estimate_required_lines(text_before_cursor, width.max(1)).saturating_sub(1);
+    cursor_row.saturating_sub(distance as u16)
+}
+
+/// Rows above the prompt starting at `prompt_start_row` to paint a menu of `menu_lines` in
+///
+/// The menu goes above the prompt when the rows below the `buffer_lines` of the prompt and
+/// buffer can't fit it and the rows above fit more of it, so that opening it doesn't scroll
+/// the prompt up.
+fn menu_rows_above(
+    prompt_start_row: u16,
+    screen_height: u16,
+    buffer_lines: u16,
+    menu_lines: u16,
+    min_rows: u16,
+) -> Option<u16> {
+    let rows_below = screen_height.saturating_sub(prompt_start_row + buffer_lines);
+    if menu_lines <= rows_below {
+        return None;
+    }
+
+    let rows_above = prompt_start_row.min(menu_lines);
+    (rows_above > rows_below && rows_above >= min_rows).then_some(rows_above)
+}
+
+/// Columns kept visible on either side of the cursor when scrolling the lines horizontally
+const HORIZONTAL_SCROLL_MARGIN: usize = 8;
+
+/// The visible columns of a `line` starting at `column`: its first `fixed` columns, then the
+/// `width` ones from `start`
+///
+/// The ANSI escape sequences are all kept, for the visible text to keep its style. A wide
+/// character cut by an end of the visible columns is replaced by spaces.
+fn visible_columns(line: &str, column: usize, fixed: usize, start: usize, width: usize) -> String {
+    let is_visible = |column: usize| column < fixed || (start..start + width).contains(&column);
+
+    let mut visible = String::new();
+    let mut column = column;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            visible.push(c);
+            // A control sequence, e.g. a color, ends with a character in the `@`..=`~` range
+            if let Some(next) = chars.next() {
+                visible.push(next);
+                if next == '[' {
+                    for c in chars.by_ref() {
+                        visible.push(c);
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            continue;
+        }
+
+        let char_width = c.width().unwrap_or(0);
+        let columns = column..column + char_width;
+        if columns.clone().all(is_visible) && (char_width > 0 || is_visible(column)) {
+            visible.push(c);
+        } else {
+            visible.extend(
+                columns
+                    .clone()
+                    .filter(|column| is_visible(*column))
+                    .map(|_| ' '),
+            );
+        }
+        column = columns.end;
+    }
+    visible
+}
+
+/// Queues the `hint` painted after the buffer, isolated from the styles around it
+///
+/// The attributes and colors are reset before the hint, for the highlighting of the buffer not
+/// to bleed into it, and after it, for its style or an escape sequence it leaves open not to
+/// leak into the lines painted next, like the prompt of the next line.
+fn queue_hint(out: &mut impl Write, hint: &str, use_ansi_coloring: bool) -> Result<()> {
+    if hint.is_empty() {
+        return Ok(());
+    }
+    if !use_ansi_coloring {
+        out.queue(Print(hint))?;
+        return Ok(());
+    }
+    out.queue(SetAttribute(Attribute::Reset))?
+        .queue(ResetColor)?
+        .queue(Print(hint))?
+        .queue(SetAttribute(Attribute::Reset))?
+        .queue(ResetColor)?;
+    Ok(())
+}
+
 /// the type used by crossterm operations
 pub type W = std::io::BufWriter<std::io::Stderr>;
 
@@ -95,6 +198,20 @@ pub struct Painter {
     last_required_lines: u16,
     large_buffer: bool,
     after_cursor_lines: Option<String>,
+    // Whether the cursor shape was changed from the one of the user
+    cursor_shape_changed: bool,
+    // Prompt and buffer painted before the cursor, to find the prompt again after a resize
+    text_before_cursor: String,
+    // Rows right above the prompt where the menu was painted, to clear them afterwards
+    menu_above_rows: u16,
+    line_overflow: LineOverflow,
+    // Columns of the buffer lines scrolled out on the left, with `LineOverflow::Scroll`
+    horizontal_offset: usize,
+    // Row of the screen the buffer was last painted from, when all of it is on the screen and
+    // its lines wrap
+    buffer_start_row: Option<u16>,
+    // Rows of the screen the menu was last painted in, and the lines it was given
+    menu_rows: Option<(Range<u16>, u16)>,
 }
 
 impl Painter {
@@ -106,9 +223,119 @@ impl Painter {
             last_required_lines: 0,
             large_buffer: false,
             after_cursor_lines: None,
+            cursor_shape_changed: false,
+            text_before_cursor: String::new(),
+            menu_above_rows: 0,
+            line_overflow: LineOverflow::default(),
+            horizontal_offset: 0,
+            buffer_start_row: None,
+            menu_rows: None,
         }
     }
 
+    /// Sets how the lines wider than the terminal are painted
+    pub(crate) fn set_line_overflow(&mut self, line_overflow: LineOverflow) {
+        self.line_overflow = line_overflow;
+        self.horizontal_offset = 0;
+    }
+
+    /// With [`LineOverflow::Scroll`], cuts the lines of the buffer and the hint to the screen
+    /// width, scrolled horizontally to keep the cursor visible
+    ///
+    /// All the lines are scrolled by the same offset, which only changes when the cursor gets
+    /// closer to an edge of the screen than [`HORIZONTAL_SCROLL_MARGIN`] columns.
+    pub(crate) fn scroll_horizontally(&mut self, prompt: &dyn Prompt, lines: &mut PromptLines) {
+        if self.line_overflow != LineOverflow::Scroll {
+            return;
+        }
+
+        let screen_width = (self.screen_width() as usize).max(1);
+        let prompt_str = lines.prompt_str_left.to_string() + &lines.prompt_indicator;
+        let prompt_width = line_width(prompt_str.rsplit('\n').next().unwrap_or_default());
+        // Columns of the indicator of each line, which is part of the buffer text but for the
+        // first line. The lines of a history search match have none.
+        let buffer_lines: Vec<_> = (lines.before_cursor.to_string() + &lines.after_cursor)
+            .split("\r\n")
+            .map(strip_ansi)
+            .collect();
+        let indicator_width = |index: usize| {
+            let indicator = strip_ansi(&prompt.render_prompt_multiline_indicator_for_line(index));
+            match buffer_lines.get(index) {
+                Some(line) if index > 0 && line.starts_with(&indicator) => line_width(&indicator),
+                _ => 0,
+            }
+        };
+        let text_width = |index: usize| {
+            let prefix_width = match index {
+                0 => prompt_width % screen_width,
+                index => indicator_width(index),
+            };
+            screen_width.saturating_sub(prefix_width).max(1)
+        };
+
+        let before_cursor: Vec<_> = lines.before_cursor.split("\r\n").collect();
+        let after_cursor: Vec<_> = lines.after_cursor.split("\r\n").collect();
+        let cursor_index = before_cursor.len() - 1;
+        let before_width = line_width(before_cursor[cursor_index]);
+        let after_width = line_width(after_cursor[0]);
+
+        // The cursor column in the text of its line
+        let cursor_column = before_width - indicator_width(cursor_index);
+        let width = text_width(cursor_index);
+        let margin = HORIZONTAL_SCROLL_MARGIN.min(width / 3);
+        let mut offset = self.horizontal_offset;
+        if cursor_column < offset + margin {
+            offset = cursor_column.saturating_sub(margin);
+        } else if cursor_column + margin >= offset + width {
+            offset = cursor_column + margin + 1 - width;
+        }
+        // Nothing scrolled out when the line fits
+        let line_end = before_width + after_width - indicator_width(cursor_index);
+        offset = offset.min((line_end + 1).saturating_sub(width));
+        self.horizontal_offset = offset;
+
+        let scroll = |line: &str, column: usize, index: usize| {
+            let fixed = indicator_width(index);
+            visible_columns(line, column, fixed, fixed + offset, text_width(index))
+        };
+
+        let last_index = cursor_index + after_cursor.len() - 1;
+        let mut last_column = line_width(after_cursor[after_cursor.len() - 1]);
+        if after_cursor.len() == 1 {
+            last_column += before_width;
+        }
+
+        let scrolled_before: Vec<_> = before_cursor
+            .iter()
+            .enumerate()
+            .map(|(index, line)| scroll(line, 0, index))
+            .collect();
+        let scrolled_after: Vec<_> = after_cursor
+            .iter()
+            .enumerate()
+            .map(|(index, line)| match index {
+                // The text after the cursor continues its line
+                0 => scroll(line, before_width, cursor_index),
+                index => scroll(line, 0, cursor_index + index),
+            })
+            .collect();
+
+        // The hint continues the last line, its next lines having no indicator
+        let scrolled_hint: Vec<_> = lines
+            .hint
+            .split("\r\n")
+            .enumerate()
+            .map(|(index, line)| match index {
+                0 => scroll(line, last_column, last_index),
+                _ => visible_columns(line, 0, 0, offset, screen_width),
+            })
+            .collect();
+
+        lines.before_cursor = Cow::Owned(scrolled_before.join("\r\n"));
+        lines.after_cursor = Cow::Owned(scrolled_after.join("\r\n"));
+        lines.hint = Cow::Owned(scrolled_hint.join("\r\n"));
+    }
+
     /// Height of the current terminal window
     pub fn screen_height(&self) -> u16 {
         self.terminal_size.1
@@ -119,6 +346,41 @@ impl Painter {
         self.terminal_size.0
     }
 
+    /// Where the `line_count` lines of the buffer painted after the prompt wrap on the screen
+    pub(crate) fn visual_layout(
+        &self,
+        prompt: &dyn Prompt,
+        lines: &PromptLines,
+        line_count: usize,
+    ) -> VisualLayout {
+        if self.line_overflow == LineOverflow::Scroll {
+            return VisualLayout::default();
+        }
+
+        let prompt_str = format!("{}{}", lines.prompt_str_left, lines.prompt_indicator);
+        let mut start_columns = vec![line_width(prompt_str.lines().last().unwrap_or_default())];
+        start_columns.extend((1..line_count).map(|line_index| {
+            line_width(&prompt.render_prompt_multiline_indicator_for_line(line_index))
+        }));
+        VisualLayout::new(self.screen_width().into(), start_columns)
+    }
+
+    /// Row of the painted buffer that `row` of the screen is, counted from the row its first
+    /// line starts on
+    ///
+    /// `None` above the buffer, or when it isn't all painted as wrapped lines from a known row.
+    pub(crate) fn buffer_row_at(&self, row: u16) -> Option<usize> {
+        row.checked_sub(self.buffer_start_row?).map(usize::from)
+    }
+
+    /// Row of the painted menu that `row` of the screen is, and the lines available to the
+    /// menu when it was painted, `None` outside of the menu
+    pub(crate) fn menu_row_at(&self, row: u16) -> Option<(u16, u16)> {
+        let (rows, available_lines) = self.menu_rows.as_ref()?;
+        rows.contains(&row)
+            .then_some((row - rows.start, *available_lines))
+    }
+
     /// Returns the available lines from the prompt down
     pub fn remaining_lines(&self) -> u16 {
         self.screen_height().saturating_sub(self.prompt_start_row)
@@ -171,6 +433,8 @@ impl Painter {
                 }
             }
         };
+        self.menu_above_rows = 0;
+        self.horizontal_offset = 0;
         Ok(())
     }
 
@@ -183,6 +447,10 @@ impl Painter {
     ///
     /// Note. The `ScrollUp` operation in `crossterm` deletes lines from the top of
     /// the screen.
+    ///
+    /// A menu that doesn't fit below the buffer is painted right above the prompt when
+    /// there is more room there (see [`menu_rows_above`]), keeping the prompt in place.
+    /// The menu keeps its layout, so moving up and down in it still follows the screen.
     pub(crate) fn repaint_buffer(
         &mut self,
         prompt: &dyn Prompt,
@@ -194,12 +462,39 @@ impl Painter {
     ) -> Result<()> {
         self.stdout.queue(cursor::Hide)?;
 
+        // The prompt, highlighter or hinter may style their text regardless of the setting
+        let plain_lines;
+        let lines = if use_ansi_coloring {
+            lines
+        } else {
+            plain_lines = lines.without_ansi();
+            &plain_lines
+        };
+
         let screen_width = self.screen_width();
         let screen_height = self.screen_height();
 
+        self.clear_menu_above()?;
+        self.buffer_start_row = None;
+        self.menu_rows = None;
+
         // Lines and distance parameters
         let remaining_lines = self.remaining_lines();
-        let required_lines = lines.required_lines(screen_width, menu);
+        let buffer_lines = lines.required_lines(screen_width, None);
+        let menu_above = menu.and_then(|menu| {
+            menu_rows_above(
+                self.prompt_start_row,
+                screen_height,
+                buffer_lines,
+                menu.menu_required_lines(screen_width),
+                menu.min_rows(),
+            )
+        });
+        let required_lines = if menu_above.is_some() {
+            buffer_lines
+        } else {
+            lines.required_lines(screen_width, menu)
+        };
 
         // Marking the painter state as larger buffer to avoid animations
         self.large_buffer = required_lines >= screen_height;
@@ -221,6 +516,7 @@ impl Painter {
             self.queue_universal_scroll(extra)?;
             self.prompt_start_row = self.prompt_start_row.saturating_sub(extra);
         }
+        let menu_above = menu_above.filter(|rows| *rows <= self.prompt_start_row);
 
         // Moving the cursor to the start of the prompt
         // from this position everything will be printed
@@ -231,11 +527,13 @@ impl Painter {
         if self.large_buffer {
             self.print_large_buffer(prompt, lines, menu, use_ansi_coloring)?;
         } else {
-            self.print_small_buffer(prompt, lines, menu, use_ansi_coloring)?;
+            self.print_small_buffer(prompt, lines, menu, menu_above, use_ansi_coloring)?;
         }
 
         // The last_required_lines is used to calculate safe range of the current prompt.
         self.last_required_lines = required_lines;
+        self.text_before_cursor =
+            lines.prompt_str_left.to_string() + &lines.prompt_indicator + &lines.before_cursor;
 
         self.after_cursor_lines = if !lines.after_cursor.is_empty() {
             Some(lines.after_cursor.to_string())
@@ -245,15 +543,21 @@ impl Painter {
 
         self.stdout.queue(RestorePosition)?;
 
-        if let Some(shapes) = cursor_config {
+        if let (true, Some(shapes)) = (use_ansi_coloring, cursor_config) {
             let shape = match &prompt_mode {
                 PromptEditMode::Emacs => shapes.emacs,
                 PromptEditMode::Vi(PromptViMode::Insert) => shapes.vi_insert,
                 PromptEditMode::Vi(PromptViMode::Normal) => shapes.vi_normal,
+                PromptEditMode::Vi(PromptViMode::Visual) => shapes.vi_visual,
                 _ => None,
             };
             if let Some(shape) = shape {
                 self.stdout.queue(shape)?;
+                self.cursor_shape_changed = true;
+            } else if self.cursor_shape_changed {
+                // The edit mode changed to one without a shape
+                self.stdout.queue(SetCursorStyle::DefaultUserShape)?;
+                self.cursor_shape_changed = false;
             }
         }
         self.stdout.queue(cursor::Show)?;
@@ -261,20 +565,29 @@ impl Painter {
         self.stdout.flush()
     }
 
+    /// Whether the cursor has a shape set for the edit mode by [`Painter::repaint_buffer`]
+    pub(crate) fn cursor_shape_changed(&self) -> bool {
+        self.cursor_shape_changed
+    }
+
+    /// Give the cursor back the shape the user had before it was set for the edit mode
+    pub(crate) fn restore_cursor_shape(&mut self) -> Result<()> {
+        if self.cursor_shape_changed {
+            self.stdout.queue(SetCursorStyle::DefaultUserShape)?;
+            self.cursor_shape_changed = false;
+        }
+        self.stdout.flush()
+    }
+
     fn print_right_prompt(&mut self, lines: &PromptLines) -> Result<()> {
-        let prompt_length_right = line_width(&lines.prompt_str_right);
-        let start_position = self
-            .screen_width()
-            .saturating_sub(prompt_length_right as u16);
         let screen_width = self.screen_width();
-        let input_width = lines.estimate_right_prompt_line_width(screen_width);
 
         let mut row = self.prompt_start_row;
         if lines.right_prompt_on_last_line {
             row += lines.prompt_lines_with_wrap(screen_width);
         }
 
-        if input_width <= start_position {
+        if let Some(start_position) = lines.right_prompt_start_column(screen_width) {
             self.stdout
                 .queue(SavePosition)?
                 .queue(cursor::MoveTo(start_position, row))?
@@ -305,6 +618,7 @@ impl Painter {
 
         let remaining_lines = screen_height.saturating_sub(starting_row);
         let menu_string = menu.menu_string(remaining_lines, use_ansi_coloring);
+        self.menu_rows = Some((starting_row..screen_height, remaining_lines));
         self.stdout
             .queue(cursor::MoveTo(0, starting_row))?
             .queue(Clear(ClearType::FromCursorDown))?
@@ -313,11 +627,49 @@ impl Painter {
         Ok(())
     }
 
+    /// Paints the menu in the `rows` right above the prompt, its last line being next to the
+    /// prompt
+    fn print_menu_above(
+        &mut self,
+        menu: &dyn Menu,
+        rows: u16,
+        use_ansi_coloring: bool,
+    ) -> Result<()> {
+        let menu_string = menu.menu_string(rows, use_ansi_coloring);
+        let menu_lines = menu_string.lines().take(rows as usize).collect::<Vec<_>>();
+
+        self.menu_above_rows = menu_lines.len() as u16;
+        let starting_row = self.prompt_start_row.saturating_sub(self.menu_above_rows);
+        self.menu_rows = Some((starting_row..self.prompt_start_row, rows));
+        for (row, line) in (starting_row..).zip(menu_lines) {
+            self.stdout
+                .queue(cursor::MoveTo(0, row))?
+                .queue(Clear(ClearType::CurrentLine))?
+                .queue(Print(line))?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the rows above the prompt the menu was painted in
+    fn clear_menu_above(&mut self) -> Result<()> {
+        let starting_row = self.prompt_start_row.saturating_sub(self.menu_above_rows);
+        for row in starting_row..self.prompt_start_row {
+            self.stdout
+                .queue(cursor::MoveTo(0, row))?
+                .queue(Clear(ClearType::CurrentLine))?;
+        }
+        self.menu_above_rows = 0;
+
+        Ok(())
+    }
+
     fn print_small_buffer(
         &mut self,
         prompt: &dyn Prompt,
         lines: &PromptLines,
         menu: Option<&ReedlineMenu>,
+        menu_above: Option<u16>,
         use_ansi_coloring: bool,
     ) -> Result<()> {
         // print our prompt with color
@@ -354,11 +706,17 @@ impl Painter {
             .queue(Print(&lines.before_cursor))?
             .queue(SavePosition)?
             .queue(Print(&lines.after_cursor))?;
+        if self.line_overflow != LineOverflow::Scroll {
+            self.buffer_start_row =
+                Some(self.prompt_start_row + lines.prompt_lines_with_wrap(self.screen_width()));
+        }
 
-        if let Some(menu) = menu {
+        if let (Some(menu), Some(rows)) = (menu, menu_above) {
+            self.print_menu_above(menu, rows, use_ansi_coloring)?;
+        } else if let Some(menu) = menu {
             self.print_menu(menu, lines, use_ansi_coloring)?;
         } else {
-            self.stdout.queue(Print(&lines.hint))?;
+            queue_hint(&mut self.stdout, &lines.hint, use_ansi_coloring)?;
         }
 
         Ok(())
@@ -465,7 +823,7 @@ impl Painter {
             self.stdout.queue(Print(after_cursor_skipped))?;
             // Hint lines
             let hint_skipped = skip_buffer_lines(&lines.hint, 0, Some(offset));
-            self.stdout.queue(Print(hint_skipped))?;
+            queue_hint(&mut self.stdout, hint_skipped, use_ansi_coloring)?;
         }
 
         Ok(())
@@ -487,7 +845,8 @@ impl Painter {
         // I assume this is a bug with the position() call but haven't figured that
         // out yet.
         if let Ok(position) = cursor::position() {
-            self.prompt_start_row = position.1;
+            self.prompt_start_row =
+                prompt_start_row_after_resize(position.1, &self.text_before_cursor, width);
         }
     }
 
@@ -522,6 +881,11 @@ impl Painter {
         self.initialize_prompt_position(None)
     }
 
+    /// Rings the terminal bell
+    pub(crate) fn ring_bell(&mut self) -> Result<()> {
+        self.stdout.queue(Print('\x07'))?.flush()
+    }
+
     pub(crate) fn clear_scrollback(&mut self) -> Result<()> {
         self.stdout
             .queue(crossterm::terminal::Clear(ClearType::All))?
@@ -533,6 +897,11 @@ impl Painter {
 
     // The prompt is moved to the end of the buffer after the event was handled
     pub(crate) fn move_cursor_to_end(&mut self) -> Result<()> {
+        if self.menu_above_rows > 0 {
+            self.stdout.queue(SavePosition)?;
+            self.clear_menu_above()?;
+            self.stdout.queue(RestorePosition)?;
+        }
         if let Some(after_cursor) = &self.after_cursor_lines {
             self.stdout
                 .queue(Clear(ClearType::FromCursorDown))?
@@ -622,6 +991,70 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn hint_styles_are_isolated() {
+        let queued = |hint: &str, use_ansi_coloring: bool| {
+            let mut out = Vec::new();
+            queue_hint(&mut out, hint, use_ansi_coloring).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let hint = nu_ansi_term::Color::Rgb(90, 90, 90)
+            .paint(" --all")
+            .to_string();
+
+        assert_eq!(
+            queued(&hint, true),
+            format!("\x1b[0m\x1b[0m{hint}\x1b[0m\x1b[0m")
+        );
+        // An escape sequence left open by the hinter is closed
+        assert!(queued("\x1b[38;2;1;2;3m --all", true).ends_with("--all\x1b[0m\x1b[0m"));
+        assert_eq!(queued(" --all", false), " --all");
+        assert_eq!(queued("", true), "");
+    }
+
+    #[rstest::rstest]
+    #[case(10, "", 80, 10)]
+    #[case(10, "> echo", 80, 10)]
+    #[case(10, "> echo (\n::: a\n::: b", 80, 8)]
+    #[case(10, "> 0123456789", 5, 8)]
+    #[case(10, "> 你好你好", 4, 8)]
+    #[case(1, "> a\n::: b\n::: c", 80, 0)]
+    fn test_prompt_start_row_after_resize(
+        #[case] cursor_row: u16,
+        #[case] text_before_cursor: &str,
+        #[case] width: u16,
+        #[case] expected: u16,
+    ) {
+        assert_eq!(
+            prompt_start_row_after_resize(cursor_row, text_before_cursor, width),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    // Fits below the prompt
+    #[case(5, 24, 1, 10, None)]
+    #[case(13, 24, 1, 10, None)]
+    // Goes above the prompt, with as many rows as it needs
+    #[case(20, 24, 1, 10, Some(10))]
+    // Goes above the prompt with less rows than it needs, when there are more than below
+    #[case(8, 24, 10, 10, Some(8))]
+    #[case(2, 24, 20, 10, None)]
+    // Too few rows above
+    #[case(1, 3, 1, 10, None)]
+    fn test_menu_rows_above(
+        #[case] prompt_start_row: u16,
+        #[case] screen_height: u16,
+        #[case] buffer_lines: u16,
+        #[case] menu_lines: u16,
+        #[case] expected: Option<u16>,
+    ) {
+        assert_eq!(
+            menu_rows_above(prompt_start_row, screen_height, buffer_lines, menu_lines, 2),
+            expected
+        );
+    }
+
     #[test]
     fn test_skip_lines() {
         let string = "sentence1\nsentence2\nsentence3\n";
@@ -703,4 +1136,154 @@ mod tests {
             PromptRowSelector::UseExistingPrompt { start_row: 11 }
         );
     }
+
+    #[rstest::rstest]
+    #[case("abcdef", 0, 0, 1, 3, "bcd")]
+    #[case("::: abcdef", 0, 4, 6, 2, "::: cd")]
+    // The columns of the line start at `column`
+    #[case("abcdef", 3, 0, 4, 2, "bc")]
+    // Cut wide characters are replaced by spaces
+    #[case("你好abc", 0, 0, 1, 4, " 好a")]
+    #[case("ab你好", 0, 0, 0, 3, "ab ")]
+    // The escape sequences are kept
+    #[case("\x1b[1mabc\x1b[0m", 0, 0, 1, 1, "\x1b[1mb\x1b[0m")]
+    fn test_visible_columns(
+        #[case] line: &str,
+        #[case] column: usize,
+        #[case] fixed: usize,
+        #[case] start: usize,
+        #[case] width: usize,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(visible_columns(line, column, fixed, start, width), expected);
+    }
+
+    struct ScrollPrompt;
+
+    impl Prompt for ScrollPrompt {
+        fn render_prompt_left(&self) -> Cow<'_, str> {
+            Cow::Borrowed("")
+        }
+
+        fn render_prompt_indicator(&self, _prompt_mode: PromptEditMode) -> Cow<'_, str> {
+            Cow::Borrowed("> ")
+        }
+
+        fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+            Cow::Borrowed("::: ")
+        }
+
+        fn render_prompt_history_search_indicator(
+            &self,
+            _history_search: crate::PromptHistorySearch,
+        ) -> Cow<'_, str> {
+            Cow::Borrowed("? ")
+        }
+    }
+
+    /// The buffer and hint lines scrolled on a screen 10 columns wide
+    fn scrolled(
+        painter: &mut Painter,
+        before_cursor: &str,
+        after_cursor: &str,
+        hint: &str,
+    ) -> (String, String, String) {
+        let context = crate::PromptContext::default();
+        let mut lines = PromptLines::new(
+            &ScrollPrompt,
+            &context,
+            PromptEditMode::Default,
+            None,
+            before_cursor,
+            after_cursor,
+            hint,
+        );
+        painter.scroll_horizontally(&ScrollPrompt, &mut lines);
+        (
+            lines.before_cursor.to_string(),
+            lines.after_cursor.to_string(),
+            lines.hint.to_string(),
+        )
+    }
+
+    fn scrolling_painter() -> Painter {
+        let mut painter = Painter::new(W::new(std::io::stderr()));
+        painter.terminal_size = (10, 24);
+        painter.set_line_overflow(LineOverflow::Scroll);
+        painter
+    }
+
+    #[test]
+    fn scrolling_follows_the_cursor() {
+        let mut painter = scrolling_painter();
+
+        // The cursor stays visible at the end of the line
+        assert_eq!(
+            scrolled(&mut painter, "abcdefghijkl", "", ""),
+            ("fghijkl".to_string(), String::new(), String::new())
+        );
+        // The offset is kept while the cursor is away from the edges
+        assert_eq!(
+            scrolled(&mut painter, "abcdefgh", "ijkl", ""),
+            ("fgh".to_string(), "ijkl".to_string(), String::new())
+        );
+        // And follows it back to the start
+        assert_eq!(
+            scrolled(&mut painter, "", "abcdefghijkl", ""),
+            (String::new(), "abcdefgh".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn scrolling_keeps_the_indicators_and_cuts_the_hint() {
+        let mut painter = scrolling_painter();
+
+        assert_eq!(
+            scrolled(&mut painter, "0123456789abc\n::: uvwxyz12", "", ""),
+            (
+                "3456789a\r\n::: xyz12".to_string(),
+                String::new(),
+                String::new()
+            )
+        );
+
+        let mut painter = scrolling_painter();
+        assert_eq!(
+            scrolled(&mut painter, "abc", "", "defghijklmnop"),
+            ("abc".to_string(), String::new(), "defgh".to_string())
+        );
+    }
+
+    #[test]
+    fn scrolling_a_history_search_match_without_indicators() {
+        let mut painter = scrolling_painter();
+
+        assert_eq!(
+            scrolled(&mut painter, "echo a\nb", "", ""),
+            ("echo a\r\nb".to_string(), String::new(), String::new())
+        );
+        assert_eq!(
+            scrolled(&mut painter, "echo a\nbcdefghijkl", "", ""),
+            (
+                "ho a\r\ndefghijkl".to_string(),
+                String::new(),
+                String::new()
+            )
+        );
+    }
+
+    #[test]
+    fn wrapping_keeps_the_lines() {
+        let mut painter = scrolling_painter();
+        painter.set_line_overflow(LineOverflow::Wrap);
+
+        assert_eq!(
+            scrolled(&mut painter, "abcdefghijkl", "mn", "op"),
+            (
+                "abcdefghijkl".to_string(),
+                "mn".to_string(),
+                "op".to_string()
+            )
+        );
+    }
 }
@@ -1,8 +1,8 @@
-use super::utils::{coerce_crlf, estimate_required_lines, line_width};
+use super::utils::{coerce_crlf, estimate_required_lines, line_width, strip_ansi};
 use crate::{
     menu::{Menu, ReedlineMenu},
     prompt::PromptEditMode,
-    Prompt, PromptHistorySearch,
+    Prompt, PromptContext, PromptHistorySearch,
 };
 use std::borrow::Cow;
 
@@ -24,18 +24,19 @@ impl<'prompt> PromptLines<'prompt> {
     /// required to print after the prompt
     pub fn new(
         prompt: &'prompt dyn Prompt,
+        prompt_context: &PromptContext,
         prompt_mode: PromptEditMode,
         history_indicator: Option<PromptHistorySearch>,
         before_cursor: &'prompt str,
         after_cursor: &'prompt str,
         hint: &'prompt str,
     ) -> Self {
-        let prompt_str_left = prompt.render_prompt_left();
-        let prompt_str_right = prompt.render_prompt_right();
+        let prompt_str_left = prompt.render_prompt_left_with_context(prompt_context);
+        let prompt_str_right = prompt.render_prompt_right_with_context(prompt_context);
 
         let prompt_indicator = match history_indicator {
             Some(prompt_search) => prompt.render_prompt_history_search_indicator(prompt_search),
-            None => prompt.render_prompt_indicator(prompt_mode),
+            None => prompt.render_prompt_indicator_with_context(prompt_mode, prompt_context),
         };
 
         let before_cursor = coerce_crlf(before_cursor);
@@ -54,6 +55,20 @@ impl<'prompt> PromptLines<'prompt> {
         }
     }
 
+    /// The same lines without their ANSI escape sequences, for a terminal without colors
+    pub(crate) fn without_ansi(&self) -> PromptLines<'static> {
+        let plain = |text: &Cow<str>| Cow::Owned(strip_ansi(text));
+        PromptLines {
+            prompt_str_left: plain(&self.prompt_str_left),
+            prompt_str_right: plain(&self.prompt_str_right),
+            prompt_indicator: plain(&self.prompt_indicator),
+            before_cursor: plain(&self.before_cursor),
+            after_cursor: plain(&self.after_cursor),
+            hint: plain(&self.hint),
+            right_prompt_on_last_line: self.right_prompt_on_last_line,
+        }
+    }
+
     /// The required lines to paint the buffer are calculated by counting the
     /// number of newlines in all the strings that form the prompt and buffer.
     /// The plus 1 is to indicate that there should be at least one line.
@@ -123,6 +138,23 @@ impl<'prompt> PromptLines<'prompt> {
         lines.saturating_sub(1) as u16
     }
 
+    /// Column where the right prompt starts so that it ends on the right edge
+    /// of the screen
+    ///
+    /// `None` if there is nothing to render, or if it would not fit on the
+    /// screen or overlap the left prompt and the input on its line.
+    pub(crate) fn right_prompt_start_column(&self, screen_width: u16) -> Option<u16> {
+        let prompt_length_right = line_width(&self.prompt_str_right);
+        if prompt_length_right == 0 || prompt_length_right >= screen_width as usize {
+            return None;
+        }
+
+        let start_position = screen_width - prompt_length_right as u16;
+        let input_width = self.estimate_right_prompt_line_width(screen_width);
+
+        (input_width <= start_position).then_some(start_position)
+    }
+
     /// Estimated width of the line where right prompt will be rendered
     pub(crate) fn estimate_right_prompt_line_width(&self, terminal_columns: u16) -> u16 {
         let first_line_left_prompt = self.prompt_str_left.lines().next();
@@ -149,8 +181,8 @@ impl<'prompt> PromptLines<'prompt> {
                 estimate += line_width(first_line_left_prompt);
             }
 
-            // A single line
-            if required_lines == 1 {
+            // A single line, not ended by a newline putting the input on the next one
+            if required_lines == 1 && !self.prompt_str_left.ends_with('\n') {
                 estimate += line_width(&self.prompt_indicator);
 
                 if let Some(prompt_lines_first) = prompt_lines_first {
@@ -252,4 +284,132 @@ mod tests {
 
         assert_eq!(pos, expected);
     }
+
+    #[rstest]
+    #[case("~/path/", "❯ ", "ls", "12:00", 40, Some(35))]
+    #[case("~/path/", "❯ ", "ls", "", 40, None)]
+    #[case("~/path/", "❯ ", "ls", "时间 12:00", 40, Some(30))]
+    #[case("~/路径/", "❯ ", "ls", "12:00", 16, Some(11))]
+    #[case("~/路径/", "❯ ", "lsd", "12:00", 16, None)]
+    #[case("~/path/", "❯ ", "ls", "\x1b[1;32m12:00\x1b[0m", 40, Some(35))]
+    #[case("~/path/", "❯ ", "", "a very long right prompt", 20, None)]
+    #[case(
+        "~/path/\n",
+        "❯ ",
+        "a long input on the second line",
+        "12:00",
+        20,
+        Some(15)
+    )]
+    fn test_right_prompt_start_column(
+        #[case] prompt_str_left: &str,
+        #[case] prompt_indicator: &str,
+        #[case] before_cursor: &str,
+        #[case] prompt_str_right: &str,
+        #[case] terminal_columns: u16,
+        #[case] expected: Option<u16>,
+    ) {
+        let prompt_lines = PromptLines {
+            prompt_str_left: Cow::Borrowed(prompt_str_left),
+            prompt_str_right: Cow::Borrowed(prompt_str_right),
+            prompt_indicator: Cow::Borrowed(prompt_indicator),
+            before_cursor: Cow::Borrowed(before_cursor),
+            after_cursor: Cow::Borrowed(""),
+            hint: Cow::Borrowed(""),
+            right_prompt_on_last_line: false,
+        };
+
+        assert_eq!(
+            prompt_lines.right_prompt_start_column(terminal_columns),
+            expected
+        );
+    }
+
+    struct StatusPrompt;
+
+    impl Prompt for StatusPrompt {
+        fn render_prompt_left(&self) -> Cow<'_, str> {
+            Cow::Borrowed("~")
+        }
+
+        fn render_prompt_indicator(&self, _prompt_mode: PromptEditMode) -> Cow<'_, str> {
+            Cow::Borrowed("> ")
+        }
+
+        fn render_prompt_indicator_with_context(
+            &self,
+            _prompt_mode: PromptEditMode,
+            context: &PromptContext,
+        ) -> Cow<'_, str> {
+            match context.last_command_status {
+                Some(0) | None => Cow::Borrowed("> "),
+                Some(status) => Cow::Owned(format!("[{status}]> ")),
+            }
+        }
+
+        fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+            Cow::Borrowed("::: ")
+        }
+
+        fn render_prompt_history_search_indicator(
+            &self,
+            _history_search: PromptHistorySearch,
+        ) -> Cow<'_, str> {
+            Cow::Borrowed("? ")
+        }
+    }
+
+    #[rstest]
+    #[case(None, "> ")]
+    #[case(Some(0), "> ")]
+    #[case(Some(127), "[127]> ")]
+    fn prompt_gets_the_last_command_status(
+        #[case] last_command_status: Option<i32>,
+        #[case] expected: &str,
+    ) {
+        let context = PromptContext {
+            last_command_status,
+            ..PromptContext::default()
+        };
+        let prompt_lines = PromptLines::new(
+            &StatusPrompt,
+            &context,
+            PromptEditMode::Default,
+            None,
+            "",
+            "",
+            "",
+        );
+
+        assert_eq!(prompt_lines.prompt_str_left, "~");
+        assert_eq!(prompt_lines.prompt_indicator, expected);
+    }
+
+    #[test]
+    fn plain_lines_have_no_escape_sequences() {
+        let green = |text: &str| nu_ansi_term::Color::Green.paint(text).to_string();
+        let (left, right, hint) = (green("~/path/"), green("12:00"), green(" --all"));
+        let prompt_lines = PromptLines {
+            prompt_str_left: Cow::Owned(left),
+            prompt_str_right: Cow::Owned(right),
+            prompt_indicator: Cow::Borrowed("\x1b[1m❯ "),
+            before_cursor: Cow::Borrowed("ls"),
+            after_cursor: Cow::Borrowed(""),
+            hint: Cow::Owned(hint),
+            right_prompt_on_last_line: true,
+        };
+
+        let plain = prompt_lines.without_ansi();
+        assert_eq!(
+            [
+                &plain.prompt_str_left,
+                &plain.prompt_str_right,
+                &plain.prompt_indicator,
+                &plain.before_cursor,
+                &plain.hint,
+            ],
+            ["~/path/", "12:00", "❯ ", "ls", " --all"]
+        );
+        assert!(plain.right_prompt_on_last_line);
+    }
 }
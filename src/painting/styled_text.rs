@@ -107,14 +107,17 @@ impl StyledText {
         let mut left_string = String::new();
         let mut right_string = String::new();
 
-        let multiline_prompt = prompt.render_prompt_multiline_indicator();
-        let prompt_style = Style::new().fg(prompt.get_prompt_multiline_color());
+        let mut multiline_prompt = MultilinePrompt {
+            prompt,
+            style: Style::new().fg(prompt.get_prompt_multiline_color()),
+            line_index: 0,
+        };
 
         for pair in &self.buffer {
             if current_idx >= insertion_point {
-                right_string.push_str(&render_as_string(pair, &prompt_style, &multiline_prompt));
+                right_string.push_str(&render_as_string(pair, &mut multiline_prompt));
             } else if pair.1.len() + current_idx <= insertion_point {
-                left_string.push_str(&render_as_string(pair, &prompt_style, &multiline_prompt));
+                left_string.push_str(&render_as_string(pair, &mut multiline_prompt));
             } else if pair.1.len() + current_idx > insertion_point {
                 let offset = insertion_point - current_idx;
 
@@ -123,13 +126,11 @@ impl StyledText {
 
                 left_string.push_str(&render_as_string(
                     &(pair.0, left_side),
-                    &prompt_style,
-                    &multiline_prompt,
+                    &mut multiline_prompt,
                 ));
                 right_string.push_str(&render_as_string(
                     &(pair.0, right_side),
-                    &prompt_style,
-                    &multiline_prompt,
+                    &mut multiline_prompt,
                 ));
             }
             current_idx += pair.1.len();
@@ -156,16 +157,30 @@ impl StyledText {
     }
 }
 
+/// Continuation prompt of the buffer lines, counting the lines rendered so far
+struct MultilinePrompt<'prompt> {
+    prompt: &'prompt dyn Prompt,
+    style: Style,
+    line_index: usize,
+}
+
 fn render_as_string(
     renderable: &(Style, String),
-    prompt_style: &Style,
-    multiline_prompt: &str,
+    multiline_prompt: &mut MultilinePrompt,
 ) -> String {
     let mut rendered = String::new();
-    let formatted_multiline_prompt = format!("\n{multiline_prompt}");
     for (line_number, line) in renderable.1.split('\n').enumerate() {
         if line_number != 0 {
-            rendered.push_str(&prompt_style.paint(&formatted_multiline_prompt).to_string());
+            multiline_prompt.line_index += 1;
+            let indicator = multiline_prompt
+                .prompt
+                .render_prompt_multiline_indicator_for_line(multiline_prompt.line_index);
+            rendered.push_str(
+                &multiline_prompt
+                    .style
+                    .paint(format!("\n{indicator}"))
+                    .to_string(),
+            );
         }
         rendered.push_str(&renderable.0.paint(line).to_string());
     }
@@ -175,8 +190,54 @@ fn render_as_string(
 #[cfg(test)]
 mod test {
     use nu_ansi_term::{Color, Style};
+    use std::borrow::Cow;
+
+    use crate::{Prompt, PromptEditMode, PromptHistorySearch, StyledText};
+
+    struct NumberedPrompt;
+
+    impl Prompt for NumberedPrompt {
+        fn render_prompt_left(&self) -> Cow<'_, str> {
+            Cow::Borrowed("")
+        }
+
+        fn render_prompt_indicator(&self, _prompt_mode: PromptEditMode) -> Cow<'_, str> {
+            Cow::Borrowed("1> ")
+        }
 
-    use crate::StyledText;
+        fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+            Cow::Borrowed("::: ")
+        }
+
+        fn render_prompt_multiline_indicator_for_line(&self, line_index: usize) -> Cow<'_, str> {
+            Cow::Owned(format!("{}> ", line_index + 1))
+        }
+
+        fn render_prompt_history_search_indicator(
+            &self,
+            _history_search: PromptHistorySearch,
+        ) -> Cow<'_, str> {
+            Cow::Borrowed("")
+        }
+    }
+
+    #[test]
+    fn multiline_indicator_gets_the_line_index() {
+        let style = Style::new();
+        let styled_text = StyledText {
+            buffer: vec![
+                (style, "if (a\n&& b".into()),
+                (style, ") {\nc\n".into()),
+                (style, "}".into()),
+            ],
+        };
+
+        let (before_cursor, after_cursor) =
+            styled_text.render_around_insertion_point(15, &NumberedPrompt, false);
+
+        assert_eq!(before_cursor, "if (a\n2> && b) {\n3> c");
+        assert_eq!(after_cursor, "\n4> }");
+    }
 
     fn get_styled_text_template() -> (super::StyledText, Style, Style) {
         let before_style = Style::new().on(Color::Black);
@@ -15,6 +15,7 @@ pub static DEFAULT_INDICATOR_COLOR: Color = Color::Cyan;
 pub static DEFAULT_PROMPT_RIGHT_COLOR: Color = Color::AnsiValue(5);
 
 /// The current success/failure of the history search
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PromptHistorySearchStatus {
     /// Success for the search
     Passing,
@@ -24,6 +25,7 @@ pub enum PromptHistorySearchStatus {
 }
 
 /// A representation of the history search
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PromptHistorySearch {
     /// The status of the search
     pub status: PromptHistorySearchStatus,
@@ -42,10 +44,35 @@ impl PromptHistorySearch {
     }
 }
 
+/// What the engine is doing and what the host told it about the previous commands, for the
+/// prompt to render, e.g. as a status line
+///
+/// Passed to the `render_prompt_*_with_context` methods of [`Prompt`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PromptContext {
+    /// Exit status of the last command run by the host, if set with
+    /// `Reedline::set_last_command_status`
+    pub last_command_status: Option<i32>,
+    /// The mode of the edit mode, also given to [`Prompt::render_prompt_indicator`]
+    pub edit_mode: PromptEditMode,
+    /// The `Ctrl-R` history search in progress, also given to
+    /// [`Prompt::render_prompt_history_search_indicator`]
+    pub history_search: Option<PromptHistorySearch>,
+    /// Name of the open menu, such as the completion menu
+    pub active_menu: Option<String>,
+    /// The keys typed so far of an unfinished command, e.g. the `d` operator waiting for its
+    /// motion in Vi normal mode
+    pub pending_command: Option<String>,
+    /// How many lines the buffer has after the first one, `0` for a single line
+    pub multiline_depth: usize,
+}
+
 /// Modes that the prompt can be in
-#[derive(Serialize, Deserialize, Clone, Debug, EnumIter)]
+#[derive(Serialize, Deserialize, Clone, Debug, EnumIter, Default, PartialEq, Eq)]
 pub enum PromptEditMode {
     /// The default mode
+    #[default]
     Default,
 
     /// Emacs normal mode
@@ -59,7 +86,7 @@ pub enum PromptEditMode {
 }
 
 /// The vi-specific modes that the prompt can be in
-#[derive(Serialize, Deserialize, Clone, Debug, EnumIter, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, EnumIter, Default, PartialEq, Eq)]
 pub enum PromptViMode {
     /// The default mode
     #[default]
@@ -67,6 +94,9 @@ pub enum PromptViMode {
 
     /// Insertion mode
     Insert,
+
+    /// Visual selection mode
+    Visual,
 }
 
 impl Display for PromptEditMode {
@@ -74,7 +104,7 @@ impl Display for PromptEditMode {
         match self {
             PromptEditMode::Default => write!(f, "Default"),
             PromptEditMode::Emacs => write!(f, "Emacs"),
-            PromptEditMode::Vi(_) => write!(f, "Vi_Normal\nVi_Insert"),
+            PromptEditMode::Vi(_) => write!(f, "Vi_Normal\nVi_Insert\nVi_Visual"),
             PromptEditMode::Custom(s) => write!(f, "Custom_{s}"),
         }
     }
@@ -87,11 +117,54 @@ pub trait Prompt: Send {
     /// Provide content of the left full prompt
     fn render_prompt_left(&self) -> Cow<str>;
     /// Provide content of the right full prompt
-    fn render_prompt_right(&self) -> Cow<str>;
+    ///
+    /// It is drawn right-aligned on the first line of the prompt (or on the
+    /// last line, see [`Prompt::right_prompt_on_last_line`]) and hidden when
+    /// it would overlap the input. Empty by default.
+    fn render_prompt_right(&self) -> Cow<str> {
+        Cow::Borrowed("")
+    }
     /// Render the prompt indicator (Last part of the prompt that changes based on the editor mode)
     fn render_prompt_indicator(&self, prompt_mode: PromptEditMode) -> Cow<str>;
+    /// Provide content of the left full prompt, knowing the [`PromptContext`], e.g. to show
+    /// the exit status of the last command
+    ///
+    /// Defaults to [`Prompt::render_prompt_left`], ignoring the context.
+    fn render_prompt_left_with_context(&self, context: &PromptContext) -> Cow<'_, str> {
+        let _ = context;
+        self.render_prompt_left()
+    }
+    /// Provide content of the right full prompt, knowing the [`PromptContext`]
+    ///
+    /// Defaults to [`Prompt::render_prompt_right`], ignoring the context.
+    fn render_prompt_right_with_context(&self, context: &PromptContext) -> Cow<'_, str> {
+        let _ = context;
+        self.render_prompt_right()
+    }
+    /// Render the prompt indicator, knowing the [`PromptContext`], e.g. to color it
+    /// depending on the exit status of the last command
+    ///
+    /// Defaults to [`Prompt::render_prompt_indicator`], ignoring the context.
+    fn render_prompt_indicator_with_context(
+        &self,
+        prompt_mode: PromptEditMode,
+        context: &PromptContext,
+    ) -> Cow<'_, str> {
+        let _ = context;
+        self.render_prompt_indicator(prompt_mode)
+    }
     /// Indicator to show before explicit new lines
     fn render_prompt_multiline_indicator(&self) -> Cow<str>;
+    /// Indicator to show before the explicit new line at `line_index` in the buffer, the first
+    /// continuation line having the index `1`
+    ///
+    /// Lets the indicator change with the line, e.g. to number the lines `2>`, `3>`...
+    /// Lines wrapped by the terminal don't get an indicator and aren't counted.
+    /// Defaults to [`Prompt::render_prompt_multiline_indicator`] for every line.
+    fn render_prompt_multiline_indicator_for_line(&self, line_index: usize) -> Cow<'_, str> {
+        let _ = line_index;
+        self.render_prompt_multiline_indicator()
+    }
     /// Render the prompt indicator for `Ctrl-R` history search
     fn render_prompt_history_search_indicator(
         &self,
@@ -63,7 +63,9 @@ impl Prompt for DefaultPrompt {
         match edit_mode {
             PromptEditMode::Default | PromptEditMode::Emacs => DEFAULT_PROMPT_INDICATOR.into(),
             PromptEditMode::Vi(vi_mode) => match vi_mode {
-                PromptViMode::Normal => DEFAULT_VI_NORMAL_PROMPT_INDICATOR.into(),
+                PromptViMode::Normal | PromptViMode::Visual => {
+                    DEFAULT_VI_NORMAL_PROMPT_INDICATOR.into()
+                }
                 PromptViMode::Insert => DEFAULT_VI_INSERT_PROMPT_INDICATOR.into(),
             },
             PromptEditMode::Custom(str) => format!("({str})").into(),
@@ -85,7 +87,7 @@ impl Prompt for DefaultPrompt {
         // NOTE: magic strings, given there is logic on how these compose I am not sure if it
         // is worth extracting in to static constant
         Cow::Owned(format!(
-            "({}reverse-search: {}) ",
+            "({}reverse-i-search)`{}': ",
             prefix, history_search.term
         ))
     }
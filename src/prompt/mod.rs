@@ -2,7 +2,8 @@ mod base;
 mod default;
 
 pub use base::{
-    Prompt, PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus, PromptViMode,
+    Prompt, PromptContext, PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus,
+    PromptViMode,
 };
 
 pub use default::{DefaultPrompt, DefaultPromptSegment};
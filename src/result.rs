@@ -1,6 +1,8 @@
 use std::fmt::Display;
 use thiserror::Error;
 
+use crate::HistoryItemId;
+
 /// non-public (for now)
 #[derive(Error, Debug)]
 pub enum ReedlineErrorVariants {
@@ -14,6 +16,18 @@ pub enum ReedlineErrorVariants {
     #[error("error in Reedline history: {0}")]
     OtherHistoryError(&'static str),
 
+    /// A history item with the given id could not be found
+    #[error("history item {0} does not exist")]
+    HistoryItemNotFound(HistoryItemId),
+
+    /// The regular expression used to search the history is invalid
+    #[error("invalid regular expression in history search: {0}")]
+    InvalidHistoryRegex(String),
+
+    /// The glob pattern used to search the history is invalid
+    #[error("invalid glob pattern in history search: {0}")]
+    InvalidHistoryGlob(String),
+
     /// History does not support a feature
     #[error("the history {history} does not support feature {feature}")]
     HistoryFeatureUnsupported {
@@ -24,6 +38,23 @@ pub enum ReedlineErrorVariants {
         feature: &'static str,
     },
 
+    /// The history file was written in a version of its format newer than the supported one
+    ///
+    /// Returned by [`crate::History::sync`] as an [`std::io::Error`] wrapping it, which converts
+    /// back to it with [`ReedlineError::from`].
+    #[error("the history file has the format version {found}, newer than the supported version {supported}")]
+    HistoryFormatVersionMismatch {
+        /// Version of the format of the file
+        found: u32,
+
+        /// Latest version of the format that can be read
+        supported: u32,
+    },
+
+    /// The cursor position isn't on a grapheme boundary of the buffer
+    #[error("the cursor position {0} is not on a grapheme boundary of the buffer")]
+    InvalidCursorPosition(usize),
+
     /// I/O error
     #[error("I/O error: {0}")]
     IOError(std::io::Error),
@@ -34,7 +65,17 @@ pub enum ReedlineErrorVariants {
 pub struct ReedlineError(pub ReedlineErrorVariants);
 
 impl From<std::io::Error> for ReedlineError {
+    /// Unwraps the [`ReedlineError`] carried by the error, if any
     fn from(err: std::io::Error) -> Self {
+        if err
+            .get_ref()
+            .map_or(false, |inner| inner.is::<ReedlineError>())
+        {
+            let inner = err
+                .into_inner()
+                .and_then(|inner| inner.downcast::<ReedlineError>().ok());
+            return *inner.expect("the error wraps a ReedlineError");
+        }
         Self(ReedlineErrorVariants::IOError(err))
     }
 }
@@ -3,12 +3,20 @@ use crossterm::{event, execute};
 /// Helper managing proper setup and teardown of bracketed paste mode
 ///
 /// <https://en.wikipedia.org/wiki/Bracketed-paste>
-#[derive(Default)]
 pub(crate) struct BracketedPasteGuard {
     enabled: bool,
     active: bool,
 }
 
+impl Default for BracketedPasteGuard {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            active: false,
+        }
+    }
+}
+
 impl BracketedPasteGuard {
     pub fn set(&mut self, enable: bool) {
         self.enabled = enable;
@@ -1,5 +1,8 @@
 pub(crate) mod bracketed_paste;
 pub(crate) mod kitty;
+pub(crate) mod mouse_capture;
+#[cfg(all(unix, feature = "suspend"))]
+pub(crate) mod suspend;
 
 /// Return if the terminal supports the kitty keyboard enhancement protocol
 ///
@@ -0,0 +1,149 @@
+use signal_hook::{
+    consts::{SIGCONT, SIGTSTP},
+    flag, low_level, SigId,
+};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Helper catching the signals stopping and resuming the process while a line is edited
+///
+/// The handlers are added with `signal-hook`, after the ones already installed, which keep
+/// running. Outside of a line, `SIGTSTP` still stops the process right away.
+#[derive(Default)]
+pub(crate) struct SuspendGuard {
+    enabled: bool,
+    signals: Option<SuspendSignals>,
+}
+
+impl SuspendGuard {
+    pub fn set(&mut self, enable: bool) {
+        self.enabled = enable;
+        if !enable {
+            self.signals = None;
+        }
+    }
+
+    pub fn enter(&mut self) -> io::Result<()> {
+        if self.enabled && self.signals.is_none() {
+            self.signals = Some(SuspendSignals::register()?);
+        }
+        if let Some(signals) = &self.signals {
+            signals.stopped.store(false, Ordering::SeqCst);
+            signals.resumed.store(false, Ordering::SeqCst);
+            signals.outside_line.store(false, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    pub fn exit(&mut self) {
+        if let Some(signals) = &self.signals {
+            signals.outside_line.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Whether the signals are caught, so the line should wait for them with the events
+    pub fn is_active(&self) -> bool {
+        self.signals.is_some()
+    }
+
+    /// Whether a `SIGTSTP` arrived since the last call
+    pub fn take_stopped(&self) -> bool {
+        self.signals.as_ref().map_or(false, |signals| {
+            signals.stopped.swap(false, Ordering::SeqCst)
+        })
+    }
+
+    /// Whether a `SIGCONT` arrived since the last call
+    pub fn take_resumed(&self) -> bool {
+        self.signals.as_ref().map_or(false, |signals| {
+            signals.resumed.swap(false, Ordering::SeqCst)
+        })
+    }
+
+    /// Stop the process like `SIGTSTP` does, returning once it is resumed
+    ///
+    /// The handlers of `SIGTSTP` run as well, so a host ignoring or handling it on its own
+    /// isn't stopped, unless the signals are caught by this guard.
+    pub fn stop_process(&self) -> io::Result<()> {
+        low_level::raise(SIGTSTP)?;
+        if self.take_stopped() {
+            low_level::emulate_default_handler(SIGTSTP)?;
+        }
+        // The process was resumed by the stop above
+        self.take_resumed();
+        Ok(())
+    }
+}
+
+struct SuspendSignals {
+    stopped: Arc<AtomicBool>,
+    resumed: Arc<AtomicBool>,
+    outside_line: Arc<AtomicBool>,
+    flags: [SigId; 2],
+}
+
+impl SuspendSignals {
+    fn register() -> io::Result<Self> {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let resumed = Arc::new(AtomicBool::new(false));
+        let outside_line = Arc::new(AtomicBool::new(true));
+
+        // Never unregistered: the default action of `SIGTSTP` isn't restored when its last
+        // handler is removed, it would be ignored from then on
+        flag::register_conditional_default(SIGTSTP, Arc::clone(&outside_line))?;
+        let flags = [
+            flag::register(SIGTSTP, Arc::clone(&stopped))?,
+            flag::register(SIGCONT, Arc::clone(&resumed))?,
+        ];
+
+        Ok(Self {
+            stopped,
+            resumed,
+            outside_line,
+            flags,
+        })
+    }
+}
+
+impl Drop for SuspendSignals {
+    fn drop(&mut self) {
+        self.outside_line.store(true, Ordering::SeqCst);
+        for id in self.flags {
+            low_level::unregister(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn signals_are_caught_while_a_line_is_edited() -> io::Result<()> {
+        let mut guard = SuspendGuard::default();
+        guard.enter()?;
+        assert!(!guard.is_active());
+
+        guard.set(true);
+        guard.enter()?;
+        assert!(guard.is_active());
+
+        // Caught instead of stopping the test process
+        low_level::raise(SIGTSTP)?;
+        low_level::raise(SIGCONT)?;
+        assert!(guard.take_stopped());
+        assert!(guard.take_resumed());
+        assert!(!guard.take_stopped());
+        assert!(!guard.take_resumed());
+
+        guard.exit();
+        guard.set(false);
+        assert!(!guard.is_active());
+        Ok(())
+    }
+}
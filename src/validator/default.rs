@@ -1,30 +1,51 @@
 use crate::{ValidationResult, Validator};
 
 /// A default validator which checks for mismatched quotes and brackets
+///
+/// Brackets inside single or double quotes, as well as characters escaped with a backslash,
+/// are ignored.
 pub struct DefaultValidator;
 
 impl Validator for DefaultValidator {
     fn validate(&self, line: &str) -> ValidationResult {
-        if line.split('"').count() % 2 == 0 || incomplete_brackets(line) {
+        if unclosed_delimiter(line).is_some() {
             ValidationResult::Incomplete
         } else {
             ValidationResult::Complete
         }
     }
+
+    fn incomplete_position(&self, line: &str) -> Option<usize> {
+        unclosed_delimiter(line)
+    }
 }
 
-fn incomplete_brackets(line: &str) -> bool {
-    let mut balance: Vec<char> = Vec::new();
+/// Byte offset of the innermost bracket or quote that is not closed
+fn unclosed_delimiter(line: &str) -> Option<usize> {
+    // Position of the open delimiters with their expected closing character
+    let mut balance: Vec<(usize, char)> = Vec::new();
+    let mut escaped = false;
 
-    for c in line.chars() {
-        if c == '{' {
-            balance.push('}');
+    for (idx, c) in line.char_indices() {
+        let in_quotes = matches!(balance.last(), Some((_, '"' | '\'')));
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if in_quotes {
+            if balance.last().map_or(false, |(_, close)| *close == c) {
+                balance.pop();
+            }
+        } else if c == '"' || c == '\'' {
+            balance.push((idx, c));
+        } else if c == '{' {
+            balance.push((idx, '}'));
         } else if c == '[' {
-            balance.push(']');
+            balance.push((idx, ']'));
         } else if c == '(' {
-            balance.push(')');
+            balance.push((idx, ')'));
         } else if ['}', ']', ')'].contains(&c) {
-            if let Some(last) = balance.last() {
+            if let Some((_, last)) = balance.last() {
                 if last == &c {
                     balance.pop();
                 }
@@ -32,7 +53,7 @@ fn incomplete_brackets(line: &str) -> bool {
         }
     }
 
-    !balance.is_empty()
+    balance.last().map(|(idx, _)| *idx)
 }
 
 #[cfg(test)]
@@ -46,8 +67,31 @@ mod test {
     #[case("{[}]", true)]
     #[case("{[]}{()}", false)]
     fn test_incomplete_brackets(#[case] input: &str, #[case] expected: bool) {
-        let result = incomplete_brackets(input);
+        let result = unclosed_delimiter(input).is_some();
 
         assert_eq!(result, expected);
     }
+
+    #[rstest]
+    #[case("(([[]]))", None)]
+    #[case("(([[]]", Some(1))]
+    #[case("{[}]", Some(0))]
+    #[case("ls (", Some(3))]
+    #[case("echo \"a", Some(5))]
+    #[case("echo \"(\"", None)]
+    #[case("echo '[' '\"'", None)]
+    #[case("(echo \")\"", Some(0))]
+    #[case("echo \"\\\"", Some(5))]
+    #[case("echo \\(", None)]
+    #[case("é(", Some(2))]
+    fn test_incomplete_position(#[case] input: &str, #[case] expected: Option<usize>) {
+        assert_eq!(DefaultValidator.incomplete_position(input), expected);
+        assert_eq!(
+            matches!(
+                DefaultValidator.validate(input),
+                ValidationResult::Incomplete
+            ),
+            expected.is_some()
+        );
+    }
 }
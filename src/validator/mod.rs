@@ -6,6 +6,15 @@ pub use default::DefaultValidator;
 pub trait Validator: Send {
     /// The action that will handle the current buffer as a line and return the corresponding validation
     fn validate(&self, line: &str) -> ValidationResult;
+
+    /// Byte offset of the delimiter left open in an incomplete `line`, so that a front-end can
+    /// point the user to it
+    ///
+    /// `None` when the input is complete or the position is unknown, which is the default.
+    fn incomplete_position(&self, line: &str) -> Option<usize> {
+        let _ = line;
+        None
+    }
 }
 
 #[derive(Clone, Copy)]